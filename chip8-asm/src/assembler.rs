@@ -0,0 +1,560 @@
+use std::collections::HashMap;
+
+use crate::errors::AsmError;
+
+/// Address programs are loaded at, labels are resolved from it
+pub const ORIGIN: u16 = 0x200;
+
+const MAX_ADDRESS: i64 = 0xFFF;
+
+struct Token<'a> {
+    line: usize,
+    text: &'a str,
+}
+
+struct Fixup<'a> {
+    offset: usize,
+    name: &'a str,
+    line: usize,
+}
+
+struct Assembler<'a> {
+    tokens: &'a [Token<'a>],
+    position: usize,
+    rom: Vec<u8>,
+    labels: HashMap<&'a str, u16>,
+    constants: HashMap<&'a str, i64>,
+    aliases: HashMap<&'a str, u16>,
+    fixups: Vec<Fixup<'a>>,
+    loops: Vec<(usize, u16)>,
+}
+
+/// Assembles the source into the bytes of a rom
+///
+/// When the program defines a `main` label somewhere else than at the start,
+/// it begins with a jump to it like Octo programs do
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let tokens = tokenize(source);
+    let mut assembler = Assembler::new(&tokens);
+    assembler.run()?;
+
+    match assembler.labels.get("main") {
+        Some(&main) if main != ORIGIN => {
+            let mut assembler = Assembler::new(&tokens);
+            assembler.emit_address(0x1000, "main", 1)?;
+            assembler.run()?;
+            Ok(assembler.rom)
+        }
+        _ => Ok(assembler.rom),
+    }
+}
+
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    source
+        .lines()
+        .enumerate()
+        .flat_map(|(index, line)| {
+            let code = line.split('#').next().unwrap_or("");
+            code.split_whitespace().map(move |text| Token {
+                line: index + 1,
+                text,
+            })
+        })
+        .collect()
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b") {
+        i64::from_str_radix(binary, 2).ok()?
+    } else {
+        digits.parse().ok()?
+    };
+
+    Some(if negative { -value } else { value })
+}
+
+fn is_name(text: &str) -> bool {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_alphabetic() || first == '_' => {
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        }
+        _ => false,
+    }
+}
+
+fn unexpected(token: &Token) -> AsmError {
+    AsmError::UnexpectedToken {
+        line: token.line,
+        token: token.text.to_string(),
+    }
+}
+
+impl<'a> Assembler<'a> {
+    fn new(tokens: &'a [Token<'a>]) -> Assembler<'a> {
+        Assembler {
+            tokens,
+            position: 0,
+            rom: Vec::new(),
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            fixups: Vec::new(),
+            loops: Vec::new(),
+        }
+    }
+
+    fn run(&mut self) -> Result<(), AsmError> {
+        while let Some(token) = self.tokens.get(self.position) {
+            self.position += 1;
+            self.statement(token)?;
+        }
+
+        if let Some(&(line, _)) = self.loops.last() {
+            return Err(AsmError::UnbalancedLoop { line });
+        }
+
+        for fixup in &self.fixups {
+            let address = *self
+                .labels
+                .get(fixup.name)
+                .ok_or_else(|| AsmError::UndefinedName {
+                    line: fixup.line,
+                    name: fixup.name.to_string(),
+                })?;
+            if i64::from(address) > MAX_ADDRESS {
+                return Err(AsmError::ValueOutOfRange {
+                    line: fixup.line,
+                    value: address.into(),
+                });
+            }
+            self.rom[fixup.offset] |= (address >> 8) as u8;
+            self.rom[fixup.offset + 1] = address as u8;
+        }
+
+        Ok(())
+    }
+
+    fn statement(&mut self, token: &'a Token<'a>) -> Result<(), AsmError> {
+        let line = token.line;
+        match token.text {
+            ":" => {
+                let name = self.expect_name()?;
+                if self.labels.insert(name, self.address()).is_some() {
+                    return Err(AsmError::DuplicateLabel {
+                        line,
+                        name: name.to_string(),
+                    });
+                }
+            }
+            ":const" => {
+                let name = self.expect_name()?;
+                let value = self.expect()?;
+                let value = self.value(value)?;
+                self.constants.insert(name, value);
+            }
+            ":alias" => {
+                let name = self.expect_name()?;
+                let register = self.expect_register()?;
+                self.aliases.insert(name, register);
+            }
+            ":byte" => {
+                let value = self.expect()?;
+                let byte = self.byte(value)?;
+                self.rom.push(byte);
+            }
+            ":call" => {
+                let target = self.expect()?;
+                self.emit_address(0x2000, target.text, target.line)?;
+            }
+            "clear" => self.emit(0x00E0),
+            "return" | ";" => self.emit(0x00EE),
+            "scroll-right" => self.emit(0x00FB),
+            "scroll-left" => self.emit(0x00FC),
+            "exit" => self.emit(0x00FD),
+            "lores" => self.emit(0x00FE),
+            "hires" => self.emit(0x00FF),
+            "scroll-down" => {
+                let rows = self.expect()?;
+                let rows = self.nibble(rows)?;
+                self.emit(0x00C0 | rows);
+            }
+            "jump" => {
+                let target = self.expect()?;
+                self.emit_address(0x1000, target.text, target.line)?;
+            }
+            "jump0" => {
+                let target = self.expect()?;
+                self.emit_address(0xB000, target.text, target.line)?;
+            }
+            "loop" => self.loops.push((line, self.address())),
+            "again" => {
+                let (_, start) = self.loops.pop().ok_or(AsmError::UnbalancedLoop { line })?;
+                self.emit(0x1000 | start);
+            }
+            "i" => self.index_statement()?,
+            "delay" => {
+                self.expect_text(":=")?;
+                let vx = self.expect_register()?;
+                self.emit(0xF015 | vx << 8);
+            }
+            "buzzer" => {
+                self.expect_text(":=")?;
+                let vx = self.expect_register()?;
+                self.emit(0xF018 | vx << 8);
+            }
+            "sprite" => {
+                let vx = self.expect_register()?;
+                let vy = self.expect_register()?;
+                let rows = self.expect()?;
+                let rows = self.nibble(rows)?;
+                self.emit(0xD000 | vx << 8 | vy << 4 | rows);
+            }
+            "bcd" => self.register_operation(0xF033)?,
+            "save" => self.register_operation(0xF055)?,
+            "load" => self.register_operation(0xF065)?,
+            "saveflags" => self.register_operation(0xF075)?,
+            "loadflags" => self.register_operation(0xF085)?,
+            "if" => self.if_statement()?,
+            text => {
+                if let Some(vx) = self.register(text) {
+                    self.register_statement(vx)?;
+                } else if let Some(value) = parse_number(text) {
+                    let byte = self.byte_in_range(value, line)?;
+                    self.rom.push(byte);
+                } else if is_name(text) {
+                    // Octo calls a subroutine by writing the name of its label
+                    self.emit_address(0x2000, text, line)?;
+                } else {
+                    return Err(unexpected(token));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn register_statement(&mut self, vx: u16) -> Result<(), AsmError> {
+        let operator = self.expect()?;
+        let operand = self.expect()?;
+        let vy = self.register(operand.text);
+        let opcode = match (operator.text, vy) {
+            (":=", Some(vy)) => 0x8000 | vx << 8 | vy << 4,
+            (":=", None) => match operand.text {
+                "random" => {
+                    let mask = self.expect()?;
+                    0xC000 | vx << 8 | u16::from(self.byte(mask)?)
+                }
+                "delay" => 0xF007 | vx << 8,
+                "key" => 0xF00A | vx << 8,
+                _ => 0x6000 | vx << 8 | u16::from(self.byte(operand)?),
+            },
+            ("+=", Some(vy)) => 0x8004 | vx << 8 | vy << 4,
+            ("+=", None) => 0x7000 | vx << 8 | u16::from(self.byte(operand)?),
+            ("-=", Some(vy)) => 0x8005 | vx << 8 | vy << 4,
+            ("-=", None) => {
+                let byte = self.byte(operand)?;
+                0x7000 | vx << 8 | u16::from(byte.wrapping_neg())
+            }
+            ("|=", Some(vy)) => 0x8001 | vx << 8 | vy << 4,
+            ("&=", Some(vy)) => 0x8002 | vx << 8 | vy << 4,
+            ("^=", Some(vy)) => 0x8003 | vx << 8 | vy << 4,
+            (">>=", Some(vy)) => 0x8006 | vx << 8 | vy << 4,
+            ("=-", Some(vy)) => 0x8007 | vx << 8 | vy << 4,
+            ("<<=", Some(vy)) => 0x800E | vx << 8 | vy << 4,
+            ("|=", None)
+            | ("&=", None)
+            | ("^=", None)
+            | (">>=", None)
+            | ("=-", None)
+            | ("<<=", None) => return Err(unexpected(operand)),
+            _ => return Err(unexpected(operator)),
+        };
+
+        self.emit(opcode);
+        Ok(())
+    }
+
+    fn index_statement(&mut self) -> Result<(), AsmError> {
+        let operator = self.expect()?;
+        match operator.text {
+            ":=" => {
+                let operand = self.expect()?;
+                match operand.text {
+                    "hex" => self.register_operation(0xF029)?,
+                    "bighex" => self.register_operation(0xF030)?,
+                    _ => self.emit_address(0xA000, operand.text, operand.line)?,
+                }
+            }
+            "+=" => self.register_operation(0xF01E)?,
+            _ => return Err(unexpected(operator)),
+        }
+
+        Ok(())
+    }
+
+    // `if ... then` skips the statement that follows when the condition doesn't hold
+    fn if_statement(&mut self) -> Result<(), AsmError> {
+        let vx = self.expect_register()?;
+        let condition = self.expect()?;
+        let opcode = match condition.text {
+            "key" => 0xE0A1 | vx << 8,
+            "-key" => 0xE09E | vx << 8,
+            "==" | "!=" => {
+                let operand = self.expect()?;
+                let equal = condition.text == "==";
+                match self.register(operand.text) {
+                    Some(vy) if equal => 0x9000 | vx << 8 | vy << 4,
+                    Some(vy) => 0x5000 | vx << 8 | vy << 4,
+                    None if equal => 0x4000 | vx << 8 | u16::from(self.byte(operand)?),
+                    None => 0x3000 | vx << 8 | u16::from(self.byte(operand)?),
+                }
+            }
+            _ => return Err(unexpected(condition)),
+        };
+        self.expect_text("then")?;
+
+        self.emit(opcode);
+        Ok(())
+    }
+
+    fn register_operation(&mut self, opcode: u16) -> Result<(), AsmError> {
+        let vx = self.expect_register()?;
+        self.emit(opcode | vx << 8);
+        Ok(())
+    }
+
+    fn address(&self) -> u16 {
+        ORIGIN + self.rom.len() as u16
+    }
+
+    fn emit(&mut self, opcode: u16) {
+        self.rom.extend_from_slice(&opcode.to_be_bytes());
+    }
+
+    fn emit_address(&mut self, opcode: u16, target: &'a str, line: usize) -> Result<(), AsmError> {
+        if let Some(&address) = self.labels.get(target) {
+            self.emit(opcode | address);
+        } else if let Some(value) =
+            parse_number(target).or_else(|| self.constants.get(target).copied())
+        {
+            if !(0..=MAX_ADDRESS).contains(&value) {
+                return Err(AsmError::ValueOutOfRange { line, value });
+            }
+            self.emit(opcode | value as u16);
+        } else if is_name(target) {
+            self.fixups.push(Fixup {
+                offset: self.rom.len(),
+                name: target,
+                line,
+            });
+            self.emit(opcode);
+        } else {
+            return Err(AsmError::UnexpectedToken {
+                line,
+                token: target.to_string(),
+            });
+        }
+
+        Ok(())
+    }
+
+    fn register(&self, text: &str) -> Option<u16> {
+        if let Some(&register) = self.aliases.get(text) {
+            return Some(register);
+        }
+        let mut chars = text.chars();
+        match (chars.next(), chars.next(), chars.next()) {
+            (Some('v'), Some(index), None) | (Some('V'), Some(index), None) => {
+                index.to_digit(16).map(|index| index as u16)
+            }
+            _ => None,
+        }
+    }
+
+    fn value(&self, token: &Token) -> Result<i64, AsmError> {
+        if let Some(value) = parse_number(token.text) {
+            return Ok(value);
+        }
+        match self.constants.get(token.text) {
+            Some(&value) => Ok(value),
+            None if is_name(token.text) => Err(AsmError::UndefinedName {
+                line: token.line,
+                name: token.text.to_string(),
+            }),
+            None => Err(unexpected(token)),
+        }
+    }
+
+    fn byte(&self, token: &Token) -> Result<u8, AsmError> {
+        let value = self.value(token)?;
+        self.byte_in_range(value, token.line)
+    }
+
+    fn byte_in_range(&self, value: i64, line: usize) -> Result<u8, AsmError> {
+        if !(-128..=255).contains(&value) {
+            return Err(AsmError::ValueOutOfRange { line, value });
+        }
+        Ok(value as u8)
+    }
+
+    fn nibble(&self, token: &Token) -> Result<u16, AsmError> {
+        let value = self.value(token)?;
+        if !(0..=0xF).contains(&value) {
+            return Err(AsmError::ValueOutOfRange {
+                line: token.line,
+                value,
+            });
+        }
+        Ok(value as u16)
+    }
+
+    fn expect(&mut self) -> Result<&'a Token<'a>, AsmError> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .ok_or_else(|| AsmError::UnexpectedEnd {
+                line: self.tokens.last().map_or(1, |token| token.line),
+            })?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn expect_text(&mut self, text: &str) -> Result<(), AsmError> {
+        let token = self.expect()?;
+        if token.text != text {
+            return Err(unexpected(token));
+        }
+        Ok(())
+    }
+
+    fn expect_name(&mut self) -> Result<&'a str, AsmError> {
+        let token = self.expect()?;
+        if !is_name(token.text) {
+            return Err(unexpected(token));
+        }
+        Ok(token.text)
+    }
+
+    fn expect_register(&mut self) -> Result<u16, AsmError> {
+        let token = self.expect()?;
+        self.register(token.text).ok_or_else(|| unexpected(token))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_assembles_instructions() -> Result<(), AsmError> {
+        let rom = assemble(
+            "clear
+            v0 := 0x12
+            v1 += 3
+            v2 := v1
+            v2 <<= v0
+            i := 0x300
+            sprite v0 v1 5
+            delay := v2
+            v3 := key
+            return",
+        )?;
+
+        assert_eq!(
+            rom,
+            vec![
+                0x00, 0xE0, 0x60, 0x12, 0x71, 0x03, 0x82, 0x10, 0x82, 0x0E, 0xA3, 0x00, 0xD0, 0x15,
+                0xF2, 0x15, 0xF3, 0x0A, 0x00, 0xEE
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn it_resolves_labels_defined_before_and_after_use() -> Result<(), AsmError> {
+        let rom = assemble(
+            ": start
+              i := sprite-data
+              draw
+              jump start
+            : draw
+              sprite v0 v0 1
+              ;
+            : sprite-data
+              0b11110000",
+        )?;
+
+        assert_eq!(
+            rom,
+            vec![0xA2, 0x0A, 0x22, 0x06, 0x12, 0x00, 0xD0, 0x01, 0x00, 0xEE, 0xF0]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn it_starts_with_a_jump_to_main_when_it_isnt_first() -> Result<(), AsmError> {
+        let rom = assemble(
+            ": data 1 2
+            : main
+              loop
+                v0 += 1
+              again",
+        )?;
+
+        assert_eq!(rom, vec![0x12, 0x04, 0x01, 0x02, 0x70, 0x01, 0x12, 0x04]);
+        Ok(())
+    }
+
+    #[test]
+    fn it_supports_constants_aliases_and_conditions() -> Result<(), AsmError> {
+        let rom = assemble(
+            ":const speed 4
+            :alias x v5
+            if x != speed then x -= 1
+            if x == v2 then exit
+            if x key then clear",
+        )?;
+
+        assert_eq!(
+            rom,
+            vec![0x35, 0x04, 0x75, 0xFF, 0x95, 0x20, 0x00, 0xFD, 0xE5, 0xA1, 0x00, 0xE0]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn it_ignores_comments() -> Result<(), AsmError> {
+        assert_eq!(assemble("# nothing here\nclear # wipe")?, vec![0x00, 0xE0]);
+        Ok(())
+    }
+
+    #[test]
+    fn it_reports_errors_with_their_line() {
+        assert_eq!(
+            assemble("clear\njump nowhere"),
+            Err(AsmError::UndefinedName {
+                line: 2,
+                name: "nowhere".to_string()
+            })
+        );
+        assert_eq!(
+            assemble("v0 := 256"),
+            Err(AsmError::ValueOutOfRange {
+                line: 1,
+                value: 256
+            })
+        );
+        assert_eq!(
+            assemble("clear\nloop"),
+            Err(AsmError::UnbalancedLoop { line: 2 })
+        );
+        assert_eq!(assemble("v0 :="), Err(AsmError::UnexpectedEnd { line: 1 }));
+    }
+}
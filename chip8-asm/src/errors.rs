@@ -0,0 +1,69 @@
+/// Errors found while assembling, each one points at the line it was found on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AsmError {
+    /// Whether the source ended in the middle of a statement
+    UnexpectedEnd {
+        /// Last line of the source
+        line: usize,
+    },
+    /// Whether a token doesn't belong where it was found
+    UnexpectedToken {
+        /// Line the token is on
+        line: usize,
+        /// The token itself
+        token: String,
+    },
+    /// Whether a label or constant is used without being defined anywhere
+    UndefinedName {
+        /// Line it was used on
+        line: usize,
+        /// Name of the label or constant
+        name: String,
+    },
+    /// Whether the same label is defined twice
+    DuplicateLabel {
+        /// Line of the second definition
+        line: usize,
+        /// Name of the label
+        name: String,
+    },
+    /// Whether a value doesn't fit in the operand it was given to
+    ValueOutOfRange {
+        /// Line the value is on
+        line: usize,
+        /// The value
+        value: i64,
+    },
+    /// Whether a `loop` has no matching `again` or the other way around
+    UnbalancedLoop {
+        /// Line of the unmatched `loop` or `again`
+        line: usize,
+    },
+}
+
+impl std::error::Error for AsmError {}
+
+impl std::fmt::Display for AsmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AsmError::UnexpectedEnd { line } => {
+                write!(f, "line {}: unexpected end of the source", line)
+            }
+            AsmError::UnexpectedToken { line, token } => {
+                write!(f, "line {}: unexpected `{}`", line, token)
+            }
+            AsmError::UndefinedName { line, name } => {
+                write!(f, "line {}: `{}` is not defined", line, name)
+            }
+            AsmError::DuplicateLabel { line, name } => {
+                write!(f, "line {}: label `{}` is already defined", line, name)
+            }
+            AsmError::ValueOutOfRange { line, value } => {
+                write!(f, "line {}: {} doesn't fit in the operand", line, value)
+            }
+            AsmError::UnbalancedLoop { line } => {
+                write!(f, "line {}: `loop` and `again` don't match", line)
+            }
+        }
+    }
+}
@@ -0,0 +1,20 @@
+#![warn(missing_docs)]
+
+//! An assembler for chip8 programs using the syntax of Octo
+//!
+//! It turns a textual program into a rom ready to be given to `chip8-core` or any other interpreter
+//!
+//! The supported part of the syntax covers what most programs need:
+//!
+//! - labels with `: name`, calling a subroutine by writing its name, `:call`, `jump` and `jump0`
+//! - constants with `:const name value` and register names with `:alias name vX`
+//! - data as bare numbers in decimal, `0x` hexadecimal or `0b` binary, or with `:byte`
+//! - every instruction, like `v0 := 5`, `v1 += v2`, `i := hex v0`, `sprite v0 v1 5` or `bcd v3`
+//! - `if vX == value then` style conditions and `loop` ... `again`
+//! - comments starting with `#`
+
+mod assembler;
+mod errors;
+
+pub use assembler::{assemble, ORIGIN};
+pub use errors::AsmError;
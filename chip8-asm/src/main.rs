@@ -0,0 +1,28 @@
+use std::{error::Error, fs, path::PathBuf};
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "chip8-asm")]
+struct CliArgs {
+    /// Assembly source to read
+    source: PathBuf,
+    /// Where to write the rom, next to the source with a .ch8 extension by default
+    #[structopt(long = "output", short = "o")]
+    output: Option<PathBuf>,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let cli_args = CliArgs::from_args();
+    let source = fs::read_to_string(&cli_args.source)?;
+    let rom = chip8_asm::assemble(&source)
+        .map_err(|error| format!("{}: {}", cli_args.source.display(), error))?;
+
+    let output = match cli_args.output {
+        Some(output) => output,
+        None => cli_args.source.with_extension("ch8"),
+    };
+    fs::write(&output, &rom)?;
+    println!("Wrote {} bytes to {}", rom.len(), output.display());
+
+    Ok(())
+}
@@ -0,0 +1,97 @@
+//! Compares `FrameBuffer::to_rgba` and `FrameBuffer::set_pixels` against the per-pixel
+//! loops frontends used to write themselves
+//!
+//! `cargo bench -p chip8-core --bench framebuffer`
+
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+use chip8_core::{FrameBuffer, Palette};
+
+const FRAMES: u32 = 20_000;
+
+/// A high resolution display with every third pixel on
+fn framebuffer() -> FrameBuffer {
+    let mut frame_buffer = FrameBuffer::new(128, 64);
+    for y in 0..64 {
+        for x in (y % 3..128).step_by(3) {
+            frame_buffer.set_pixel(x, y, true);
+        }
+    }
+    frame_buffer
+}
+
+fn time(mut frame: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        frame();
+    }
+    start.elapsed()
+}
+
+fn main() {
+    let frame_buffer = framebuffer();
+    let palette = Palette::default();
+    let mut buffer = vec![0; frame_buffer.width() * frame_buffer.height() * 4];
+
+    let naive_rgba = time(|| {
+        for y in 0..frame_buffer.height() {
+            for x in 0..frame_buffer.width() {
+                let color = if frame_buffer.get_pixel(x, y) {
+                    palette.foreground
+                } else {
+                    palette.background
+                };
+                let index = (x + y * frame_buffer.width()) * 4;
+                buffer[index..index + 4].copy_from_slice(&color);
+            }
+        }
+        black_box(&buffer);
+    });
+    let to_rgba = time(|| {
+        frame_buffer.to_rgba(&palette, &mut buffer);
+        black_box(&buffer);
+    });
+
+    let naive_lit = time(|| {
+        let mut lit = 0;
+        for y in 0..frame_buffer.height() {
+            for x in 0..frame_buffer.width() {
+                if frame_buffer.get_pixel(x, y) {
+                    lit += black_box(x + y);
+                }
+            }
+        }
+        black_box(lit);
+    });
+    let set_pixels = time(|| {
+        let lit: usize = frame_buffer
+            .set_pixels()
+            .map(|(x, y)| black_box(x + y))
+            .sum();
+        black_box(lit);
+    });
+
+    println!(
+        "per-pixel rgba:  {:?} ({:?}/frame)",
+        naive_rgba,
+        naive_rgba / FRAMES
+    );
+    println!(
+        "to_rgba:         {:?} ({:?}/frame)",
+        to_rgba,
+        to_rgba / FRAMES
+    );
+    println!(
+        "per-pixel lit:   {:?} ({:?}/frame)",
+        naive_lit,
+        naive_lit / FRAMES
+    );
+    println!(
+        "set_pixels:      {:?} ({:?}/frame)",
+        set_pixels,
+        set_pixels / FRAMES
+    );
+}
@@ -0,0 +1,90 @@
+//! Compares the cost of a cycle when the keyboard is polled every time
+//! against one where `has_pending_events` lets the core skip the update
+//!
+//! `cargo bench -p chip8-core --bench input_polling`
+
+use std::time::{Duration, Instant};
+
+use chip8_core::{
+    Audio, Chip8, Chip8Error, KeyEvent, Keyboard, NumberGenerator, ProgramEndDetection, Quirks,
+};
+
+const CYCLES: u32 = 1_000_000;
+
+/// Jumps to itself forever
+const ROM: [u8; 2] = [0x12, 0x00];
+
+struct SilentAudio;
+impl Audio for SilentAudio {
+    fn play(&self, _duration: Duration) -> Result<(), Chip8Error> {
+        Ok(())
+    }
+
+    fn stop(&self) -> Result<(), Chip8Error> {
+        Ok(())
+    }
+}
+
+struct FixedNumberGenerator;
+impl NumberGenerator for FixedNumberGenerator {
+    fn generate(&self) -> Result<u8, Chip8Error> {
+        Ok(0)
+    }
+}
+
+/// Reports the whole keypad on every poll, like a frontend mapping every key would
+struct ScanningKeyboard {
+    report_pending_events: bool,
+}
+
+impl Keyboard for ScanningKeyboard {
+    fn poll_events(&mut self) -> Vec<KeyEvent> {
+        (0..16)
+            .map(|key| KeyEvent::Up(std::hint::black_box(key)))
+            .collect()
+    }
+
+    fn has_pending_events(&mut self) -> bool {
+        self.report_pending_events
+    }
+}
+
+fn run(report_pending_events: bool) -> Result<Duration, Chip8Error> {
+    let mut chip8 = Chip8::without_graphics(
+        Box::new(FixedNumberGenerator),
+        Box::new(SilentAudio),
+        Box::new(ScanningKeyboard {
+            report_pending_events,
+        }),
+        Quirks::default(),
+    );
+    chip8.set_program_end_detection(ProgramEndDetection {
+        jump_to_self: false,
+        ..ProgramEndDetection::default()
+    });
+    chip8.load_program(ROM.to_vec())?;
+
+    let start = Instant::now();
+    for _ in 0..CYCLES {
+        chip8.emulate_cycle()?;
+    }
+    Ok(start.elapsed())
+}
+
+fn main() -> Result<(), Chip8Error> {
+    let always_polling = run(true)?;
+    let skipping = run(false)?;
+
+    println!(
+        "always polling:      {:?} ({:?}/cycle)",
+        always_polling,
+        always_polling / CYCLES
+    );
+    println!(
+        "no pending events:   {:?} ({:?}/cycle)",
+        skipping,
+        skipping / CYCLES
+    );
+
+    Ok(())
+}
@@ -0,0 +1,39 @@
+//! Sends the JSON commands read on standard input to the control socket of a running interpreter
+//! and prints the responses, like `{"command":"pause"}` or `{"command":"key","key":5,"down":true}`
+//!
+//! `cargo run -p chip8-core --features control-socket --example control -- <socket>`
+
+use std::{
+    env,
+    error::Error,
+    io::{self, BufRead},
+};
+
+use chip8_core::{ControlClient, ControlCommand};
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let socket = env::args()
+        .nth(1)
+        .ok_or("Usage: control <socket>, with the commands on standard input")?;
+    let mut client = ControlClient::connect(&socket)?;
+
+    for line in io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let command: ControlCommand = match line.parse() {
+            Ok(command) => command,
+            Err(error) => {
+                eprintln!("{}", error);
+                continue;
+            }
+        };
+        match client.send(&command)? {
+            response if response.ok => println!("ok"),
+            response => println!("error: {}", response.error.unwrap_or_default()),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,62 @@
+//! Runs a rom without any window, audio or keyboard and prints the final frame to the terminal
+//!
+//! `cargo run -p chip8-core --example headless`
+
+use std::time::Duration;
+
+use chip8_core::{Audio, Chip8, Chip8Error, KeyEvent, Keyboard, NumberGenerator, Quirks, State};
+
+const ROM: &[u8] = include_bytes!("../../roms/IBM Logo.ch8");
+const CYCLES: usize = 1000;
+
+struct SilentAudio;
+impl Audio for SilentAudio {
+    fn play(&self, _duration: Duration) -> Result<(), Chip8Error> {
+        Ok(())
+    }
+
+    fn stop(&self) -> Result<(), Chip8Error> {
+        Ok(())
+    }
+}
+
+struct IdleKeyboard;
+impl Keyboard for IdleKeyboard {
+    fn poll_events(&mut self) -> Vec<KeyEvent> {
+        Vec::new()
+    }
+}
+
+struct FixedNumberGenerator;
+impl NumberGenerator for FixedNumberGenerator {
+    fn generate(&self) -> Result<u8, Chip8Error> {
+        Ok(4)
+    }
+}
+
+fn main() -> Result<(), Chip8Error> {
+    let mut chip8 = Chip8::without_graphics(
+        Box::new(FixedNumberGenerator),
+        Box::new(SilentAudio),
+        Box::new(IdleKeyboard),
+        Quirks::default(),
+    );
+    chip8.load_program(ROM.to_vec())?;
+
+    for _ in 0..CYCLES {
+        if let State::Exit = chip8.emulate_cycle()? {
+            break;
+        }
+    }
+
+    let frame_buffer = chip8.framebuffer();
+    for row in frame_buffer.pixels().chunks(frame_buffer.width()) {
+        let line: String = row
+            .iter()
+            .map(|pixel| if *pixel == 1 { '#' } else { ' ' })
+            .collect();
+        println!("{}", line);
+    }
+
+    Ok(())
+}
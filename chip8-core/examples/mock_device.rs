@@ -0,0 +1,106 @@
+//! Implements the core traits for a scripted mock device, the way a test harness would
+//!
+//! The keyboard replays a list of key events and the audio device records every call,
+//! which lets the example assert on what the rom did
+//!
+//! `cargo run -p chip8-core --example mock_device`
+
+use std::{cell::RefCell, collections::VecDeque, rc::Rc, time::Duration};
+
+use chip8_core::{
+    Audio, Chip8, Chip8Error, Frame, Graphics, KeyEvent, Keyboard, NumberGenerator,
+    ProgramEndDetection, Quirks, State,
+};
+
+/// Sets the sound timer to 2 and then spins forever
+const ROM: [u8; 6] = [
+    0x60, 0x02, // V0 = 2
+    0xF0, 0x18, // sound timer = V0
+    0x12, 0x04, // jump to self
+];
+
+#[derive(Debug, PartialEq)]
+enum AudioCall {
+    Play(Duration),
+    Stop,
+}
+
+struct RecordingAudio {
+    calls: Rc<RefCell<Vec<AudioCall>>>,
+}
+
+impl Audio for RecordingAudio {
+    fn play(&self, duration: Duration) -> Result<(), Chip8Error> {
+        self.calls.borrow_mut().push(AudioCall::Play(duration));
+        Ok(())
+    }
+
+    fn stop(&self) -> Result<(), Chip8Error> {
+        self.calls.borrow_mut().push(AudioCall::Stop);
+        Ok(())
+    }
+}
+
+/// Feeds scripted key events on every cycle and quits when the script runs out
+struct ScriptedKeyboard {
+    script: VecDeque<Vec<KeyEvent>>,
+}
+
+impl Keyboard for ScriptedKeyboard {
+    fn poll_events(&mut self) -> Vec<KeyEvent> {
+        self.script
+            .pop_front()
+            .unwrap_or_else(|| vec![KeyEvent::Quit])
+    }
+}
+
+struct NoGraphics;
+impl Graphics for NoGraphics {
+    fn draw(&mut self, _frame: &Frame) -> Result<(), Chip8Error> {
+        Ok(())
+    }
+}
+
+struct FixedNumberGenerator;
+impl NumberGenerator for FixedNumberGenerator {
+    fn generate(&self) -> Result<u8, Chip8Error> {
+        Ok(0)
+    }
+}
+
+fn main() -> Result<(), Chip8Error> {
+    let calls = Rc::new(RefCell::new(Vec::new()));
+    let script = vec![Vec::new(); 5].into_iter().collect();
+
+    let mut chip8 = Chip8::with_quirks(
+        Box::new(FixedNumberGenerator),
+        Box::new(RecordingAudio {
+            calls: Rc::clone(&calls),
+        }),
+        Box::new(ScriptedKeyboard { script }),
+        Box::new(NoGraphics),
+        Quirks::default(),
+    );
+    // Keep spinning on the jump to self until the keyboard quits instead of finishing there
+    chip8.set_program_end_detection(ProgramEndDetection {
+        jump_to_self: false,
+        ..ProgramEndDetection::default()
+    });
+    chip8.load_program(ROM.to_vec())?;
+
+    let mut cycles = 0;
+    while let State::Continue = chip8.emulate_cycle()? {
+        cycles += 1;
+    }
+
+    println!("Ran {} cycles, audio calls: {:?}", cycles, calls.borrow());
+    assert_eq!(
+        *calls.borrow(),
+        vec![
+            AudioCall::Play(Duration::from_nanos(1_000_000_000 / 60) * 2),
+            AudioCall::Stop
+        ]
+    );
+
+    Ok(())
+}
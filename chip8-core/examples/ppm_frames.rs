@@ -0,0 +1,110 @@
+//! Custom `Graphics` backend that writes every distinct frame as a PPM image
+//!
+//! `cargo run -p chip8-core --example ppm_frames -- <output-dir>`
+
+use std::{env, fs, path::PathBuf, time::Duration};
+
+use chip8_core::{
+    Audio, Chip8, Chip8Error, Frame, FrameBuffer, Graphics, KeyEvent, Keyboard, NumberGenerator,
+    Palette, Pipeline, Quirks, Scaler, State,
+};
+
+const ROM: &[u8] = include_bytes!("../../roms/IBM Logo.ch8");
+const CYCLES: usize = 1000;
+
+struct PpmGraphics {
+    output_dir: PathBuf,
+    pipeline: Pipeline,
+    previous_frame: Option<FrameBuffer>,
+    frames_written: usize,
+}
+
+impl PpmGraphics {
+    fn new(output_dir: PathBuf) -> PpmGraphics {
+        PpmGraphics {
+            output_dir,
+            pipeline: Pipeline::new(Palette::default()).with_stage(Scaler::new(4)),
+            previous_frame: None,
+            frames_written: 0,
+        }
+    }
+}
+
+impl Graphics for PpmGraphics {
+    fn draw(&mut self, frame: &Frame) -> Result<(), Chip8Error> {
+        let frame_buffer = frame.framebuffer();
+        if self.previous_frame.as_ref() == Some(frame_buffer) {
+            return Ok(());
+        }
+        self.previous_frame = Some(frame_buffer.clone());
+
+        let image = self.pipeline.process(
+            frame_buffer.pixels(),
+            frame_buffer.width() as u32,
+            frame_buffer.height() as u32,
+        );
+        let mut ppm = format!("P6\n{} {}\n255\n", image.width(), image.height()).into_bytes();
+        for pixel in image.pixels().chunks(4) {
+            ppm.extend_from_slice(&pixel[..3]);
+        }
+
+        let path = self
+            .output_dir
+            .join(format!("frame-{:05}.ppm", self.frames_written));
+        fs::write(path, ppm).map_err(|error| Chip8Error::GraphicsError(error.into()))?;
+        self.frames_written += 1;
+
+        Ok(())
+    }
+}
+
+struct SilentAudio;
+impl Audio for SilentAudio {
+    fn play(&self, _duration: Duration) -> Result<(), Chip8Error> {
+        Ok(())
+    }
+
+    fn stop(&self) -> Result<(), Chip8Error> {
+        Ok(())
+    }
+}
+
+struct IdleKeyboard;
+impl Keyboard for IdleKeyboard {
+    fn poll_events(&mut self) -> Vec<KeyEvent> {
+        Vec::new()
+    }
+}
+
+struct FixedNumberGenerator;
+impl NumberGenerator for FixedNumberGenerator {
+    fn generate(&self) -> Result<u8, Chip8Error> {
+        Ok(4)
+    }
+}
+
+fn main() -> Result<(), Chip8Error> {
+    let output_dir = env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| env::temp_dir().join("chip8-frames"));
+    fs::create_dir_all(&output_dir)?;
+
+    let mut chip8 = Chip8::with_quirks(
+        Box::new(FixedNumberGenerator),
+        Box::new(SilentAudio),
+        Box::new(IdleKeyboard),
+        Box::new(PpmGraphics::new(output_dir.clone())),
+        Quirks::default(),
+    );
+    chip8.load_program(ROM.to_vec())?;
+
+    for _ in 0..CYCLES {
+        if let State::Exit = chip8.emulate_cycle()? {
+            break;
+        }
+    }
+
+    println!("Frames written to {}", output_dir.display());
+    Ok(())
+}
@@ -0,0 +1,127 @@
+//! Differential fuzzing of `chip8-core` against a minimal reference interpreter
+//!
+//! Every input is turned into a program, run instruction by instruction through both, and the
+//! machine state is compared after each one. Any difference panics, which libFuzzer reports as
+//! a crash with the input that caused it
+
+mod reference;
+
+use chip8_core::{
+    Chip8, Chip8Error, Chip8Variant, Component, NumberGenerator, ProgramEndDetection, Quirks, State,
+};
+
+use reference::{Outcome, Reference, HEIGHT, MEMORY_SIZE, WIDTH};
+
+// Programs that loop forever are cut short, the interesting part is usually at the start
+const MAX_INSTRUCTIONS: usize = 2_000;
+
+struct FixedNumberGenerator(u8);
+
+impl NumberGenerator for FixedNumberGenerator {
+    fn generate(&self) -> Result<u8, Chip8Error> {
+        Ok(self.0)
+    }
+}
+
+/// Inputs are a byte of quirk flags, the byte CXNN draws from, and the program itself
+pub fn run(data: &[u8]) {
+    let (flags, random, program) = match data {
+        [flags, random, program @ ..] => (*flags, *random, program),
+        _ => return,
+    };
+    let quirks = Quirks {
+        shift_uses_vy: flags & 0x01 != 0,
+        load_store_increments_i: flags & 0x02 != 0,
+        logic_resets_vf: flags & 0x04 != 0,
+        jump_uses_vx: flags & 0x08 != 0,
+        wrap_sprites: flags & 0x10 != 0,
+        display_wait: false,
+    };
+    let program = &program[..program.len().min(MEMORY_SIZE - 0x200)];
+
+    let mut chip8 = Chip8::builder()
+        .variant(Chip8Variant::Chip8)
+        .quirks(quirks)
+        .rng(Box::new(FixedNumberGenerator(random)))
+        .build();
+    chip8.set_component_enabled(Component::Timers, false);
+    chip8.set_program_end_detection(ProgramEndDetection {
+        return_with_empty_stack: false,
+        jump_to_self: false,
+    });
+    chip8
+        .load_program(program.to_vec())
+        .expect("the program fits in memory");
+    let mut reference = Reference::new(chip8.memory(), quirks, random);
+    compare(&chip8, &reference, "loading the program");
+
+    for _ in 0..MAX_INSTRUCTIONS {
+        let address = reference.pc;
+        let opcode = chip8
+            .read_memory(address, 2)
+            .map(|bytes| format!("{:02X}{:02X}", bytes[0], bytes[1]))
+            .unwrap_or_default();
+        let step = format!("running {} at {:#05X}", opcode, address);
+
+        match (chip8.run_cycles(1), reference.step()) {
+            (Ok(State::Continue), Outcome::Ran) => compare(&chip8, &reference, &step),
+            (Ok(State::WaitingForKey), Outcome::WaitingForKey) | (Err(_), Outcome::Fault) => {
+                return compare(&chip8, &reference, &step)
+            }
+            (core, reference) => panic!(
+                "{}: chip8-core returned {:?}, the reference {:?}",
+                step, core, reference
+            ),
+        }
+    }
+}
+
+fn compare(chip8: &Chip8, reference: &Reference, step: &str) {
+    assert_eq!(chip8.program_counter(), reference.pc, "PC after {}", step);
+    assert_eq!(chip8.index_register(), reference.i, "I after {}", step);
+    assert_eq!(chip8.v_registers(), &reference.v, "V after {}", step);
+    assert_eq!(
+        &chip8.stack()[..chip8.stack_pointer() as usize],
+        &reference.stack[..],
+        "stack after {}",
+        step
+    );
+    if let Some(address) = (0..MEMORY_SIZE).find(|a| chip8.memory()[*a] != reference.memory[*a]) {
+        panic!(
+            "memory at {:#05X} after {}: chip8-core has {:02X}, the reference {:02X}",
+            address,
+            step,
+            chip8.memory()[address],
+            reference.memory[address]
+        );
+    }
+
+    let framebuffer = chip8.framebuffer();
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            assert_eq!(
+                framebuffer.get_pixel(x, y),
+                reference.display[y][x],
+                "pixel {},{} after {}",
+                x,
+                y,
+                step
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs, path::Path};
+
+    #[test]
+    fn it_passes_on_its_own_corpus() {
+        let corpus = Path::new(env!("CARGO_MANIFEST_DIR")).join("corpus/differential");
+        for entry in fs::read_dir(corpus).expect("the corpus is checked in") {
+            run(&fs::read(entry.expect("the corpus can be read").path())
+                .expect("seeds can be read"));
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use chip8_core::Quirks;
+
+pub const MEMORY_SIZE: usize = 4096;
+pub const WIDTH: usize = 64;
+pub const HEIGHT: usize = 32;
+
+/// What running one instruction did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Ran,
+    /// FX0A with no key pressed, the program can't go on
+    WaitingForKey,
+    /// The instruction is invalid or accesses something outside of the machine
+    Fault,
+}
+
+/// The original CHIP-8, written straight from the instruction set with no shortcuts
+///
+/// It is kept as plain as possible on purpose, so it can be trusted to tell when `chip8-core`
+/// computes something different. Timers and keys are left out, the harness runs both with
+/// the timers frozen and no key pressed
+#[derive(Debug, Clone)]
+pub struct Reference {
+    pub memory: Vec<u8>,
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub stack: Vec<u16>,
+    pub display: [[bool; WIDTH]; HEIGHT],
+    pub quirks: Quirks,
+    pub random: u8,
+}
+
+impl Reference {
+    /// Starts from `memory`, which already holds the font and the program
+    pub fn new(memory: &[u8], quirks: Quirks, random: u8) -> Reference {
+        Reference {
+            memory: memory.to_vec(),
+            v: [0; 16],
+            i: 0,
+            pc: 0x200,
+            stack: Vec::new(),
+            display: [[false; WIDTH]; HEIGHT],
+            quirks,
+            random,
+        }
+    }
+
+    pub fn step(&mut self) -> Outcome {
+        let pc = self.pc as usize;
+        if pc + 2 > MEMORY_SIZE {
+            return Outcome::Fault;
+        }
+        let opcode = u16::from_be_bytes([self.memory[pc], self.memory[pc + 1]]);
+        let x = ((opcode >> 8) & 0xF) as usize;
+        let y = ((opcode >> 4) & 0xF) as usize;
+        let n = (opcode & 0xF) as u8;
+        let nn = (opcode & 0xFF) as u8;
+        let nnn = opcode & 0xFFF;
+        let mut next = self.pc + 2;
+
+        match opcode >> 12 {
+            0x0 if opcode == 0x00E0 => self.display = [[false; WIDTH]; HEIGHT],
+            0x0 if opcode == 0x00EE => match self.stack.pop() {
+                Some(address) => next = address + 2,
+                None => return Outcome::Fault,
+            },
+            0x1 => next = nnn,
+            0x2 => {
+                if self.stack.len() == 16 {
+                    return Outcome::Fault;
+                }
+                self.stack.push(self.pc);
+                next = nnn;
+            }
+            0x3 if self.v[x] == nn => next += 2,
+            0x4 if self.v[x] != nn => next += 2,
+            0x5 if self.v[x] == self.v[y] => next += 2,
+            0x3..=0x5 => (),
+            0x6 => self.v[x] = nn,
+            0x7 => self.v[x] = self.v[x].wrapping_add(nn),
+            0x8 => match n {
+                0x0 => self.v[x] = self.v[y],
+                0x1..=0x3 => {
+                    self.v[x] = match n {
+                        0x1 => self.v[x] | self.v[y],
+                        0x2 => self.v[x] & self.v[y],
+                        _ => self.v[x] ^ self.v[y],
+                    };
+                    if self.quirks.logic_resets_vf {
+                        self.v[0xF] = 0;
+                    }
+                }
+                0x4 => {
+                    let (sum, carry) = self.v[x].overflowing_add(self.v[y]);
+                    self.v[x] = sum;
+                    self.v[0xF] = carry as u8;
+                }
+                0x5 => {
+                    let (difference, borrow) = self.v[x].overflowing_sub(self.v[y]);
+                    self.v[x] = difference;
+                    self.v[0xF] = !borrow as u8;
+                }
+                0x7 => {
+                    let (difference, borrow) = self.v[y].overflowing_sub(self.v[x]);
+                    self.v[x] = difference;
+                    self.v[0xF] = !borrow as u8;
+                }
+                0x6 | 0xE => {
+                    let value = if self.quirks.shift_uses_vy {
+                        self.v[y]
+                    } else {
+                        self.v[x]
+                    };
+                    if n == 0x6 {
+                        self.v[x] = value >> 1;
+                        self.v[0xF] = value & 1;
+                    } else {
+                        self.v[x] = value << 1;
+                        self.v[0xF] = value >> 7;
+                    }
+                }
+                _ => return Outcome::Fault,
+            },
+            0x9 if self.v[x] != self.v[y] => next += 2,
+            0x9 => (),
+            0xA => self.i = nnn,
+            0xB => {
+                let offset = if self.quirks.jump_uses_vx { x } else { 0 };
+                next = nnn + u16::from(self.v[offset]);
+            }
+            0xC => self.v[x] = self.random & nn,
+            0xD => {
+                if !self.draw(x, y, n) {
+                    return Outcome::Fault;
+                }
+            }
+            // No key is ever pressed
+            0xE if nn == 0x9E => (),
+            0xE if nn == 0xA1 => next += 2,
+            0xF => match nn {
+                // The timers are frozen at 0
+                0x07 => self.v[x] = 0,
+                0x0A => return Outcome::WaitingForKey,
+                0x15 | 0x18 => (),
+                0x1E => self.i = self.i.wrapping_add(u16::from(self.v[x])) & 0xFFF,
+                0x29 => self.i = u16::from(self.v[x] & 0xF) * 5,
+                0x33 => {
+                    let value = self.v[x];
+                    if !self.store(&[value / 100, value / 10 % 10, value % 10]) {
+                        return Outcome::Fault;
+                    }
+                }
+                0x55 => {
+                    let registers = self.v;
+                    if !self.store(&registers[..=x]) {
+                        return Outcome::Fault;
+                    }
+                    self.increment_i(x);
+                }
+                0x65 => {
+                    let start = self.i as usize;
+                    match self.memory.get(start..start + x + 1) {
+                        Some(bytes) => self.v[..=x].copy_from_slice(bytes),
+                        None => return Outcome::Fault,
+                    }
+                    self.increment_i(x);
+                }
+                _ => return Outcome::Fault,
+            },
+            _ => return Outcome::Fault,
+        }
+
+        self.pc = next;
+        Outcome::Ran
+    }
+
+    fn store(&mut self, bytes: &[u8]) -> bool {
+        let start = self.i as usize;
+        match self.memory.get_mut(start..start + bytes.len()) {
+            Some(memory) => {
+                memory.copy_from_slice(bytes);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn increment_i(&mut self, x: usize) {
+        if self.quirks.load_store_increments_i {
+            self.i = self.i.wrapping_add(x as u16 + 1);
+        }
+    }
+
+    fn draw(&mut self, x: usize, y: usize, rows: u8) -> bool {
+        let start = self.i as usize;
+        let sprite = match self.memory.get(start..start + rows as usize) {
+            Some(sprite) => sprite.to_vec(),
+            None => return false,
+        };
+        let left = self.v[x] as usize % WIDTH;
+        let top = self.v[y] as usize % HEIGHT;
+
+        self.v[0xF] = 0;
+        for (row, bits) in sprite.iter().enumerate() {
+            for col in 0..8 {
+                if bits & (0x80 >> col) == 0 {
+                    continue;
+                }
+                let (mut px, mut py) = (left + col, top + row);
+                if px >= WIDTH || py >= HEIGHT {
+                    if !self.quirks.wrap_sprites {
+                        continue;
+                    }
+                    px %= WIDTH;
+                    py %= HEIGHT;
+                }
+                if self.display[py][px] {
+                    self.v[0xF] = 1;
+                }
+                self.display[py][px] ^= true;
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,168 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{conditions::Condition, errors::Chip8Error, Chip8};
+
+/// Goals of a rom, each unlocked the first frame its condition holds
+///
+/// They are read from a toml file next to the rom, with the syntax of `Condition`:
+///
+/// ```toml
+/// [[achievement]]
+/// title = "On the board"
+/// description = "Score a point"
+/// condition = "0xH2F0>d0xH2F0"
+///
+/// [[achievement]]
+/// title = "Close call"
+/// description = "Win with a single life left"
+/// condition = "0xH2F1=1_v0=h10"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Achievements {
+    achievements: Vec<Achievement>,
+}
+
+/// A goal of a rom
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Achievement {
+    /// Name shown when it is unlocked
+    pub title: String,
+    /// What it takes to unlock it
+    pub description: String,
+    condition: Condition,
+    unlocked: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SavedAchievements {
+    #[serde(default)]
+    achievement: Vec<SavedAchievement>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SavedAchievement {
+    title: String,
+    #[serde(default)]
+    description: String,
+    condition: String,
+}
+
+impl Achievements {
+    /// Parses the contents of an achievements file
+    pub fn parse(achievements: &str) -> Result<Achievements, Chip8Error> {
+        let saved: SavedAchievements = toml::from_str(achievements)
+            .map_err(|error| Chip8Error::InvalidConfig(error.to_string()))?;
+        let achievements = saved
+            .achievement
+            .into_iter()
+            .map(|achievement| {
+                let condition = achievement.condition.parse().map_err(|error| {
+                    Chip8Error::InvalidConfig(format!("{}: {}", achievement.title, error))
+                })?;
+                Ok(Achievement {
+                    title: achievement.title,
+                    description: achievement.description,
+                    condition,
+                    unlocked: false,
+                })
+            })
+            .collect::<Result<_, Chip8Error>>()?;
+        Ok(Achievements { achievements })
+    }
+
+    /// Where the achievements of a rom live, `pong.ch8` has them in `pong.achievements.toml`
+    pub fn path_for(rom_path: &Path) -> PathBuf {
+        rom_path.with_extension("achievements.toml")
+    }
+
+    /// Reads the achievements next to a rom, roms without any get `None`
+    pub fn load_for_rom(rom_path: &Path) -> Result<Option<Achievements>, Chip8Error> {
+        match fs::read_to_string(Achievements::path_for(rom_path)) {
+            Ok(achievements) => Achievements::parse(&achievements).map(Some),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Checks the conditions of the achievements still locked against the state `chip8` is in,
+    /// returning the ones that just unlocked. Meant to be called once per frame
+    pub fn check(&mut self, chip8: &Chip8) -> Vec<&Achievement> {
+        let mut unlocked = Vec::new();
+        for achievement in &mut self.achievements {
+            if !achievement.unlocked && achievement.condition.check(chip8) {
+                achievement.unlocked = true;
+                unlocked.push(&*achievement);
+            }
+        }
+        unlocked
+    }
+
+    /// Every achievement, in the order of the file
+    pub fn iter(&self) -> impl Iterator<Item = &Achievement> {
+        self.achievements.iter()
+    }
+}
+
+impl Achievement {
+    /// Whether its condition held at some point
+    pub fn is_unlocked(&self) -> bool {
+        self.unlocked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACHIEVEMENTS: &str = r#"
+        [[achievement]]
+        title = "Five"
+        description = "Count to five"
+        condition = "v0>=5"
+
+        [[achievement]]
+        title = "Ten"
+        condition = "v0=10"
+    "#;
+
+    #[test]
+    fn it_unlocks_each_achievement_once() -> Result<(), Chip8Error> {
+        let mut achievements = Achievements::parse(ACHIEVEMENTS)?;
+        let mut chip8 = Chip8::builder().build();
+        // Adds 1 to V0 forever
+        chip8.load_program(vec![0x70, 0x01, 0x12, 0x00])?;
+
+        let mut unlocked = Vec::new();
+        for _ in 0..15 {
+            chip8.run_frame(2)?;
+            unlocked.extend(
+                achievements
+                    .check(&chip8)
+                    .iter()
+                    .map(|achievement| achievement.title.clone()),
+            );
+        }
+
+        assert_eq!(unlocked, vec!["Five", "Ten"]);
+        assert!(achievements.iter().all(Achievement::is_unlocked));
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_names_the_achievement_with_an_invalid_condition() {
+        let achievements = "[[achievement]]\ntitle = \"Broken\"\ncondition = \"v0>>1\"";
+
+        match Achievements::parse(achievements) {
+            Err(Chip8Error::InvalidConfig(error)) => assert!(error.starts_with("Broken: ")),
+            other => panic!("Expected an invalid config, got {:?}", other),
+        }
+    }
+}
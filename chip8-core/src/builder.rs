@@ -0,0 +1,148 @@
+#[cfg(feature = "rom-config")]
+use crate::config::Chip8Config;
+use crate::{
+    devices::{NullAudio, NullKeyboard},
+    quirks::Quirks,
+    rng::SeededNumberGenerator,
+    traits::{Audio, Graphics, Keyboard, NumberGenerator},
+    variant::Chip8Variant,
+    Chip8, Font,
+};
+
+/// Builds a `Chip8`, only asking for the devices the frontend actually has
+///
+/// Anything left out gets a default that does nothing: no sound, no key presses and no graphics
+/// device, the framebuffer is still kept up to date for `Chip8::framebuffer`. Random numbers come
+/// from a generator with a fixed seed, so runs are reproducible until `rng` gives another one
+pub struct Chip8Builder {
+    random_number_generator: Option<Box<dyn NumberGenerator>>,
+    audio_device: Option<Box<dyn Audio>>,
+    keyboard_device: Option<Box<dyn Keyboard>>,
+    graphics_device: Option<Box<dyn Graphics>>,
+    variant: Chip8Variant,
+    quirks: Option<Quirks>,
+    font: Font,
+}
+
+impl Chip8Builder {
+    /// Starts from the defaults, behaving like `Chip8Variant::Chip8`
+    pub fn new() -> Chip8Builder {
+        Chip8Builder {
+            random_number_generator: None,
+            audio_device: None,
+            keyboard_device: None,
+            graphics_device: None,
+            variant: Chip8Variant::default(),
+            quirks: None,
+            font: Font::default(),
+        }
+    }
+
+    /// Behaves like `variant`, using its quirks unless `quirks` overrides them
+    pub fn variant(mut self, variant: Chip8Variant) -> Chip8Builder {
+        self.variant = variant;
+        self
+    }
+
+    /// Overrides the quirks of the variant
+    pub fn quirks(mut self, quirks: Quirks) -> Chip8Builder {
+        self.quirks = Some(quirks);
+        self
+    }
+
+    /// Behaves like the variant of `config` with its quirks, its speed is left to the frontend
+    #[cfg(feature = "rom-config")]
+    pub fn config(self, config: &Chip8Config) -> Chip8Builder {
+        self.variant(config.variant).quirks(config.quirks)
+    }
+
+    /// Sprites drawn by FX29 and FX30
+    pub fn font(mut self, font: Font) -> Chip8Builder {
+        self.font = font;
+        self
+    }
+
+    /// Generator used by CXNN
+    pub fn rng(mut self, random_number_generator: Box<dyn NumberGenerator>) -> Chip8Builder {
+        self.random_number_generator = Some(random_number_generator);
+        self
+    }
+
+    /// Device playing the sound while the sound timer is running
+    pub fn audio(mut self, audio_device: Box<dyn Audio>) -> Chip8Builder {
+        self.audio_device = Some(audio_device);
+        self
+    }
+
+    /// Device reporting the key presses
+    pub fn keyboard(mut self, keyboard_device: Box<dyn Keyboard>) -> Chip8Builder {
+        self.keyboard_device = Some(keyboard_device);
+        self
+    }
+
+    /// Device drawing the display whenever it changes
+    pub fn graphics(mut self, graphics_device: Box<dyn Graphics>) -> Chip8Builder {
+        self.graphics_device = Some(graphics_device);
+        self
+    }
+
+    /// Creates the interpreter
+    pub fn build(self) -> Chip8 {
+        let quirks = self.quirks.unwrap_or_else(|| self.variant.quirks());
+        let mut chip8 = Chip8::with_devices(
+            self.random_number_generator
+                .unwrap_or_else(|| Box::new(SeededNumberGenerator::new(0))),
+            self.audio_device.unwrap_or_else(|| Box::new(NullAudio)),
+            self.keyboard_device
+                .unwrap_or_else(|| Box::new(NullKeyboard)),
+            self.graphics_device,
+            self.variant,
+            quirks,
+        );
+        chip8.set_font(self.font);
+        chip8
+    }
+}
+
+impl Default for Chip8Builder {
+    fn default() -> Chip8Builder {
+        Chip8Builder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{errors::Chip8Error, State};
+
+    #[test]
+    fn it_builds_a_headless_interpreter_from_the_defaults() -> Result<(), Chip8Error> {
+        let mut chip8 = Chip8Builder::new().build();
+        chip8.load_program(vec![0x60, 0x2A, 0xD1, 0x15])?;
+
+        assert_eq!(chip8.run_frame(2)?, State::Continue);
+        assert_eq!(chip8.v_register(0), 0x2A);
+        assert_eq!(chip8.variant(), Chip8Variant::Chip8);
+        assert!(chip8.framebuffer().get_pixel(0, 0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_overrides_the_quirks_of_the_variant() -> Result<(), Chip8Error> {
+        let mut chip8 = Chip8Builder::new()
+            .variant(Chip8Variant::SuperChip)
+            .quirks(Quirks {
+                shift_uses_vy: true,
+                ..Chip8Variant::SuperChip.quirks()
+            })
+            .build();
+        chip8.load_program(vec![0x61, 0x04, 0x80, 0x16])?;
+
+        chip8.run_cycles(2)?;
+        assert_eq!(chip8.v_register(0), 0x02);
+        assert_eq!(chip8.variant(), Chip8Variant::SuperChip);
+
+        Ok(())
+    }
+}
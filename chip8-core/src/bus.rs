@@ -0,0 +1,65 @@
+use std::ops::Range;
+
+use crate::traits::Peripheral;
+
+struct Mapping {
+    addresses: Range<u16>,
+    peripheral: Box<dyn Peripheral>,
+}
+
+/// Routes the memory accesses of instructions to the peripherals mapped over RAM
+///
+/// RAM under a mapped range keeps a copy of the last bytes read from or written to the
+/// peripheral, so memory views and snapshots still show something meaningful
+#[derive(Default)]
+pub(crate) struct Bus {
+    mappings: Vec<Mapping>,
+}
+
+impl Bus {
+    /// Maps `peripheral` over `addresses`, ranges mapped earlier take precedence where they overlap
+    pub(crate) fn map(&mut self, addresses: Range<u16>, peripheral: Box<dyn Peripheral>) {
+        self.mappings.push(Mapping {
+            addresses,
+            peripheral,
+        });
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.mappings.clear();
+    }
+
+    /// Refreshes the bytes of `memory`, which starts at `address`, that belong to a peripheral
+    pub(crate) fn read(&mut self, address: u16, memory: &mut [u8]) {
+        if self.mappings.is_empty() {
+            return;
+        }
+        for (offset, byte) in memory.iter_mut().enumerate() {
+            let address = address.wrapping_add(offset as u16);
+            if let Some(mapping) = self.mapping(address) {
+                *byte = mapping.peripheral.read(address - mapping.addresses.start);
+            }
+        }
+    }
+
+    /// Hands the bytes of `memory`, which starts at `address`, to the peripherals they belong to
+    pub(crate) fn write(&mut self, address: u16, memory: &[u8]) {
+        if self.mappings.is_empty() {
+            return;
+        }
+        for (offset, byte) in memory.iter().enumerate() {
+            let address = address.wrapping_add(offset as u16);
+            if let Some(mapping) = self.mapping(address) {
+                mapping
+                    .peripheral
+                    .write(address - mapping.addresses.start, *byte);
+            }
+        }
+    }
+
+    fn mapping(&mut self, address: u16) -> Option<&mut Mapping> {
+        self.mappings
+            .iter_mut()
+            .find(|mapping| mapping.addresses.contains(&address))
+    }
+}
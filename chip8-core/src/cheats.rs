@@ -0,0 +1,193 @@
+use std::str::FromStr;
+
+/// How a cheat changes memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheatKind {
+    /// Writes the value every frame, so the program can never change it
+    Freeze,
+    /// Writes the value once, when it is added or enabled again
+    Patch,
+}
+
+/// A value written to an address of memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cheat {
+    /// Address written to
+    pub address: u16,
+    /// Byte written
+    pub value: u8,
+    /// Whether it is written every frame or only once
+    pub kind: CheatKind,
+}
+
+impl Cheat {
+    /// Keeps `address` at `value`
+    pub fn freeze(address: u16, value: u8) -> Cheat {
+        Cheat {
+            address,
+            value,
+            kind: CheatKind::Freeze,
+        }
+    }
+
+    /// Writes `value` at `address` once
+    pub fn patch(address: u16, value: u8) -> Cheat {
+        Cheat {
+            address,
+            value,
+            kind: CheatKind::Patch,
+        }
+    }
+}
+
+/// Parses `address:value` freezes and `address=value` patches, both in hex: `3F0:09`, `0x2A4=12`
+impl FromStr for Cheat {
+    type Err = String;
+
+    fn from_str(cheat: &str) -> Result<Self, Self::Err> {
+        let hex = |number: &str| {
+            let number = number.trim();
+            let number = number.strip_prefix("0x").unwrap_or(number);
+            u16::from_str_radix(number, 16).ok()
+        };
+        let parsed = match cheat.split_once(':') {
+            Some((address, value)) => Some((address, value, CheatKind::Freeze)),
+            None => cheat
+                .split_once('=')
+                .map(|(address, value)| (address, value, CheatKind::Patch)),
+        }
+        .and_then(|(address, value, kind)| {
+            let value = hex(value).filter(|value| *value <= 0xFF)?;
+            Some(Cheat {
+                address: hex(address)?,
+                value: value as u8,
+                kind,
+            })
+        });
+        parsed.ok_or_else(|| format!("Invalid cheat: {}", cheat))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    cheat: Cheat,
+    enabled: bool,
+    pending: bool,
+}
+
+/// Cheats applied by the interpreter at the end of every frame
+///
+/// Each cheat is identified by the index it was added at, which doesn't change when others are removed
+#[derive(Debug, Clone, Default)]
+pub struct Cheats {
+    entries: Vec<Option<Entry>>,
+}
+
+impl Cheats {
+    /// Adds an enabled cheat and returns its index
+    pub fn add(&mut self, cheat: Cheat) -> usize {
+        self.entries.push(Some(Entry {
+            cheat,
+            enabled: true,
+            pending: true,
+        }));
+        self.entries.len() - 1
+    }
+
+    /// Removes a cheat, memory keeps whatever it wrote last
+    pub fn remove(&mut self, index: usize) -> Option<Cheat> {
+        self.entries
+            .get_mut(index)
+            .and_then(Option::take)
+            .map(|entry| entry.cheat)
+    }
+
+    /// Removes every cheat
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Turns a cheat on or off, returns false when there is no cheat at `index`
+    ///
+    /// Enabling a patch again writes it once more
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) -> bool {
+        match self.entries.get_mut(index) {
+            Some(Some(entry)) => {
+                entry.pending |= enabled && !entry.enabled;
+                entry.enabled = enabled;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether the cheat at `index` is enabled
+    pub fn is_enabled(&self, index: usize) -> bool {
+        matches!(self.entries.get(index), Some(Some(entry)) if entry.enabled)
+    }
+
+    /// Every cheat with its index
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &Cheat)> + '_ {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| entry.as_ref().map(|entry| (index, &entry.cheat)))
+    }
+
+    /// Writes the enabled cheats to memory, returns whether any byte changed
+    pub(crate) fn apply(&mut self, memory: &mut [u8]) -> bool {
+        let mut changed = false;
+        for entry in self.entries.iter_mut().flatten() {
+            let due = entry.cheat.kind == CheatKind::Freeze || entry.pending;
+            if !entry.enabled || !due {
+                continue;
+            }
+            entry.pending = false;
+            if let Some(byte) = memory.get_mut(entry.cheat.address as usize) {
+                changed |= *byte != entry.cheat.value;
+                *byte = entry.cheat.value;
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_freezes_and_patches() {
+        assert_eq!("3F0:09".parse(), Ok(Cheat::freeze(0x3F0, 0x09)));
+        assert_eq!("0x2A4 = 12".parse(), Ok(Cheat::patch(0x2A4, 0x12)));
+        assert!("3F0:100".parse::<Cheat>().is_err());
+        assert!("lives".parse::<Cheat>().is_err());
+    }
+
+    #[test]
+    fn it_freezes_every_frame_and_patches_once() {
+        let mut cheats = Cheats::default();
+        let freeze = cheats.add(Cheat::freeze(0, 9));
+        let patch = cheats.add(Cheat::patch(1, 5));
+        let mut memory = [0; 2];
+
+        assert!(cheats.apply(&mut memory));
+        memory = [1, 1];
+        cheats.apply(&mut memory);
+        assert_eq!(memory, [9, 1]);
+
+        cheats.set_enabled(freeze, false);
+        cheats.set_enabled(patch, false);
+        cheats.set_enabled(patch, true);
+        memory = [1, 1];
+        cheats.apply(&mut memory);
+        assert_eq!(memory, [1, 5]);
+
+        assert_eq!(cheats.remove(freeze), Some(Cheat::freeze(0, 9)));
+        assert_eq!(
+            cheats.iter().map(|(index, _)| index).collect::<Vec<_>>(),
+            [patch]
+        );
+        assert!(!cheats.set_enabled(freeze, true));
+    }
+}
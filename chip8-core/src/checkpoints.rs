@@ -0,0 +1,79 @@
+use crate::{conditions::Condition, snapshot::Snapshot, Chip8};
+
+/// States kept automatically whenever one of the watched conditions holds, to practice a hard part
+/// of a game over and over
+///
+/// Watching for a value changing, like `0xH2F0!=d0xH2F0` for a level number kept at `0x2F0`,
+/// keeps the state at the start of every level for the frontend to go back to
+#[derive(Debug, Clone)]
+pub struct Checkpoints {
+    conditions: Vec<Condition>,
+    latest: Option<Snapshot>,
+}
+
+impl Checkpoints {
+    /// Watches `conditions`, without any checkpoint until one of them holds
+    pub fn new(conditions: Vec<Condition>) -> Checkpoints {
+        Checkpoints {
+            conditions,
+            latest: None,
+        }
+    }
+
+    /// Keeps the state `chip8` is in when any of the conditions holds, returning whether it did.
+    /// Meant to be called once per frame
+    pub fn check(&mut self, chip8: &Chip8) -> bool {
+        // Every condition is checked to keep their previous values up to date
+        let mut holds = false;
+        for condition in &mut self.conditions {
+            holds |= condition.check(chip8);
+        }
+        if holds {
+            self.latest = Some(chip8.snapshot());
+        }
+        holds
+    }
+
+    /// The state kept last, to restore with `Chip8::restore`
+    pub fn latest(&self) -> Option<&Snapshot> {
+        self.latest.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8Error;
+
+    #[test]
+    fn it_keeps_the_state_whenever_a_watched_value_changes() -> Result<(), Chip8Error> {
+        let mut chip8 = Chip8::builder().build();
+        // V0 counts frames and V1 goes up a level every 5 of them
+        chip8.load_program(vec![
+            0x70, 0x01, 0x30, 0x05, 0x12, 0x00, 0x71, 0x01, 0x60, 0x00, 0x12, 0x00,
+        ])?;
+        let level_changed = "v1!=dv1".parse().map_err(Chip8Error::InvalidConfig)?;
+        let mut checkpoints = Checkpoints::new(vec![level_changed]);
+
+        let mut taken = 0;
+        for _ in 0..12 {
+            chip8.run_frame(3)?;
+            if checkpoints.check(&chip8) {
+                taken += 1;
+            }
+        }
+        assert!(chip8.v_register(1) >= 2);
+        assert_eq!(taken, chip8.v_register(1));
+
+        let level = chip8.v_register(1);
+        chip8.run_frame(30)?;
+        assert_ne!(chip8.v_register(1), level);
+        if let Some(checkpoint) = checkpoints.latest() {
+            chip8.restore(checkpoint);
+        }
+        assert_eq!(chip8.v_register(1), level);
+        assert_eq!(chip8.v_register(0), 0);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+use std::time::Duration;
+
+/// Monotonic time source used to tick the timers at 60Hz in real time
+///
+/// Frontends pass one to `Chip8::set_clock` so the timers keep their pace however fast the
+/// instructions run, without one the timers tick once per frame
+pub trait Clock {
+    /// Time elapsed since an arbitrary starting point, it never goes backwards
+    fn now(&self) -> Duration;
+}
+
+/// Clock based on `std::time::Instant`
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug, Clone, Copy)]
+pub struct InstantClock {
+    start: std::time::Instant,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl InstantClock {
+    /// Creates a clock counting from now
+    pub fn new() -> InstantClock {
+        InstantClock {
+            start: std::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for InstantClock {
+    fn default() -> InstantClock {
+        InstantClock::new()
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Clock for InstantClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Clock based on `performance.now()`, for browsers where `Instant` isn't available
+#[cfg(target_arch = "wasm32")]
+#[derive(Debug, Clone)]
+pub struct PerformanceClock {
+    performance: web_sys::Performance,
+}
+
+#[cfg(target_arch = "wasm32")]
+impl PerformanceClock {
+    /// Creates a clock reading the performance timer of the window, if there is one
+    pub fn new() -> Option<PerformanceClock> {
+        let performance = web_sys::window()?.performance()?;
+        Some(PerformanceClock { performance })
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Clock for PerformanceClock {
+    fn now(&self) -> Duration {
+        Duration::from_secs_f64(self.performance.now() / 1000.0)
+    }
+}
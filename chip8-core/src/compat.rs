@@ -0,0 +1,274 @@
+//! Shims that keep code written against older versions of the core compiling
+//!
+//! Every item here is deprecated and points at its replacement
+
+/// The interface the core shipped with: a four device constructor and the first device traits
+///
+/// Devices written for the old traits are wrapped in an adapter and handed to `Chip8::new` as
+/// before:
+///
+/// ```
+/// # #![allow(deprecated)]
+/// use chip8_core::{compat::v1, Chip8, Chip8Error, NumberGenerator};
+///
+/// struct Speaker;
+///
+/// impl v1::Audio for Speaker {
+///     fn play(&self) -> Result<(), Chip8Error> {
+///         Ok(())
+///     }
+///
+///     fn stop(&self) -> Result<(), Chip8Error> {
+///         Ok(())
+///     }
+/// }
+/// # struct Dice;
+/// # impl NumberGenerator for Dice {
+/// #     fn generate(&self) -> Result<u8, Chip8Error> { Ok(4) }
+/// # }
+/// # struct Keys;
+/// # impl v1::Keyboard for Keys {
+/// #     fn update_state(&mut self, _keyboard: &mut [u8; 16]) -> bool { false }
+/// #     fn wait_next_key_press(&mut self) -> u8 { 0 }
+/// # }
+/// # struct Screen;
+/// # impl v1::Graphics for Screen {
+/// #     fn draw(&mut self, _graphics: &[u8]) -> Result<(), Chip8Error> { Ok(()) }
+/// # }
+///
+/// let chip8 = Chip8::new(
+///     Box::new(Dice),
+///     Box::new(v1::AudioAdapter::new(Speaker)),
+///     Box::new(v1::KeyboardAdapter::new(Keys)),
+///     Box::new(v1::GraphicsAdapter::new(Screen)),
+/// );
+/// ```
+#[allow(deprecated)]
+pub mod v1 {
+    use std::time::Duration;
+
+    use crate::{Chip8, Chip8Error, Frame, KeyEvent, NumberGenerator};
+
+    /// Instantiates the Chip8 the way `Chip8::new` used to, with the default quirks
+    #[deprecated(note = "use `Chip8::builder()`, or `Chip8::with_quirks` to pick the quirks")]
+    pub fn new(
+        random_number_generator: Box<dyn NumberGenerator>,
+        audio_device: Box<dyn crate::Audio>,
+        keyboard_device: Box<dyn crate::Keyboard>,
+        graphics_device: Box<dyn crate::Graphics>,
+    ) -> Chip8 {
+        Chip8::new(
+            random_number_generator,
+            audio_device,
+            keyboard_device,
+            graphics_device,
+        )
+    }
+
+    /// The first keyboard trait, which filled in the state of the keypad on every cycle
+    #[deprecated(note = "implement `chip8_core::Keyboard`, which reports `KeyEvent`s")]
+    pub trait Keyboard {
+        /// Updates the current state of the keyboard
+        ///
+        /// Returns true if the user triggered an exit event
+        fn update_state(&mut self, keyboard: &mut [u8; 16]) -> bool;
+        /// Add support for blocking and waiting for the next key press
+        ///
+        /// No longer called, FX0A waits for a key without blocking the frontend
+        fn wait_next_key_press(&mut self) -> u8;
+    }
+
+    /// The first graphics trait, which got the display as a byte per pixel
+    #[deprecated(note = "implement `chip8_core::Graphics`, which gets a `Frame`")]
+    pub trait Graphics {
+        /// Provides the current state of the graphics so it can be drawn on screen
+        fn draw(&mut self, graphics: &[u8]) -> Result<(), Chip8Error>;
+    }
+
+    /// The first audio trait, whose sound didn't know how long it would last
+    #[deprecated(note = "implement `chip8_core::Audio`, whose `play` gets the duration")]
+    pub trait Audio {
+        /// Start audio output
+        fn play(&self) -> Result<(), Chip8Error>;
+        /// Stop audio output
+        fn stop(&self) -> Result<(), Chip8Error>;
+    }
+
+    /// Turns a `v1::Keyboard` into a `Keyboard`, reporting the keys that changed as events
+    #[deprecated(note = "implement `chip8_core::Keyboard`, which reports `KeyEvent`s")]
+    pub struct KeyboardAdapter<K> {
+        keyboard: K,
+        keys: [u8; 16],
+    }
+
+    impl<K: Keyboard> KeyboardAdapter<K> {
+        /// Wraps `keyboard`, with every key up
+        pub fn new(keyboard: K) -> KeyboardAdapter<K> {
+            KeyboardAdapter {
+                keyboard,
+                keys: [0; 16],
+            }
+        }
+    }
+
+    impl<K: Keyboard> crate::Keyboard for KeyboardAdapter<K> {
+        fn poll_events(&mut self) -> Vec<KeyEvent> {
+            let mut keys = self.keys;
+            let quit = self.keyboard.update_state(&mut keys);
+
+            let mut events = (0..16u8)
+                .filter(|key| (keys[*key as usize] != 0) != (self.keys[*key as usize] != 0))
+                .map(|key| match keys[key as usize] {
+                    0 => KeyEvent::Up(key),
+                    _ => KeyEvent::Down(key),
+                })
+                .collect::<Vec<_>>();
+            if quit {
+                events.push(KeyEvent::Quit);
+            }
+            self.keys = keys;
+            events
+        }
+    }
+
+    /// Turns a `v1::Graphics` into a `Graphics`, flattening the frame into a byte per pixel
+    #[deprecated(note = "implement `chip8_core::Graphics`, which gets a `Frame`")]
+    pub struct GraphicsAdapter<G> {
+        graphics: G,
+    }
+
+    impl<G: Graphics> GraphicsAdapter<G> {
+        /// Wraps `graphics`
+        pub fn new(graphics: G) -> GraphicsAdapter<G> {
+            GraphicsAdapter { graphics }
+        }
+    }
+
+    impl<G: Graphics> crate::Graphics for GraphicsAdapter<G> {
+        fn draw(&mut self, frame: &Frame) -> Result<(), Chip8Error> {
+            // Lit in any plane, row by row, like the 64x32 array the trait used to get
+            let pixels = (0..frame.height())
+                .flat_map(|y| (0..frame.width()).map(move |x| frame.get_pixel(x, y) as u8))
+                .collect::<Vec<u8>>();
+            self.graphics.draw(&pixels)
+        }
+    }
+
+    /// Turns a `v1::Audio` into an `Audio`, dropping the duration of the sound
+    #[deprecated(note = "implement `chip8_core::Audio`, whose `play` gets the duration")]
+    pub struct AudioAdapter<A> {
+        audio: A,
+    }
+
+    impl<A: Audio> AudioAdapter<A> {
+        /// Wraps `audio`
+        pub fn new(audio: A) -> AudioAdapter<A> {
+            AudioAdapter { audio }
+        }
+    }
+
+    impl<A: Audio> crate::Audio for AudioAdapter<A> {
+        fn play(&self, _duration: Duration) -> Result<(), Chip8Error> {
+            self.audio.play()
+        }
+
+        fn stop(&self) -> Result<(), Chip8Error> {
+            self.audio.stop()
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(deprecated)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::{compat::v1, Chip8, Chip8Error, NumberGenerator, State};
+
+    struct Dice;
+
+    impl NumberGenerator for Dice {
+        fn generate(&self) -> Result<u8, Chip8Error> {
+            Ok(4)
+        }
+    }
+
+    // Holds key 5 down from the first cycle, then asks to quit on the sixth
+    struct Keys {
+        cycles: usize,
+    }
+
+    impl v1::Keyboard for Keys {
+        fn update_state(&mut self, keyboard: &mut [u8; 16]) -> bool {
+            self.cycles += 1;
+            keyboard[5] = 1;
+            self.cycles == 6
+        }
+
+        fn wait_next_key_press(&mut self) -> u8 {
+            5
+        }
+    }
+
+    struct Screen {
+        frames: Rc<RefCell<Vec<Vec<u8>>>>,
+    }
+
+    impl v1::Graphics for Screen {
+        fn draw(&mut self, graphics: &[u8]) -> Result<(), Chip8Error> {
+            self.frames.borrow_mut().push(graphics.to_vec());
+            Ok(())
+        }
+    }
+
+    struct Speaker {
+        playing: Rc<RefCell<bool>>,
+    }
+
+    impl v1::Audio for Speaker {
+        fn play(&self) -> Result<(), Chip8Error> {
+            self.playing.replace(true);
+            Ok(())
+        }
+
+        fn stop(&self) -> Result<(), Chip8Error> {
+            self.playing.replace(false);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn it_runs_devices_written_for_the_v1_traits() -> Result<(), Chip8Error> {
+        let frames = Rc::new(RefCell::new(Vec::new()));
+        let playing = Rc::new(RefCell::new(false));
+        let mut chip8 = Chip8::new(
+            Box::new(Dice),
+            Box::new(v1::AudioAdapter::new(Speaker {
+                playing: Rc::clone(&playing),
+            })),
+            Box::new(v1::KeyboardAdapter::new(Keys { cycles: 0 })),
+            Box::new(v1::GraphicsAdapter::new(Screen {
+                frames: Rc::clone(&frames),
+            })),
+        );
+        // Draw the font sprite of 0, start a sound, then skip to a loop when key 5 is down
+        chip8.load_program(vec![
+            0xD0, 0x05, 0x60, 0x10, 0xF0, 0x18, 0x61, 0x05, 0xE1, 0x9E, 0x00, 0x00, 0x12, 0x0C,
+        ])?;
+
+        for _ in 0..5 {
+            assert_eq!(chip8.emulate_cycle()?, State::Continue);
+        }
+        {
+            let frames = frames.borrow();
+            let frame = frames.last().expect("the sprite was drawn");
+            assert_eq!(frame.len(), 64 * 32);
+            assert_eq!(frame[..4], [1, 1, 1, 1]);
+        }
+        assert!(*playing.borrow());
+        assert_eq!(chip8.program_counter, 0x20C);
+        assert_eq!(chip8.emulate_cycle()?, State::Exit);
+
+        Ok(())
+    }
+}
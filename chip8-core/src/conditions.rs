@@ -0,0 +1,327 @@
+use std::{fmt, str::FromStr};
+
+use crate::Chip8;
+
+/// Something about the memory and registers that is true or not at the end of a frame, written
+/// in the spirit of the RetroAchievements conditions
+///
+/// A condition is made of comparisons joined with `_`, all of them have to hold. Each side of a
+/// comparison is one of:
+///
+/// - `0xH300`, the byte at `0x300`
+/// - `0x300`, the 16 bit word at `0x300`, its high byte first like the chip8 stores them
+/// - `v0` to `vF`, `i`, `dt` and `st`, the registers and timers
+/// - `12` or `h0C`, a number in decimal or hexadecimal
+///
+/// A `d` in front of a value reads it as it was the frame before, and the comparisons are `=`,
+/// `!=`, `<`, `<=`, `>` and `>=`. `0xH300>d0xH300_v5=3` holds on the frames the byte at `0x300`
+/// goes up while V5 is 3
+///
+/// ```
+/// use chip8_core::{Chip8, Condition};
+///
+/// let mut chip8 = Chip8::builder().build();
+/// chip8.load_program(vec![0x65, 0x02, 0x12, 0x02]).unwrap();
+/// let mut condition: Condition = "v5>=2_v5!=dv5".parse()?;
+/// assert!(!condition.check(&chip8));
+///
+/// chip8.run_cycles(1).unwrap();
+/// assert!(condition.check(&chip8));
+/// # Ok::<(), String>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    comparisons: Vec<Comparison>,
+    source: String,
+}
+
+/// A value of the memory or registers, written like a side of a `Condition`, that keeps the
+/// score of a game, like `0x2F0` for a word at `0x2F0`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Score {
+    value: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Comparison {
+    left: Operand,
+    operator: Operator,
+    right: Operand,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Operand {
+    value: Value,
+    delta: bool,
+    // What the value was when the condition was last checked
+    previous: Option<u16>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Value {
+    Byte(u16),
+    Word(u16),
+    Register(usize),
+    Index,
+    DelayTimer,
+    SoundTimer,
+    Constant(u16),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+// A comparison starting where another one does is the longer of the two, `<=` rather than `<`
+const OPERATORS: [(&str, Operator); 6] = [
+    ("!=", Operator::NotEqual),
+    ("<=", Operator::LessOrEqual),
+    (">=", Operator::GreaterOrEqual),
+    ("=", Operator::Equal),
+    ("<", Operator::Less),
+    (">", Operator::Greater),
+];
+
+impl Condition {
+    /// Whether the condition holds for the state `chip8` is in, meant to be called once per frame
+    ///
+    /// Values read with `d` compare against the ones of the previous call, and the current ones
+    /// the first time
+    pub fn check(&mut self, chip8: &Chip8) -> bool {
+        // Every comparison is read, even after one fails, to keep the previous values up to date
+        let mut holds = true;
+        for comparison in &mut self.comparisons {
+            let left = comparison.left.read(chip8);
+            let right = comparison.right.read(chip8);
+            holds &= comparison.operator.holds(left, right);
+        }
+        holds
+    }
+}
+
+impl Score {
+    /// The score in the state `chip8` is in
+    pub fn read(&self, chip8: &Chip8) -> u16 {
+        self.value.read(chip8)
+    }
+}
+
+impl Operand {
+    fn read(&mut self, chip8: &Chip8) -> u16 {
+        let current = self.value.read(chip8);
+        let previous = self.previous.replace(current).unwrap_or(current);
+        if self.delta {
+            previous
+        } else {
+            current
+        }
+    }
+}
+
+impl Value {
+    fn read(self, chip8: &Chip8) -> u16 {
+        let byte = |address: u16| {
+            chip8
+                .memory()
+                .get(address as usize)
+                .copied()
+                .unwrap_or_default()
+        };
+        match self {
+            Value::Byte(address) => byte(address).into(),
+            Value::Word(address) => {
+                u16::from_be_bytes([byte(address), byte(address.wrapping_add(1))])
+            }
+            Value::Register(vx) => chip8.v_register(vx).into(),
+            Value::Index => chip8.index_register(),
+            Value::DelayTimer => chip8.delay_timer().into(),
+            Value::SoundTimer => chip8.sound_timer().into(),
+            Value::Constant(value) => value,
+        }
+    }
+}
+
+impl Operator {
+    fn holds(self, left: u16, right: u16) -> bool {
+        match self {
+            Operator::Equal => left == right,
+            Operator::NotEqual => left != right,
+            Operator::Less => left < right,
+            Operator::LessOrEqual => left <= right,
+            Operator::Greater => left > right,
+            Operator::GreaterOrEqual => left >= right,
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(condition: &str) -> Result<Self, Self::Err> {
+        let comparisons = condition
+            .split('_')
+            .map(|comparison| {
+                comparison
+                    .parse()
+                    .map_err(|error| format!("Invalid condition {}: {}", condition, error))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Condition {
+            comparisons,
+            source: condition.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl FromStr for Score {
+    type Err = String;
+
+    fn from_str(score: &str) -> Result<Self, Self::Err> {
+        match score.parse::<Operand>()? {
+            Operand {
+                value: Value::Constant(_),
+                ..
+            }
+            | Operand { delta: true, .. } => Err(format!(
+                "a score is read from memory or a register: {}",
+                score
+            )),
+            Operand { value, .. } => Ok(Score { value }),
+        }
+    }
+}
+
+impl FromStr for Comparison {
+    type Err = String;
+
+    fn from_str(comparison: &str) -> Result<Self, Self::Err> {
+        let (position, symbol, operator) = OPERATORS
+            .iter()
+            .filter_map(|(symbol, operator)| {
+                comparison
+                    .find(symbol)
+                    .map(|position| (position, *symbol, *operator))
+            })
+            .min_by_key(|(position, symbol, _)| (*position, usize::MAX - symbol.len()))
+            .ok_or_else(|| format!("missing comparison in {}", comparison))?;
+        Ok(Comparison {
+            left: comparison[..position].parse()?,
+            operator,
+            right: comparison[position + symbol.len()..].parse()?,
+        })
+    }
+}
+
+impl FromStr for Operand {
+    type Err = String;
+
+    fn from_str(operand: &str) -> Result<Self, Self::Err> {
+        let trimmed = operand.trim().to_ascii_lowercase();
+        let (delta, value) = match trimmed.strip_prefix('d') {
+            // `dt` is the delay timer, `ddt` its previous value
+            Some(value) if trimmed != "dt" => (true, value),
+            _ => (false, trimmed.as_str()),
+        };
+        let hex = |digits: &str| u16::from_str_radix(digits, 16).ok();
+        let value = if let Some(address) = value.strip_prefix("0xh") {
+            hex(address).map(Value::Byte)
+        } else if let Some(address) = value.strip_prefix("0x") {
+            hex(address).map(Value::Word)
+        } else if let Some(constant) = value.strip_prefix('h') {
+            hex(constant).map(Value::Constant)
+        } else if let Some(vx) = value.strip_prefix('v') {
+            hex(vx)
+                .filter(|vx| *vx <= 0xF)
+                .map(|vx| Value::Register(vx.into()))
+        } else {
+            match value {
+                "i" => Some(Value::Index),
+                "dt" => Some(Value::DelayTimer),
+                "st" => Some(Value::SoundTimer),
+                _ => value.parse().ok().map(Value::Constant),
+            }
+        };
+
+        match value {
+            Some(Value::Constant(_)) if delta => {
+                Err(format!("a number has no previous value: {}", operand))
+            }
+            Some(value) => Ok(Operand {
+                value,
+                delta,
+                previous: None,
+            }),
+            None => Err(format!("unknown value {}", operand)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Chip8Error;
+
+    #[test]
+    fn it_compares_memory_registers_and_numbers() -> Result<(), Chip8Error> {
+        let mut chip8 = Chip8::builder().build();
+        // Stores 0x1234 at 0x300, then sets V5 to 7 and I to 0x300
+        chip8.load_program(vec![
+            0x60, 0x12, 0x61, 0x34, 0xA3, 0x00, 0xF1, 0x55, 0x65, 0x07, 0xA3, 0x00, 0x12, 0x0C,
+        ])?;
+        chip8.run_cycles(6)?;
+
+        let holds = |condition: &str| condition.parse::<Condition>().map(|mut c| c.check(&chip8));
+
+        assert_eq!(holds("0xH300=h12"), Ok(true));
+        assert_eq!(holds("0x300=4660"), Ok(true));
+        assert_eq!(holds("v5>=7_i=0x0300"), Ok(false));
+        assert_eq!(holds("v5>=7_i=h300"), Ok(true));
+        assert_eq!(holds("vF<v5_dt=0_st!=1"), Ok(true));
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_compares_values_with_the_ones_of_the_previous_check() -> Result<(), Chip8Error> {
+        let mut chip8 = Chip8::builder().build();
+        // Stores 1 at 0x300
+        chip8.load_program(vec![0xA3, 0x00, 0x60, 0x01, 0xF0, 0x55, 0x12, 0x06])?;
+        let mut went_up: Condition = "0xH300>d0xH300"
+            .parse()
+            .map_err(Chip8Error::InvalidConfig)?;
+
+        assert!(!went_up.check(&chip8));
+        chip8.run_cycles(3)?;
+        assert!(went_up.check(&chip8));
+        assert!(!went_up.check(&chip8));
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_refuses_conditions_it_cant_read() {
+        for condition in &["0xH300", "v10=1", "0xHZZ=1", "dh10=1", "v1=>2", "v1==2"] {
+            assert!(
+                condition.parse::<Condition>().is_err(),
+                "{} should be refused",
+                condition
+            );
+        }
+        assert!("dt=0_ddt>dt".parse::<Condition>().is_ok());
+        assert!("12".parse::<Score>().is_err());
+        assert!("d0xH300".parse::<Score>().is_err());
+        assert!("0x2F0".parse::<Score>().is_ok());
+    }
+}
@@ -0,0 +1,398 @@
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Deserializer};
+
+use crate::{
+    errors::Chip8Error,
+    keymap::{KeyboardLayout, Keymap},
+    postprocess::Palette,
+    quirks::Quirks,
+    variant::Chip8Variant,
+};
+
+/// Overrides for a single rom, read from a toml file sitting next to it
+///
+/// Everything is optional, anything left out keeps what the frontend would have used:
+///
+/// ```toml
+/// variant = "schip"
+/// hertz = 1000
+///
+/// [quirks]
+/// shift_uses_vy = true
+///
+/// [palette]
+/// background = "#1D2B53"
+/// foreground = "#FFEC27"
+///
+/// [keymap]
+/// layout = "azerty"
+/// keys = { " " = 0x5 }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RomConfig {
+    /// Interpreter the rom was written for
+    #[serde(deserialize_with = "parsed")]
+    pub variant: Option<Chip8Variant>,
+    /// Instructions per second
+    pub hertz: Option<u32>,
+    /// Quirks to change from the ones of the variant
+    pub quirks: QuirkOverrides,
+    /// Colors to draw with
+    pub palette: PaletteOverrides,
+    /// Keys to play with
+    pub keymap: KeymapOverrides,
+}
+
+/// Quirks a rom config turns on or off, see `Quirks` for what each one does
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuirkOverrides {
+    /// Overrides `Quirks::shift_uses_vy`
+    pub shift_uses_vy: Option<bool>,
+    /// Overrides `Quirks::load_store_increments_i`
+    pub load_store_increments_i: Option<bool>,
+    /// Overrides `Quirks::logic_resets_vf`
+    pub logic_resets_vf: Option<bool>,
+    /// Overrides `Quirks::jump_uses_vx`
+    pub jump_uses_vx: Option<bool>,
+    /// Overrides `Quirks::wrap_sprites`
+    pub wrap_sprites: Option<bool>,
+    /// Overrides `Quirks::display_wait`
+    pub display_wait: Option<bool>,
+}
+
+/// Colors a rom config replaces, written as `#RRGGBB` or `#RRGGBBAA`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PaletteOverrides {
+    /// Color of the pixels that are off
+    #[serde(deserialize_with = "color")]
+    pub background: Option<[u8; 4]>,
+    /// Color of the pixels that are on
+    #[serde(deserialize_with = "color")]
+    pub foreground: Option<[u8; 4]>,
+}
+
+/// Keyboard layout and individual keys a rom config changes
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeymapOverrides {
+    /// Layout to start from
+    #[serde(deserialize_with = "parsed")]
+    pub layout: Option<KeyboardLayout>,
+    /// Characters pressing a keypad key, on top of the ones of the layout
+    pub keys: BTreeMap<char, u8>,
+}
+
+impl RomConfig {
+    /// Parses the contents of a rom config
+    pub fn parse(config: &str) -> Result<RomConfig, Chip8Error> {
+        toml::from_str(config).map_err(|error| Chip8Error::InvalidConfig(error.to_string()))
+    }
+
+    /// Where the config of a rom lives, `pong.ch8` is configured by `pong.toml`
+    pub fn path_for(rom_path: &Path) -> PathBuf {
+        rom_path.with_extension("toml")
+    }
+
+    /// Reads the config next to a rom, roms without one get `None`
+    pub fn load_for_rom(rom_path: &Path) -> Result<Option<RomConfig>, Chip8Error> {
+        match fs::read_to_string(RomConfig::path_for(rom_path)) {
+            Ok(config) => RomConfig::parse(&config).map(Some),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// `quirks` with the overrides applied
+    pub fn quirks(&self, quirks: Quirks) -> Quirks {
+        let overrides = &self.quirks;
+        Quirks {
+            shift_uses_vy: overrides.shift_uses_vy.unwrap_or(quirks.shift_uses_vy),
+            load_store_increments_i: overrides
+                .load_store_increments_i
+                .unwrap_or(quirks.load_store_increments_i),
+            logic_resets_vf: overrides.logic_resets_vf.unwrap_or(quirks.logic_resets_vf),
+            jump_uses_vx: overrides.jump_uses_vx.unwrap_or(quirks.jump_uses_vx),
+            wrap_sprites: overrides.wrap_sprites.unwrap_or(quirks.wrap_sprites),
+            display_wait: overrides.display_wait.unwrap_or(quirks.display_wait),
+        }
+    }
+
+    /// `palette` with the overridden colors replaced
+    pub fn palette(&self, palette: Palette) -> Palette {
+        Palette {
+            background: self.palette.background.unwrap_or(palette.background),
+            foreground: self.palette.foreground.unwrap_or(palette.foreground),
+        }
+    }
+
+    /// Keymap of `layout` with the configured keys remapped
+    ///
+    /// The layout is left to the caller, which usually prefers a flag over `keymap.layout`
+    pub fn keymap(&self, layout: KeyboardLayout) -> Keymap {
+        let mut keymap = Keymap::new(layout);
+        for (character, key) in &self.keymap.keys {
+            keymap.remap(*character, *key);
+        }
+        keymap
+    }
+}
+
+/// Instructions per second of a `Chip8Config` that doesn't set `hertz`
+pub const DEFAULT_HERTZ: u32 = 500;
+
+/// How to run a rom: the interpreter to behave like, its quirks and its speed
+///
+/// Read from the same toml frontends and tools use, so they all agree on what a config means
+/// instead of each one turning it into builder calls its own way. Quirks start from the ones of
+/// the variant:
+///
+/// ```toml
+/// variant = "schip"
+/// hertz = 1000
+///
+/// [quirks]
+/// shift_uses_vy = true
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chip8Config {
+    /// Interpreter to behave like
+    pub variant: Chip8Variant,
+    /// Behaviours of the interpreter
+    pub quirks: Quirks,
+    /// Instructions per second
+    pub hertz: u32,
+}
+
+/// Why a `Chip8Config` was refused
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// Not toml, or with a field that doesn't exist or has the wrong type
+    Syntax(String),
+    /// A variant that isn't one of `Chip8Variant`
+    UnknownVariant(String),
+    /// A speed of 0 instructions per second
+    InvalidHertz(u32),
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct SavedConfig {
+    variant: Option<String>,
+    hertz: Option<u32>,
+    quirks: QuirkOverrides,
+}
+
+impl Chip8Config {
+    /// Parses and validates a config, anything left out gets the default
+    pub fn from_toml(config: &str) -> Result<Chip8Config, ConfigError> {
+        let saved: SavedConfig =
+            toml::from_str(config).map_err(|error| ConfigError::Syntax(error.message().into()))?;
+        let variant = match saved.variant {
+            Some(variant) => variant
+                .parse()
+                .map_err(|_| ConfigError::UnknownVariant(variant))?,
+            None => Chip8Variant::default(),
+        };
+        let hertz = saved.hertz.unwrap_or(DEFAULT_HERTZ);
+        if hertz == 0 {
+            return Err(ConfigError::InvalidHertz(hertz));
+        }
+        let overrides = RomConfig {
+            quirks: saved.quirks,
+            ..RomConfig::default()
+        };
+        Ok(Chip8Config {
+            variant,
+            quirks: overrides.quirks(variant.quirks()),
+            hertz,
+        })
+    }
+}
+
+impl Default for Chip8Config {
+    fn default() -> Self {
+        Chip8Config {
+            variant: Chip8Variant::default(),
+            quirks: Chip8Variant::default().quirks(),
+            hertz: DEFAULT_HERTZ,
+        }
+    }
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Syntax(message) => write!(f, "Invalid config: {}", message),
+            ConfigError::UnknownVariant(variant) => write!(f, "Unknown variant: {}", variant),
+            ConfigError::InvalidHertz(hertz) => {
+                write!(f, "Can't run {} instructions per second", hertz)
+            }
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+impl From<ConfigError> for Chip8Error {
+    fn from(error: ConfigError) -> Self {
+        Chip8Error::InvalidConfig(error.to_string())
+    }
+}
+
+fn parsed<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    String::deserialize(deserializer)?
+        .parse()
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+fn color<'de, D>(deserializer: D) -> Result<Option<[u8; 4]>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let color = String::deserialize(deserializer)?;
+    let hex = color.trim_start_matches('#');
+    let channel = |index: usize| {
+        hex.get(index * 2..index * 2 + 2)
+            .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+    };
+    match hex.len() {
+        6 => channel(0)
+            .zip(channel(1))
+            .zip(channel(2))
+            .map(|((r, g), b)| [r, g, b, 255]),
+        8 => channel(0)
+            .zip(channel(1))
+            .zip(channel(2))
+            .zip(channel(3))
+            .map(|(((r, g), b), a)| [r, g, b, a]),
+        _ => None,
+    }
+    .map(Some)
+    .ok_or_else(|| serde::de::Error::custom(format!("invalid color: {}", color)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_applies_the_overrides_on_top_of_the_defaults() -> Result<(), Chip8Error> {
+        let config = RomConfig::parse(
+            r##"
+            variant = "schip"
+            hertz = 1000
+
+            [quirks]
+            shift_uses_vy = true
+
+            [palette]
+            foreground = "#FFEC27"
+
+            [keymap]
+            layout = "azerty"
+            keys = { " " = 0x5 }
+            "##,
+        )?;
+
+        assert_eq!(config.variant, Some(Chip8Variant::SuperChip));
+        assert_eq!(config.hertz, Some(1000));
+        assert_eq!(
+            config.quirks(Chip8Variant::SuperChip.quirks()),
+            Quirks {
+                shift_uses_vy: true,
+                ..Chip8Variant::SuperChip.quirks()
+            }
+        );
+        assert_eq!(
+            config.palette(Palette::default()).foreground,
+            [0xFF, 0xEC, 0x27, 0xFF]
+        );
+        assert_eq!(config.keymap.layout, Some(KeyboardLayout::Azerty));
+        let keymap = config.keymap(KeyboardLayout::Qwerty);
+        assert_eq!(keymap.key(' '), Some(0x5));
+        assert_eq!(keymap.key('q'), Some(0x4));
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_rejects_configs_it_does_not_understand() {
+        let configs = [
+            "variant = \"nes\"",
+            "speed = 10",
+            "[palette]\nbackground = \"#12\"",
+        ];
+        for config in configs.iter() {
+            assert!(matches!(
+                RomConfig::parse(config),
+                Err(Chip8Error::InvalidConfig(_))
+            ));
+        }
+        assert_eq!(RomConfig::parse("").ok(), Some(RomConfig::default()));
+    }
+
+    #[test]
+    fn it_reads_a_config_starting_from_the_quirks_of_the_variant() -> Result<(), ConfigError> {
+        let config = Chip8Config::from_toml(
+            r#"
+            variant = "vip"
+            hertz = 1000
+
+            [quirks]
+            display_wait = false
+            "#,
+        )?;
+
+        assert_eq!(
+            config,
+            Chip8Config {
+                variant: Chip8Variant::CosmacVip,
+                quirks: Quirks {
+                    display_wait: false,
+                    ..Chip8Variant::CosmacVip.quirks()
+                },
+                hertz: 1000,
+            }
+        );
+        assert_eq!(Chip8Config::from_toml("")?, Chip8Config::default());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_tells_why_a_config_is_refused() {
+        assert_eq!(
+            Chip8Config::from_toml("variant = \"nes\""),
+            Err(ConfigError::UnknownVariant("nes".to_string()))
+        );
+        assert_eq!(
+            Chip8Config::from_toml("hertz = 0"),
+            Err(ConfigError::InvalidHertz(0))
+        );
+        assert!(matches!(
+            Chip8Config::from_toml("[quirks]\nshift_uses_vy = 1"),
+            Err(ConfigError::Syntax(_))
+        ));
+        assert!(matches!(
+            Chip8Config::from_toml("speed = 10").map_err(Chip8Error::from),
+            Err(Chip8Error::InvalidConfig(_))
+        ));
+    }
+}
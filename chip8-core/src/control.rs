@@ -0,0 +1,297 @@
+//! Driving an interpreter from another process through a local Unix socket, for scripts and tools
+//!
+//! Each line a client writes is a JSON command and gets a line with a JSON response back:
+//!
+//! ```text
+//! {"command":"pause"}
+//! {"ok":true}
+//! {"command":"load","path":"roms/PONG"}
+//! {"ok":true}
+//! {"command":"save_state","path":"pong.toml"}
+//! {"ok":true}
+//! {"command":"key","key":5,"down":true}
+//! {"ok":true}
+//! {"command":"key","key":16,"down":true}
+//! {"ok":false,"error":"Key 16 isn't on the keypad"}
+//! ```
+//!
+//! The commands are sent through a `Chip8Handle`, the interpreter applies them at the start of
+//! its next frame. Save states are written as toml, the format of the `Snapshot` save states
+
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::mpsc,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{handle::Chip8Handle, traits::KeyEvent};
+
+// A save state waits this long for the emulation thread to get to its next frame
+const SAVE_STATE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A request written on the control socket
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum ControlCommand {
+    /// Stops running instructions and timers, like `Chip8Handle::pause`
+    Pause,
+    /// Runs again after a pause
+    Resume,
+    /// Restarts the current rom
+    Reset,
+    /// Reads a rom from a file and runs it instead of the current one
+    Load {
+        /// File of the rom, read by the server
+        path: PathBuf,
+    },
+    /// Writes a save state of the interpreter to a file
+    SaveState {
+        /// File the save state is written to, by the server
+        path: PathBuf,
+    },
+    /// Presses or releases a key of the keypad
+    Key {
+        /// Key of the keypad, from 0 to F
+        key: u8,
+        /// Pressed if true, released otherwise
+        down: bool,
+    },
+}
+
+impl FromStr for ControlCommand {
+    type Err = String;
+
+    /// Reads a command from its JSON line
+    fn from_str(line: &str) -> Result<ControlCommand, String> {
+        serde_json::from_str(line).map_err(|error| format!("Invalid command: {}", error))
+    }
+}
+
+/// What the control socket answers to a `ControlCommand`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ControlResponse {
+    /// Whether the command was sent to the interpreter
+    pub ok: bool,
+    /// Why it wasn't
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl From<Result<(), String>> for ControlResponse {
+    fn from(result: Result<(), String>) -> ControlResponse {
+        ControlResponse {
+            ok: result.is_ok(),
+            error: result.err(),
+        }
+    }
+}
+
+/// Listens on a Unix socket and forwards the commands of its clients to a `Chip8Handle`
+///
+/// Clients are served one at a time, in the order they connect. The socket file is removed when
+/// the server is dropped
+pub struct ControlServer {
+    listener: UnixListener,
+    path: PathBuf,
+    handle: Chip8Handle,
+}
+
+impl ControlServer {
+    /// Creates the socket at `path`, which fails if a file is already there
+    pub fn bind(path: impl AsRef<Path>, handle: Chip8Handle) -> io::Result<ControlServer> {
+        let path = path.as_ref().to_path_buf();
+        let listener = UnixListener::bind(&path)?;
+        Ok(ControlServer {
+            listener,
+            path,
+            handle,
+        })
+    }
+
+    /// Serves clients until accepting a connection fails, run it on a thread of its own
+    pub fn serve(&self) -> io::Result<()> {
+        for stream in self.listener.incoming() {
+            // A client that hangs up halfway only ends its own connection
+            let _ = self.serve_client(stream?);
+        }
+        Ok(())
+    }
+
+    /// Sends `command` to the interpreter, or tells why it couldn't
+    pub fn apply(&self, command: ControlCommand) -> Result<(), String> {
+        let sent = match command {
+            ControlCommand::Pause => self.handle.pause(),
+            ControlCommand::Resume => self.handle.resume(),
+            ControlCommand::Reset => self.handle.reset(),
+            ControlCommand::Load { path } => {
+                let rom_data = fs::read(&path)
+                    .map_err(|error| format!("Couldn't read {}: {}", path.display(), error))?;
+                self.handle.load_rom(rom_data)
+            }
+            ControlCommand::SaveState { path } => return self.save_state(&path),
+            ControlCommand::Key { key, .. } if key > 0xF => {
+                return Err(format!("Key {} isn't on the keypad", key))
+            }
+            ControlCommand::Key { key, down } => self.handle.send_key(if down {
+                KeyEvent::Down(key)
+            } else {
+                KeyEvent::Up(key)
+            }),
+        };
+        if sent {
+            Ok(())
+        } else {
+            Err(interpreter_gone())
+        }
+    }
+
+    fn save_state(&self, path: &Path) -> Result<(), String> {
+        let (sender, receiver) = mpsc::channel();
+        if !self.handle.save_state(sender) {
+            return Err(interpreter_gone());
+        }
+        let snapshot = receiver
+            .recv_timeout(SAVE_STATE_TIMEOUT)
+            .map_err(|_| "The interpreter didn't get to its next frame".to_string())?;
+        let state = toml::to_string(&snapshot).map_err(|error| error.to_string())?;
+        fs::write(path, state)
+            .map_err(|error| format!("Couldn't write {}: {}", path.display(), error))
+    }
+
+    fn serve_client(&self, stream: UnixStream) -> io::Result<()> {
+        let mut writer = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let result = line.parse().and_then(|command| self.apply(command));
+            writeln!(
+                writer,
+                "{}",
+                serde_json::to_string(&ControlResponse::from(result))?
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn interpreter_gone() -> String {
+    "The interpreter is gone".to_string()
+}
+
+/// Connects to a `ControlServer` to send it commands
+pub struct ControlClient {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl ControlClient {
+    /// Connects to the socket at `path`
+    pub fn connect(path: impl AsRef<Path>) -> io::Result<ControlClient> {
+        let writer = UnixStream::connect(path)?;
+        let reader = BufReader::new(writer.try_clone()?);
+        Ok(ControlClient { reader, writer })
+    }
+
+    /// Sends `command` and waits for the response of the server
+    pub fn send(&mut self, command: &ControlCommand) -> io::Result<ControlResponse> {
+        writeln!(self.writer, "{}", serde_json::to_string(command)?)?;
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Chip8, Chip8Error, Snapshot};
+    use std::{sync::Arc, thread};
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("chip8-control-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn it_reads_the_commands_of_the_protocol() {
+        assert_eq!(
+            r#"{"command":"key","key":5,"down":true}"#.parse(),
+            Ok(ControlCommand::Key { key: 5, down: true })
+        );
+        assert_eq!(
+            r#"{"command":"save_state","path":"a.toml"}"#.parse(),
+            Ok(ControlCommand::SaveState {
+                path: PathBuf::from("a.toml")
+            })
+        );
+        assert!(r#"{"command":"jump"}"#.parse::<ControlCommand>().is_err());
+        assert_eq!(
+            serde_json::to_string(&ControlResponse::from(Ok(()))).unwrap(),
+            r#"{"ok":true}"#
+        );
+    }
+
+    #[test]
+    fn it_drives_the_interpreter_from_the_socket() -> Result<(), Chip8Error> {
+        let mut chip8 = Chip8::builder().build();
+        chip8.load_program(vec![0x70, 0x01, 0x12, 0x00])?;
+        let socket = temp_path("socket");
+        let state = temp_path("state.toml");
+        let server = Arc::new(ControlServer::bind(&socket, chip8.handle())?);
+        let serving = Arc::clone(&server);
+        thread::spawn(move || serving.serve());
+
+        let mut client = ControlClient::connect(&socket)?;
+        assert!(client.send(&ControlCommand::Key { key: 7, down: true })?.ok);
+        assert!(client.send(&ControlCommand::Pause)?.ok);
+        chip8.run_frame(10)?;
+        assert_eq!(chip8.v_registers[0], 0);
+        assert_eq!(chip8.keyboard[7], 1);
+
+        let rejected = client.send(&ControlCommand::Key {
+            key: 0x10,
+            down: true,
+        })?;
+        assert!(!rejected.ok);
+        assert_eq!(
+            rejected.error.as_deref(),
+            Some("Key 16 isn't on the keypad")
+        );
+
+        assert!(client.send(&ControlCommand::Resume)?.ok);
+        chip8.run_frame(10)?;
+        assert_eq!(chip8.v_registers[0], 5);
+
+        let saving = thread::spawn(move || {
+            client.send(&ControlCommand::SaveState {
+                path: state.clone(),
+            })
+        });
+        while !saving.is_finished() {
+            chip8.apply_controls()?;
+        }
+        assert!(saving.join().unwrap()?.ok);
+        let saved: Snapshot = toml::from_str(&fs::read_to_string(temp_path("state.toml"))?)
+            .map_err(|error| Chip8Error::InvalidConfig(error.to_string()))?;
+        assert_eq!(saved, chip8.snapshot());
+        fs::remove_file(temp_path("state.toml"))?;
+        fs::remove_file(socket)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+/// Which bytes of memory were executed as part of an instruction, gathered once enabled with
+/// `Chip8::set_coverage_tracking`
+///
+/// Both bytes of every opcode executed are marked, so bytes that are never marked were only ever
+/// used as data, or belong to code that was never reached
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    bitmap: Vec<u8>,
+}
+
+impl Coverage {
+    pub(crate) fn new(memory_size: usize) -> Coverage {
+        Coverage {
+            bitmap: vec![0; memory_size.div_ceil(8)],
+        }
+    }
+
+    /// Whether the byte at `address` was executed
+    pub fn is_executed(&self, address: u16) -> bool {
+        let address = address as usize;
+        self.bitmap
+            .get(address / 8)
+            .is_some_and(|bits| bits & (1 << (address % 8)) != 0)
+    }
+
+    /// Every address executed, in ascending order
+    pub fn executed_addresses(&self) -> impl Iterator<Item = u16> + '_ {
+        (0..self.bitmap.len() * 8)
+            .map(|address| address as u16)
+            .filter(move |address| self.is_executed(*address))
+    }
+
+    /// One bit per byte of memory, set when the byte was executed
+    ///
+    /// The byte at `address` is bit `address % 8` of `bitmap[address / 8]`
+    pub fn bitmap(&self) -> &[u8] {
+        &self.bitmap
+    }
+
+    pub(crate) fn record(&mut self, address: u16) {
+        for address in [address as usize, address as usize + 1].iter() {
+            if let Some(bits) = self.bitmap.get_mut(address / 8) {
+                *bits |= 1 << (address % 8);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_marks_both_bytes_of_the_executed_opcodes() {
+        let mut coverage = Coverage::new(4096);
+
+        coverage.record(0x200);
+        coverage.record(0x206);
+
+        assert!(coverage.is_executed(0x201));
+        assert!(!coverage.is_executed(0x202));
+        assert_eq!(
+            coverage.executed_addresses().collect::<Vec<_>>(),
+            vec![0x200, 0x201, 0x206, 0x207]
+        );
+        assert_eq!(coverage.bitmap()[0x200 / 8], 0b1100_0011);
+    }
+
+    #[test]
+    fn it_ignores_addresses_outside_of_memory() {
+        let mut coverage = Coverage::new(16);
+
+        coverage.record(0xF);
+        coverage.record(0x100);
+
+        assert!(coverage.is_executed(0xF));
+        assert!(!coverage.is_executed(0x10));
+        assert_eq!(coverage.bitmap().len(), 2);
+    }
+}
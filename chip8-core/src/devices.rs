@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+use crate::{
+    errors::Chip8Error,
+    traits::{Audio, KeyEvent, Keyboard},
+};
+#[cfg(feature = "null-devices")]
+use crate::{framebuffer::Frame, traits::Graphics};
+
+/// Audio device that never makes a sound
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullAudio;
+
+impl Audio for NullAudio {
+    fn play(&self, _duration: Duration) -> Result<(), Chip8Error> {
+        Ok(())
+    }
+
+    fn stop(&self) -> Result<(), Chip8Error> {
+        Ok(())
+    }
+}
+
+/// Keyboard that never has a key pressed
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullKeyboard;
+
+impl Keyboard for NullKeyboard {
+    fn poll_events(&mut self) -> Vec<KeyEvent> {
+        Vec::new()
+    }
+
+    fn has_pending_events(&mut self) -> bool {
+        false
+    }
+}
+
+/// Graphics device that ignores every frame, `Chip8::framebuffer` still has the display
+#[cfg(feature = "null-devices")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullGraphics;
+
+#[cfg(feature = "null-devices")]
+impl Graphics for NullGraphics {
+    fn draw(&mut self, _frame: &Frame) -> Result<(), Chip8Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "null-devices")]
+mod tests {
+    use super::*;
+    use crate::{Chip8, Quirks, SeededNumberGenerator, State};
+
+    #[test]
+    fn it_runs_a_program_with_the_null_devices() -> Result<(), Chip8Error> {
+        let mut chip8 = Chip8::with_quirks(
+            Box::new(SeededNumberGenerator::new(0)),
+            Box::new(NullAudio),
+            Box::new(NullKeyboard),
+            Box::new(NullGraphics),
+            Quirks::default(),
+        );
+        chip8.load_program(vec![0xA0, 0x00, 0xD0, 0x05, 0xF0, 0x18])?;
+
+        assert_eq!(chip8.run_frame(3)?, State::Continue);
+        assert!(chip8.framebuffer().get_pixel(0, 0));
+
+        Ok(())
+    }
+}
@@ -1,32 +1,206 @@
+/// Boxed error a frontend device failed with
+pub type DeviceError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Where the emulation was when a runtime error happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// Address of the instruction that failed
+    pub program_counter: u16,
+    /// Opcode of the instruction that failed
+    pub opcode: u16,
+}
+
+impl std::fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04X} at {:#05X}", self.opcode, self.program_counter)
+    }
+}
+
 /// Errors enum used both within the chip8 core and exported for use in a frontend
 #[derive(Debug)]
 pub enum Chip8Error {
-    /// Whether it failed when loading the program into memory
-    UnableToLoadProgram,
+    /// Reading or writing failed, usually while loading the program
+    Io(std::io::Error),
     /// Whether the program contains an opcode that is not valid
-    InvalidOpcode(u16),
+    InvalidOpcode {
+        /// The invalid opcode and where it was
+        context: ErrorContext,
+    },
     /// Error while trying to draw graphics
-    GraphicsError(String),
+    ///
+    /// Anything that converts into a boxed error fits, messages included:
+    /// `Chip8Error::GraphicsError("window closed".into())`
+    GraphicsError(DeviceError),
+    /// Whether the program returned from a routine without having called one
+    StackUnderflow {
+        /// The return instruction and where it was
+        context: ErrorContext,
+    },
+    /// Whether the program called a routine with all 16 levels of the stack already in use
+    StackOverflow {
+        /// The call instruction and where it was
+        context: ErrorContext,
+    },
+    /// Whether the program tried to access an address past the end of memory
+    MemoryOutOfBounds {
+        /// First address outside of memory that was accessed
+        address: usize,
+        /// The instruction that accessed it, missing when it was fetching an opcode
+        /// or the frontend reading and writing memory
+        context: Option<ErrorContext>,
+    },
+    /// Whether the program doesn't fit in memory after the load address
+    ProgramTooLarge {
+        /// Size of the program in bytes
+        size: usize,
+        /// Bytes available from the load address to the end of memory
+        available: usize,
+    },
+    /// Error while writing the execution trace
+    TraceError(std::io::Error),
+    /// A rom configuration file couldn't be understood
+    InvalidConfig(String),
+    /// The JIT couldn't generate code for this machine
+    JitUnavailable(String),
 }
 
-impl std::error::Error for Chip8Error {}
+impl std::error::Error for Chip8Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Chip8Error::Io(error) | Chip8Error::TraceError(error) => Some(error),
+            Chip8Error::GraphicsError(error) => Some(error.as_ref()),
+            Chip8Error::InvalidOpcode { .. }
+            | Chip8Error::StackUnderflow { .. }
+            | Chip8Error::StackOverflow { .. }
+            | Chip8Error::MemoryOutOfBounds { .. }
+            | Chip8Error::ProgramTooLarge { .. }
+            | Chip8Error::InvalidConfig(_)
+            | Chip8Error::JitUnavailable(_) => None,
+        }
+    }
+}
 
 impl std::fmt::Display for Chip8Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Chip8Error::UnableToLoadProgram => write!(f, "Unable to load program"),
-            Chip8Error::InvalidOpcode(invalid_opcode) => {
-                write!(f, "Invalid opcode: {}", invalid_opcode)
+            Chip8Error::Io(error) => write!(f, "Input/output error: {}", error),
+            Chip8Error::InvalidOpcode { context } => write!(f, "Invalid opcode {}", context),
+            Chip8Error::GraphicsError(error) => {
+                write!(f, "Error while drawing graphics: {}", error)
+            }
+            Chip8Error::StackUnderflow { context } => {
+                write!(f, "Returned with an empty stack by {}", context)
+            }
+            Chip8Error::StackOverflow { context } => {
+                write!(f, "Called a routine with a full stack by {}", context)
             }
-            Chip8Error::GraphicsError(message) => {
-                write!(f, "Error while drawing graphics: {}", message)
+            Chip8Error::MemoryOutOfBounds { address, context } => {
+                write!(f, "Memory accessed out of bounds at {:#X}", address)?;
+                match context {
+                    Some(context) => write!(f, " by {}", context),
+                    None => Ok(()),
+                }
             }
+            Chip8Error::ProgramTooLarge { size, available } => write!(
+                f,
+                "Program of {} bytes doesn't fit in the {} bytes available",
+                size, available
+            ),
+            Chip8Error::TraceError(error) => {
+                write!(f, "Error while writing the trace: {}", error)
+            }
+            Chip8Error::InvalidConfig(message) => {
+                write!(f, "Invalid rom configuration: {}", message)
+            }
+            Chip8Error::JitUnavailable(message) => write!(f, "JIT unavailable: {}", message),
+        }
+    }
+}
+
+impl Chip8Error {
+    /// Where the emulation was when the error happened, for errors raised by an instruction
+    pub fn context(&self) -> Option<ErrorContext> {
+        match self {
+            Chip8Error::InvalidOpcode { context }
+            | Chip8Error::StackUnderflow { context }
+            | Chip8Error::StackOverflow { context } => Some(*context),
+            Chip8Error::MemoryOutOfBounds { context, .. } => *context,
+            Chip8Error::Io(_)
+            | Chip8Error::GraphicsError(_)
+            | Chip8Error::ProgramTooLarge { .. }
+            | Chip8Error::TraceError(_)
+            | Chip8Error::InvalidConfig(_)
+            | Chip8Error::JitUnavailable(_) => None,
         }
     }
+
+    // Memory errors are raised by helpers that don't know which instruction is running
+    pub(crate) fn with_context(mut self, context: ErrorContext) -> Chip8Error {
+        if let Chip8Error::MemoryOutOfBounds {
+            context: missing @ None,
+            ..
+        } = &mut self
+        {
+            *missing = Some(context);
+        }
+        self
+    }
 }
 
 impl From<std::io::Error> for Chip8Error {
-    fn from(_: std::io::Error) -> Self {
-        Chip8Error::UnableToLoadProgram
+    fn from(error: std::io::Error) -> Self {
+        Chip8Error::Io(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{error::Error, io};
+
+    #[test]
+    fn it_keeps_the_underlying_error_as_the_source() {
+        let error = Chip8Error::from(io::Error::new(io::ErrorKind::NotFound, "rom.ch8"));
+        assert_eq!(
+            error.source().map(|source| source.to_string()),
+            Some("rom.ch8".to_string())
+        );
+
+        let error = Chip8Error::GraphicsError("window closed".into());
+        assert_eq!(
+            error.to_string(),
+            "Error while drawing graphics: window closed"
+        );
+        assert!(error.source().is_some());
+
+        assert!(Chip8Error::ProgramTooLarge {
+            size: 4096,
+            available: 3584
+        }
+        .source()
+        .is_none());
+    }
+
+    #[test]
+    fn it_describes_where_runtime_errors_happened() {
+        let context = ErrorContext {
+            program_counter: 0x2A4,
+            opcode: 0xF155,
+        };
+        let error = Chip8Error::MemoryOutOfBounds {
+            address: 0x1000,
+            context: None,
+        }
+        .with_context(context);
+
+        assert_eq!(error.context(), Some(context));
+        assert_eq!(
+            error.to_string(),
+            "Memory accessed out of bounds at 0x1000 by F155 at 0x2A4"
+        );
+        assert_eq!(
+            Chip8Error::InvalidOpcode { context }.to_string(),
+            "Invalid opcode F155 at 0x2A4"
+        );
     }
 }
@@ -0,0 +1,258 @@
+#[cfg(feature = "save-states")]
+use std::convert::TryFrom;
+
+use crate::postprocess::Palette;
+
+/// Everything a graphics device gets to draw the display
+///
+/// The display is made of one or more planes of the same size, a pixel is lit when it is on in
+/// any of them. The palette is the one the frontend was asked to use, which it is free to ignore
+#[derive(Debug, Clone, Copy)]
+pub struct Frame<'a> {
+    planes: &'a [FrameBuffer],
+    palette: &'a Palette,
+}
+
+impl<'a> Frame<'a> {
+    /// Creates a frame out of its planes, panics when there isn't any
+    pub fn new(planes: &'a [FrameBuffer], palette: &'a Palette) -> Frame<'a> {
+        assert!(!planes.is_empty(), "a frame needs at least one plane");
+        Frame { planes, palette }
+    }
+
+    /// Width in pixels
+    pub fn width(&self) -> usize {
+        self.planes[0].width()
+    }
+
+    /// Height in pixels
+    pub fn height(&self) -> usize {
+        self.planes[0].height()
+    }
+
+    /// Number of planes the display is made of
+    pub fn plane_count(&self) -> usize {
+        self.planes.len()
+    }
+
+    /// The plane at `index`, if there is one
+    pub fn plane(&self, index: usize) -> Option<&'a FrameBuffer> {
+        self.planes.get(index)
+    }
+
+    /// The first plane, the only one until a program uses more
+    pub fn framebuffer(&self) -> &'a FrameBuffer {
+        &self.planes[0]
+    }
+
+    /// Colors the frontend was asked to draw with
+    pub fn palette(&self) -> &'a Palette {
+        self.palette
+    }
+
+    /// Whether the pixel at `x`, `y` is on in any plane
+    pub fn get_pixel(&self, x: usize, y: usize) -> bool {
+        self.planes.iter().any(|plane| plane.get_pixel(x, y))
+    }
+}
+
+/// Pixels currently on the display
+///
+/// Each pixel is a byte set to either 0 or 1, stored row by row
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "save-states",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "SavedFrameBuffer")
+)]
+pub struct FrameBuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+// What a save state holds, checked before it becomes a frame buffer that could be indexed past its end
+#[cfg(feature = "save-states")]
+#[derive(serde::Deserialize)]
+struct SavedFrameBuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+#[cfg(feature = "save-states")]
+impl TryFrom<SavedFrameBuffer> for FrameBuffer {
+    type Error = String;
+
+    fn try_from(saved: SavedFrameBuffer) -> Result<FrameBuffer, String> {
+        if saved.width.checked_mul(saved.height) != Some(saved.pixels.len()) {
+            return Err(format!(
+                "{} pixels don't make a {}x{} display",
+                saved.pixels.len(),
+                saved.width,
+                saved.height
+            ));
+        }
+        Ok(FrameBuffer {
+            width: saved.width,
+            height: saved.height,
+            pixels: saved.pixels,
+        })
+    }
+}
+
+impl FrameBuffer {
+    /// Creates a blank frame buffer of the given size
+    pub fn new(width: usize, height: usize) -> FrameBuffer {
+        FrameBuffer {
+            width,
+            height,
+            pixels: vec![0; width * height],
+        }
+    }
+
+    /// Width in pixels
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Height in pixels
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// All the pixels, row by row
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Whether the pixel at `x`, `y` is on
+    pub fn get_pixel(&self, x: usize, y: usize) -> bool {
+        self.pixels[x + y * self.width] == 1
+    }
+
+    /// Turns the pixel at `x`, `y` on or off
+    pub fn set_pixel(&mut self, x: usize, y: usize, on: bool) {
+        self.pixels[x + y * self.width] = on as u8;
+    }
+
+    /// Coordinates of the pixels that are on, row by row
+    pub fn set_pixels(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let width = self.width;
+        self.pixels
+            .iter()
+            .enumerate()
+            .filter(|(_, pixel)| **pixel == 1)
+            .map(move |(index, _)| (index % width, index / width))
+    }
+
+    /// Fills `buffer` with the RGBA color of every pixel, row by row
+    ///
+    /// The buffer needs 4 bytes per pixel, anything past that is left untouched
+    pub fn to_rgba(&self, palette: &Palette, buffer: &mut [u8]) {
+        assert!(
+            buffer.len() >= self.pixels.len() * 4,
+            "the buffer needs 4 bytes per pixel"
+        );
+        for (color, pixel) in buffer.chunks_exact_mut(4).zip(&self.pixels) {
+            color.copy_from_slice(match pixel {
+                0 => &palette.background,
+                _ => &palette.foreground,
+            });
+        }
+    }
+
+    /// Turns every pixel off
+    pub fn clear(&mut self) {
+        self.pixels.iter_mut().for_each(|pixel| *pixel = 0);
+    }
+
+    /// Flips the pixel at `x`, `y` the way sprites are drawn
+    ///
+    /// Returns true when the pixel was on and got turned off, which is a collision for DXYN
+    pub fn xor_pixel(&mut self, x: usize, y: usize) -> bool {
+        let pixel = &mut self.pixels[x + y * self.width];
+        *pixel ^= 1;
+        *pixel == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_gets_and_sets_pixels_row_by_row() {
+        let mut frame_buffer = FrameBuffer::new(4, 2);
+
+        frame_buffer.set_pixel(1, 1, true);
+
+        assert!(frame_buffer.get_pixel(1, 1));
+        assert_eq!(frame_buffer.pixels(), [0, 0, 0, 0, 0, 1, 0, 0]);
+    }
+
+    #[test]
+    fn it_reports_collisions_when_xoring_a_lit_pixel() {
+        let mut frame_buffer = FrameBuffer::new(4, 2);
+
+        assert!(!frame_buffer.xor_pixel(2, 0));
+        assert!(frame_buffer.xor_pixel(2, 0));
+        assert!(!frame_buffer.get_pixel(2, 0));
+    }
+
+    #[test]
+    fn it_lists_the_pixels_that_are_on() {
+        let mut frame_buffer = FrameBuffer::new(4, 2);
+        frame_buffer.set_pixel(3, 0, true);
+        frame_buffer.set_pixel(1, 1, true);
+
+        assert_eq!(
+            frame_buffer.set_pixels().collect::<Vec<_>>(),
+            [(3, 0), (1, 1)]
+        );
+    }
+
+    #[test]
+    fn it_fills_a_buffer_with_the_palette_colors() {
+        let mut frame_buffer = FrameBuffer::new(2, 1);
+        frame_buffer.set_pixel(1, 0, true);
+        let palette = Palette {
+            background: [1, 2, 3, 4],
+            foreground: [5, 6, 7, 8],
+        };
+        let mut buffer = [0; 9];
+
+        frame_buffer.to_rgba(&palette, &mut buffer);
+
+        assert_eq!(buffer, [1, 2, 3, 4, 5, 6, 7, 8, 0]);
+    }
+
+    #[test]
+    fn it_lights_pixels_that_are_on_in_any_plane() {
+        let mut planes = [FrameBuffer::new(4, 2), FrameBuffer::new(4, 2)];
+        planes[0].set_pixel(0, 0, true);
+        planes[1].set_pixel(3, 1, true);
+        let palette = Palette::default();
+
+        let frame = Frame::new(&planes, &palette);
+
+        assert_eq!(
+            (frame.width(), frame.height(), frame.plane_count()),
+            (4, 2, 2)
+        );
+        assert!(frame.get_pixel(0, 0));
+        assert!(frame.get_pixel(3, 1));
+        assert!(!frame.get_pixel(1, 0));
+    }
+
+    #[test]
+    fn it_clears_every_pixel() {
+        let mut frame_buffer = FrameBuffer::new(4, 2);
+        frame_buffer.set_pixel(0, 0, true);
+        frame_buffer.set_pixel(3, 1, true);
+
+        frame_buffer.clear();
+
+        assert_eq!(frame_buffer.pixels(), [0; 8]);
+    }
+}
@@ -0,0 +1,140 @@
+use std::{fmt, str::FromStr};
+
+use crate::{
+    builder::Chip8Builder, errors::Chip8Error, framebuffer::FrameBuffer, replay::Replay, Chip8,
+};
+
+/// The run of a rom that reached the highest score, kept to race it again as a `Ghost`
+///
+/// As text, the score comes first and the replay follows:
+///
+/// ```text
+/// score 120
+/// seed 42
+/// 8
+/// 9 +5
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BestRun {
+    /// Score reached at the end of the run
+    pub score: u16,
+    /// Input of the run, from the rom freshly loaded
+    pub replay: Replay,
+}
+
+/// A best run played again in an interpreter of its own, one frame for every frame of the live
+/// game, for the frontend to draw its display faintly behind the live one
+pub struct Ghost {
+    chip8: Chip8,
+    rom: Vec<u8>,
+    replay: Replay,
+}
+
+impl BestRun {
+    /// Whether a run that reached `score` does better than this one
+    pub fn is_beaten_by(&self, score: u16) -> bool {
+        score > self.score
+    }
+}
+
+impl fmt::Display for BestRun {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "score {}", self.score)?;
+        write!(f, "{}", self.replay)
+    }
+}
+
+impl FromStr for BestRun {
+    type Err = String;
+
+    fn from_str(best_run: &str) -> Result<Self, Self::Err> {
+        let (score, replay) = best_run.split_once('\n').unwrap_or((best_run, ""));
+        let score = score
+            .strip_prefix("score ")
+            .and_then(|score| score.trim().parse().ok())
+            .ok_or_else(|| "A best run starts with its score".to_string())?;
+        Ok(BestRun {
+            score,
+            replay: replay.parse()?,
+        })
+    }
+}
+
+impl Ghost {
+    /// Plays `replay` on `rom` in an interpreter made by `builder`, which should have the variant,
+    /// quirks and font of the live one
+    pub fn new(builder: Chip8Builder, rom: Vec<u8>, replay: Replay) -> Result<Ghost, Chip8Error> {
+        let mut ghost = Ghost {
+            chip8: builder.build(),
+            rom,
+            replay,
+        };
+        ghost.restart()?;
+        Ok(ghost)
+    }
+
+    /// Plays the run again from its start, when the live game restarts
+    pub fn restart(&mut self) -> Result<(), Chip8Error> {
+        self.chip8.reset(false)?;
+        self.chip8.load_program(self.rom.clone())?;
+        self.chip8.play_replay(self.replay.clone());
+        Ok(())
+    }
+
+    /// Runs the next frame of the run, the display stays as the run ended once it's over
+    pub fn run_frame(&mut self) -> Result<(), Chip8Error> {
+        if self.chip8.is_replaying() {
+            // The replay picks how many instructions the frame runs
+            self.chip8.run_frame(0)?;
+        }
+        Ok(())
+    }
+
+    /// What the run shows on its display
+    pub fn framebuffer(&self) -> &FrameBuffer {
+        self.chip8.framebuffer()
+    }
+
+    /// Whether every frame of the run was played
+    pub fn is_over(&self) -> bool {
+        !self.chip8.is_replaying()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_plays_the_best_run_next_to_the_live_game() -> Result<(), Chip8Error> {
+        // Draws the font sprite of a random digit, over and over
+        let rom = vec![0xC0, 0x0F, 0xF0, 0x29, 0x00, 0xE0, 0xD0, 0x15, 0x12, 0x00];
+        let mut live = Chip8::builder().build();
+        live.load_program(rom.clone())?;
+        live.start_recording(7);
+        live.run_frame(10)?;
+        live.run_frame(10)?;
+        let best_run = BestRun {
+            score: 1,
+            replay: live.stop_recording().unwrap_or_default(),
+        };
+
+        let best_run = best_run
+            .to_string()
+            .parse::<BestRun>()
+            .map_err(Chip8Error::InvalidConfig)?;
+        assert!(best_run.is_beaten_by(2));
+        assert!(!best_run.is_beaten_by(1));
+
+        let mut ghost = Ghost::new(Chip8::builder(), rom, best_run.replay)?;
+        ghost.run_frame()?;
+        ghost.run_frame()?;
+        ghost.run_frame()?;
+
+        assert!(ghost.is_over());
+        assert!(ghost.framebuffer().set_pixels().next().is_some());
+        assert_eq!(ghost.framebuffer().pixels(), live.framebuffer().pixels());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,74 @@
+use std::sync::mpsc::Sender;
+
+use crate::{KeyEvent, Snapshot};
+
+/// Request sent by a `Chip8Handle`, applied by the interpreter at the start of the next frame
+#[derive(Debug, Clone)]
+pub(crate) enum Control {
+    Pause,
+    Resume,
+    Reset,
+    LoadRom(Vec<u8>),
+    SetSpeed(usize),
+    Key(KeyEvent),
+    SaveState(Sender<Snapshot>),
+}
+
+/// Controls an interpreter running on another thread
+///
+/// Created by `Chip8::handle`, it can be cloned and sent to any thread. Requests are queued and
+/// applied in order at the start of the next `run_frame` or `emulate_cycle`, or whenever the
+/// emulation thread calls `Chip8::apply_controls`. Every method returns false once the
+/// interpreter is gone
+#[derive(Debug, Clone)]
+pub struct Chip8Handle {
+    sender: Sender<Control>,
+}
+
+impl Chip8Handle {
+    pub(crate) fn new(sender: Sender<Control>) -> Chip8Handle {
+        Chip8Handle { sender }
+    }
+
+    /// Stops fetching instructions, freezes the timers and cuts the sound, like `Chip8::pause`
+    pub fn pause(&self) -> bool {
+        self.send(Control::Pause)
+    }
+
+    /// Runs instructions and timers again after `pause`, like `Chip8::resume`
+    pub fn resume(&self) -> bool {
+        self.send(Control::Resume)
+    }
+
+    /// Restarts the current rom, like `Chip8::reset(true)`
+    pub fn reset(&self) -> bool {
+        self.send(Control::Reset)
+    }
+
+    /// Resets the interpreter and loads another rom
+    pub fn load_rom(&self, rom_data: Vec<u8>) -> bool {
+        self.send(Control::LoadRom(rom_data))
+    }
+
+    /// Runs `instructions_per_frame` instructions in every `run_frame`, whatever the frontend passes it
+    pub fn set_speed(&self, instructions_per_frame: usize) -> bool {
+        self.send(Control::SetSpeed(instructions_per_frame))
+    }
+
+    /// Presses or releases a key of the keypad as if the keyboard had reported it
+    pub fn send_key(&self, event: KeyEvent) -> bool {
+        self.send(Control::Key(event))
+    }
+
+    /// Sends a snapshot of the interpreter to `reply` once it applies the request
+    ///
+    /// Wait on the receiving end from another thread, the snapshot only comes when the emulation
+    /// thread gets to the next frame
+    pub fn save_state(&self, reply: Sender<Snapshot>) -> bool {
+        self.send(Control::SaveState(reply))
+    }
+
+    fn send(&self, control: Control) -> bool {
+        self.sender.send(control).is_ok()
+    }
+}
@@ -0,0 +1,745 @@
+use std::collections::HashSet;
+
+/// A single instruction with its operands already extracted from the opcode
+///
+/// `vx` and `vy` are register indexes, `nnn` an address, `nn` a byte and `n` a nibble
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// 0NNN: calls a machine code routine, only 0230 on two-page roms is supported
+    System {
+        /// Address of the routine
+        nnn: u16,
+    },
+    /// 00CN: scrolls the display down N rows (SUPER-CHIP)
+    ScrollDown {
+        /// Rows to scroll
+        n: u8,
+    },
+    /// 00DN: scrolls the display up N rows (XO-CHIP)
+    ScrollUp {
+        /// Rows to scroll
+        n: u8,
+    },
+    /// 00E0: clears the display
+    ClearDisplay,
+    /// 00EE: returns from a subroutine
+    Return,
+    /// 00FB: scrolls the display right 4 pixels (SUPER-CHIP)
+    ScrollRight,
+    /// 00FC: scrolls the display left 4 pixels (SUPER-CHIP)
+    ScrollLeft,
+    /// 00FD: exits the interpreter (SUPER-CHIP)
+    Exit,
+    /// 00FE: switches to the 64x32 display (SUPER-CHIP)
+    LowResolution,
+    /// 00FF: switches to the 128x64 display (SUPER-CHIP)
+    HighResolution,
+    /// 1NNN: jumps to NNN
+    Jump {
+        /// Address to jump to
+        nnn: u16,
+    },
+    /// 2NNN: calls the subroutine at NNN
+    Call {
+        /// Address of the subroutine
+        nnn: u16,
+    },
+    /// 3XNN: skips the next instruction if VX equals NN
+    SkipIfEqual {
+        /// Register to compare
+        vx: usize,
+        /// Value to compare against
+        nn: u8,
+    },
+    /// 4XNN: skips the next instruction if VX doesn't equal NN
+    SkipIfNotEqual {
+        /// Register to compare
+        vx: usize,
+        /// Value to compare against
+        nn: u8,
+    },
+    /// 5XY0: skips the next instruction if VX equals VY
+    SkipIfRegistersEqual {
+        /// First register
+        vx: usize,
+        /// Second register
+        vy: usize,
+    },
+    /// 5XY2: stores VX to VY in memory starting at I, without moving I (XO-CHIP)
+    StoreRange {
+        /// First register stored
+        vx: usize,
+        /// Last register stored, registers go backwards when it comes before VX
+        vy: usize,
+    },
+    /// 5XY3: loads VX to VY from memory starting at I, without moving I (XO-CHIP)
+    LoadRange {
+        /// First register loaded
+        vx: usize,
+        /// Last register loaded, registers go backwards when it comes before VX
+        vy: usize,
+    },
+    /// 6XNN: sets VX to NN
+    SetRegister {
+        /// Register to set
+        vx: usize,
+        /// Value to set
+        nn: u8,
+    },
+    /// 7XNN: adds NN to VX without touching VF
+    AddToRegister {
+        /// Register to add to
+        vx: usize,
+        /// Value to add
+        nn: u8,
+    },
+    /// 8XY0: sets VX to VY
+    Copy {
+        /// Destination register
+        vx: usize,
+        /// Source register
+        vy: usize,
+    },
+    /// 8XY1: sets VX to VX | VY
+    Or {
+        /// Destination register
+        vx: usize,
+        /// Source register
+        vy: usize,
+    },
+    /// 8XY2: sets VX to VX & VY
+    And {
+        /// Destination register
+        vx: usize,
+        /// Source register
+        vy: usize,
+    },
+    /// 8XY3: sets VX to VX ^ VY
+    Xor {
+        /// Destination register
+        vx: usize,
+        /// Source register
+        vy: usize,
+    },
+    /// 8XY4: adds VY to VX, VF holds the carry
+    Add {
+        /// Destination register
+        vx: usize,
+        /// Source register
+        vy: usize,
+    },
+    /// 8XY5: subtracts VY from VX, VF holds whether there was no borrow
+    Subtract {
+        /// Destination register
+        vx: usize,
+        /// Source register
+        vy: usize,
+    },
+    /// 8XY6: shifts right by one, VF holds the bit shifted out
+    ShiftRight {
+        /// Destination register
+        vx: usize,
+        /// Source register when the `shift_uses_vy` quirk is enabled
+        vy: usize,
+    },
+    /// 8XY7: sets VX to VY - VX, VF holds whether there was no borrow
+    SubtractReversed {
+        /// Destination register
+        vx: usize,
+        /// Source register
+        vy: usize,
+    },
+    /// 8XYE: shifts left by one, VF holds the bit shifted out
+    ShiftLeft {
+        /// Destination register
+        vx: usize,
+        /// Source register when the `shift_uses_vy` quirk is enabled
+        vy: usize,
+    },
+    /// 9XY0: skips the next instruction if VX doesn't equal VY
+    SkipIfRegistersNotEqual {
+        /// First register
+        vx: usize,
+        /// Second register
+        vy: usize,
+    },
+    /// ANNN: sets I to NNN
+    SetIndex {
+        /// Address to set
+        nnn: u16,
+    },
+    /// BNNN: jumps to NNN + V0, or to XNN + VX with the `jump_uses_vx` quirk
+    JumpWithOffset {
+        /// Register added with the `jump_uses_vx` quirk
+        vx: usize,
+        /// Base address
+        nnn: u16,
+    },
+    /// CXNN: sets VX to a random number masked with NN
+    Random {
+        /// Register to set
+        vx: usize,
+        /// Mask applied to the random number
+        nn: u8,
+    },
+    /// DXYN: draws an 8xN sprite from I at VX, VY, or a 16x16 one when N is 0 on SUPER-CHIP
+    Draw {
+        /// Register holding the column
+        vx: usize,
+        /// Register holding the row
+        vy: usize,
+        /// Rows of the sprite
+        n: u8,
+    },
+    /// EX9E: skips the next instruction if the key in VX is pressed
+    SkipIfKeyPressed {
+        /// Register holding the key
+        vx: usize,
+    },
+    /// EXA1: skips the next instruction if the key in VX isn't pressed
+    SkipIfKeyNotPressed {
+        /// Register holding the key
+        vx: usize,
+    },
+    /// F000 NNNN: sets I to the 16 bit address in the next two bytes (XO-CHIP)
+    LoadLongIndex,
+    /// FN01: selects the display planes drawn, cleared and scrolled by the next instructions (XO-CHIP)
+    SelectPlanes {
+        /// Bit mask of the planes, 1 for the first one, 2 for the second one and 3 for both
+        planes: u8,
+    },
+    /// F002: loads the 16 bytes at I as the audio pattern played by the sound timer (XO-CHIP)
+    LoadAudioPattern,
+    /// FX07: sets VX to the delay timer
+    LoadDelayTimer {
+        /// Register to set
+        vx: usize,
+    },
+    /// FX0A: waits for a key press and stores it in VX
+    WaitForKey {
+        /// Register to set
+        vx: usize,
+    },
+    /// FX15: sets the delay timer to VX
+    SetDelayTimer {
+        /// Register holding the value
+        vx: usize,
+    },
+    /// FX18: sets the sound timer to VX
+    SetSoundTimer {
+        /// Register holding the value
+        vx: usize,
+    },
+    /// FX1E: adds VX to I, wrapping around past the last address
+    AddToIndex {
+        /// Register holding the value
+        vx: usize,
+    },
+    /// FX29: points I at the font sprite for the digit in VX
+    LoadFontDigit {
+        /// Register holding the digit
+        vx: usize,
+    },
+    /// FX30: points I at the big font sprite for the digit in VX (SUPER-CHIP)
+    LoadBigFontDigit {
+        /// Register holding the digit
+        vx: usize,
+    },
+    /// FX33: stores the binary coded decimal of VX at I
+    StoreBcd {
+        /// Register holding the value
+        vx: usize,
+    },
+    /// FX3A: sets the pitch the audio pattern plays at to VX (XO-CHIP)
+    SetPitch {
+        /// Register holding the pitch
+        vx: usize,
+    },
+    /// FX55: stores V0 to VX in memory starting at I
+    StoreRegisters {
+        /// Last register stored
+        vx: usize,
+    },
+    /// FX65: loads V0 to VX from memory starting at I
+    LoadRegisters {
+        /// Last register loaded
+        vx: usize,
+    },
+    /// FX75: stores V0 to VX in the RPL user flags (SUPER-CHIP)
+    StoreFlags {
+        /// Last register stored
+        vx: usize,
+    },
+    /// FX85: loads V0 to VX from the RPL user flags (SUPER-CHIP)
+    LoadFlags {
+        /// Last register loaded
+        vx: usize,
+    },
+    /// Opcode that doesn't match any instruction
+    Unknown {
+        /// The opcode as read from memory
+        opcode: u16,
+    },
+}
+
+impl Instruction {
+    /// Whether the instruction only exists on SUPER-CHIP and later interpreters
+    pub fn requires_superchip(self) -> bool {
+        matches!(
+            self,
+            Instruction::ScrollDown { .. }
+                | Instruction::ScrollRight
+                | Instruction::ScrollLeft
+                | Instruction::Exit
+                | Instruction::LowResolution
+                | Instruction::HighResolution
+                | Instruction::LoadBigFontDigit { .. }
+                | Instruction::StoreFlags { .. }
+                | Instruction::LoadFlags { .. }
+        )
+    }
+
+    /// Whether the instruction only exists on XO-CHIP
+    pub fn requires_xochip(self) -> bool {
+        matches!(
+            self,
+            Instruction::ScrollUp { .. }
+                | Instruction::StoreRange { .. }
+                | Instruction::LoadRange { .. }
+                | Instruction::LoadLongIndex
+                | Instruction::SelectPlanes { .. }
+                | Instruction::LoadAudioPattern
+                | Instruction::SetPitch { .. }
+        )
+    }
+
+    /// Whether the instruction sets the program counter itself instead of moving to the next one
+    pub fn is_jump(self) -> bool {
+        matches!(
+            self,
+            Instruction::Jump { .. }
+                | Instruction::Call { .. }
+                | Instruction::JumpWithOffset { .. }
+        )
+    }
+
+    /// Opcode pattern of the instruction family, like `8XY6` or `DXYN`
+    ///
+    /// Opcodes that don't match any instruction are reported as `????`
+    pub fn pattern(self) -> &'static str {
+        match self {
+            Instruction::System { .. } => "0NNN",
+            Instruction::ScrollDown { .. } => "00CN",
+            Instruction::ScrollUp { .. } => "00DN",
+            Instruction::ClearDisplay => "00E0",
+            Instruction::Return => "00EE",
+            Instruction::ScrollRight => "00FB",
+            Instruction::ScrollLeft => "00FC",
+            Instruction::Exit => "00FD",
+            Instruction::LowResolution => "00FE",
+            Instruction::HighResolution => "00FF",
+            Instruction::Jump { .. } => "1NNN",
+            Instruction::Call { .. } => "2NNN",
+            Instruction::SkipIfEqual { .. } => "3XNN",
+            Instruction::SkipIfNotEqual { .. } => "4XNN",
+            Instruction::SkipIfRegistersEqual { .. } => "5XY0",
+            Instruction::StoreRange { .. } => "5XY2",
+            Instruction::LoadRange { .. } => "5XY3",
+            Instruction::SetRegister { .. } => "6XNN",
+            Instruction::AddToRegister { .. } => "7XNN",
+            Instruction::Copy { .. } => "8XY0",
+            Instruction::Or { .. } => "8XY1",
+            Instruction::And { .. } => "8XY2",
+            Instruction::Xor { .. } => "8XY3",
+            Instruction::Add { .. } => "8XY4",
+            Instruction::Subtract { .. } => "8XY5",
+            Instruction::ShiftRight { .. } => "8XY6",
+            Instruction::SubtractReversed { .. } => "8XY7",
+            Instruction::ShiftLeft { .. } => "8XYE",
+            Instruction::SkipIfRegistersNotEqual { .. } => "9XY0",
+            Instruction::SetIndex { .. } => "ANNN",
+            Instruction::JumpWithOffset { .. } => "BNNN",
+            Instruction::Random { .. } => "CXNN",
+            Instruction::Draw { .. } => "DXYN",
+            Instruction::SkipIfKeyPressed { .. } => "EX9E",
+            Instruction::SkipIfKeyNotPressed { .. } => "EXA1",
+            Instruction::LoadLongIndex => "F000",
+            Instruction::SelectPlanes { .. } => "FN01",
+            Instruction::LoadAudioPattern => "F002",
+            Instruction::LoadDelayTimer { .. } => "FX07",
+            Instruction::WaitForKey { .. } => "FX0A",
+            Instruction::SetDelayTimer { .. } => "FX15",
+            Instruction::SetSoundTimer { .. } => "FX18",
+            Instruction::AddToIndex { .. } => "FX1E",
+            Instruction::LoadFontDigit { .. } => "FX29",
+            Instruction::LoadBigFontDigit { .. } => "FX30",
+            Instruction::StoreBcd { .. } => "FX33",
+            Instruction::SetPitch { .. } => "FX3A",
+            Instruction::StoreRegisters { .. } => "FX55",
+            Instruction::LoadRegisters { .. } => "FX65",
+            Instruction::StoreFlags { .. } => "FX75",
+            Instruction::LoadFlags { .. } => "FX85",
+            Instruction::Unknown { .. } => "????",
+        }
+    }
+
+    /// What the instruction does, in a sentence
+    pub fn summary(self) -> &'static str {
+        match self {
+            Instruction::System { .. } => {
+                "Calls a machine code routine, only 0230 on two-page roms is supported"
+            }
+            Instruction::ScrollDown { .. } => "Scrolls the display down N rows",
+            Instruction::ScrollUp { .. } => "Scrolls the display up N rows",
+            Instruction::ClearDisplay => "Clears the display",
+            Instruction::Return => "Returns from a subroutine",
+            Instruction::ScrollRight => "Scrolls the display right 4 pixels",
+            Instruction::ScrollLeft => "Scrolls the display left 4 pixels",
+            Instruction::Exit => "Exits the interpreter",
+            Instruction::LowResolution => "Switches to the 64x32 display",
+            Instruction::HighResolution => "Switches to the 128x64 display",
+            Instruction::Jump { .. } => "Jumps to NNN",
+            Instruction::Call { .. } => "Calls the subroutine at NNN",
+            Instruction::SkipIfEqual { .. } => "Skips the next instruction if VX equals NN",
+            Instruction::SkipIfNotEqual { .. } => {
+                "Skips the next instruction if VX doesn't equal NN"
+            }
+            Instruction::SkipIfRegistersEqual { .. } => {
+                "Skips the next instruction if VX equals VY"
+            }
+            Instruction::StoreRange { .. } => {
+                "Stores VX to VY in memory starting at I, without moving I"
+            }
+            Instruction::LoadRange { .. } => {
+                "Loads VX to VY from memory starting at I, without moving I"
+            }
+            Instruction::SetRegister { .. } => "Sets VX to NN",
+            Instruction::AddToRegister { .. } => "Adds NN to VX without touching VF",
+            Instruction::Copy { .. } => "Sets VX to VY",
+            Instruction::Or { .. } => "Sets VX to VX | VY",
+            Instruction::And { .. } => "Sets VX to VX & VY",
+            Instruction::Xor { .. } => "Sets VX to VX ^ VY",
+            Instruction::Add { .. } => "Adds VY to VX, VF holds the carry",
+            Instruction::Subtract { .. } => {
+                "Subtracts VY from VX, VF holds whether there was no borrow"
+            }
+            Instruction::ShiftRight { .. } => {
+                "Shifts VX right by one, VF holds the bit shifted out"
+            }
+            Instruction::SubtractReversed { .. } => {
+                "Sets VX to VY - VX, VF holds whether there was no borrow"
+            }
+            Instruction::ShiftLeft { .. } => "Shifts VX left by one, VF holds the bit shifted out",
+            Instruction::SkipIfRegistersNotEqual { .. } => {
+                "Skips the next instruction if VX doesn't equal VY"
+            }
+            Instruction::SetIndex { .. } => "Sets I to NNN",
+            Instruction::JumpWithOffset { .. } => {
+                "Jumps to NNN + V0, or to XNN + VX with the `jump_uses_vx` quirk"
+            }
+            Instruction::Random { .. } => "Sets VX to a random number masked with NN",
+            Instruction::Draw { .. } => {
+                "Draws an 8xN sprite from I at VX, VY, or a 16x16 one when N is 0 on SUPER-CHIP"
+            }
+            Instruction::SkipIfKeyPressed { .. } => {
+                "Skips the next instruction if the key in VX is pressed"
+            }
+            Instruction::SkipIfKeyNotPressed { .. } => {
+                "Skips the next instruction if the key in VX isn't pressed"
+            }
+            Instruction::LoadLongIndex => "Sets I to the 16 bit address in the next two bytes",
+            Instruction::SelectPlanes { .. } => {
+                "Selects the display planes drawn, cleared and scrolled by the next instructions"
+            }
+            Instruction::LoadAudioPattern => {
+                "Loads the 16 bytes at I as the audio pattern played by the sound timer"
+            }
+            Instruction::LoadDelayTimer { .. } => "Sets VX to the delay timer",
+            Instruction::WaitForKey { .. } => "Waits for a key press and stores it in VX",
+            Instruction::SetDelayTimer { .. } => "Sets the delay timer to VX",
+            Instruction::SetSoundTimer { .. } => "Sets the sound timer to VX",
+            Instruction::AddToIndex { .. } => "Adds VX to I, wrapping around past the last address",
+            Instruction::LoadFontDigit { .. } => "Points I at the font sprite for the digit in VX",
+            Instruction::LoadBigFontDigit { .. } => {
+                "Points I at the big font sprite for the digit in VX"
+            }
+            Instruction::StoreBcd { .. } => "Stores the binary coded decimal of VX at I",
+            Instruction::SetPitch { .. } => "Sets the pitch the audio pattern plays at to VX",
+            Instruction::StoreRegisters { .. } => "Stores V0 to VX in memory starting at I",
+            Instruction::LoadRegisters { .. } => "Loads V0 to VX from memory starting at I",
+            Instruction::StoreFlags { .. } => "Stores V0 to VX in the RPL user flags",
+            Instruction::LoadFlags { .. } => "Loads V0 to VX from the RPL user flags",
+            Instruction::Unknown { .. } => "Opcode that doesn't match any instruction",
+        }
+    }
+
+    /// Names of the `Quirks` fields that change what the instruction does
+    pub fn affecting_quirks(self) -> &'static [&'static str] {
+        match self {
+            Instruction::ShiftRight { .. } | Instruction::ShiftLeft { .. } => &["shift_uses_vy"],
+            Instruction::StoreRegisters { .. } | Instruction::LoadRegisters { .. } => {
+                &["load_store_increments_i"]
+            }
+            Instruction::Or { .. } | Instruction::And { .. } | Instruction::Xor { .. } => {
+                &["logic_resets_vf"]
+            }
+            Instruction::JumpWithOffset { .. } => &["jump_uses_vx"],
+            Instruction::Draw { .. } => &["wrap_sprites", "display_wait"],
+            _ => &[],
+        }
+    }
+}
+
+/// Writes the instruction with the syntax of Octo, like `v0 += 0x01` or `sprite v1 v2 5`
+///
+/// Skips are written as the `if ... then` condition under which the next instruction runs
+impl std::fmt::Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Instruction::System { nnn } => write!(f, "native {:#05X}", nnn),
+            Instruction::ScrollDown { n } => write!(f, "scroll-down {}", n),
+            Instruction::ScrollUp { n } => write!(f, "scroll-up {}", n),
+            Instruction::ClearDisplay => write!(f, "clear"),
+            Instruction::Return => write!(f, "return"),
+            Instruction::ScrollRight => write!(f, "scroll-right"),
+            Instruction::ScrollLeft => write!(f, "scroll-left"),
+            Instruction::Exit => write!(f, "exit"),
+            Instruction::LowResolution => write!(f, "lores"),
+            Instruction::HighResolution => write!(f, "hires"),
+            Instruction::Jump { nnn } => write!(f, "jump {:#05X}", nnn),
+            Instruction::Call { nnn } => write!(f, ":call {:#05X}", nnn),
+            Instruction::SkipIfEqual { vx, nn } => write!(f, "if v{:x} != {:#04X} then", vx, nn),
+            Instruction::SkipIfNotEqual { vx, nn } => {
+                write!(f, "if v{:x} == {:#04X} then", vx, nn)
+            }
+            Instruction::SkipIfRegistersEqual { vx, vy } => {
+                write!(f, "if v{:x} != v{:x} then", vx, vy)
+            }
+            Instruction::StoreRange { vx, vy } => write!(f, "save v{:x} - v{:x}", vx, vy),
+            Instruction::LoadRange { vx, vy } => write!(f, "load v{:x} - v{:x}", vx, vy),
+            Instruction::SetRegister { vx, nn } => write!(f, "v{:x} := {:#04X}", vx, nn),
+            Instruction::AddToRegister { vx, nn } => write!(f, "v{:x} += {:#04X}", vx, nn),
+            Instruction::Copy { vx, vy } => write!(f, "v{:x} := v{:x}", vx, vy),
+            Instruction::Or { vx, vy } => write!(f, "v{:x} |= v{:x}", vx, vy),
+            Instruction::And { vx, vy } => write!(f, "v{:x} &= v{:x}", vx, vy),
+            Instruction::Xor { vx, vy } => write!(f, "v{:x} ^= v{:x}", vx, vy),
+            Instruction::Add { vx, vy } => write!(f, "v{:x} += v{:x}", vx, vy),
+            Instruction::Subtract { vx, vy } => write!(f, "v{:x} -= v{:x}", vx, vy),
+            Instruction::ShiftRight { vx, vy } => write!(f, "v{:x} >>= v{:x}", vx, vy),
+            Instruction::SubtractReversed { vx, vy } => write!(f, "v{:x} =- v{:x}", vx, vy),
+            Instruction::ShiftLeft { vx, vy } => write!(f, "v{:x} <<= v{:x}", vx, vy),
+            Instruction::SkipIfRegistersNotEqual { vx, vy } => {
+                write!(f, "if v{:x} == v{:x} then", vx, vy)
+            }
+            Instruction::SetIndex { nnn } => write!(f, "i := {:#05X}", nnn),
+            Instruction::JumpWithOffset { nnn, .. } => write!(f, "jump0 {:#05X}", nnn),
+            Instruction::Random { vx, nn } => write!(f, "v{:x} := random {:#04X}", vx, nn),
+            Instruction::Draw { vx, vy, n } => write!(f, "sprite v{:x} v{:x} {}", vx, vy, n),
+            Instruction::SkipIfKeyPressed { vx } => write!(f, "if v{:x} -key then", vx),
+            Instruction::SkipIfKeyNotPressed { vx } => write!(f, "if v{:x} key then", vx),
+            Instruction::LoadLongIndex => write!(f, "i := long"),
+            Instruction::SelectPlanes { planes } => write!(f, "plane {}", planes),
+            Instruction::LoadAudioPattern => write!(f, "audio"),
+            Instruction::LoadDelayTimer { vx } => write!(f, "v{:x} := delay", vx),
+            Instruction::WaitForKey { vx } => write!(f, "v{:x} := key", vx),
+            Instruction::SetDelayTimer { vx } => write!(f, "delay := v{:x}", vx),
+            Instruction::SetSoundTimer { vx } => write!(f, "buzzer := v{:x}", vx),
+            Instruction::AddToIndex { vx } => write!(f, "i += v{:x}", vx),
+            Instruction::LoadFontDigit { vx } => write!(f, "i := hex v{:x}", vx),
+            Instruction::LoadBigFontDigit { vx } => write!(f, "i := bighex v{:x}", vx),
+            Instruction::StoreBcd { vx } => write!(f, "bcd v{:x}", vx),
+            Instruction::SetPitch { vx } => write!(f, "pitch := v{:x}", vx),
+            Instruction::StoreRegisters { vx } => write!(f, "save v{:x}", vx),
+            Instruction::LoadRegisters { vx } => write!(f, "load v{:x}", vx),
+            Instruction::StoreFlags { vx } => write!(f, "saveflags v{:x}", vx),
+            Instruction::LoadFlags { vx } => write!(f, "loadflags v{:x}", vx),
+            Instruction::Unknown { opcode } => {
+                write!(f, "{:#04X} {:#04X}", opcode >> 8, opcode & 0xFF)
+            }
+        }
+    }
+}
+
+/// Splits an opcode into the instruction it encodes and its operands
+///
+/// Every instruction known to the core is decoded, whether the variant running it supports it or not
+pub fn decode(opcode: u16) -> Instruction {
+    let vx = ((opcode & 0x0F00) >> 8) as usize;
+    let vy = ((opcode & 0x00F0) >> 4) as usize;
+    let nnn = opcode & 0x0FFF;
+    let nn = (opcode & 0x00FF) as u8;
+    let n = (opcode & 0x000F) as u8;
+
+    match opcode {
+        0x00C0..=0x00CF => Instruction::ScrollDown { n },
+        0x00D0..=0x00DF => Instruction::ScrollUp { n },
+        0x00E0 => Instruction::ClearDisplay,
+        0x00EE => Instruction::Return,
+        0x00FB => Instruction::ScrollRight,
+        0x00FC => Instruction::ScrollLeft,
+        0x00FD => Instruction::Exit,
+        0x00FE => Instruction::LowResolution,
+        0x00FF => Instruction::HighResolution,
+        0x0000..=0x0FFF => Instruction::System { nnn },
+        0x1000..=0x1FFF => Instruction::Jump { nnn },
+        0x2000..=0x2FFF => Instruction::Call { nnn },
+        0x3000..=0x3FFF => Instruction::SkipIfEqual { vx, nn },
+        0x4000..=0x4FFF => Instruction::SkipIfNotEqual { vx, nn },
+        0x5000..=0x5FFF => match n {
+            0x2 => Instruction::StoreRange { vx, vy },
+            0x3 => Instruction::LoadRange { vx, vy },
+            _ => Instruction::SkipIfRegistersEqual { vx, vy },
+        },
+        0x6000..=0x6FFF => Instruction::SetRegister { vx, nn },
+        0x7000..=0x7FFF => Instruction::AddToRegister { vx, nn },
+        0x8000..=0x8FFF => match n {
+            0x0 => Instruction::Copy { vx, vy },
+            0x1 => Instruction::Or { vx, vy },
+            0x2 => Instruction::And { vx, vy },
+            0x3 => Instruction::Xor { vx, vy },
+            0x4 => Instruction::Add { vx, vy },
+            0x5 => Instruction::Subtract { vx, vy },
+            0x6 => Instruction::ShiftRight { vx, vy },
+            0x7 => Instruction::SubtractReversed { vx, vy },
+            0xE => Instruction::ShiftLeft { vx, vy },
+            _ => Instruction::Unknown { opcode },
+        },
+        0x9000..=0x9FFF => Instruction::SkipIfRegistersNotEqual { vx, vy },
+        0xA000..=0xAFFF => Instruction::SetIndex { nnn },
+        0xB000..=0xBFFF => Instruction::JumpWithOffset { vx, nnn },
+        0xC000..=0xCFFF => Instruction::Random { vx, nn },
+        0xD000..=0xDFFF => Instruction::Draw { vx, vy, n },
+        0xE000..=0xEFFF => match nn {
+            0x9E => Instruction::SkipIfKeyPressed { vx },
+            0xA1 => Instruction::SkipIfKeyNotPressed { vx },
+            _ => Instruction::Unknown { opcode },
+        },
+        0xF000..=0xFFFF => match nn {
+            0x00 if vx == 0 => Instruction::LoadLongIndex,
+            0x01 => Instruction::SelectPlanes { planes: vx as u8 },
+            0x02 if vx == 0 => Instruction::LoadAudioPattern,
+            0x07 => Instruction::LoadDelayTimer { vx },
+            0x0A => Instruction::WaitForKey { vx },
+            0x15 => Instruction::SetDelayTimer { vx },
+            0x18 => Instruction::SetSoundTimer { vx },
+            0x1E => Instruction::AddToIndex { vx },
+            0x29 => Instruction::LoadFontDigit { vx },
+            0x30 => Instruction::LoadBigFontDigit { vx },
+            0x33 => Instruction::StoreBcd { vx },
+            0x3A => Instruction::SetPitch { vx },
+            0x55 => Instruction::StoreRegisters { vx },
+            0x65 => Instruction::LoadRegisters { vx },
+            0x75 => Instruction::StoreFlags { vx },
+            0x85 => Instruction::LoadFlags { vx },
+            _ => Instruction::Unknown { opcode },
+        },
+    }
+}
+
+/// One instruction of every family `decode` knows, in the order of their opcodes
+///
+/// The operands are those of the lowest opcode of the family, like `00C0` for `00CN`
+pub fn instruction_set() -> Vec<Instruction> {
+    let mut patterns = HashSet::new();
+    (0..=u16::MAX)
+        .map(decode)
+        .filter(|instruction| !matches!(instruction, Instruction::Unknown { .. }))
+        .filter(|instruction| patterns.insert(instruction.pattern()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_decodes_the_operands() {
+        assert_eq!(
+            decode(0xD12F),
+            Instruction::Draw {
+                vx: 1,
+                vy: 2,
+                n: 0xF
+            }
+        );
+        assert_eq!(
+            decode(0x3A42),
+            Instruction::SkipIfEqual { vx: 0xA, nn: 0x42 }
+        );
+        assert_eq!(
+            decode(0xB123),
+            Instruction::JumpWithOffset { vx: 1, nnn: 0x123 }
+        );
+        assert_eq!(decode(0xF865), Instruction::LoadRegisters { vx: 8 });
+    }
+
+    #[test]
+    fn it_decodes_machine_code_calls_as_system() {
+        assert_eq!(decode(0x0230), Instruction::System { nnn: 0x230 });
+        assert_eq!(decode(0x00E0), Instruction::ClearDisplay);
+    }
+
+    #[test]
+    fn it_decodes_unknown_opcodes() {
+        assert_eq!(decode(0x8008), Instruction::Unknown { opcode: 0x8008 });
+        assert_eq!(decode(0xE000), Instruction::Unknown { opcode: 0xE000 });
+        assert_eq!(decode(0xF0FF), Instruction::Unknown { opcode: 0xF0FF });
+    }
+
+    #[test]
+    fn it_flags_superchip_instructions() {
+        assert!(decode(0x00FF).requires_superchip());
+        assert!(decode(0xF130).requires_superchip());
+        assert!(!decode(0x00E0).requires_superchip());
+    }
+
+    #[test]
+    fn it_decodes_xochip_instructions() {
+        assert_eq!(decode(0xF000), Instruction::LoadLongIndex);
+        assert_eq!(decode(0x5132), Instruction::StoreRange { vx: 1, vy: 3 });
+        assert_eq!(decode(0x5313), Instruction::LoadRange { vx: 3, vy: 1 });
+        assert_eq!(
+            decode(0x5130),
+            Instruction::SkipIfRegistersEqual { vx: 1, vy: 3 }
+        );
+        assert_eq!(decode(0xF301), Instruction::SelectPlanes { planes: 3 });
+        assert_eq!(decode(0xF002), Instruction::LoadAudioPattern);
+        assert_eq!(decode(0xF43A), Instruction::SetPitch { vx: 4 });
+        assert_eq!(decode(0x00D4), Instruction::ScrollUp { n: 4 });
+        assert!(decode(0xF000).requires_xochip());
+        assert!(!decode(0x00C4).requires_xochip());
+        assert_eq!(decode(0x5132).to_string(), "save v1 - v3");
+    }
+
+    #[test]
+    fn it_lists_every_instruction_family_once() {
+        let instructions = instruction_set();
+        assert_eq!(instructions.len(), 51);
+        assert_eq!(instructions[0], Instruction::System { nnn: 0 });
+        assert_eq!(instructions[1], Instruction::ScrollDown { n: 0 });
+        assert!(instructions.contains(&Instruction::Draw { vx: 0, vy: 0, n: 0 }));
+        assert!(instructions.contains(&Instruction::LoadLongIndex));
+        assert_eq!(
+            Instruction::Draw { vx: 0, vy: 0, n: 0 }.affecting_quirks(),
+            &["wrap_sprites", "display_wait"]
+        );
+        assert_eq!(Instruction::SetIndex { nnn: 0 }.summary(), "Sets I to NNN");
+    }
+
+    #[test]
+    fn it_writes_instructions_with_the_octo_syntax() {
+        assert_eq!(decode(0x6A12).to_string(), "va := 0x12");
+        assert_eq!(decode(0x3105).to_string(), "if v1 != 0x05 then");
+        assert_eq!(decode(0xA2F0).to_string(), "i := 0x2F0");
+        assert_eq!(decode(0xD125).to_string(), "sprite v1 v2 5");
+        assert_eq!(decode(0x8008).to_string(), "0x80 0x08");
+    }
+
+    #[test]
+    fn it_names_the_instruction_family() {
+        assert_eq!(decode(0x8AB6).pattern(), "8XY6");
+        assert_eq!(decode(0xD12F).pattern(), "DXYN");
+        assert_eq!(decode(0x00E0).pattern(), "00E0");
+        assert_eq!(decode(0x8008).pattern(), "????");
+    }
+}
@@ -0,0 +1,372 @@
+use std::collections::{HashMap, HashSet};
+
+use cranelift_codegen::{
+    ir::{condcodes::IntCC, types, AbiParam, InstBuilder, MemFlags, Value},
+    settings::{self, Configurable},
+};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, Module};
+
+use crate::{
+    errors::Chip8Error,
+    instruction::{decode, Instruction},
+    quirks::Quirks,
+};
+
+// Times a block has to start running in the interpreter before it is compiled
+const HOT_THRESHOLD: u32 = 8;
+const MAX_BLOCK_LENGTH: usize = 64;
+// Blocks rewritten this many times are self-modifying code, left to the interpreter for good
+const MAX_INVALIDATIONS: u32 = 4;
+
+// Takes the V registers and I, returns the address of the next instruction
+type CompiledBlock = unsafe extern "C" fn(*mut u8, *mut u16) -> u32;
+
+struct Block {
+    code: CompiledBlock,
+    // Copy of the memory it was compiled from, to notice when the program rewrites it
+    bytes: Vec<u8>,
+    instructions: usize,
+    last_opcode: u16,
+}
+
+/// What running a compiled block did
+pub(crate) struct Ran {
+    pub(crate) next: u16,
+    pub(crate) instructions: usize,
+    pub(crate) last_opcode: u16,
+}
+
+/// Compiles the hot basic blocks of a program to native code with cranelift
+///
+/// Only instructions working on the V registers and I are compiled, a block ends right before
+/// anything touching memory, the display, the timers, the keypad or the stack, or right after a
+/// jump or skip. Whatever is left goes through the interpreter as usual. XO-CHIP skips depend on
+/// the instruction they skip, so they are left to the interpreter too.
+///
+/// Blocks keep a copy of the bytes they were compiled from and are thrown away when memory no
+/// longer matches it. The code of those blocks is only freed with the whole `Jit`, which is why
+/// addresses invalidated too often stop being compiled
+pub(crate) struct Jit {
+    module: JITModule,
+    builder_context: FunctionBuilderContext,
+    compiled_for: (Quirks, bool, bool),
+    blocks: HashMap<u16, Block>,
+    hits: HashMap<u16, u32>,
+    invalidations: HashMap<u16, u32>,
+    interpreted: HashSet<u16>,
+}
+
+impl Jit {
+    pub(crate) fn new() -> Result<Jit, Chip8Error> {
+        let unavailable =
+            |error: &dyn std::fmt::Display| Chip8Error::JitUnavailable(error.to_string());
+        let mut flags = settings::builder();
+        flags
+            .set("opt_level", "speed")
+            .map_err(|error| unavailable(&error))?;
+        let isa = cranelift_native::builder()
+            .map_err(|error| unavailable(&error))?
+            .finish(settings::Flags::new(flags))
+            .map_err(|error| unavailable(&error))?;
+
+        Ok(Jit {
+            module: JITModule::new(JITBuilder::with_isa(isa, default_libcall_names())),
+            builder_context: FunctionBuilderContext::new(),
+            compiled_for: (Quirks::default(), true, false),
+            blocks: HashMap::new(),
+            hits: HashMap::new(),
+            invalidations: HashMap::new(),
+            interpreted: HashSet::new(),
+        })
+    }
+
+    /// Runs the block starting at `address` if it is compiled and no longer than `budget`
+    /// instructions, compiling it first once it is hot
+    ///
+    /// `None` leaves the instruction at `address` to the interpreter
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn run(
+        &mut self,
+        memory: &[u8],
+        address: u16,
+        v_registers: &mut [u8; 16],
+        index_register: &mut u16,
+        budget: usize,
+        quirks: Quirks,
+        jump_to_self_ends: bool,
+        long_skips: bool,
+    ) -> Option<Ran> {
+        // Quirks are baked into the compiled code
+        if self.compiled_for != (quirks, jump_to_self_ends, long_skips) {
+            self.compiled_for = (quirks, jump_to_self_ends, long_skips);
+            self.blocks.clear();
+            self.hits.clear();
+            self.interpreted.clear();
+        }
+        if self.interpreted.contains(&address) {
+            return None;
+        }
+
+        if !self.blocks.contains_key(&address) {
+            let hits = self.hits.entry(address).or_insert(0);
+            *hits += 1;
+            if *hits < HOT_THRESHOLD {
+                return None;
+            }
+            self.hits.remove(&address);
+            match self.compile(memory, address) {
+                Some(block) => self.blocks.insert(address, block),
+                None => {
+                    self.interpreted.insert(address);
+                    return None;
+                }
+            };
+        }
+
+        let block = &self.blocks[&address];
+        let start = address as usize;
+        if memory.get(start..start + block.bytes.len()) != Some(&block.bytes[..]) {
+            self.blocks.remove(&address);
+            let invalidations = self.invalidations.entry(address).or_insert(0);
+            *invalidations += 1;
+            if *invalidations >= MAX_INVALIDATIONS {
+                self.interpreted.insert(address);
+            }
+            return None;
+        }
+        if block.instructions > budget {
+            return None;
+        }
+
+        // SAFETY: the block was compiled for this signature, and only reads and writes the 16
+        // bytes of the V registers and the 2 bytes of I it is handed
+        let next = unsafe { (block.code)(v_registers.as_mut_ptr(), index_register) };
+        Some(Ran {
+            next: next as u16,
+            instructions: block.instructions,
+            last_opcode: block.last_opcode,
+        })
+    }
+
+    /// Number of blocks currently compiled
+    #[cfg(test)]
+    pub(crate) fn compiled_blocks(&self) -> usize {
+        self.blocks.len()
+    }
+
+    fn compile(&mut self, memory: &[u8], start: u16) -> Option<Block> {
+        let (quirks, jump_to_self_ends, long_skips) = self.compiled_for;
+        let pointer = self.module.target_config().pointer_type();
+        let mut context = self.module.make_context();
+        context.func.signature.params.push(AbiParam::new(pointer));
+        context.func.signature.params.push(AbiParam::new(pointer));
+        context
+            .func
+            .signature
+            .returns
+            .push(AbiParam::new(types::I32));
+
+        let mut builder = FunctionBuilder::new(&mut context.func, &mut self.builder_context);
+        let entry = builder.create_block();
+        builder.append_block_params_for_function_params(entry);
+        builder.switch_to_block(entry);
+        builder.seal_block(entry);
+        let v_registers = builder.block_params(entry)[0];
+        let index_register = builder.block_params(entry)[1];
+        let mut emitter = Emitter {
+            builder,
+            v_registers,
+        };
+
+        let mut address = start;
+        let mut instructions = 0;
+        let mut last_opcode = 0;
+        let mut next = None;
+        while instructions < MAX_BLOCK_LENGTH && next.is_none() {
+            let opcode = match memory.get(address as usize..address as usize + 2) {
+                // The last opcode of a 64 KiB memory is left to the interpreter, which wraps around
+                Some(bytes) if address < u16::MAX - 1 => u16::from_be_bytes([bytes[0], bytes[1]]),
+                _ => break,
+            };
+            let instruction = decode(opcode);
+            match instruction {
+                Instruction::SetRegister { vx, nn } => {
+                    let value = emitter.constant(types::I8, nn.into());
+                    emitter.store(vx, value);
+                }
+                Instruction::AddToRegister { vx, nn } => {
+                    let value = emitter.load(vx);
+                    let sum = emitter.builder.ins().iadd_imm(value, i64::from(nn));
+                    emitter.store(vx, sum);
+                }
+                Instruction::Copy { vx, vy } => {
+                    let value = emitter.load(vy);
+                    emitter.store(vx, value);
+                }
+                Instruction::Or { vx, vy }
+                | Instruction::And { vx, vy }
+                | Instruction::Xor { vx, vy } => {
+                    let (x, y) = (emitter.load(vx), emitter.load(vy));
+                    let result = match instruction {
+                        Instruction::Or { .. } => emitter.builder.ins().bor(x, y),
+                        Instruction::And { .. } => emitter.builder.ins().band(x, y),
+                        _ => emitter.builder.ins().bxor(x, y),
+                    };
+                    emitter.store(vx, result);
+                    if quirks.logic_resets_vf {
+                        let zero = emitter.constant(types::I8, 0);
+                        emitter.store(0xF, zero);
+                    }
+                }
+                Instruction::Add { vx, vy } => {
+                    let (x, y) = (emitter.load(vx), emitter.load(vy));
+                    let sum = emitter.builder.ins().iadd(x, y);
+                    let carry = emitter.builder.ins().icmp(IntCC::UnsignedLessThan, sum, x);
+                    // VF is stored last so the flag wins when VX is VF
+                    emitter.store(vx, sum);
+                    emitter.store(0xF, carry);
+                }
+                Instruction::Subtract { vx, vy } | Instruction::SubtractReversed { vx, vy } => {
+                    let (x, y) = (emitter.load(vx), emitter.load(vy));
+                    // 8XY5 computes VX - VY and 8XY7 VY - VX, VF is NOT borrow for both
+                    let (minuend, subtrahend) = match instruction {
+                        Instruction::Subtract { .. } => (x, y),
+                        _ => (y, x),
+                    };
+                    let difference = emitter.builder.ins().isub(minuend, subtrahend);
+                    let no_borrow = emitter.builder.ins().icmp(
+                        IntCC::UnsignedGreaterThanOrEqual,
+                        minuend,
+                        subtrahend,
+                    );
+                    emitter.store(vx, difference);
+                    emitter.store(0xF, no_borrow);
+                }
+                Instruction::ShiftRight { vx, vy } | Instruction::ShiftLeft { vx, vy } => {
+                    let value = emitter.load(if quirks.shift_uses_vy { vy } else { vx });
+                    let (shifted, flag) = match instruction {
+                        Instruction::ShiftRight { .. } => (
+                            emitter.builder.ins().ushr_imm(value, 1),
+                            emitter.builder.ins().band_imm(value, 1),
+                        ),
+                        _ => (
+                            emitter.builder.ins().ishl_imm(value, 1),
+                            emitter.builder.ins().ushr_imm(value, 7),
+                        ),
+                    };
+                    emitter.store(vx, shifted);
+                    emitter.store(0xF, flag);
+                }
+                Instruction::SetIndex { nnn } => {
+                    let value = emitter.constant(types::I16, nnn.into());
+                    emitter
+                        .builder
+                        .ins()
+                        .store(MemFlags::trusted(), value, index_register, 0);
+                }
+                Instruction::Jump { nnn } if nnn == address && jump_to_self_ends => break,
+                Instruction::Jump { nnn } => {
+                    next = Some(emitter.constant(types::I32, nnn.into()));
+                }
+                Instruction::JumpWithOffset { vx, nnn } => {
+                    let offset = emitter.load(if quirks.jump_uses_vx { vx } else { 0 });
+                    let offset = emitter.builder.ins().uextend(types::I32, offset);
+                    next = Some(emitter.builder.ins().iadd_imm(offset, i64::from(nnn)));
+                }
+                Instruction::SkipIfEqual { .. }
+                | Instruction::SkipIfNotEqual { .. }
+                | Instruction::SkipIfRegistersEqual { .. }
+                | Instruction::SkipIfRegistersNotEqual { .. }
+                    if long_skips =>
+                {
+                    break
+                }
+                Instruction::SkipIfEqual { vx, nn } | Instruction::SkipIfNotEqual { vx, nn } => {
+                    let condition = match instruction {
+                        Instruction::SkipIfEqual { .. } => IntCC::Equal,
+                        _ => IntCC::NotEqual,
+                    };
+                    let x = emitter.load(vx);
+                    let skip = emitter.builder.ins().icmp_imm(condition, x, i64::from(nn));
+                    next = Some(emitter.skip_if(skip, address));
+                }
+                Instruction::SkipIfRegistersEqual { vx, vy }
+                | Instruction::SkipIfRegistersNotEqual { vx, vy } => {
+                    let condition = match instruction {
+                        Instruction::SkipIfRegistersEqual { .. } => IntCC::Equal,
+                        _ => IntCC::NotEqual,
+                    };
+                    let (x, y) = (emitter.load(vx), emitter.load(vy));
+                    let skip = emitter.builder.ins().icmp(condition, x, y);
+                    next = Some(emitter.skip_if(skip, address));
+                }
+                _ => break,
+            }
+            address += 2;
+            instructions += 1;
+            last_opcode = opcode;
+        }
+        let next = match next {
+            Some(next) => next,
+            None => emitter.constant(types::I32, address.into()),
+        };
+        emitter.builder.ins().return_(&[next]);
+        emitter.builder.finalize();
+        if instructions == 0 {
+            return None;
+        }
+
+        let id = self
+            .module
+            .declare_anonymous_function(&context.func.signature)
+            .ok()?;
+        self.module.define_function(id, &mut context).ok()?;
+        self.module.finalize_definitions().ok()?;
+        let code = self.module.get_finalized_function(id);
+
+        Some(Block {
+            // SAFETY: the function was just compiled with this signature
+            code: unsafe { std::mem::transmute::<*const u8, CompiledBlock>(code) },
+            bytes: memory[start as usize..address as usize].to_vec(),
+            instructions,
+            last_opcode,
+        })
+    }
+}
+
+struct Emitter<'a> {
+    builder: FunctionBuilder<'a>,
+    v_registers: Value,
+}
+
+impl Emitter<'_> {
+    fn constant(&mut self, kind: types::Type, value: i64) -> Value {
+        self.builder.ins().iconst(kind, value)
+    }
+
+    fn load(&mut self, register: usize) -> Value {
+        self.builder.ins().load(
+            types::I8,
+            MemFlags::trusted(),
+            self.v_registers,
+            register as i32,
+        )
+    }
+
+    fn store(&mut self, register: usize, value: Value) {
+        self.builder.ins().store(
+            MemFlags::trusted(),
+            value,
+            self.v_registers,
+            register as i32,
+        );
+    }
+
+    fn skip_if(&mut self, condition: Value, address: u16) -> Value {
+        let skipped = self.constant(types::I32, i64::from(address) + 4);
+        let following = self.constant(types::I32, i64::from(address) + 2);
+        self.builder.ins().select(condition, skipped, following)
+    }
+}
@@ -0,0 +1,166 @@
+//! Mapping between computer keyboards and the hex keypad shared by every frontend
+
+use std::{collections::BTreeMap, str::FromStr};
+
+/// The hex keypad as laid out on the COSMAC VIP, row by row
+pub const KEYPAD: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+/// Keyboard layouts frontends can map to the keypad
+///
+/// The keypad is played on the four by four block on the left of the keyboard, so the same
+/// physical keys are used whatever characters the layout prints on them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyboardLayout {
+    /// 1234 / QWER / ASDF / ZXCV
+    #[default]
+    Qwerty,
+    /// 1234 / AZER / QSDF / WXCV
+    Azerty,
+}
+
+impl KeyboardLayout {
+    /// Characters printed on the keys that make up the keypad, in the same order as [`KEYPAD`]
+    pub fn characters(self) -> [[char; 4]; 4] {
+        match self {
+            KeyboardLayout::Qwerty => [
+                ['1', '2', '3', '4'],
+                ['q', 'w', 'e', 'r'],
+                ['a', 's', 'd', 'f'],
+                ['z', 'x', 'c', 'v'],
+            ],
+            KeyboardLayout::Azerty => [
+                ['1', '2', '3', '4'],
+                ['a', 'z', 'e', 'r'],
+                ['q', 's', 'd', 'f'],
+                ['w', 'x', 'c', 'v'],
+            ],
+        }
+    }
+
+    /// Keypad key for a typed character, ignoring case
+    pub fn key(self, character: char) -> Option<u8> {
+        let character = character.to_ascii_lowercase();
+        self.characters()
+            .iter()
+            .flatten()
+            .zip(KEYPAD.iter().flatten())
+            .find(|(mapped, _)| **mapped == character)
+            .map(|(_, key)| *key)
+    }
+
+    /// Character to type for a keypad key
+    pub fn character(self, key: u8) -> Option<char> {
+        KEYPAD
+            .iter()
+            .flatten()
+            .zip(self.characters().iter().flatten())
+            .find(|(mapped, _)| **mapped == key)
+            .map(|(_, character)| *character)
+    }
+}
+
+/// A layout with some characters mapped to other keys, for games whose controls are awkward to reach
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Keymap {
+    layout: KeyboardLayout,
+    overrides: BTreeMap<char, u8>,
+}
+
+impl Keymap {
+    /// Maps the keys of `layout` without any override
+    pub fn new(layout: KeyboardLayout) -> Keymap {
+        Keymap {
+            layout,
+            overrides: BTreeMap::new(),
+        }
+    }
+
+    /// Makes `character` press `key`, on top of whatever the layout maps to `key`
+    pub fn remap(&mut self, character: char, key: u8) {
+        self.overrides
+            .insert(character.to_ascii_lowercase(), key & 0xF);
+    }
+
+    /// Layout the overrides apply to
+    pub fn layout(&self) -> KeyboardLayout {
+        self.layout
+    }
+
+    /// Keypad key for a typed character, ignoring case
+    pub fn key(&self, character: char) -> Option<u8> {
+        self.overrides
+            .get(&character.to_ascii_lowercase())
+            .copied()
+            .or_else(|| self.layout.key(character))
+    }
+}
+
+impl From<KeyboardLayout> for Keymap {
+    fn from(layout: KeyboardLayout) -> Keymap {
+        Keymap::new(layout)
+    }
+}
+
+/// Keypad key at a physical position of the keyboard, for frontends reading scancodes
+///
+/// Rows start at the number row and columns at its `1` key
+pub fn key_at(row: usize, column: usize) -> Option<u8> {
+    KEYPAD.get(row)?.get(column).copied()
+}
+
+impl FromStr for KeyboardLayout {
+    type Err = String;
+
+    fn from_str(layout: &str) -> Result<Self, Self::Err> {
+        match layout.to_ascii_lowercase().as_str() {
+            "qwerty" => Ok(KeyboardLayout::Qwerty),
+            "azerty" => Ok(KeyboardLayout::Azerty),
+            _ => Err(format!("Unknown keyboard layout: {}", layout)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_maps_qwerty_characters_to_the_keypad() {
+        assert_eq!(KeyboardLayout::Qwerty.key('4'), Some(0xC));
+        assert_eq!(KeyboardLayout::Qwerty.key('X'), Some(0x0));
+        assert_eq!(KeyboardLayout::Qwerty.key('p'), None);
+    }
+
+    #[test]
+    fn it_maps_the_same_physical_keys_on_azerty() {
+        assert_eq!(
+            KeyboardLayout::Azerty.key('a'),
+            KeyboardLayout::Qwerty.key('q')
+        );
+        assert_eq!(KeyboardLayout::Azerty.key('w'), Some(0xA));
+        assert_eq!(KeyboardLayout::Azerty.character(0x5), Some('z'));
+    }
+
+    #[test]
+    fn it_prefers_remapped_characters_over_the_layout() {
+        let mut keymap = Keymap::new(KeyboardLayout::Qwerty);
+        keymap.remap(' ', 0x5);
+        keymap.remap('Q', 0x6);
+
+        assert_eq!(keymap.key(' '), Some(0x5));
+        assert_eq!(keymap.key('q'), Some(0x6));
+        assert_eq!(keymap.key('w'), Some(0x5));
+    }
+
+    #[test]
+    fn it_finds_keys_by_position() {
+        assert_eq!(key_at(0, 3), Some(0xC));
+        assert_eq!(key_at(3, 1), Some(0x0));
+        assert_eq!(key_at(4, 0), None);
+    }
+}
@@ -8,42 +8,370 @@
 //!
 //! It also tries to expose a few traits in order to allow that
 
+#[cfg(feature = "rom-config")]
+mod achievements;
+mod builder;
+mod bus;
+mod cheats;
+mod checkpoints;
+mod clock;
+pub mod compat;
+mod conditions;
+#[cfg(feature = "rom-config")]
+mod config;
+#[cfg(all(unix, feature = "control-socket"))]
+mod control;
+mod coverage;
+mod devices;
 mod errors;
+mod font;
+mod framebuffer;
+#[cfg(feature = "replay")]
+mod ghost;
+mod handle;
+mod instruction;
+#[cfg(feature = "jit")]
+mod jit;
+pub mod keymap;
+#[cfg(feature = "midi")]
+pub mod midi;
+mod postprocess;
+mod profile;
+mod quirks;
+mod remap;
+#[cfg(feature = "replay")]
+mod replay;
+mod rewind;
+mod rng;
+#[cfg(feature = "rom-db")]
+pub mod romdb;
+mod search;
+mod snapshot;
+mod speed;
+mod sprite_budget;
+mod trace;
 mod traits;
+mod variant;
+
+use bus::Bus;
+use font::{BIG_FONT_SET, FONT_SET};
+use handle::Control;
+#[cfg(feature = "jit")]
+use jit::Jit;
+#[cfg(feature = "replay")]
+use replay::ReplayMode;
+use rewind::RewindBuffer;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::prelude::*,
+    ops::Range,
+    sync::mpsc::{self, Receiver, Sender},
+    time::Duration,
+};
+
+#[cfg(feature = "rom-config")]
+pub use achievements::{Achievement, Achievements};
+pub use builder::Chip8Builder;
+pub use cheats::{Cheat, CheatKind, Cheats};
+pub use checkpoints::Checkpoints;
+pub use clock::Clock;
+#[cfg(not(target_arch = "wasm32"))]
+pub use clock::InstantClock;
+#[cfg(target_arch = "wasm32")]
+pub use clock::PerformanceClock;
+pub use conditions::{Condition, Score};
+#[cfg(feature = "rom-config")]
+pub use config::{
+    Chip8Config, ConfigError, KeymapOverrides, PaletteOverrides, QuirkOverrides, RomConfig,
+    DEFAULT_HERTZ,
+};
+#[cfg(all(unix, feature = "control-socket"))]
+pub use control::{ControlClient, ControlCommand, ControlResponse, ControlServer};
+pub use coverage::Coverage;
+#[cfg(feature = "null-devices")]
+pub use devices::{NullAudio, NullGraphics, NullKeyboard};
+pub use errors::{Chip8Error, DeviceError, ErrorContext};
+pub use font::Font;
+pub use framebuffer::{Frame, FrameBuffer};
+#[cfg(feature = "replay")]
+pub use ghost::{BestRun, Ghost};
+pub use handle::Chip8Handle;
+pub use instruction::{decode, instruction_set, Instruction};
+pub use keymap::{KeyboardLayout, Keymap, KEYPAD};
+pub use postprocess::{
+    Curvature, FlickerFilter, Osd, Palette, Pipeline, PostProcessor, RgbaImage, Scaler, Scanlines,
+};
+pub use profile::Profile;
+pub use quirks::Quirks;
+pub use remap::KeyRemap;
+#[cfg(feature = "replay")]
+pub use replay::{Replay, ReplayFrame, ReplaySound};
+#[cfg(feature = "rand")]
+pub use rng::RandomNumberGenerator;
+#[cfg(feature = "seeded-rng")]
+pub use rng::SeededNumberGenerator;
+pub use search::{RamSearch, SearchFilter};
+pub use snapshot::{Register, RegisterChange, Snapshot, StateDiff};
+pub use speed::SpeedController;
+pub use sprite_budget::SpriteBudget;
+pub use trace::Tracer;
+pub use traits::{Audio, Graphics, KeyEvent, Keyboard, NumberGenerator, Peripheral, Storage};
+pub use variant::Chip8Variant;
+
+const TIMER_PERIOD: Duration = Duration::from_nanos(1_000_000_000 / 60);
+// Timers are a byte, ticking them more than this at once makes no difference
+const MAX_TIMER_TICKS: u32 = 255;
+// XO-CHIP audio patterns play at 4000 bits per second until FX3A changes the pitch
+pub(crate) const DEFAULT_PITCH: u8 = 64;
+
+/// Where the font used by FX29 starts in memory, each digit takes 5 bytes
+pub const FONT_ADDRESS: usize = 0;
+
+/// Where the big font used by FX30 starts, right after the regular font, each digit takes 10 bytes
+pub const BIG_FONT_ADDRESS: usize = FONT_ADDRESS + FONT_SET.len();
+
+/// First instruction of roms written for the two-page 64x64 interpreter
+const TWO_PAGE_STARTUP_JUMP: [u8; 2] = [0x12, 0x60];
+/// Where two-page roms continue once the interpreter patch they ship with is skipped
+const TWO_PAGE_ENTRY_POINT: [u8; 2] = [0x12, 0xC0];
+
+/// Why the program stopped executing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "save-states", derive(serde::Serialize, serde::Deserialize))]
+enum ProgramEnd {
+    Finished,
+    Halted,
+}
+
+/// Size of the display the roms are drawing to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "save-states", derive(serde::Serialize, serde::Deserialize))]
+enum DisplayMode {
+    /// Standard 64x32 display
+    LowResolution,
+    /// 64x64 display of the original two-page high resolution interpreter
+    TwoPage,
+    /// 128x64 display of SUPER-CHIP
+    HighResolution,
+}
 
-use std::io::prelude::*;
-
-pub use errors::Chip8Error;
-pub use traits::{Audio, Graphics, Keyboard, NumberGenerator};
-
-const FONT_SET: [u8; 80] = [
-    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-    0x20, 0x60, 0x20, 0x20, 0x70, // 1
-    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-];
+impl DisplayMode {
+    fn resolution(self) -> (usize, usize) {
+        match self {
+            DisplayMode::LowResolution => (64, 32),
+            DisplayMode::TwoPage => (64, 64),
+            DisplayMode::HighResolution => (128, 64),
+        }
+    }
+}
 
 /// Basic enum to keep track of wether the user wants to quit
 ///
 /// This is important because the chip8 will be the one
 /// listening for keyboard events
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum State {
     /// No key was pressed to exit
     Continue,
     /// Should exit immediately
     Exit,
+    /// The program ended on its own and there is nothing left to execute
+    Finished,
+    /// The program asked the interpreter to exit with 00FD
+    Halted,
+    /// FX0A is waiting for a key to be pressed and released, timers and drawing keep running
+    WaitingForKey,
+    /// The program counter reached a breakpoint, the instruction at the address hasn't run yet
+    BreakpointHit(u16),
+    /// An instruction changed the byte at a watched address, the next instruction hasn't run yet
+    WatchpointHit(u16),
+}
+
+/// Progress of FX0A waiting for a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "save-states", derive(serde::Serialize, serde::Deserialize))]
+enum KeyWait {
+    Press,
+    Release(u8),
+}
+
+/// Heuristics used to decide that a rom has ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProgramEndDetection {
+    /// Treat 00EE with an empty stack as the end of the program instead of an error
+    pub return_with_empty_stack: bool,
+    /// Treat a 1NNN jumping to its own address as the end of the program
+    ///
+    /// Turn it off for roms that spin on a jump to self while the timers or an interrupt do the work
+    pub jump_to_self: bool,
+}
+
+impl Default for ProgramEndDetection {
+    fn default() -> Self {
+        ProgramEndDetection {
+            return_with_empty_stack: true,
+            jump_to_self: true,
+        }
+    }
+}
+
+/// Size of the memory and where programs are loaded in it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryLayout {
+    /// Bytes of RAM, 4096 for most interpreters and up to 65536 for XO-CHIP
+    pub size: usize,
+    /// Address the program is loaded at and starts running from, 0x600 for ETI-660 roms
+    pub load_address: u16,
+}
+
+impl Default for MemoryLayout {
+    fn default() -> Self {
+        MemoryLayout {
+            size: 4096,
+            load_address: 0x200,
+        }
+    }
+}
+
+impl MemoryLayout {
+    // The fonts go at the start of memory and the program after them, both have to fit in it
+    fn validate(&self) -> Result<(), Chip8Error> {
+        let fonts_end = BIG_FONT_ADDRESS + BIG_FONT_SET.len();
+        if self.size < fonts_end {
+            return Err(Chip8Error::InvalidConfig(format!(
+                "{} bytes of memory can't hold the {} bytes of the fonts",
+                self.size, fonts_end
+            )));
+        }
+        if self.load_address as usize >= self.size {
+            return Err(Chip8Error::InvalidConfig(format!(
+                "Programs can't be loaded at {:#06X} in {} bytes of memory",
+                self.load_address, self.size
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Operands extracted from an opcode during the decode stage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Operands {
+    /// Register index in the second nibble
+    pub vx_index: usize,
+    /// Register index in the third nibble
+    pub vy_index: usize,
+    /// Lowest 12 bits, used as an address
+    pub nnn_address: u16,
+    /// Lowest byte
+    pub nn_address: u16,
+    /// Lowest nibble
+    pub n_address: u16,
+}
+
+impl Operands {
+    fn from_opcode(opcode: u16) -> Operands {
+        Operands {
+            vx_index: ((opcode & 0x0F00) >> 8) as usize,
+            vy_index: ((opcode & 0x00F0) >> 4) as usize,
+            nnn_address: opcode & 0x0FFF,
+            nn_address: opcode & 0x00FF,
+            n_address: opcode & 0x000F,
+        }
+    }
+}
+
+/// Stage of the interpreter that just ran when calling `micro_step`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MicroStep {
+    /// The opcode was read from memory at `address`
+    Fetch {
+        /// Address the opcode was read from
+        address: u16,
+        /// Opcode that was read
+        opcode: u16,
+    },
+    /// The opcode was split into its operands
+    Decode {
+        /// Opcode being decoded
+        opcode: u16,
+        /// Operands extracted from it
+        operands: Operands,
+        /// Instruction the opcode encodes
+        instruction: Instruction,
+    },
+    /// The instruction ran, followed by drawing, the timers and the keyboard update
+    Execute {
+        /// Opcode that was executed
+        opcode: u16,
+        /// Program counter after executing it
+        program_counter: u16,
+        /// Whether the user asked to quit
+        state: State,
+    },
+}
+
+/// Registers, stack and timers as they were right before an instruction executed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "save-states", derive(serde::Serialize, serde::Deserialize))]
+pub struct CpuSnapshot {
+    /// Address of the instruction about to execute
+    pub program_counter: u16,
+    /// Opcode of the instruction about to execute
+    pub opcode: u16,
+    /// The I register
+    pub index_register: u16,
+    /// V0 to VF
+    pub v_registers: [u8; 16],
+    /// Return addresses, only the first `stack_pointer` entries are in use
+    pub stack: [u16; 16],
+    /// Number of return addresses on the stack
+    pub stack_pointer: u16,
+    /// Value of the delay timer
+    pub delay_timer: u8,
+    /// Value of the sound timer
+    pub sound_timer: u8,
+}
+
+type InstructionHook = dyn FnMut(&CpuSnapshot, Instruction);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    Fetch,
+    Decode,
+    Execute,
+}
+
+/// Parts of the interpreter that can be halted independently
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Component {
+    /// Fetching and executing instructions
+    Cpu,
+    /// Decrementing the delay and sound timers
+    Timers,
+}
+
+/// What to do when the audio, graphics or storage device returns an error
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceErrorPolicy {
+    /// Return the error from `emulate_cycle`, stopping the emulation
+    #[default]
+    Abort,
+    /// Keep the error in a log available through `take_device_errors` and keep going
+    Continue,
+}
+
+/// What to do when the program runs into an opcode that isn't a valid instruction
+///
+/// Roms often keep data next to their code, which the program counter can wander into
+#[derive(Default)]
+pub enum OnInvalidOpcode {
+    /// Return `Chip8Error::InvalidOpcode` from `emulate_cycle`, stopping the emulation
+    #[default]
+    Halt,
+    /// Skip the opcode and carry on with the next instruction
+    SkipAndContinue,
+    /// Call the closure with the opcode and its address, then skip it
+    Callback(Box<dyn FnMut(u16, u16)>),
 }
 
 /// This struct is the main part of the Chip8 implementation
@@ -52,913 +380,4922 @@ pub enum State {
 /// and stores the frontends implementations of the required traits
 pub struct Chip8 {
     delay_timer: u8,
-    graphics: [u8; 2048],
+    planes: [FrameBuffer; 2],
+    selected_planes: u8,
+    display_changed: bool,
+    display_mode: DisplayMode,
     index_register: u16,
     keyboard: [u8; 16],
-    memory: [u8; 4096],
+    memory: Vec<u8>,
+    memory_layout: MemoryLayout,
+    program: Vec<u8>,
     opcode: u16,
+    instruction: Instruction,
     program_counter: u16,
     sound_timer: u8,
+    sound_playing: bool,
+    audio_pattern: Option<[u8; 16]>,
+    pitch: u8,
     stack: [u16; 16],
     stack_pointer: u16,
     v_registers: [u8; 16],
+    rpl_flags: [u8; 8],
+    stage: Stage,
+    variant: Chip8Variant,
+    quirks: Quirks,
+    program_end_detection: ProgramEndDetection,
+    program_end: Option<ProgramEnd>,
+    waiting_for_next_frame: bool,
+    key_wait: Option<KeyWait>,
+    cpu_enabled: bool,
+    timers_enabled: bool,
     random_number_generator: Box<dyn NumberGenerator>,
     audio_device: Box<dyn Audio>,
     keyboard_device: Box<dyn Keyboard>,
-    graphics_device: Box<dyn Graphics>,
+    graphics_device: Option<Box<dyn Graphics>>,
+    storage_device: Option<Box<dyn Storage>>,
+    device_error_policy: DeviceErrorPolicy,
+    on_invalid_opcode: OnInvalidOpcode,
+    device_errors: Vec<Chip8Error>,
+    hook: Option<Box<InstructionHook>>,
+    profile: Option<Profile>,
+    breakpoints: BTreeSet<u16>,
+    watchpoints: BTreeMap<u16, u8>,
+    resumed_breakpoint: Option<u16>,
+    frame: u64,
+    rewind_buffer: RewindBuffer,
+    tracer: Option<Tracer>,
+    coverage: Option<Coverage>,
+    controls: Option<(Sender<Control>, Receiver<Control>)>,
+    speed: Option<usize>,
+    sprite_budget: SpriteBudget,
+    clock: Option<Box<dyn Clock>>,
+    last_timer_tick: Duration,
+    palette: Palette,
+    font: Font,
+    bus: Bus,
+    cheats: Cheats,
+    #[cfg(feature = "jit")]
+    jit: Option<Jit>,
+    #[cfg(feature = "replay")]
+    replay: Option<ReplayMode>,
+    // Instructions run so far by the frame, to know when the sound turned on or off
+    #[cfg(feature = "replay")]
+    frame_cycles: usize,
 }
 
 impl Chip8 {
-    /// Instantiates the Chip8 with the provided implementations
+    const MAX_DEVICE_ERRORS: usize = 64;
+
+    /// Instantiates the Chip8 with the provided implementations and the default quirks
+    ///
+    /// The constructor the core shipped with, kept so existing frontends compile
+    #[deprecated(note = "use `Chip8::builder()`, or `Chip8::with_quirks` to pick the quirks")]
     pub fn new(
         random_number_generator: Box<dyn NumberGenerator>,
         audio_device: Box<dyn Audio>,
         keyboard_device: Box<dyn Keyboard>,
         graphics_device: Box<dyn Graphics>,
     ) -> Chip8 {
+        Chip8::builder()
+            .rng(random_number_generator)
+            .audio(audio_device)
+            .keyboard(keyboard_device)
+            .graphics(graphics_device)
+            .build()
+    }
+
+    /// Instantiates the Chip8 with the provided implementations and quirks
+    pub fn with_quirks(
+        random_number_generator: Box<dyn NumberGenerator>,
+        audio_device: Box<dyn Audio>,
+        keyboard_device: Box<dyn Keyboard>,
+        graphics_device: Box<dyn Graphics>,
+        quirks: Quirks,
+    ) -> Chip8 {
+        Self::with_devices(
+            random_number_generator,
+            audio_device,
+            keyboard_device,
+            Some(graphics_device),
+            Chip8Variant::Chip8,
+            quirks,
+        )
+    }
+
+    /// Instantiates the Chip8 behaving like a well known variant
+    ///
+    /// The variant decides which instructions are available and which quirks are enabled
+    pub fn with_variant(
+        random_number_generator: Box<dyn NumberGenerator>,
+        audio_device: Box<dyn Audio>,
+        keyboard_device: Box<dyn Keyboard>,
+        graphics_device: Box<dyn Graphics>,
+        variant: Chip8Variant,
+    ) -> Chip8 {
+        Self::with_devices(
+            random_number_generator,
+            audio_device,
+            keyboard_device,
+            Some(graphics_device),
+            variant,
+            variant.quirks(),
+        )
+    }
+
+    /// Instantiates the Chip8 without a graphics device
+    ///
+    /// The framebuffer is still kept up to date and the frontend reads it with `framebuffer`
+    /// whenever it wants to render, which suits hosts that drive rendering themselves
+    pub fn without_graphics(
+        random_number_generator: Box<dyn NumberGenerator>,
+        audio_device: Box<dyn Audio>,
+        keyboard_device: Box<dyn Keyboard>,
+        quirks: Quirks,
+    ) -> Chip8 {
+        Self::with_devices(
+            random_number_generator,
+            audio_device,
+            keyboard_device,
+            None,
+            Chip8Variant::Chip8,
+            quirks,
+        )
+    }
+
+    /// Starts building a Chip8, the devices that aren't given get defaults that do nothing
+    pub fn builder() -> Chip8Builder {
+        Chip8Builder::new()
+    }
+
+    pub(crate) fn with_devices(
+        random_number_generator: Box<dyn NumberGenerator>,
+        audio_device: Box<dyn Audio>,
+        keyboard_device: Box<dyn Keyboard>,
+        graphics_device: Option<Box<dyn Graphics>>,
+        variant: Chip8Variant,
+        quirks: Quirks,
+    ) -> Chip8 {
+        let memory_layout = MemoryLayout {
+            size: variant.memory_size(),
+            ..MemoryLayout::default()
+        };
         let mut chip8 = Chip8 {
             delay_timer: 0,
-            graphics: [0; 2048],
+            planes: [FrameBuffer::new(64, 32), FrameBuffer::new(64, 32)],
+            selected_planes: 1,
+            display_changed: true,
+            display_mode: DisplayMode::LowResolution,
             index_register: 0,
             keyboard: [0; 16],
-            memory: [0; 4096],
+            memory: vec![0; memory_layout.size],
+            memory_layout,
+            program: Vec::new(),
             opcode: 0,
-            program_counter: 0x200,
+            instruction: decode(0),
+            program_counter: MemoryLayout::default().load_address,
             sound_timer: 0,
+            sound_playing: false,
+            audio_pattern: None,
+            pitch: DEFAULT_PITCH,
             stack: [0; 16],
             stack_pointer: 0,
             v_registers: [0; 16],
+            rpl_flags: [0; 8],
+            stage: Stage::Fetch,
+            variant,
+            quirks,
+            program_end_detection: ProgramEndDetection::default(),
+            program_end: None,
+            waiting_for_next_frame: false,
+            key_wait: None,
+            cpu_enabled: true,
+            timers_enabled: true,
             random_number_generator,
             audio_device,
             keyboard_device,
             graphics_device,
+            storage_device: None,
+            device_error_policy: DeviceErrorPolicy::default(),
+            on_invalid_opcode: OnInvalidOpcode::default(),
+            device_errors: Vec::new(),
+            hook: None,
+            profile: None,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeMap::new(),
+            resumed_breakpoint: None,
+            frame: 0,
+            rewind_buffer: RewindBuffer::new(0, 1),
+            tracer: None,
+            coverage: None,
+            controls: None,
+            speed: None,
+            sprite_budget: SpriteBudget::default(),
+            clock: None,
+            last_timer_tick: Duration::default(),
+            palette: Palette::default(),
+            font: Font::default(),
+            bus: Bus::default(),
+            cheats: Cheats::default(),
+            #[cfg(feature = "jit")]
+            jit: None,
+            #[cfg(feature = "replay")]
+            replay: None,
+            #[cfg(feature = "replay")]
+            frame_cycles: 0,
         };
         chip8.load_font_set();
         chip8
     }
+
     /// Loads a rom onto memory
+    ///
+    /// Roms starting with a jump to 0x260 were written for the two-page 64x64 interpreter,
+    /// those switch the display to 64x64 and skip the interpreter patch they carry
     pub fn load_program(&mut self, rom_data: Vec<u8>) -> Result<(), Chip8Error> {
-        let mut program_memory = &mut self.memory[self.program_counter as usize..];
+        let start = self.memory_layout.load_address as usize;
+        let available = self.memory.len().saturating_sub(start);
+        if rom_data.len() > available {
+            return Err(Chip8Error::ProgramTooLarge {
+                size: rom_data.len(),
+                available,
+            });
+        }
+
+        let mut program_memory = &mut self.memory[start..];
         program_memory.write_all(&rom_data)?;
 
+        if rom_data.starts_with(&TWO_PAGE_STARTUP_JUMP) {
+            self.set_display_mode(DisplayMode::TwoPage);
+            self.memory[start..start + 2].copy_from_slice(&TWO_PAGE_ENTRY_POINT);
+        }
+
+        self.program = rom_data;
+        self.sync_watchpoints();
         Ok(())
     }
 
-    /// Emulates a cycle of the interpreter
+    /// Restarts the interpreter without having to build it and its devices again
     ///
-    /// It retrieves the next opcode to execute, it draws the next frame, updates the timers and listens to keyboard events
-    ///
-    /// In case the user wants to exit, either by clicking the `X` on the window or pressing the escape key
-    /// this state is returned to the caller so it can interrupt the loop
-    pub fn emulate_cycle(&mut self) -> Result<State, Chip8Error> {
-        self.fetch_opcode();
-        self.interpret_opcode()?;
-        self.graphics_device.draw(&self.graphics)?;
-        self.update_timers()?;
-
-        let state = match self.keyboard_device.update_state(&mut self.keyboard) {
-            true => State::Exit,
-            false => State::Continue,
-        };
-
-        Ok(state)
-    }
-
-    fn interpret_opcode(&mut self) -> Result<(), Chip8Error> {
-        let leading_opcode_number = ((self.opcode & 0xF000) >> 12) as usize;
-        let vx_index = ((self.opcode & 0x0F00) >> 8) as usize;
-        let vy_index = ((self.opcode & 0x00F0) >> 4) as usize;
-        let nnn_address = self.opcode & 0x0FFF;
-        let nn_address = self.opcode & 0x00FF;
-        let n_address = self.opcode & 0x000F;
-
-        match self.opcode {
-            0x00E0 => self.clear_display(),
-            0x00EE => self.return_from_routine(),
-            0x1000..=0x1FFF => self.jump_to_address(nnn_address),
-            0x2000..=0x2FFF => self.jump_to_routine(nnn_address),
-            0x3000..=0x3FFF => self.skip_instruction_if_vx_equals_nn(vx_index, nn_address),
-            0x4000..=0x4FFF => self.skip_instruction_if_vx_not_equals_nn(vx_index, nn_address),
-            0x5000..=0x5FFF => self.skip_instruction_if_vx_equals_vy(vx_index, vy_index),
-            0x6000..=0x6FFF => self.set_vx_to_nn(vx_index, nn_address),
-            0x7000..=0x7FFF => self.add_nn_to_vx(vx_index, nn_address),
-            0x8000..=0x8FFF => match n_address {
-                0x0000 => self.sets_vx_to_vy(vx_index, vy_index),
-                0x0001 => self.sets_vx_to_vx_bitwise_or_vy(vx_index, vy_index),
-                0x0002 => self.sets_vx_to_vx_bitwise_and_vy(vx_index, vy_index),
-                0x0003 => self.sets_vx_to_vx_bitwise_xor_vy(vx_index, vy_index),
-                0x0004 => self.adds_vy_to_vx_setting_vf_on_borrow(vx_index, vy_index),
-                0x0005 => self.subtracts_vy_from_vx_setting_vf_on_borrow(vx_index, vy_index),
-                0x0006 => self.store_lsb_of_vx_in_vf_shifting_vx_by_1(vx_index),
-                0x0007 => self.set_vx_to_vy_minus_vx_setting_vf_on_borrow(vx_index, vy_index),
-                0x000E => self.store_msb_of_vx_in_vf_shifting_vx_by_1(vx_index),
-                _ => return Err(Chip8Error::InvalidOpcode(self.opcode)),
-            },
-            0x9000..=0x9FFF => self.skip_instruction_if_vx_not_equals_vy(vx_index, vy_index),
-            0xA000..=0xAFFF => self.set_index_register_to_nnn(nnn_address),
-            0xB000..=0xBFFF => self.jump_to_address_nnn_plus_v0(nnn_address),
-            0xC000..=0xCFFF => self.set_vx_to_random_number_bitwise_and_nn(vx_index, nn_address)?,
-            0xD000..=0xDFFF => self.set_graphics(vx_index, vy_index, n_address),
-            0xE000..=0xEFFF => match nn_address {
-                0x009E => self.skips_instruction_if_vx_key_is_pressed(vx_index),
-                0x00A1 => self.skips_instruction_if_vx_key_is_not_pressed(vx_index),
-                _ => return Err(Chip8Error::InvalidOpcode(self.opcode)),
-            },
-            0xF000..=0xFFFF => match nn_address {
-                0x0007 => self.sets_vx_to_delay_timer(vx_index),
-                0x000A => self.sets_vx_to_key_press(vx_index),
-                0x0015 => self.sets_delay_timer_to_vx(vx_index),
-                0x0018 => self.sets_sound_timer_to_vx(vx_index),
-                0x001E => self.adds_vx_to_i(vx_index),
-                0x0029 => self.sets_i_to_vx(vx_index),
-                0x0033 => self.store_bcd_of_vx_from_i(vx_index),
-                0x0055 => self.stores_v0_to_vx_in_memory_from_i(vx_index),
-                0x0065 => self.writes_v0_to_vx_from_memory_i(vx_index),
-                _ => return Err(Chip8Error::InvalidOpcode(self.opcode)),
-            },
-            _ => return Err(Chip8Error::InvalidOpcode(self.opcode)),
-        };
+    /// Registers, stack, timers, display and memory are cleared and the font set is loaded again.
+    /// When `reload_program` is true the last rom given to `load_program` is loaded back,
+    /// otherwise a new one has to be loaded. Devices, quirks and the other settings are kept
+    pub fn reset(&mut self, reload_program: bool) -> Result<(), Chip8Error> {
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.set_display_mode(DisplayMode::LowResolution);
+        self.selected_planes = 1;
+        self.index_register = 0;
+        self.keyboard = [0; 16];
+        self.memory.iter_mut().for_each(|byte| *byte = 0);
+        self.opcode = 0;
+        self.instruction = decode(0);
+        self.program_counter = self.memory_layout.load_address;
+        self.stack = [0; 16];
+        self.stack_pointer = 0;
+        self.v_registers = [0; 16];
+        self.stage = Stage::Fetch;
+        self.program_end = None;
+        self.waiting_for_next_frame = false;
+        self.sprite_budget.next_frame();
+        self.key_wait = None;
+        self.resumed_breakpoint = None;
+        self.frame = 0;
+        self.rewind_buffer.clear();
+        if let Some(clock) = &self.clock {
+            self.last_timer_tick = clock.now();
+        }
+        self.load_font_set();
+        self.sync_watchpoints();
+
+        self.sound_playing = false;
+        let stop_result = self.audio_device.stop();
+        self.handle_device_result(stop_result)?;
+        self.pitch = DEFAULT_PITCH;
+        if self.audio_pattern.take().is_some() {
+            let pattern_result = self.audio_device.set_pattern(None, self.pitch);
+            self.handle_device_result(pattern_result)?;
+        }
 
-        let jumping_operations = [0x1usize, 0x2, 0xB];
-        if !jumping_operations.contains(&leading_opcode_number) {
-            self.program_counter += 2;
+        if reload_program {
+            let program = std::mem::take(&mut self.program);
+            self.load_program(program)?;
         }
 
         Ok(())
     }
 
-    fn clear_display(&mut self) {
-        for i in self.graphics.iter_mut() {
-            *i = 0;
+    /// Resizes the memory and moves where programs are loaded, call it before `load_program`
+    ///
+    /// The memory is cleared, the fonts are loaded again and the program counter moves to the load address.
+    /// Fails, leaving everything as it was, when the memory is too small for the fonts or the load
+    /// address is past its end
+    pub fn set_memory_layout(&mut self, memory_layout: MemoryLayout) -> Result<(), Chip8Error> {
+        memory_layout.validate()?;
+        self.memory = vec![0; memory_layout.size];
+        self.memory_layout = memory_layout;
+        self.program_counter = memory_layout.load_address;
+        if self.coverage.is_some() {
+            self.coverage = Some(Coverage::new(memory_layout.size));
         }
+        self.load_font_set();
+        self.sync_watchpoints();
+        Ok(())
     }
 
-    fn return_from_routine(&mut self) {
-        self.stack_pointer -= 1;
-        self.program_counter = self.stack[self.stack_pointer as usize];
+    /// Replaces the sprites drawn by FX29 and FX30, they are written to memory straight away
+    pub fn set_font(&mut self, font: Font) {
+        self.font = font;
+        self.load_font_set();
     }
 
-    fn jump_to_address(&mut self, nnn_address: u16) {
-        self.program_counter = nnn_address
+    /// Font the digit sprites come from
+    pub fn font(&self) -> Font {
+        self.font
     }
 
-    fn jump_to_routine(&mut self, nnn_address: u16) {
-        self.stack[self.stack_pointer as usize] = self.program_counter;
-        self.stack_pointer += 1;
-        self.program_counter = nnn_address;
+    /// Current state of the display, sized to the resolution in use
+    ///
+    /// This is the first plane, XO-CHIP programs can draw on a second one given by `planes`
+    pub fn framebuffer(&self) -> &FrameBuffer {
+        &self.planes[0]
     }
 
-    fn skip_instruction_if_vx_equals_nn(&mut self, vx_index: usize, nn_address: u16) {
-        let v_register_value = self.v_registers[vx_index];
-        let value = nn_address as u8;
-
-        if v_register_value == value {
-            self.program_counter += 2;
+    /// Every plane of the display, the second one only for XO-CHIP
+    pub fn planes(&self) -> &[FrameBuffer] {
+        if self.variant.has_xochip_instructions() {
+            &self.planes
+        } else {
+            &self.planes[..1]
         }
     }
 
-    fn skip_instruction_if_vx_not_equals_nn(&mut self, vx_index: usize, nn_address: u16) {
-        let v_register_value = self.v_registers[vx_index];
-        let value = nn_address as u8;
-
-        if v_register_value != value {
-            self.program_counter += 2;
-        }
+    /// The whole memory, fonts and program included
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
     }
 
-    fn skip_instruction_if_vx_equals_vy(&mut self, vx_index: usize, vy_index: usize) {
-        let x_register_value = self.v_registers[vx_index];
-        let y_register_value = self.v_registers[vy_index];
-
-        if x_register_value == y_register_value {
-            self.program_counter += 2;
-        }
+    /// `length` bytes of memory starting at `address`
+    pub fn read_memory(&self, address: u16, length: usize) -> Result<&[u8], Chip8Error> {
+        let range = self.memory_range(address, length)?;
+        Ok(&self.memory[range])
     }
 
-    fn set_vx_to_nn(&mut self, vx_index: usize, nn_address: u16) {
-        let new_v_register_value = nn_address as u8;
-        self.v_registers[vx_index] = new_v_register_value;
+    /// Value of the register VX, panics when `vx` is over 0xF
+    pub fn v_register(&self, vx: usize) -> u8 {
+        self.v_registers[vx]
     }
 
-    fn add_nn_to_vx(&mut self, vx_index: usize, nn_address: u16) {
-        let value_to_add = nn_address as u8;
-
-        let (sum, _) = self.v_registers[vx_index].overflowing_add(value_to_add);
-        self.v_registers[vx_index] = sum;
+    /// V0 to VF
+    pub fn v_registers(&self) -> &[u8; 16] {
+        &self.v_registers
     }
 
-    fn skip_instruction_if_vx_not_equals_vy(&mut self, vx_index: usize, vy_index: usize) {
-        let vy = self.v_registers[vy_index];
-        let vx = self.v_registers[vx_index];
+    /// The I register
+    pub fn index_register(&self) -> u16 {
+        self.index_register
+    }
 
-        if vx != vy {
-            self.program_counter += 2;
-        }
+    /// Address of the next instruction to execute
+    pub fn program_counter(&self) -> u16 {
+        self.program_counter
     }
 
-    fn set_index_register_to_nnn(&mut self, nnn_address: u16) {
-        self.index_register = nnn_address;
+    /// Number of return addresses on the stack
+    pub fn stack_pointer(&self) -> u16 {
+        self.stack_pointer
     }
 
-    fn jump_to_address_nnn_plus_v0(&mut self, nnn_address: u16) {
-        let value_to_add = nnn_address;
-        let v0_value = self.v_registers[0] as u16;
-        self.program_counter += value_to_add + v0_value;
+    /// Return addresses on the stack, the most recent call last
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.stack_pointer as usize]
     }
 
-    fn set_vx_to_random_number_bitwise_and_nn(
-        &mut self,
-        vx_index: usize,
-        nn_address: u16,
-    ) -> Result<(), Chip8Error> {
-        let opcode_value = nn_address as u8;
-        let random_number = self.random_number_generator.generate()?;
-        self.v_registers[vx_index] = random_number & opcode_value;
-        Ok(())
+    /// Value of the delay timer
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
     }
 
-    fn set_graphics(&mut self, vx_index: usize, vy_index: usize, n_address: u16) {
-        let vx = self.v_registers[vx_index] as usize;
-        let vy = self.v_registers[vy_index] as usize;
+    /// Value of the sound timer
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
 
-        let bytes_to_draw =
-            &self.memory[self.index_register as usize..(self.index_register + n_address) as usize];
+    /// Whether the key, from 0x0 to 0xF, is held down
+    pub fn is_key_pressed(&self, key: u8) -> bool {
+        self.keyboard[(key & 0xF) as usize] == 1
+    }
 
-        self.v_registers[15usize] = 0;
-        for (row, byte) in bytes_to_draw.iter().enumerate() {
-            for col in 0..8 {
-                if byte & 0x80 >> col > 0 {
-                    let col = (vx + col) % 64;
-                    let row = (vy + row) % 32;
-                    let index = col + (row * 64);
+    /// Sets the register VX, panics when `vx` is over 0xF
+    #[cfg(feature = "state-write")]
+    pub fn set_v_register(&mut self, vx: usize, value: u8) {
+        self.v_registers[vx] = value;
+    }
 
-                    self.v_registers[0xF] = if self.graphics[index] == 1 { 1 } else { 0 };
+    /// Sets the I register
+    #[cfg(feature = "state-write")]
+    pub fn set_index_register(&mut self, value: u16) {
+        self.index_register = value;
+    }
 
-                    self.graphics[index] ^= 1;
-                }
-            }
-        }
+    /// Moves execution to `address`, restarting a cycle left half way through by `micro_step`
+    #[cfg(feature = "state-write")]
+    pub fn set_program_counter(&mut self, address: u16) {
+        self.program_counter = address;
+        self.stage = Stage::Fetch;
+        self.resumed_breakpoint = None;
     }
 
-    fn skips_instruction_if_vx_key_is_pressed(&mut self, vx_index: usize) {
-        let vx_value = self.v_registers[vx_index];
-        if self.keyboard[vx_value as usize] == 1 {
-            self.program_counter += 2;
-        }
+    /// Sets the delay timer
+    #[cfg(feature = "state-write")]
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.delay_timer = value;
     }
 
-    fn skips_instruction_if_vx_key_is_not_pressed(&mut self, vx_index: usize) {
-        let vx_value = self.v_registers[vx_index];
-        if self.keyboard[vx_value as usize] == 0 {
-            self.program_counter += 2;
-        }
+    /// Sets the sound timer
+    #[cfg(feature = "state-write")]
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.sound_timer = value;
     }
 
-    fn sets_vx_to_delay_timer(&mut self, vx_index: usize) {
-        self.v_registers[vx_index] = self.delay_timer
+    /// Holds down or releases the key, from 0x0 to 0xF, until the keyboard reports otherwise
+    #[cfg(feature = "state-write")]
+    pub fn set_key_pressed(&mut self, key: u8, pressed: bool) {
+        self.keyboard[(key & 0xF) as usize] = pressed as u8;
     }
 
-    fn sets_vx_to_key_press(&mut self, vx_index: usize) {
-        self.v_registers[vx_index] = self.keyboard_device.wait_next_key_press();
+    /// Overwrites memory from `address` with `bytes`
+    #[cfg(feature = "state-write")]
+    pub fn write_memory(&mut self, address: u16, bytes: &[u8]) -> Result<(), Chip8Error> {
+        let range = self.memory_range(address, bytes.len())?;
+        self.memory[range].copy_from_slice(bytes);
+        self.sync_watchpoints();
+        Ok(())
     }
 
-    fn sets_delay_timer_to_vx(&mut self, vx_index: usize) {
-        self.delay_timer = self.v_registers[vx_index];
+    /// Variant the interpreter is behaving like
+    pub fn variant(&self) -> Chip8Variant {
+        self.variant
     }
 
-    fn sets_sound_timer_to_vx(&mut self, vx_index: usize) {
-        self.sound_timer = self.v_registers[vx_index];
+    /// Maps a peripheral over `addresses`, instructions reading or writing there reach it instead of RAM
+    ///
+    /// Ranges mapped earlier take precedence where they overlap, the mappings survive resets
+    pub fn map_peripheral(&mut self, addresses: Range<u16>, peripheral: Box<dyn Peripheral>) {
+        self.bus.map(addresses, peripheral);
     }
 
-    fn adds_vx_to_i(&mut self, vx_index: usize) {
-        self.index_register += self.v_registers[vx_index] as u16;
+    /// Removes every peripheral, the addresses they were mapped over are plain RAM again
+    pub fn clear_peripherals(&mut self) {
+        self.bus.clear();
     }
 
-    fn sets_i_to_vx(&mut self, vx_index: usize) {
-        self.index_register = self.v_registers[vx_index] as u16;
+    /// Suggests the colors graphics devices should draw with, passed along in every `Frame`
+    ///
+    /// A ROM database or a per-ROM configuration usually knows which colors suit the program
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        self.display_changed = true;
     }
 
-    fn store_bcd_of_vx_from_i(&mut self, vx_index: usize) {
-        let vx_value = self.v_registers[vx_index];
+    /// Colors graphics devices are asked to draw with
+    pub fn palette(&self) -> Palette {
+        self.palette
+    }
 
-        self.memory[self.index_register as usize] = vx_value / 100;
-        self.memory[self.index_register as usize + 1] = (vx_value / 10) % 10;
-        self.memory[self.index_register as usize + 2] = vx_value % 10;
+    /// Draws the display at the end of the next frame even if it didn't change
+    ///
+    /// For graphics devices that draw more than the display, like a message over it
+    pub fn redraw(&mut self) {
+        self.display_changed = true;
     }
 
-    fn stores_v0_to_vx_in_memory_from_i(&mut self, vx_index: usize) {
-        let v_registers_to_copy = &self.v_registers[0..=vx_index];
+    /// Width and height of the display
+    ///
+    /// 64x32 by default, 64x64 for two-page roms and 128x64 in SUPER-CHIP high resolution mode
+    pub fn resolution(&self) -> (usize, usize) {
+        self.display_mode.resolution()
+    }
 
-        for (index, v_register_value) in v_registers_to_copy.iter().enumerate() {
-            self.memory[self.index_register as usize + index] = *v_register_value;
+    /// Halts or resumes a single part of the interpreter
+    ///
+    /// With the CPU disabled `emulate_cycle` still draws, ticks the timers and reads the keyboard,
+    /// which lets a debugger pause execution while keeping the screen and audio alive
+    pub fn set_component_enabled(&mut self, component: Component, enabled: bool) {
+        match component {
+            Component::Cpu => self.cpu_enabled = enabled,
+            Component::Timers => self.timers_enabled = enabled,
         }
     }
 
-    fn writes_v0_to_vx_from_memory_i(&mut self, vx_index: usize) {
-        let v_registers_to_write = &mut self.v_registers[0..=vx_index];
-
-        for (index, v_register_to_write) in v_registers_to_write.iter_mut().enumerate() {
-            *v_register_to_write = self.memory[self.index_register as usize + index];
+    /// Whether the given part of the interpreter is running
+    pub fn is_component_enabled(&self, component: Component) -> bool {
+        match component {
+            Component::Cpu => self.cpu_enabled,
+            Component::Timers => self.timers_enabled,
         }
     }
 
-    fn sets_vx_to_vy(&mut self, vx_index: usize, vy_index: usize) {
-        self.v_registers[vx_index] = self.v_registers[vy_index]
+    /// Freezes the whole machine: no instructions run, the timers stop and the sound is cut
+    ///
+    /// Frames still draw and read the keyboard, so a frontend stays responsive while paused.
+    /// It is the same as disabling the CPU and the timers, plus taking care of the sound
+    pub fn pause(&mut self) -> Result<(), Chip8Error> {
+        self.cpu_enabled = false;
+        self.timers_enabled = false;
+        if self.sound_playing {
+            let stop_result = self.audio_device.stop();
+            self.handle_device_result(stop_result)?;
+        }
+        Ok(())
     }
 
-    fn sets_vx_to_vx_bitwise_or_vy(&mut self, vx_index: usize, vy_index: usize) {
-        self.v_registers[vx_index] |= self.v_registers[vy_index]
+    /// Runs the machine again after `pause`, the sound picks up for what was left of it
+    pub fn resume(&mut self) -> Result<(), Chip8Error> {
+        self.cpu_enabled = true;
+        self.timers_enabled = true;
+        if self.sound_playing {
+            let play_result = self
+                .audio_device
+                .play(TIMER_PERIOD * self.sound_timer.into());
+            self.handle_device_result(play_result)?;
+        }
+        Ok(())
     }
 
-    fn sets_vx_to_vx_bitwise_and_vy(&mut self, vx_index: usize, vy_index: usize) {
-        self.v_registers[vx_index] &= self.v_registers[vy_index]
+    /// Whether the CPU and the timers are both halted, like after `pause`
+    pub fn is_paused(&self) -> bool {
+        !self.cpu_enabled && !self.timers_enabled
     }
 
-    fn sets_vx_to_vx_bitwise_xor_vy(&mut self, vx_index: usize, vy_index: usize) {
-        self.v_registers[vx_index] ^= self.v_registers[vy_index]
+    /// Whether nothing changes until a key is pressed: FX0A is waiting and no sound is playing
+    ///
+    /// Frames can be run less often meanwhile to save power, with a `Clock` set the timers still
+    /// catch up with the time that went by. `run_frame` reports the wait as `State::WaitingForKey`
+    pub fn is_idle(&self) -> bool {
+        #[cfg(feature = "replay")]
+        if self.is_replaying() {
+            return false;
+        }
+        self.program_state() == State::WaitingForKey && self.sound_timer == 0 && !self.is_paused()
     }
 
-    fn adds_vy_to_vx_setting_vf_on_borrow(&mut self, vx_index: usize, vy_index: usize) {
-        let vy = self.v_registers[vy_index];
-        let vx = self.v_registers[vx_index];
-
-        let (result, overflowed) = vx.overflowing_add(vy);
+    /// Waits for the keyboard to get an input event, for up to `timeout`
+    ///
+    /// Meant for low-power loops while `is_idle`, it returns false right away when the keyboard
+    /// can't wait, see `Keyboard::wait_for_events`
+    pub fn wait_for_input(&mut self, timeout: Duration) -> bool {
+        self.keyboard_device.wait_for_events(timeout)
+    }
 
-        if overflowed {
-            self.v_registers[0xF] = 1;
+    /// Runs exactly one frame of `instructions_per_frame` instructions while paused, then stays paused
+    ///
+    /// The timers tick once whatever the clock says, and the frame is drawn like with `run_frame`.
+    /// Passing 1 advances a single instruction and shows its result
+    pub fn step_frame(&mut self, instructions_per_frame: usize) -> Result<State, Chip8Error> {
+        let (cpu_enabled, timers_enabled) = (self.cpu_enabled, self.timers_enabled);
+        self.cpu_enabled = true;
+        self.timers_enabled = true;
+        if let Some(clock) = &self.clock {
+            self.last_timer_tick = clock.now().saturating_sub(TIMER_PERIOD);
         }
+        let state = self.run_frame(instructions_per_frame);
+        self.cpu_enabled = cpu_enabled;
+        self.timers_enabled = timers_enabled;
+        state
+    }
 
-        self.v_registers[vx_index] = result;
+    /// Configures which heuristics report `State::Finished`
+    pub fn set_program_end_detection(&mut self, program_end_detection: ProgramEndDetection) {
+        self.program_end_detection = program_end_detection;
     }
 
-    fn subtracts_vy_from_vx_setting_vf_on_borrow(&mut self, vx_index: usize, vy_index: usize) {
-        let vy = self.v_registers[vy_index];
-        let vx = self.v_registers[vx_index];
+    /// Uses the given storage to keep the RPL user flags across runs
+    ///
+    /// The flags saved by a previous run are loaded straight away and FX75 saves them from then on
+    pub fn set_storage(&mut self, mut storage_device: Box<dyn Storage>) -> Result<(), Chip8Error> {
+        self.rpl_flags = storage_device.load_flags()?;
+        self.storage_device = Some(storage_device);
+        Ok(())
+    }
 
-        let (result, overflowed) = vx.overflowing_sub(vy);
+    /// Sets how errors coming from the audio, graphics and storage devices are handled
+    pub fn set_device_error_policy(&mut self, policy: DeviceErrorPolicy) {
+        self.device_error_policy = policy;
+    }
 
-        if overflowed {
-            self.v_registers[0xF] = 1;
-        }
+    /// Replaces the number generator with a `SeededNumberGenerator` started from `seed`
+    ///
+    /// CXNN then produces the same numbers on every run, which makes test runs and replays reproducible
+    #[cfg(feature = "seeded-rng")]
+    pub fn seed_rng(&mut self, seed: u64) {
+        self.random_number_generator = Box::new(SeededNumberGenerator::new(seed));
+    }
 
-        self.v_registers[vx_index] = result;
+    /// Starts recording a `Replay` of the frames run from now on, with the number generator seeded
+    /// from `seed`
+    ///
+    /// Only frames run by `run_frame` are recorded, each with the keys read at its end. While
+    /// recording or playing, the timers tick once per frame whatever the clock says
+    #[cfg(feature = "replay")]
+    pub fn start_recording(&mut self, seed: u64) {
+        self.seed_rng(seed);
+        self.replay = Some(ReplayMode::Recording(Replay::new(seed)));
     }
 
-    fn store_lsb_of_vx_in_vf_shifting_vx_by_1(&mut self, vx_index: usize) {
-        let vx = self.v_registers[vx_index];
-        self.v_registers[0xF] = vx & 1;
-        self.v_registers[vx_index] >>= 1;
+    /// Stops recording and returns the replay, if one was being recorded
+    #[cfg(feature = "replay")]
+    pub fn stop_recording(&mut self) -> Option<Replay> {
+        match self.replay.take() {
+            Some(ReplayMode::Recording(replay)) => Some(replay),
+            replay => {
+                self.replay = replay;
+                None
+            }
+        }
     }
 
-    fn set_vx_to_vy_minus_vx_setting_vf_on_borrow(&mut self, vx_index: usize, vy_index: usize) {
-        let vy = self.v_registers[vy_index];
-        let vx = self.v_registers[vx_index];
+    /// Plays `replay` from now on, which should be the state it was recorded from
+    ///
+    /// Until its last frame, `run_frame` runs as many instructions as the recorded frame did and
+    /// the keys come from the replay. Only `KeyEvent::Quit` is still read from the keyboard. The
+    /// audio device starts and stops at the instructions the recording did, rather than following
+    /// the sound timer, unless the replay was recorded without its sound
+    #[cfg(feature = "replay")]
+    pub fn play_replay(&mut self, replay: Replay) {
+        self.seed_rng(replay.seed());
+        self.replay = Some(ReplayMode::playing(replay));
+    }
 
-        let (result, overflowed) = vx.overflowing_sub(vy);
+    /// Whether a replay is playing, false again once its last frame ran
+    #[cfg(feature = "replay")]
+    pub fn is_replaying(&self) -> bool {
+        self.replay.as_ref().is_some_and(ReplayMode::is_playing)
+    }
 
-        if overflowed {
-            self.v_registers[15] = 1;
-        } else {
-            self.v_registers[15] = 0;
-        }
+    /// Sets what happens when the program runs into an invalid opcode
+    pub fn set_on_invalid_opcode(&mut self, policy: OnInvalidOpcode) {
+        self.on_invalid_opcode = policy;
+    }
 
-        self.v_registers[vx_index] = result;
+    /// Calls `hook` with the state of the CPU and the decoded instruction before every instruction executes
+    ///
+    /// Tracers, debuggers and cheat engines can be built on top of it without touching the core.
+    /// Setting a new hook replaces the previous one
+    pub fn set_hook<H>(&mut self, hook: H)
+    where
+        H: FnMut(&CpuSnapshot, Instruction) + 'static,
+    {
+        self.hook = Some(Box::new(hook));
     }
 
-    fn store_msb_of_vx_in_vf_shifting_vx_by_1(&mut self, vx_index: usize) {
-        let vx_msb = self.v_registers[vx_index] >> 7;
-        self.v_registers[15usize] = vx_msb;
-        self.v_registers[vx_index] <<= 1;
+    /// Removes the hook given to `set_hook`
+    pub fn clear_hook(&mut self) {
+        self.hook = None;
     }
 
-    fn load_font_set(&mut self) {
-        for (i, _) in FONT_SET.iter().enumerate() {
-            self.memory[i] = FONT_SET[i];
-        }
+    /// Starts or stops counting the instructions executed per family and per address
+    ///
+    /// Enabling it starts from an empty profile, disabling it throws the counts away
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profile = if enabled {
+            Some(Profile::default())
+        } else {
+            None
+        };
     }
 
-    fn fetch_opcode(&mut self) {
-        self.opcode = (self.memory[self.program_counter as usize] as u16) << 8;
-        self.opcode |= self.memory[self.program_counter as usize + 1] as u16;
+    /// Counts gathered since profiling was enabled, `None` when it is disabled
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    /// Starts or stops compiling the hot blocks of the program to native code
+    ///
+    /// Compiled blocks only run inside `run_frame`, and only while no hook, profile, coverage,
+    /// tracer or breakpoint needs to see every instruction. Disabling it frees the compiled code
+    #[cfg(feature = "jit")]
+    pub fn set_jit(&mut self, enabled: bool) -> Result<(), Chip8Error> {
+        self.jit = match (enabled, self.jit.take()) {
+            (true, Some(jit)) => Some(jit),
+            (true, None) => Some(Jit::new()?),
+            (false, _) => None,
+        };
+        Ok(())
+    }
+
+    /// Whether hot blocks are compiled to native code
+    #[cfg(feature = "jit")]
+    pub fn is_jit_enabled(&self) -> bool {
+        self.jit.is_some()
+    }
+
+    /// Stops the emulation right before the instruction at `address` runs
+    ///
+    /// `emulate_cycle`, `run_frame`, `run_cycles` and `run_until` return `State::BreakpointHit`
+    /// when they reach it, and the next call resumes by running that instruction
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.insert(address);
+    }
+
+    /// Removes the breakpoint at `address`, returning whether there was one
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    /// Removes every breakpoint
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// Ticks the timers at 60Hz according to `clock` instead of once per frame
+    ///
+    /// Every frame, and every instruction of `run_cycles` and the other headless runs, ticks the
+    /// timers as many times as 60Hz periods went by since the last tick
+    pub fn set_clock(&mut self, clock: Box<dyn Clock>) {
+        self.last_timer_tick = clock.now();
+        self.clock = Some(clock);
+    }
+
+    /// Goes back to ticking the timers once per frame
+    pub fn clear_clock(&mut self) {
+        self.clock = None;
+    }
+
+    /// Handle another thread can use to pause, resume, reset, load a rom or change the speed
+    ///
+    /// Every handle talks to this interpreter, the requests are applied by `apply_controls`
+    pub fn handle(&mut self) -> Chip8Handle {
+        let (sender, _) = self.controls.get_or_insert_with(mpsc::channel);
+        Chip8Handle::new(sender.clone())
+    }
+
+    /// Applies the requests sent through the handles, in the order they were sent
+    ///
+    /// `run_frame` and `emulate_cycle` call it before doing anything else, frontends with their own
+    /// loop call it once per iteration
+    pub fn apply_controls(&mut self) -> Result<(), Chip8Error> {
+        let controls: Vec<Control> = match &self.controls {
+            Some((_, receiver)) => receiver.try_iter().collect(),
+            None => return Ok(()),
+        };
+        for control in controls {
+            match control {
+                Control::Pause => self.pause()?,
+                Control::Resume => self.resume()?,
+                Control::Reset => self.reset(true)?,
+                Control::LoadRom(rom_data) => {
+                    self.reset(false)?;
+                    self.load_program(rom_data)?;
+                }
+                Control::SetSpeed(instructions_per_frame) => {
+                    self.speed = Some(instructions_per_frame)
+                }
+                #[cfg(feature = "replay")]
+                Control::Key(_) if self.is_replaying() => (),
+                Control::Key(event) => self.input_key_event(event),
+                Control::SaveState(reply) => {
+                    let _ = reply.send(self.snapshot());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Addresses with a breakpoint, in ascending order
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Stops the emulation right after an instruction changes the byte at `address`
+    ///
+    /// The same functions that stop at breakpoints return `State::WatchpointHit`. Only the program
+    /// triggers it, changes made through `write_memory`, `restore` or `reset` don't
+    pub fn add_watchpoint(&mut self, address: u16) {
+        let value = self.memory.get(address as usize).copied().unwrap_or(0);
+        self.watchpoints.insert(address, value);
+    }
+
+    /// Removes the watchpoint at `address`, returning whether there was one
+    pub fn remove_watchpoint(&mut self, address: u16) -> bool {
+        self.watchpoints.remove(&address).is_some()
+    }
+
+    /// Removes every watchpoint
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
+    }
+
+    /// Addresses with a watchpoint, in ascending order
+    pub fn watchpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.watchpoints.keys().copied()
+    }
+
+    /// Starts or stops tracking which addresses are executed
+    ///
+    /// Enabling it starts from an empty map, disabling it throws the map away
+    pub fn set_coverage_tracking(&mut self, enabled: bool) {
+        self.coverage = if enabled {
+            Some(Coverage::new(self.memory.len()))
+        } else {
+            None
+        };
+    }
+
+    /// Addresses executed since coverage tracking was enabled, `None` when it is disabled
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+
+    /// Cheats written to memory at the end of every frame
+    pub fn cheats(&self) -> &Cheats {
+        &self.cheats
+    }
+
+    /// Cheats to add, remove or toggle while running
+    pub fn cheats_mut(&mut self) -> &mut Cheats {
+        &mut self.cheats
+    }
+
+    /// Starts writing a line per instruction executed with the given tracer
+    ///
+    /// Failing to write is a device error, handled according to the `DeviceErrorPolicy`
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
+    }
+
+    /// Stops tracing and gives the tracer back
+    pub fn take_tracer(&mut self) -> Option<Tracer> {
+        self.tracer.take()
+    }
+
+    /// Copy of the whole state of the interpreter, to go back to it later with `restore`
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            cpu: self.cpu_snapshot(),
+            memory: self.memory.clone(),
+            graphics: self.planes[0].clone(),
+            second_plane: self.planes[1].clone(),
+            selected_planes: self.selected_planes,
+            display_mode: self.display_mode,
+            audio_pattern: self.audio_pattern,
+            pitch: self.pitch,
+            rpl_flags: self.rpl_flags,
+            program_end: self.program_end,
+            key_wait: self.key_wait,
+            waiting_for_next_frame: self.waiting_for_next_frame,
+        }
+    }
+
+    /// Puts the interpreter back in the state it was in when the snapshot was taken
+    ///
+    /// A cycle left half way through by `micro_step` is restarted and the display is drawn again
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        let cpu = snapshot.cpu;
+        self.program_counter = cpu.program_counter;
+        self.opcode = cpu.opcode;
+        self.instruction = decode(cpu.opcode);
+        self.index_register = cpu.index_register;
+        self.v_registers = cpu.v_registers;
+        self.stack = cpu.stack;
+        self.stack_pointer = cpu.stack_pointer;
+        self.delay_timer = cpu.delay_timer;
+        self.sound_timer = cpu.sound_timer;
+        self.memory.clone_from(&snapshot.memory);
+        self.planes[0].clone_from(&snapshot.graphics);
+        self.planes[1].clone_from(&snapshot.second_plane);
+        self.selected_planes = snapshot.selected_planes;
+        self.display_mode = snapshot.display_mode;
+        self.audio_pattern = snapshot.audio_pattern;
+        self.pitch = snapshot.pitch;
+        self.display_changed = true;
+        self.rpl_flags = snapshot.rpl_flags;
+        self.program_end = snapshot.program_end;
+        self.key_wait = snapshot.key_wait;
+        self.waiting_for_next_frame = snapshot.waiting_for_next_frame;
+        self.stage = Stage::Fetch;
+        self.resumed_breakpoint = None;
+        self.sync_watchpoints();
+    }
+
+    /// Keeps up to `depth` snapshots, one every `interval` frames, for `rewind` to go back to
+    ///
+    /// A frame ends every time the display is drawn and the timers tick, so once per `run_frame`
+    /// or per `emulate_cycle`. A depth of 0 turns rewinding off and frees the snapshots
+    pub fn set_rewind(&mut self, depth: usize, interval: usize) {
+        self.rewind_buffer = RewindBuffer::new(depth, interval as u64);
+    }
+
+    /// Lets `run_frame` draw at most `limit` sprites per frame, `None` lifts the limit
+    ///
+    /// A DXYN over the limit ends the frame and runs first thing in the next one. Setting it
+    /// starts the counters of `sprite_budget` over
+    pub fn set_sprite_limit(&mut self, limit: Option<usize>) {
+        self.sprite_budget.set_limit(limit);
+    }
+
+    /// The sprite limit with how many sprites were drawn and how many frames it cut short
+    pub fn sprite_budget(&self) -> &SpriteBudget {
+        &self.sprite_budget
+    }
+
+    /// Goes back about `frames` frames, returning how many frames it actually went back
+    ///
+    /// It lands on the closest snapshot taken at least `frames` frames ago, or on the oldest one when
+    /// there isn't any that old. Calling it repeatedly keeps going back in time
+    pub fn rewind(&mut self, frames: usize) -> usize {
+        let target = self.frame.saturating_sub(frames as u64);
+        let (frame, snapshot) = match self.rewind_buffer.rewind_to(target) {
+            Some((frame, snapshot)) => (*frame, snapshot.clone()),
+            None => return 0,
+        };
+
+        self.restore(&snapshot);
+        let rewound = self.frame.saturating_sub(frame);
+        self.frame = frame;
+        rewound as usize
+    }
+
+    /// Registers, stack and timers at this point of the emulation
+    pub fn cpu_snapshot(&self) -> CpuSnapshot {
+        CpuSnapshot {
+            program_counter: self.program_counter,
+            opcode: self.opcode,
+            index_register: self.index_register,
+            v_registers: self.v_registers,
+            stack: self.stack,
+            stack_pointer: self.stack_pointer,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+        }
+    }
+
+    /// Returns the device errors logged under `DeviceErrorPolicy::Continue` since the last call
+    ///
+    /// Only the most recent errors are kept so a device that keeps failing doesn't grow the log forever
+    pub fn take_device_errors(&mut self) -> Vec<Chip8Error> {
+        std::mem::take(&mut self.device_errors)
+    }
+
+    /// Emulates a cycle of the interpreter
+    ///
+    /// It retrieves the next opcode to execute, it draws the next frame, updates the timers and listens to keyboard events
+    ///
+    /// In case the user wants to exit, either by clicking the `X` on the window or pressing the escape key
+    /// this state is returned to the caller so it can interrupt the loop
+    pub fn emulate_cycle(&mut self) -> Result<State, Chip8Error> {
+        if self.stage == Stage::Fetch {
+            self.apply_controls()?;
+        }
+        if self.stage == Stage::Fetch && !self.is_cpu_halted() {
+            if let Some(address) = self.hit_breakpoint() {
+                return Ok(State::BreakpointHit(address));
+            }
+        }
+
+        loop {
+            if let MicroStep::Execute { state, .. } = self.micro_step()? {
+                return match (state, self.hit_watchpoint()) {
+                    (State::Exit, _) | (_, None) => Ok(state),
+                    (_, Some(address)) => Ok(State::WatchpointHit(address)),
+                };
+            }
+        }
+    }
+
+    /// Runs only the next stage of the current cycle: fetch, decode or execute
+    ///
+    /// Meant for frontends that want to show how the interpreter works one stage at a time.
+    /// Calling `emulate_cycle` afterwards finishes the cycle that is in progress.
+    /// Breakpoints are ignored since every stage is already a stop
+    pub fn micro_step(&mut self) -> Result<MicroStep, Chip8Error> {
+        if self.is_cpu_halted() && self.stage == Stage::Fetch {
+            self.stage = Stage::Execute;
+        }
+
+        match self.stage {
+            Stage::Fetch => {
+                let address = self.program_counter;
+                self.fetch_opcode()?;
+                self.stage = Stage::Decode;
+                Ok(MicroStep::Fetch {
+                    address,
+                    opcode: self.opcode,
+                })
+            }
+            Stage::Decode => {
+                self.instruction = decode(self.opcode);
+                self.stage = Stage::Execute;
+                Ok(MicroStep::Decode {
+                    opcode: self.opcode,
+                    operands: Operands::from_opcode(self.opcode),
+                    instruction: self.instruction,
+                })
+            }
+            Stage::Execute => {
+                self.stage = Stage::Fetch;
+                let state = self.finish_cycle()?;
+                Ok(MicroStep::Execute {
+                    opcode: self.opcode,
+                    program_counter: self.program_counter,
+                    state,
+                })
+            }
+        }
+    }
+
+    fn is_cpu_halted(&self) -> bool {
+        !self.cpu_enabled || self.program_end.is_some()
+    }
+
+    // Whether the next instruction draws a sprite, looked at before it is fetched
+    fn is_at_draw(&self) -> bool {
+        self.memory
+            .get(self.program_counter as usize)
+            .is_some_and(|byte| byte & 0xF0 == 0xD0)
+    }
+
+    // A breakpoint stops the emulation once, resuming runs the instruction it sits on
+    fn hit_breakpoint(&mut self) -> Option<u16> {
+        let address = self.program_counter;
+        if !self.breakpoints.contains(&address) {
+            self.resumed_breakpoint = None;
+            return None;
+        }
+        if self.resumed_breakpoint.take() == Some(address) {
+            return None;
+        }
+
+        self.resumed_breakpoint = Some(address);
+        Some(address)
+    }
+
+    // Reports the first watched byte that changed since the last check
+    fn hit_watchpoint(&mut self) -> Option<u16> {
+        let memory = &self.memory;
+        self.watchpoints.iter_mut().find_map(|(address, value)| {
+            let current = memory.get(*address as usize).copied().unwrap_or(0);
+            if current == *value {
+                return None;
+            }
+            *value = current;
+            Some(*address)
+        })
+    }
+
+    fn sync_watchpoints(&mut self) {
+        for (address, value) in self.watchpoints.iter_mut() {
+            *value = self.memory.get(*address as usize).copied().unwrap_or(0);
+        }
+    }
+
+    /// Runs a whole frame: up to `instructions_per_frame` instructions, then draws,
+    /// ticks the timers and reads the keyboard once
+    ///
+    /// Calling it 60 times per second keeps the timers at their intended 60Hz no matter how many
+    /// instructions run in between. A cycle left half way through by `micro_step` is restarted.
+    /// With the `display_wait` quirk the frame also ends right after a sprite is drawn, as it does
+    /// on a DXYN past the limit of `set_sprite_limit`. A speed set through a `Chip8Handle` replaces
+    /// `instructions_per_frame`
+    pub fn run_frame(&mut self, instructions_per_frame: usize) -> Result<State, Chip8Error> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("run_frame", instructions_per_frame).entered();
+
+        self.apply_controls()?;
+        let instructions_per_frame = self.speed.unwrap_or(instructions_per_frame);
+        #[cfg(feature = "replay")]
+        let instructions_per_frame = self.start_replay_frame(instructions_per_frame);
+        self.stage = Stage::Fetch;
+        let mut stop = None;
+        let mut executed = 0;
+        while executed < instructions_per_frame {
+            if self.is_cpu_halted() || self.waiting_for_next_frame {
+                break;
+            }
+            if self.sprite_budget.is_spent() && self.is_at_draw() {
+                self.sprite_budget.record_deferred();
+                break;
+            }
+            if let Some(address) = self.hit_breakpoint() {
+                stop = Some(State::BreakpointHit(address));
+                break;
+            }
+            #[cfg(feature = "jit")]
+            if let Some(instructions) = self.run_compiled_block(instructions_per_frame - executed) {
+                executed += instructions;
+                continue;
+            }
+            #[cfg(feature = "replay")]
+            {
+                self.frame_cycles = executed + 1;
+            }
+            self.fetch_opcode()?;
+            self.instruction = decode(self.opcode);
+            self.interpret_opcode()?;
+            #[cfg(feature = "replay")]
+            self.play_replay_sounds(executed + 1)?;
+            if let Some(address) = self.hit_watchpoint() {
+                stop = Some(State::WatchpointHit(address));
+                break;
+            }
+            executed += 1;
+        }
+        #[cfg(feature = "replay")]
+        {
+            self.frame_cycles = executed;
+        }
+
+        let state = self.end_frame()?;
+        #[cfg(feature = "replay")]
+        self.play_replay_sounds(usize::MAX)?;
+        match (state, stop) {
+            (State::Exit, _) => Ok(State::Exit),
+            (_, Some(stop)) => Ok(stop),
+            (state, None) => Ok(state),
+        }
+    }
+
+    /// Runs up to `cycles` instructions without drawing or reading the keyboard
+    ///
+    /// Timers still tick once per instruction like in `emulate_cycle`. Meant for automated tests and
+    /// benchmarks of roms, it stops early when the program ends or the CPU is disabled
+    pub fn run_cycles(&mut self, cycles: usize) -> Result<State, Chip8Error> {
+        self.stage = Stage::Fetch;
+        for _ in 0..cycles {
+            if self.is_cpu_halted() || self.key_wait.is_some() {
+                break;
+            }
+            if let Some(address) = self.hit_breakpoint() {
+                return Ok(State::BreakpointHit(address));
+            }
+            self.run_headless_cycle()?;
+            if let Some(address) = self.hit_watchpoint() {
+                return Ok(State::WatchpointHit(address));
+            }
+        }
+
+        Ok(self.program_state())
+    }
+
+    /// Runs instructions without drawing or reading the keyboard until `predicate` returns true
+    ///
+    /// The predicate is checked before every instruction. It also stops when the program ends or
+    /// the CPU is disabled, otherwise it keeps going for as long as the predicate returns false
+    pub fn run_until<P>(&mut self, mut predicate: P) -> Result<State, Chip8Error>
+    where
+        P: FnMut(&Chip8) -> bool,
+    {
+        self.stage = Stage::Fetch;
+        while !predicate(self) && !self.is_cpu_halted() && self.key_wait.is_none() {
+            if let Some(address) = self.hit_breakpoint() {
+                return Ok(State::BreakpointHit(address));
+            }
+            self.run_headless_cycle()?;
+            if let Some(address) = self.hit_watchpoint() {
+                return Ok(State::WatchpointHit(address));
+            }
+        }
+
+        Ok(self.program_state())
+    }
+
+    /// Runs the instruction under the program counter and stops
+    ///
+    /// Like `run_cycles`, timers tick once per instruction and nothing is drawn or read from the keyboard.
+    /// The instruction runs even when it sits on a breakpoint, landing on one returns `State::BreakpointHit`
+    pub fn step(&mut self) -> Result<State, Chip8Error> {
+        self.run_to_condition(|_| true)
+    }
+
+    /// Runs the next instruction, treating a 2NNN call and the whole subroutine as a single step
+    ///
+    /// It stops once the subroutine returns, or earlier at a breakpoint inside it
+    pub fn step_over(&mut self) -> Result<State, Chip8Error> {
+        if let Instruction::Call { .. } = decode(self.read_opcode(self.program_counter)?) {
+            let return_address = self.program_counter + 2;
+            let stack_pointer = self.stack_pointer;
+            self.run_to_condition(|chip8| {
+                chip8.program_counter == return_address && chip8.stack_pointer == stack_pointer
+            })
+        } else {
+            self.step()
+        }
+    }
+
+    /// Runs instructions until the program counter reaches `address`, or a breakpoint is hit
+    ///
+    /// At least one instruction runs, so calling it at `address` goes around the loop once
+    pub fn run_to(&mut self, address: u16) -> Result<State, Chip8Error> {
+        self.run_to_condition(|chip8| chip8.program_counter == address)
+    }
+
+    fn run_to_condition<P>(&mut self, mut stop: P) -> Result<State, Chip8Error>
+    where
+        P: FnMut(&Chip8) -> bool,
+    {
+        self.stage = Stage::Fetch;
+        // The emulation is already stopped where it is, a breakpoint here shouldn't stop it again
+        self.resumed_breakpoint = Some(self.program_counter);
+        loop {
+            if self.is_cpu_halted() || self.key_wait.is_some() {
+                return Ok(self.program_state());
+            }
+            if let Some(address) = self.hit_breakpoint() {
+                return Ok(State::BreakpointHit(address));
+            }
+            self.run_headless_cycle()?;
+            if let Some(address) = self.hit_watchpoint() {
+                return Ok(State::WatchpointHit(address));
+            }
+            if stop(self) {
+                break;
+            }
+        }
+
+        if self.program_end.is_none() {
+            if let Some(address) = self.hit_breakpoint() {
+                return Ok(State::BreakpointHit(address));
+            }
+        }
+        Ok(self.program_state())
+    }
+
+    // Compiled blocks never touch memory, so watchpoints can't be hit while one runs
+    #[cfg(feature = "jit")]
+    fn run_compiled_block(&mut self, budget: usize) -> Option<usize> {
+        let observed = self.hook.is_some()
+            || self.profile.is_some()
+            || self.coverage.is_some()
+            || self.tracer.is_some()
+            || !self.breakpoints.is_empty();
+        let jit = self.jit.as_mut().filter(|_| !observed)?;
+        let ran = jit.run(
+            &self.memory,
+            self.program_counter,
+            &mut self.v_registers,
+            &mut self.index_register,
+            budget,
+            self.quirks,
+            self.program_end_detection.jump_to_self,
+            self.variant.has_xochip_instructions(),
+        )?;
+        self.program_counter = ran.next;
+        self.opcode = ran.last_opcode;
+        self.instruction = decode(ran.last_opcode);
+        Some(ran.instructions)
+    }
+
+    fn run_headless_cycle(&mut self) -> Result<(), Chip8Error> {
+        self.fetch_opcode()?;
+        self.instruction = decode(self.opcode);
+        self.interpret_opcode()?;
+        self.tick_due_timers()
+    }
+
+    fn program_state(&self) -> State {
+        match (self.program_end, self.key_wait) {
+            (Some(ProgramEnd::Finished), _) => State::Finished,
+            (Some(ProgramEnd::Halted), _) => State::Halted,
+            (None, Some(_)) => State::WaitingForKey,
+            (None, None) => State::Continue,
+        }
+    }
+
+    fn finish_cycle(&mut self) -> Result<State, Chip8Error> {
+        if !self.is_cpu_halted() {
+            self.interpret_opcode()?;
+        }
+
+        self.end_frame()
+    }
+
+    fn end_frame(&mut self) -> Result<State, Chip8Error> {
+        self.tick_due_timers()?;
+        self.present()
+    }
+
+    // Without a clock the timers tick once per call, with one they follow the time that went by
+    fn tick_due_timers(&mut self) -> Result<(), Chip8Error> {
+        #[cfg(feature = "replay")]
+        if self.replay.is_some() {
+            return self.tick_timers();
+        }
+        let now = match &self.clock {
+            Some(clock) => clock.now(),
+            None => return self.tick_timers(),
+        };
+        if !self.timers_enabled {
+            self.last_timer_tick = now;
+            return Ok(());
+        }
+
+        let elapsed = now.saturating_sub(self.last_timer_tick);
+        let ticks = (elapsed.as_nanos() / TIMER_PERIOD.as_nanos()) as u32;
+        if ticks > MAX_TIMER_TICKS {
+            self.last_timer_tick = now;
+        } else {
+            self.last_timer_tick += TIMER_PERIOD * ticks;
+        }
+        for _ in 0..ticks.min(MAX_TIMER_TICKS) {
+            self.tick_timers()?;
+        }
+
+        Ok(())
+    }
+
+    /// Fetches and decodes the instruction under the program counter without running it
+    ///
+    /// `fetch`, `execute`, `tick_timers` and `present` are the phases `emulate_cycle` and `run_frame`
+    /// are made of, so frontends can arrange them in their own loop. Breakpoints, watchpoints and
+    /// the requests of a `Chip8Handle` are left to the caller, see `apply_controls`
+    pub fn fetch(&mut self) -> Result<Instruction, Chip8Error> {
+        self.fetch_opcode()?;
+        self.instruction = decode(self.opcode);
+        self.stage = Stage::Execute;
+        Ok(self.instruction)
+    }
+
+    /// Runs the fetched instruction, fetching it first when `fetch` wasn't called
+    ///
+    /// Nothing is drawn, the timers don't tick and the keyboard isn't read. It returns right away
+    /// when the program ended or the CPU is disabled
+    pub fn execute(&mut self) -> Result<State, Chip8Error> {
+        if !self.is_cpu_halted() {
+            match self.stage {
+                Stage::Fetch => {
+                    self.fetch()?;
+                }
+                Stage::Decode => self.instruction = decode(self.opcode),
+                Stage::Execute => (),
+            }
+            self.interpret_opcode()?;
+        }
+
+        self.stage = Stage::Fetch;
+        Ok(self.program_state())
+    }
+
+    /// Decrements the delay and sound timers once, meant to be called at 60Hz
+    ///
+    /// Does nothing while the timers are disabled
+    pub fn tick_timers(&mut self) -> Result<(), Chip8Error> {
+        if self.timers_enabled {
+            let timers_result = self.update_timers();
+            self.handle_device_result(timers_result)?;
+        }
+
+        Ok(())
+    }
+
+    /// Ends the frame: draws the display when it changed and reads the keyboard
+    ///
+    /// It is also when cheats are applied, snapshots for `rewind` are taken, and what lets a
+    /// program waiting for the next frame with the `display_wait` quirk go on
+    pub fn present(&mut self) -> Result<State, Chip8Error> {
+        self.waiting_for_next_frame = false;
+        self.sprite_budget.next_frame();
+        // Cheats aren't the program writing, watchpoints shouldn't see them as a change
+        if self.cheats.apply(&mut self.memory) {
+            self.sync_watchpoints();
+        }
+        // Most instructions don't touch the display, only draw when it changed
+        if self.display_changed {
+            if let Some(graphics_device) = self.graphics_device.as_mut() {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(
+                    width = self.planes[0].width(),
+                    height = self.planes[0].height(),
+                    "drawing"
+                );
+                let planes = if self.variant.has_xochip_instructions() {
+                    &self.planes[..]
+                } else {
+                    &self.planes[..1]
+                };
+                let frame = Frame::new(planes, &self.palette);
+                let draw_result = graphics_device.draw(&frame);
+                self.display_changed = draw_result.is_err();
+                self.handle_device_result(draw_result)?;
+            }
+        }
+
+        self.frame += 1;
+        if self.rewind_buffer.is_due(self.frame) {
+            self.rewind_buffer.push(self.frame, self.snapshot());
+        }
+
+        let mut exit_requested = false;
+        if self.keyboard_device.has_pending_events() {
+            for event in self.keyboard_device.poll_events() {
+                match event {
+                    KeyEvent::Quit => exit_requested = true,
+                    #[cfg(feature = "replay")]
+                    _ if self.is_replaying() => (),
+                    event => self.input_key_event(event),
+                }
+            }
+        }
+        #[cfg(feature = "replay")]
+        if let Some(replay) = self.replay.as_mut() {
+            for event in replay.take_events() {
+                self.handle_key_event(event);
+            }
+        }
+
+        if exit_requested {
+            return Ok(State::Exit);
+        }
+
+        Ok(self.program_state())
+    }
+
+    // A key pressed or released by the player, recorded when a replay is being recorded
+    fn input_key_event(&mut self, event: KeyEvent) {
+        #[cfg(feature = "replay")]
+        if let Some(replay) = self.replay.as_mut() {
+            replay.record(event);
+        }
+        self.handle_key_event(event);
+    }
+
+    fn handle_key_event(&mut self, event: KeyEvent) {
+        match event {
+            KeyEvent::Down(key) => self.keyboard[(key & 0xF) as usize] = 1,
+            KeyEvent::Up(key) => self.keyboard[(key & 0xF) as usize] = 0,
+            KeyEvent::Quit => (),
+        }
+    }
+
+    // A replay that ran out of frames is over, the frame then runs as asked and reads the keyboard
+    #[cfg(feature = "replay")]
+    fn start_replay_frame(&mut self, instructions_per_frame: usize) -> usize {
+        let replay = match self.replay.as_mut() {
+            Some(replay) => replay,
+            None => return instructions_per_frame,
+        };
+        match replay.start_frame(instructions_per_frame) {
+            Some(instructions) => instructions,
+            None => {
+                self.replay = None;
+                instructions_per_frame
+            }
+        }
+    }
+
+    fn interpret_opcode(&mut self) -> Result<(), Chip8Error> {
+        let instruction = self.instruction;
+        if self.hook.is_some() {
+            let snapshot = self.cpu_snapshot();
+            if let Some(hook) = self.hook.as_mut() {
+                hook(&snapshot, instruction);
+            }
+        }
+        if let Some(profile) = self.profile.as_mut() {
+            profile.record(self.program_counter, instruction);
+        }
+        if let Some(coverage) = self.coverage.as_mut() {
+            coverage.record(self.program_counter);
+        }
+        if self.tracer.is_some() {
+            let snapshot = self.cpu_snapshot();
+            if let Some(tracer) = self.tracer.as_mut() {
+                let trace_result = tracer.trace(&snapshot).map_err(Chip8Error::TraceError);
+                self.handle_device_result(trace_result)?;
+            }
+        }
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            program_counter = self.program_counter,
+            opcode = self.opcode,
+            ?instruction,
+            "executing instruction"
+        );
+        let context = self.error_context();
+        self.run_instruction(instruction)
+            .map_err(|error| error.with_context(context))
+    }
+
+    fn run_instruction(&mut self, instruction: Instruction) -> Result<(), Chip8Error> {
+        let instruction = match instruction {
+            // Before XO-CHIP the last nibble of 5XYN is ignored
+            Instruction::StoreRange { vx, vy } | Instruction::LoadRange { vx, vy }
+                if !self.variant.has_xochip_instructions() =>
+            {
+                Instruction::SkipIfRegistersEqual { vx, vy }
+            }
+            instruction => instruction,
+        };
+        if !self.variant.supports(instruction) {
+            return self.handle_invalid_opcode();
+        }
+
+        match instruction {
+            Instruction::System { nnn: 0x230 } if self.display_mode == DisplayMode::TwoPage => {
+                self.clear_display()
+            }
+            Instruction::ScrollDown { n } => self.scroll_display_down(n.into()),
+            Instruction::ScrollUp { n } => self.scroll_display_up(n.into()),
+            Instruction::ClearDisplay => self.clear_display(),
+            Instruction::Return => {
+                if self.stack_pointer == 0 {
+                    return self.end_program_on_empty_stack_return();
+                }
+                self.return_from_routine()
+            }
+            Instruction::ScrollRight => self.scroll_display_right(),
+            Instruction::ScrollLeft => self.scroll_display_left(),
+            Instruction::Exit => {
+                self.program_end = Some(ProgramEnd::Halted);
+                return Ok(());
+            }
+            Instruction::LowResolution => self.set_high_resolution(false),
+            Instruction::HighResolution => self.set_high_resolution(true),
+            Instruction::Jump { nnn }
+                if nnn == self.program_counter && self.program_end_detection.jump_to_self =>
+            {
+                self.program_end = Some(ProgramEnd::Finished);
+            }
+            Instruction::Jump { nnn } => self.jump_to_address(nnn),
+            Instruction::Call { nnn } => {
+                if self.stack_pointer as usize == self.stack.len() {
+                    return Err(Chip8Error::StackOverflow {
+                        context: self.error_context(),
+                    });
+                }
+                self.jump_to_routine(nnn)
+            }
+            Instruction::SkipIfEqual { vx, nn } => {
+                self.skip_instruction_if_vx_equals_nn(vx, nn.into())
+            }
+            Instruction::SkipIfNotEqual { vx, nn } => {
+                self.skip_instruction_if_vx_not_equals_nn(vx, nn.into())
+            }
+            Instruction::SkipIfRegistersEqual { vx, vy } => {
+                self.skip_instruction_if_vx_equals_vy(vx, vy)
+            }
+            Instruction::StoreRange { vx, vy } => self.stores_vx_to_vy_in_memory_from_i(vx, vy)?,
+            Instruction::LoadRange { vx, vy } => self.writes_vx_to_vy_from_memory_i(vx, vy)?,
+            Instruction::SetRegister { vx, nn } => self.set_vx_to_nn(vx, nn.into()),
+            Instruction::AddToRegister { vx, nn } => self.add_nn_to_vx(vx, nn.into()),
+            Instruction::Copy { vx, vy } => self.sets_vx_to_vy(vx, vy),
+            Instruction::Or { vx, vy } => self.sets_vx_to_vx_bitwise_or_vy(vx, vy),
+            Instruction::And { vx, vy } => self.sets_vx_to_vx_bitwise_and_vy(vx, vy),
+            Instruction::Xor { vx, vy } => self.sets_vx_to_vx_bitwise_xor_vy(vx, vy),
+            Instruction::Add { vx, vy } => self.adds_vy_to_vx_setting_vf_on_borrow(vx, vy),
+            Instruction::Subtract { vx, vy } => {
+                self.subtracts_vy_from_vx_setting_vf_on_borrow(vx, vy)
+            }
+            Instruction::ShiftRight { vx, vy } => {
+                self.store_lsb_of_vx_in_vf_shifting_vx_by_1(vx, vy)
+            }
+            Instruction::SubtractReversed { vx, vy } => {
+                self.set_vx_to_vy_minus_vx_setting_vf_on_borrow(vx, vy)
+            }
+            Instruction::ShiftLeft { vx, vy } => {
+                self.store_msb_of_vx_in_vf_shifting_vx_by_1(vx, vy)
+            }
+            Instruction::SkipIfRegistersNotEqual { vx, vy } => {
+                self.skip_instruction_if_vx_not_equals_vy(vx, vy)
+            }
+            Instruction::SetIndex { nnn } => self.set_index_register_to_nnn(nnn),
+            Instruction::JumpWithOffset { vx, nnn } => self.jump_to_address_nnn_plus_v0(vx, nnn),
+            Instruction::Random { vx, nn } => {
+                self.set_vx_to_random_number_bitwise_and_nn(vx, nn.into())?
+            }
+            Instruction::Draw { vx, vy, n } => self.set_graphics(vx, vy, n.into())?,
+            Instruction::SkipIfKeyPressed { vx } => self.skips_instruction_if_vx_key_is_pressed(vx),
+            Instruction::SkipIfKeyNotPressed { vx } => {
+                self.skips_instruction_if_vx_key_is_not_pressed(vx)
+            }
+            Instruction::LoadLongIndex => self.sets_i_to_the_address_that_follows()?,
+            Instruction::SelectPlanes { planes } => self.selects_planes(planes),
+            Instruction::LoadAudioPattern => self.loads_audio_pattern_from_i()?,
+            Instruction::LoadDelayTimer { vx } => self.sets_vx_to_delay_timer(vx),
+            Instruction::WaitForKey { vx } => {
+                if !self.sets_vx_to_key_release(vx) {
+                    return Ok(());
+                }
+            }
+            Instruction::SetDelayTimer { vx } => self.sets_delay_timer_to_vx(vx),
+            Instruction::SetSoundTimer { vx } => self.sets_sound_timer_to_vx(vx)?,
+            Instruction::AddToIndex { vx } => self.adds_vx_to_i(vx),
+            Instruction::LoadFontDigit { vx } => self.sets_i_to_font_digit_in_vx(vx),
+            Instruction::LoadBigFontDigit { vx } => self.sets_i_to_big_font_digit_in_vx(vx),
+            Instruction::StoreBcd { vx } => self.store_bcd_of_vx_from_i(vx)?,
+            Instruction::SetPitch { vx } => self.sets_pitch_to_vx(vx)?,
+            Instruction::StoreRegisters { vx } => self.stores_v0_to_vx_in_memory_from_i(vx)?,
+            Instruction::LoadRegisters { vx } => self.writes_v0_to_vx_from_memory_i(vx)?,
+            Instruction::StoreFlags { vx } => self.stores_v0_to_vx_in_rpl_flags(vx)?,
+            Instruction::LoadFlags { vx } => self.writes_v0_to_vx_from_rpl_flags(vx),
+            Instruction::System { .. } | Instruction::Unknown { .. } => {
+                return self.handle_invalid_opcode()
+            }
+        };
+
+        if !instruction.is_jump() {
+            // XO-CHIP programs can run up to the very end of their 64 KiB
+            self.program_counter = self.program_counter.wrapping_add(2);
+        }
+
+        Ok(())
+    }
+
+    fn handle_invalid_opcode(&mut self) -> Result<(), Chip8Error> {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(
+            program_counter = self.program_counter,
+            opcode = self.opcode,
+            "invalid opcode"
+        );
+        match &mut self.on_invalid_opcode {
+            OnInvalidOpcode::Halt => {
+                return Err(Chip8Error::InvalidOpcode {
+                    context: self.error_context(),
+                })
+            }
+            OnInvalidOpcode::SkipAndContinue => (),
+            OnInvalidOpcode::Callback(callback) => callback(self.opcode, self.program_counter),
+        }
+
+        // An invalid opcode in the last two bytes of a 64 KiB memory skips to the start of it
+        self.program_counter = self.program_counter.wrapping_add(2);
+        Ok(())
+    }
+
+    fn clear_display(&mut self) {
+        selected_planes(&mut self.planes, self.selected_planes).for_each(FrameBuffer::clear);
+        self.display_changed = true;
+    }
+
+    fn set_display_mode(&mut self, display_mode: DisplayMode) {
+        let (width, height) = display_mode.resolution();
+        self.display_mode = display_mode;
+        self.planes = [
+            FrameBuffer::new(width, height),
+            FrameBuffer::new(width, height),
+        ];
+        self.display_changed = true;
+    }
+
+    fn set_high_resolution(&mut self, hires: bool) {
+        self.set_display_mode(if hires {
+            DisplayMode::HighResolution
+        } else {
+            DisplayMode::LowResolution
+        });
+    }
+
+    fn scroll_display_down(&mut self, n_address: u16) {
+        let (width, height) = self.resolution();
+        let rows = n_address as usize;
+
+        for plane in selected_planes(&mut self.planes, self.selected_planes) {
+            for row in (0..height).rev() {
+                for col in 0..width {
+                    let on = row >= rows && plane.get_pixel(col, row - rows);
+                    plane.set_pixel(col, row, on);
+                }
+            }
+        }
+
+        self.display_changed = true;
+    }
+
+    fn scroll_display_up(&mut self, n_address: u16) {
+        let (width, height) = self.resolution();
+        let rows = n_address as usize;
+
+        for plane in selected_planes(&mut self.planes, self.selected_planes) {
+            for row in 0..height {
+                for col in 0..width {
+                    let on = row + rows < height && plane.get_pixel(col, row + rows);
+                    plane.set_pixel(col, row, on);
+                }
+            }
+        }
+
+        self.display_changed = true;
+    }
+
+    fn scroll_display_right(&mut self) {
+        let (width, height) = self.resolution();
+
+        for plane in selected_planes(&mut self.planes, self.selected_planes) {
+            for row in 0..height {
+                for col in (0..width).rev() {
+                    let on = col >= 4 && plane.get_pixel(col - 4, row);
+                    plane.set_pixel(col, row, on);
+                }
+            }
+        }
+
+        self.display_changed = true;
+    }
+
+    fn scroll_display_left(&mut self) {
+        let (width, height) = self.resolution();
+
+        for plane in selected_planes(&mut self.planes, self.selected_planes) {
+            for row in 0..height {
+                for col in 0..width {
+                    let on = col + 4 < width && plane.get_pixel(col + 4, row);
+                    plane.set_pixel(col, row, on);
+                }
+            }
+        }
+
+        self.display_changed = true;
+    }
+
+    fn end_program_on_empty_stack_return(&mut self) -> Result<(), Chip8Error> {
+        if !self.program_end_detection.return_with_empty_stack {
+            #[cfg(feature = "tracing")]
+            tracing::error!(program_counter = self.program_counter, "stack underflow");
+            return Err(Chip8Error::StackUnderflow {
+                context: self.error_context(),
+            });
+        }
+        self.program_end = Some(ProgramEnd::Finished);
+        Ok(())
+    }
+
+    fn return_from_routine(&mut self) {
+        self.stack_pointer -= 1;
+        self.program_counter = self.stack[self.stack_pointer as usize];
+    }
+
+    fn jump_to_address(&mut self, nnn_address: u16) {
+        self.program_counter = nnn_address
+    }
+
+    fn jump_to_routine(&mut self, nnn_address: u16) {
+        self.stack[self.stack_pointer as usize] = self.program_counter;
+        self.stack_pointer += 1;
+        self.program_counter = nnn_address;
+    }
+
+    fn skip_instruction_if_vx_equals_nn(&mut self, vx_index: usize, nn_address: u16) {
+        let v_register_value = self.v_registers[vx_index];
+        let value = nn_address as u8;
+
+        if v_register_value == value {
+            self.skip_next_instruction();
+        }
+    }
+
+    fn skip_instruction_if_vx_not_equals_nn(&mut self, vx_index: usize, nn_address: u16) {
+        let v_register_value = self.v_registers[vx_index];
+        let value = nn_address as u8;
+
+        if v_register_value != value {
+            self.skip_next_instruction();
+        }
+    }
+
+    fn skip_instruction_if_vx_equals_vy(&mut self, vx_index: usize, vy_index: usize) {
+        let x_register_value = self.v_registers[vx_index];
+        let y_register_value = self.v_registers[vy_index];
+
+        if x_register_value == y_register_value {
+            self.skip_next_instruction();
+        }
+    }
+
+    // XO-CHIP skips over both words of F000 NNNN
+    fn skip_next_instruction(&mut self) {
+        let next = self.program_counter.wrapping_add(2);
+        let long =
+            self.variant.has_xochip_instructions() && self.read_opcode(next).ok() == Some(0xF000);
+        self.program_counter = next.wrapping_add(if long { 2 } else { 0 });
+    }
+
+    fn set_vx_to_nn(&mut self, vx_index: usize, nn_address: u16) {
+        let new_v_register_value = nn_address as u8;
+        self.v_registers[vx_index] = new_v_register_value;
+    }
+
+    fn add_nn_to_vx(&mut self, vx_index: usize, nn_address: u16) {
+        let value_to_add = nn_address as u8;
+
+        let (sum, _) = self.v_registers[vx_index].overflowing_add(value_to_add);
+        self.v_registers[vx_index] = sum;
+    }
+
+    fn skip_instruction_if_vx_not_equals_vy(&mut self, vx_index: usize, vy_index: usize) {
+        let vy = self.v_registers[vy_index];
+        let vx = self.v_registers[vx_index];
+
+        if vx != vy {
+            self.skip_next_instruction();
+        }
+    }
+
+    fn set_index_register_to_nnn(&mut self, nnn_address: u16) {
+        self.index_register = nnn_address;
+    }
+
+    fn jump_to_address_nnn_plus_v0(&mut self, vx_index: usize, nnn_address: u16) {
+        let register_index = if self.quirks.jump_uses_vx {
+            vx_index
+        } else {
+            0
+        };
+        let register_value = self.v_registers[register_index] as u16;
+        self.program_counter = nnn_address + register_value;
+    }
+
+    fn set_vx_to_random_number_bitwise_and_nn(
+        &mut self,
+        vx_index: usize,
+        nn_address: u16,
+    ) -> Result<(), Chip8Error> {
+        let opcode_value = nn_address as u8;
+        let random_number = self.random_number_generator.generate()?;
+        self.v_registers[vx_index] = random_number & opcode_value;
+        Ok(())
+    }
+
+    fn set_graphics(
+        &mut self,
+        vx_index: usize,
+        vy_index: usize,
+        n_address: u16,
+    ) -> Result<(), Chip8Error> {
+        let (width, height) = self.resolution();
+        // The starting position always wraps, the quirk only decides what happens past the edge
+        let vx = self.v_registers[vx_index] as usize % width;
+        let vy = self.v_registers[vy_index] as usize % height;
+
+        // DXY0 draws a 16x16 sprite made of two bytes per row
+        let (sprite_width, sprite_height) = match n_address {
+            0 if self.variant.has_superchip_instructions() => (16, 16),
+            n => (8, n as usize),
+        };
+        let bytes_per_row = sprite_width / 8;
+        let sprite_length = sprite_height * bytes_per_row;
+        // With both planes selected the sprite of the second plane follows the one of the first
+        let planes = self.selected_planes.count_ones() as usize;
+        let sprite = self.memory_range(self.index_register, sprite_length * planes)?;
+        self.bus
+            .read(self.index_register, &mut self.memory[sprite.clone()]);
+
+        self.v_registers[15usize] = 0;
+        let sprites = self.memory[sprite].chunks(sprite_length.max(1));
+        for (plane, sprite) in selected_planes(&mut self.planes, self.selected_planes).zip(sprites)
+        {
+            for (row, row_bytes) in sprite.chunks(bytes_per_row).enumerate() {
+                let row_bits = row_bytes
+                    .iter()
+                    .fold(0u16, |bits, byte| (bits << 8) | *byte as u16)
+                    << (16 - sprite_width);
+
+                for col in 0..sprite_width {
+                    if row_bits & 0x8000 >> col > 0 {
+                        let (col, row) = (vx + col, vy + row);
+                        if !self.quirks.wrap_sprites && (col >= width || row >= height) {
+                            continue;
+                        }
+                        if plane.xor_pixel(col % width, row % height) {
+                            self.v_registers[0xF] = 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        self.display_changed = true;
+        self.waiting_for_next_frame = self.quirks.display_wait;
+        self.sprite_budget.record_draw();
+        Ok(())
+    }
+
+    fn skips_instruction_if_vx_key_is_pressed(&mut self, vx_index: usize) {
+        // Only the low nibble names a key, like everywhere else the keypad is read
+        let vx_value = self.v_registers[vx_index] & 0xF;
+        if self.keyboard[vx_value as usize] == 1 {
+            self.skip_next_instruction();
+        }
+    }
+
+    fn skips_instruction_if_vx_key_is_not_pressed(&mut self, vx_index: usize) {
+        let vx_value = self.v_registers[vx_index] & 0xF;
+        if self.keyboard[vx_value as usize] == 0 {
+            self.skip_next_instruction();
+        }
+    }
+
+    fn sets_vx_to_delay_timer(&mut self, vx_index: usize) {
+        self.v_registers[vx_index] = self.delay_timer
+    }
+
+    /// Returns whether a key was pressed and released, otherwise FX0A runs again on the next cycle
+    fn sets_vx_to_key_release(&mut self, vx_index: usize) -> bool {
+        match self.key_wait {
+            None | Some(KeyWait::Press) => {
+                self.key_wait = match self.keyboard.iter().position(|key| *key == 1) {
+                    Some(key) => Some(KeyWait::Release(key as u8)),
+                    None => Some(KeyWait::Press),
+                };
+                false
+            }
+            Some(KeyWait::Release(key)) if self.keyboard[key as usize] == 1 => false,
+            Some(KeyWait::Release(key)) => {
+                self.v_registers[vx_index] = key;
+                self.key_wait = None;
+                true
+            }
+        }
+    }
+
+    fn sets_delay_timer_to_vx(&mut self, vx_index: usize) {
+        self.delay_timer = self.v_registers[vx_index];
+    }
+
+    fn sets_sound_timer_to_vx(&mut self, vx_index: usize) -> Result<(), Chip8Error> {
+        self.sound_timer = self.v_registers[vx_index];
+        let was_playing = self.sound_playing;
+        self.sound_playing = self.sound_timer > 0;
+        if self.sound_playing != was_playing {
+            self.record_sound(self.sound_playing);
+        }
+        if self.sound_from_replay() {
+            return Ok(());
+        }
+
+        let audio_result = if self.sound_playing {
+            self.audio_device
+                .play(TIMER_PERIOD * self.sound_timer.into())
+        } else if was_playing {
+            self.audio_device.stop()
+        } else {
+            Ok(())
+        };
+        self.handle_device_result(audio_result)
+    }
+
+    // Keeps the sound turning on or off in the replay being recorded
+    #[cfg(feature = "replay")]
+    fn record_sound(&mut self, on: bool) {
+        if let Some(replay) = self.replay.as_mut() {
+            replay.record_sound(self.frame_cycles, on);
+        }
+    }
+
+    #[cfg(not(feature = "replay"))]
+    fn record_sound(&mut self, _on: bool) {}
+
+    // Whether the replay playing starts and stops the audio device instead of the sound timer
+    #[cfg(feature = "replay")]
+    fn sound_from_replay(&self) -> bool {
+        self.replay.as_ref().is_some_and(ReplayMode::plays_sounds)
+    }
+
+    #[cfg(not(feature = "replay"))]
+    fn sound_from_replay(&self) -> bool {
+        false
+    }
+
+    // Starts and stops the audio device where the replay playing recorded it, up to `cycle`
+    // instructions into the frame
+    #[cfg(feature = "replay")]
+    fn play_replay_sounds(&mut self, cycle: usize) -> Result<(), Chip8Error> {
+        let sounds = match self.replay.as_mut() {
+            Some(replay) if replay.plays_sounds() => replay.take_sounds(cycle),
+            _ => return Ok(()),
+        };
+        for on in sounds {
+            let audio_result = if on {
+                self.audio_device
+                    .play(TIMER_PERIOD * self.sound_timer.max(1).into())
+            } else {
+                self.audio_device.stop()
+            };
+            self.handle_device_result(audio_result)?;
+        }
+        Ok(())
+    }
+
+    // FX1E never reads memory, I wraps around the 12 bit addresses, or the 16 bit ones of XO-CHIP,
+    // and the instructions using it check it instead
+    fn adds_vx_to_i(&mut self, vx_index: usize) {
+        let address = self
+            .index_register
+            .wrapping_add(self.v_registers[vx_index].into());
+        self.index_register = if self.variant.has_xochip_instructions() {
+            address
+        } else {
+            address & 0xFFF
+        };
+    }
+
+    fn sets_i_to_the_address_that_follows(&mut self) -> Result<(), Chip8Error> {
+        self.index_register = self.read_opcode(self.program_counter.wrapping_add(2))?;
+        self.program_counter = self.program_counter.wrapping_add(2);
+        Ok(())
+    }
+
+    fn selects_planes(&mut self, planes: u8) {
+        self.selected_planes = planes & 0b11;
+    }
+
+    fn loads_audio_pattern_from_i(&mut self) -> Result<(), Chip8Error> {
+        let source = self.memory_range(self.index_register, 16)?;
+        self.bus
+            .read(self.index_register, &mut self.memory[source.clone()]);
+
+        let mut pattern = [0; 16];
+        pattern.copy_from_slice(&self.memory[source]);
+        self.audio_pattern = Some(pattern);
+        let pattern_result = self
+            .audio_device
+            .set_pattern(self.audio_pattern, self.pitch);
+        self.handle_device_result(pattern_result)
+    }
+
+    fn sets_pitch_to_vx(&mut self, vx_index: usize) -> Result<(), Chip8Error> {
+        self.pitch = self.v_registers[vx_index];
+        let pattern_result = self
+            .audio_device
+            .set_pattern(self.audio_pattern, self.pitch);
+        self.handle_device_result(pattern_result)
+    }
+
+    fn sets_i_to_font_digit_in_vx(&mut self, vx_index: usize) {
+        let digit = (self.v_registers[vx_index] & 0xF) as usize;
+        self.index_register = (FONT_ADDRESS + digit * 5) as u16;
+    }
+
+    fn sets_i_to_big_font_digit_in_vx(&mut self, vx_index: usize) {
+        let digit = self.v_registers[vx_index] as usize % 10;
+        self.index_register = (BIG_FONT_ADDRESS + digit * 10) as u16;
+    }
+
+    fn store_bcd_of_vx_from_i(&mut self, vx_index: usize) -> Result<(), Chip8Error> {
+        let vx_value = self.v_registers[vx_index];
+        let digits = self.memory_range(self.index_register, 3)?;
+
+        self.memory[digits.clone()].copy_from_slice(&[
+            vx_value / 100,
+            (vx_value / 10) % 10,
+            vx_value % 10,
+        ]);
+        self.bus.write(self.index_register, &self.memory[digits]);
+        Ok(())
+    }
+
+    fn stores_v0_to_vx_in_memory_from_i(&mut self, vx_index: usize) -> Result<(), Chip8Error> {
+        let destination = self.memory_range(self.index_register, vx_index + 1)?;
+
+        self.memory[destination.clone()].copy_from_slice(&self.v_registers[0..=vx_index]);
+        self.bus
+            .write(self.index_register, &self.memory[destination]);
+
+        if self.quirks.load_store_increments_i {
+            self.index_register = self.index_register.wrapping_add(vx_index as u16 + 1);
+        }
+        Ok(())
+    }
+
+    fn writes_v0_to_vx_from_memory_i(&mut self, vx_index: usize) -> Result<(), Chip8Error> {
+        let source = self.memory_range(self.index_register, vx_index + 1)?;
+
+        self.bus
+            .read(self.index_register, &mut self.memory[source.clone()]);
+        self.v_registers[0..=vx_index].copy_from_slice(&self.memory[source]);
+
+        if self.quirks.load_store_increments_i {
+            self.index_register = self.index_register.wrapping_add(vx_index as u16 + 1);
+        }
+        Ok(())
+    }
+
+    fn stores_vx_to_vy_in_memory_from_i(
+        &mut self,
+        vx_index: usize,
+        vy_index: usize,
+    ) -> Result<(), Chip8Error> {
+        let registers = register_range(vx_index, vy_index);
+        let destination = self.memory_range(self.index_register, registers.len())?;
+
+        for (address, register) in destination.clone().zip(registers) {
+            self.memory[address] = self.v_registers[register];
+        }
+        self.bus
+            .write(self.index_register, &self.memory[destination]);
+        Ok(())
+    }
+
+    fn writes_vx_to_vy_from_memory_i(
+        &mut self,
+        vx_index: usize,
+        vy_index: usize,
+    ) -> Result<(), Chip8Error> {
+        let registers = register_range(vx_index, vy_index);
+        let source = self.memory_range(self.index_register, registers.len())?;
+
+        self.bus
+            .read(self.index_register, &mut self.memory[source.clone()]);
+        for (address, register) in source.zip(registers) {
+            self.v_registers[register] = self.memory[address];
+        }
+        Ok(())
+    }
+
+    fn stores_v0_to_vx_in_rpl_flags(&mut self, vx_index: usize) -> Result<(), Chip8Error> {
+        let last_flag = vx_index.min(self.rpl_flags.len() - 1);
+        self.rpl_flags[..=last_flag].copy_from_slice(&self.v_registers[..=last_flag]);
+
+        if let Some(storage_device) = &mut self.storage_device {
+            let result = storage_device.save_flags(&self.rpl_flags);
+            self.handle_device_result(result)?;
+        }
+
+        Ok(())
+    }
+
+    fn writes_v0_to_vx_from_rpl_flags(&mut self, vx_index: usize) {
+        let last_flag = vx_index.min(self.rpl_flags.len() - 1);
+        self.v_registers[..=last_flag].copy_from_slice(&self.rpl_flags[..=last_flag]);
+    }
+
+    fn sets_vx_to_vy(&mut self, vx_index: usize, vy_index: usize) {
+        self.v_registers[vx_index] = self.v_registers[vy_index]
+    }
+
+    fn sets_vx_to_vx_bitwise_or_vy(&mut self, vx_index: usize, vy_index: usize) {
+        self.v_registers[vx_index] |= self.v_registers[vy_index];
+        self.reset_vf_after_logic_operation();
+    }
+
+    fn sets_vx_to_vx_bitwise_and_vy(&mut self, vx_index: usize, vy_index: usize) {
+        self.v_registers[vx_index] &= self.v_registers[vy_index];
+        self.reset_vf_after_logic_operation();
+    }
+
+    fn sets_vx_to_vx_bitwise_xor_vy(&mut self, vx_index: usize, vy_index: usize) {
+        self.v_registers[vx_index] ^= self.v_registers[vy_index];
+        self.reset_vf_after_logic_operation();
+    }
+
+    fn reset_vf_after_logic_operation(&mut self) {
+        if self.quirks.logic_resets_vf {
+            self.v_registers[0xF] = 0;
+        }
+    }
+
+    fn adds_vy_to_vx_setting_vf_on_borrow(&mut self, vx_index: usize, vy_index: usize) {
+        let vy = self.v_registers[vy_index];
+        let vx = self.v_registers[vx_index];
+
+        let (result, carried) = vx.overflowing_add(vy);
+
+        // VF is written last so the flag wins when VX is VF
+        self.v_registers[vx_index] = result;
+        self.v_registers[0xF] = carried as u8;
+    }
+
+    fn subtracts_vy_from_vx_setting_vf_on_borrow(&mut self, vx_index: usize, vy_index: usize) {
+        let vy = self.v_registers[vy_index];
+        let vx = self.v_registers[vx_index];
+
+        let (result, borrowed) = vx.overflowing_sub(vy);
+
+        self.v_registers[vx_index] = result;
+        self.v_registers[0xF] = !borrowed as u8;
+    }
+
+    fn store_lsb_of_vx_in_vf_shifting_vx_by_1(&mut self, vx_index: usize, vy_index: usize) {
+        let source_index = if self.quirks.shift_uses_vy {
+            vy_index
+        } else {
+            vx_index
+        };
+        let value = self.v_registers[source_index];
+        self.v_registers[vx_index] = value >> 1;
+        self.v_registers[0xF] = value & 1;
+    }
+
+    fn set_vx_to_vy_minus_vx_setting_vf_on_borrow(&mut self, vx_index: usize, vy_index: usize) {
+        let vy = self.v_registers[vy_index];
+        let vx = self.v_registers[vx_index];
+
+        let (result, borrowed) = vy.overflowing_sub(vx);
+
+        self.v_registers[vx_index] = result;
+        self.v_registers[0xF] = !borrowed as u8;
+    }
+
+    fn store_msb_of_vx_in_vf_shifting_vx_by_1(&mut self, vx_index: usize, vy_index: usize) {
+        let source_index = if self.quirks.shift_uses_vy {
+            vy_index
+        } else {
+            vx_index
+        };
+        let value = self.v_registers[source_index];
+        self.v_registers[vx_index] = value << 1;
+        self.v_registers[15usize] = value >> 7;
+    }
+
+    fn load_font_set(&mut self) {
+        self.memory[FONT_ADDRESS..FONT_ADDRESS + FONT_SET.len()].copy_from_slice(&self.font.small);
+        self.memory[BIG_FONT_ADDRESS..BIG_FONT_ADDRESS + BIG_FONT_SET.len()]
+            .copy_from_slice(&self.font.big);
+    }
+
+    fn error_context(&self) -> ErrorContext {
+        ErrorContext {
+            program_counter: self.program_counter,
+            opcode: self.opcode,
+        }
+    }
+
+    fn fetch_opcode(&mut self) -> Result<(), Chip8Error> {
+        self.opcode = self.read_opcode(self.program_counter)?;
+        Ok(())
+    }
+
+    fn read_opcode(&self, address: u16) -> Result<u16, Chip8Error> {
+        let opcode = self.memory_range(address, 2)?;
+        Ok(u16::from_be_bytes([
+            self.memory[opcode.start],
+            self.memory[opcode.start + 1],
+        ]))
+    }
+
+    /// Range of `length` bytes starting at `address`, as long as all of them are in memory
+    fn memory_range(&self, address: u16, length: usize) -> Result<Range<usize>, Chip8Error> {
+        let start = address as usize;
+        let end = start + length;
+        if end > self.memory.len() {
+            #[cfg(feature = "tracing")]
+            tracing::error!(
+                program_counter = self.program_counter,
+                address = start,
+                length,
+                "memory access out of bounds"
+            );
+            return Err(Chip8Error::MemoryOutOfBounds {
+                address: start.max(self.memory.len()),
+                context: None,
+            });
+        }
+        Ok(start..end)
+    }
+
+    fn handle_device_result(&mut self, result: Result<(), Chip8Error>) -> Result<(), Chip8Error> {
+        #[cfg(feature = "tracing")]
+        if let Err(error) = &result {
+            tracing::warn!(%error, policy = ?self.device_error_policy, "device error");
+        }
+        match (result, self.device_error_policy) {
+            (Err(error), DeviceErrorPolicy::Continue) => {
+                if self.device_errors.len() == Self::MAX_DEVICE_ERRORS {
+                    self.device_errors.remove(0);
+                }
+                self.device_errors.push(error);
+                Ok(())
+            }
+            (result, _) => result,
+        }
     }
 
     fn update_timers(&mut self) -> Result<(), Chip8Error> {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            delay_timer = self.delay_timer,
+            sound_timer = self.sound_timer,
+            "ticking timers"
+        );
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
 
-        if self.sound_timer > 0 {
-            if self.sound_timer == 1 {
-                self.audio_device.play()?;
-            }
-            self.sound_timer -= 1;
-        }
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
+        }
+        if self.sound_timer == 0 && self.sound_playing {
+            self.sound_playing = false;
+            self.record_sound(false);
+            if !self.sound_from_replay() {
+                self.audio_device.stop()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Planes picked by the bit mask of FN01, only the first one outside of XO-CHIP
+fn selected_planes(planes: &mut [FrameBuffer], mask: u8) -> impl Iterator<Item = &mut FrameBuffer> {
+    planes
+        .iter_mut()
+        .enumerate()
+        .filter(move |(plane, _)| mask & (1 << plane) != 0)
+        .map(|(_, plane)| plane)
+}
+
+// Indexes of the registers from VX to VY, going backwards when VY comes before VX
+fn register_range(vx_index: usize, vy_index: usize) -> Vec<usize> {
+    if vx_index <= vy_index {
+        (vx_index..=vy_index).collect()
+    } else {
+        (vy_index..=vx_index).rev().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        cell::{Cell, RefCell},
+        rc::Rc,
+    };
+
+    pub fn set_initial_opcode_to(opcode: u16, memory: &mut [u8]) {
+        memory[0x200] = ((opcode & 0xFF00) >> 8) as u8;
+        memory[0x201] = (opcode & 0x00FF) as u8;
+    }
+
+    struct MockAudio;
+    impl Audio for MockAudio {
+        fn play(&self, _duration: Duration) -> Result<(), Chip8Error> {
+            Ok(())
+        }
+
+        fn stop(&self) -> Result<(), Chip8Error> {
+            Ok(())
+        }
+    }
+
+    struct MockNumberGenerator;
+    impl NumberGenerator for MockNumberGenerator {
+        fn generate(&self) -> Result<u8, Chip8Error> {
+            Ok(1)
+        }
+    }
+
+    struct MockKeyboardDevice;
+    impl Keyboard for MockKeyboardDevice {
+        fn poll_events(&mut self) -> Vec<KeyEvent> {
+            vec![KeyEvent::Quit]
+        }
+    }
+
+    struct MockGraphicsDevice;
+    impl Graphics for MockGraphicsDevice {
+        fn draw(&mut self, _frame: &Frame) -> Result<(), Chip8Error> {
+            Ok(())
+        }
+    }
+
+    struct CountingGraphicsDevice {
+        draws: Rc<Cell<usize>>,
+    }
+    impl Graphics for CountingGraphicsDevice {
+        fn draw(&mut self, _frame: &Frame) -> Result<(), Chip8Error> {
+            self.draws.set(self.draws.get() + 1);
+            Ok(())
+        }
+    }
+
+    struct IdleKeyboardDevice;
+    impl Keyboard for IdleKeyboardDevice {
+        fn poll_events(&mut self) -> Vec<KeyEvent> {
+            panic!("poll_events shouldn't be called without pending events")
+        }
+
+        fn has_pending_events(&mut self) -> bool {
+            false
+        }
+    }
+
+    struct ScriptedKeyboardDevice {
+        events: Vec<KeyEvent>,
+    }
+    impl Keyboard for ScriptedKeyboardDevice {
+        fn poll_events(&mut self) -> Vec<KeyEvent> {
+            std::mem::take(&mut self.events)
+        }
+    }
+
+    struct FailingGraphicsDevice;
+    impl Graphics for FailingGraphicsDevice {
+        fn draw(&mut self, _frame: &Frame) -> Result<(), Chip8Error> {
+            Err(Chip8Error::GraphicsError("window minimized".into()))
+        }
+    }
+
+    fn get_chip8_instance() -> Chip8 {
+        get_chip8_instance_with_quirks(Quirks::default())
+    }
+
+    fn get_superchip_instance() -> Chip8 {
+        Chip8::with_variant(
+            Box::new(MockNumberGenerator),
+            Box::new(MockAudio),
+            Box::new(MockKeyboardDevice),
+            Box::new(MockGraphicsDevice),
+            Chip8Variant::SuperChip,
+        )
+    }
+
+    fn get_xochip_instance() -> Chip8 {
+        Chip8::with_variant(
+            Box::new(MockNumberGenerator),
+            Box::new(MockAudio),
+            Box::new(MockKeyboardDevice),
+            Box::new(MockGraphicsDevice),
+            Chip8Variant::XoChip,
+        )
+    }
+
+    fn get_chip8_instance_with_quirks(quirks: Quirks) -> Chip8 {
+        Chip8::with_quirks(
+            Box::new(MockNumberGenerator),
+            Box::new(MockAudio),
+            Box::new(MockKeyboardDevice),
+            Box::new(MockGraphicsDevice),
+            quirks,
+        )
+    }
+
+    #[test]
+    fn it_sets_the_correct_default_values() {
+        let chip8 = get_chip8_instance();
+
+        assert_eq!(chip8.opcode, 0);
+        assert_eq!(chip8.program_counter, 0x200);
+        assert_eq!(chip8.index_register, 0);
+        assert_eq!(chip8.stack_pointer, 0);
+        assert_eq!(chip8.framebuffer().pixels(), &[0; 2048][..]);
+        assert_eq!(chip8.v_registers, [0; 16]);
+        assert_eq!(chip8.stack, [0; 16]);
+        assert_eq!(chip8.delay_timer, 0);
+        assert_eq!(chip8.sound_timer, 0);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn it_keeps_the_v1_constructor_working() -> Result<(), Chip8Error> {
+        let chips = vec![
+            Chip8::new(
+                Box::new(MockNumberGenerator),
+                Box::new(MockAudio),
+                Box::new(MockKeyboardDevice),
+                Box::new(MockGraphicsDevice),
+            ),
+            compat::v1::new(
+                Box::new(MockNumberGenerator),
+                Box::new(MockAudio),
+                Box::new(MockKeyboardDevice),
+                Box::new(MockGraphicsDevice),
+            ),
+        ];
+
+        for mut chip8 in chips {
+            set_initial_opcode_to(0x6A42, &mut chip8.memory);
+
+            chip8.emulate_cycle()?;
+
+            assert_eq!(chip8.quirks, Quirks::default());
+            assert_eq!(chip8.v_registers[0xA], 0x42);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_loads_the_font_set_on_initialization() {
+        let chip8 = get_chip8_instance();
+
+        assert_eq!(&chip8.memory[0..80], FONT_SET);
+    }
+
+    #[test]
+    fn it_keeps_a_custom_font_across_resets() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        chip8.set_font(Font::vip());
+        chip8.load_program(vec![0x60, 0x0B, 0xF0, 0x29])?;
+        chip8.run_cycles(2)?;
+
+        let address = chip8.index_register as usize;
+        assert_eq!(
+            chip8.memory[address..address + 5],
+            Font::vip().small[55..60]
+        );
+
+        chip8.reset(true)?;
+        assert_eq!(&chip8.memory[0..80], Font::vip().small);
+        assert_eq!(&chip8.memory[80..180], BIG_FONT_SET);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_ticks_the_timers_once_per_frame() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        for address in (0x200..0x214).step_by(2) {
+            chip8.memory[address] = 0x70;
+            chip8.memory[address + 1] = 0x01;
+        }
+        chip8.delay_timer = 5;
+
+        chip8.run_frame(10)?;
+
+        assert_eq!(chip8.v_registers[0], 10);
+        assert_eq!(chip8.program_counter, 0x214);
+        assert_eq!(chip8.delay_timer, 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_ends_the_frame_after_drawing_with_the_display_wait_quirk() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance_with_quirks(Quirks {
+            display_wait: true,
+            ..Quirks::default()
+        });
+        chip8.index_register = 0x300;
+        for address in (0x200..0x206).step_by(2) {
+            chip8.memory[address] = 0xD0;
+            chip8.memory[address + 1] = 0x01;
+        }
+
+        chip8.run_frame(10)?;
+        assert_eq!(chip8.program_counter, 0x202);
+
+        chip8.run_frame(10)?;
+        assert_eq!(chip8.program_counter, 0x204);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_defers_sprites_over_the_limit_to_the_next_frame() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        chip8.set_sprite_limit(Some(2));
+        chip8.index_register = 0x300;
+        // Three sprites with a register set after each, then a jump to itself
+        chip8.load_program(vec![
+            0xD0, 0x01, 0x60, 0x01, 0xD0, 0x01, 0x61, 0x02, 0xD0, 0x01, 0x62, 0x03, 0x12, 0x0C,
+        ])?;
+
+        chip8.run_frame(10)?;
+        assert_eq!(chip8.program_counter, 0x208);
+        assert_eq!(chip8.v_registers[1], 0x02);
+        assert_eq!(chip8.sprite_budget().drawn(), 2);
+        assert_eq!(chip8.sprite_budget().deferred_frames(), 1);
+
+        chip8.run_frame(10)?;
+        assert_eq!(chip8.program_counter, 0x20C);
+        assert_eq!(chip8.v_registers[2], 0x03);
+        assert_eq!(chip8.sprite_budget().drawn(), 3);
+        assert_eq!(chip8.sprite_budget().deferred_frames(), 1);
+
+        chip8.set_sprite_limit(None);
+        assert_eq!(chip8.sprite_budget(), &SpriteBudget::default());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_is_idle_while_waiting_for_a_key_in_silence() -> Result<(), Chip8Error> {
+        let mut chip8 = Chip8::builder().build();
+        chip8.sound_timer = 2;
+        chip8.load_program(vec![0xF0, 0x0A])?;
+        assert!(!chip8.is_idle());
+
+        assert_eq!(chip8.run_frame(10)?, State::WaitingForKey);
+        assert!(!chip8.is_idle());
+        chip8.sound_timer = 0;
+        assert!(chip8.is_idle());
+        assert!(!chip8.wait_for_input(Duration::from_millis(100)));
+
+        chip8.pause()?;
+        assert!(!chip8.is_idle());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "jit")]
+    fn run_with_and_without_jit(program: &[u8], frames: usize) -> Result<[Chip8; 2], Chip8Error> {
+        let run = |jit: bool| -> Result<Chip8, Chip8Error> {
+            let mut chip8 = Chip8::with_quirks(
+                Box::new(MockNumberGenerator),
+                Box::new(MockAudio),
+                Box::new(IdleKeyboardDevice),
+                Box::new(MockGraphicsDevice),
+                Quirks::default(),
+            );
+            chip8.set_jit(jit)?;
+            chip8.load_program(program.to_vec())?;
+            for _ in 0..frames {
+                chip8.run_frame(100)?;
+            }
+            Ok(chip8)
+        };
+        Ok([run(true)?, run(false)?])
+    }
+
+    #[cfg(feature = "jit")]
+    #[test]
+    fn it_runs_compiled_blocks_like_the_interpreter() -> Result<(), Chip8Error> {
+        let [compiled, interpreted] = run_with_and_without_jit(
+            &[
+                0x70, 0x03, // loop: v0 += 3
+                0x81, 0x04, 0x82, 0x16, 0x83, 0x27, 0x84, 0x15, // v1..v4 from v0 and v1
+                0x85, 0xE3, 0xA1, 0x23, // vf-touching xor, i := 0x123
+                0x30, 0x00, 0x12, 0x00, // loop until v0 wraps around to 0
+                0x12, 0x12, // jump to self
+            ],
+            30,
+        )?;
+
+        assert!(compiled.jit.as_ref().map_or(0, Jit::compiled_blocks) > 0);
+        assert_eq!(compiled.v_registers, interpreted.v_registers);
+        assert_eq!(compiled.index_register, interpreted.index_register);
+        assert_eq!(compiled.program_counter, interpreted.program_counter);
+        assert_eq!(compiled.program_end, Some(ProgramEnd::Finished));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "jit")]
+    #[test]
+    fn it_compiles_the_flags_of_additions_and_subtractions() -> Result<(), Chip8Error> {
+        let [compiled, interpreted] = run_with_and_without_jit(
+            &[
+                0x60, 0xC8, 0x61, 0x64, 0x80, 0x14, 0x8A,
+                0xF0, // v0 += v1 with a carry, va := vf
+                0x62, 0x10, 0x63, 0x20, 0x6F, 0x05, 0x82, 0x34, 0x8B, 0xF0, // without a carry
+                0x64, 0x10, 0x65, 0x20, 0x84, 0x55, 0x8C, 0xF0, // v4 -= v5 with a borrow
+                0x66, 0x20, 0x67, 0x10, 0x86, 0x77, 0x8D, 0xF0, // v6 := v7 - v6 with a borrow
+                0x68, 0x10, 0x88, 0x37, 0x8E, 0xF0, // v8 := v3 - v8 without a borrow
+                0x79, 0x01, 0x39, 0x20, 0x12, 0x00, // run it 32 times to get it compiled
+                0x12, 0x2E, // jump to self
+            ],
+            10,
+        )?;
+
+        let expected = [
+            0x2C, 0x64, 0x30, 0x20, 0xF0, 0x20, 0xF0, 0x10, 0x10, 0x20, 1, 0, 0, 0, 1, 1,
+        ];
+        assert!(compiled.jit.as_ref().map_or(0, Jit::compiled_blocks) > 0);
+        assert_eq!(compiled.v_registers, expected);
+        assert_eq!(interpreted.v_registers, expected);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "jit")]
+    #[test]
+    fn it_notices_programs_rewriting_compiled_code() -> Result<(), Chip8Error> {
+        let [compiled, interpreted] = run_with_and_without_jit(
+            &[
+                0x70, 0x01, 0x30, 0x10, 0x12, 0x00, // count v0 up to 0x10
+                0x60, 0x70, 0x61, 0x02, 0xA2, 0x00, 0xF1,
+                0x55, // turn the first 7001 into 7002
+                0x60, 0x00, 0x12, 0x00, // and count again
+            ],
+            5,
+        )?;
+
+        assert_eq!(compiled.memory[0x200..0x202], [0x70, 0x02]);
+        assert_eq!(compiled.v_registers, interpreted.v_registers);
+        assert_eq!(compiled.program_counter, interpreted.program_counter);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_stops_running_the_frame_when_the_program_ends() -> Result<(), Chip8Error> {
+        let mut chip8 = Chip8::with_quirks(
+            Box::new(MockNumberGenerator),
+            Box::new(MockAudio),
+            Box::new(IdleKeyboardDevice),
+            Box::new(MockGraphicsDevice),
+            Quirks::default(),
+        );
+        set_initial_opcode_to(0x00EE, &mut chip8.memory);
+
+        assert_eq!(chip8.run_frame(10)?, State::Finished);
+        assert_eq!(chip8.program_counter, 0x200);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_loads_the_program_to_memory() -> Result<(), Chip8Error> {
+        let fake_data = vec![1, 2, 3];
+        let fake_data_len = fake_data.len();
+        let mut chip8 = get_chip8_instance();
+
+        chip8.load_program(fake_data)?;
+
+        assert_eq!(&chip8.memory[0x200..0x200 + fake_data_len], vec![1, 2, 3]);
+        Ok(())
+    }
+
+    #[test]
+    fn it_errors_when_the_program_does_not_fit_in_memory() {
+        let mut chip8 = get_chip8_instance();
+
+        let result = chip8.load_program(vec![0; 4096 - 0x200 + 1]);
+
+        assert!(matches!(
+            result,
+            Err(Chip8Error::ProgramTooLarge {
+                size: 3585,
+                available: 3584
+            })
+        ));
+    }
+
+    #[test]
+    fn it_loads_the_program_with_a_custom_memory_layout() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        chip8.set_memory_layout(MemoryLayout {
+            size: 65536,
+            load_address: 0x600,
+        })?;
+
+        chip8.load_program(vec![0; 65536 - 0x600])?;
+        chip8.memory[0x600] = 0x1F;
+        chip8.memory[0x601] = 0xFE;
+
+        assert_eq!(chip8.program_counter, 0x600);
+        assert_eq!(&chip8.memory[0..80], FONT_SET);
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.program_counter, 0xFFE);
+        Ok(())
+    }
+
+    #[test]
+    fn it_rejects_memory_layouts_the_fonts_or_the_program_dont_fit_in() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        assert!(matches!(
+            chip8.set_memory_layout(MemoryLayout {
+                size: 0x100,
+                load_address: 0x200,
+            }),
+            Err(Chip8Error::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            chip8.set_memory_layout(MemoryLayout {
+                size: 64,
+                load_address: 0x20,
+            }),
+            Err(Chip8Error::InvalidConfig(_))
+        ));
+        assert_eq!(chip8.memory.len(), 4096);
+
+        chip8.load_program(vec![0x12, 0x00])?;
+        assert_eq!(chip8.memory[0x200], 0x12);
+        Ok(())
+    }
+
+    #[test]
+    fn it_resets_and_reloads_the_last_program() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        chip8.load_program(vec![0x6A, 0x05, 0x22, 0x00])?;
+        chip8.emulate_cycle()?;
+        chip8.emulate_cycle()?;
+        chip8.planes[0].set_pixel(0, 0, true);
+        chip8.delay_timer = 10;
+
+        chip8.reset(true)?;
+
+        assert_eq!(chip8.program_counter, 0x200);
+        assert_eq!(chip8.v_registers, [0; 16]);
+        assert_eq!(chip8.stack_pointer, 0);
+        assert_eq!(chip8.delay_timer, 0);
+        assert_eq!(chip8.framebuffer().pixels(), &[0; 2048][..]);
+        assert_eq!(&chip8.memory[0..80], FONT_SET);
+        assert_eq!(chip8.memory[0x200..0x204], [0x6A, 0x05, 0x22, 0x00]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_resets_without_reloading_the_program() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        chip8.load_program(vec![0x6A, 0x05])?;
+
+        chip8.reset(false)?;
+
+        assert_eq!(chip8.memory[0x200..0x202], [0, 0]);
+        assert_eq!(&chip8.memory[0..80], FONT_SET);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_switches_to_the_two_page_display_for_roms_starting_with_a_jump_to_0x260(
+    ) -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+
+        chip8.load_program(vec![0x12, 0x60, 0xAA])?;
+
+        assert_eq!(chip8.resolution(), (64, 64));
+        assert_eq!(chip8.memory[0x200..0x203], [0x12, 0xC0, 0xAA]);
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.program_counter, 0x2C0);
+        Ok(())
+    }
+
+    #[test]
+    fn it_draws_and_clears_the_whole_two_page_display() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        chip8.load_program(vec![0x12, 0x60])?;
+        chip8.v_registers[1] = 63;
+        chip8.index_register = 0x300;
+        chip8.memory[0x300] = 0x80;
+        chip8.memory[0x2C0..0x2C4].copy_from_slice(&[0xD0, 0x11, 0x02, 0x30]);
+
+        chip8.emulate_cycle()?;
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.framebuffer().pixels()[63 * 64], 1);
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.framebuffer().pixels(), &[0; 4096][..]);
+        Ok(())
+    }
+
+    #[test]
+    fn it_fetches_correct_opcode_when_emulating_the_first_cycle() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        chip8.memory[0x200] = 0x10;
+        chip8.memory[0x201] = 0x20;
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.opcode, 4128);
+        Ok(())
+    }
+
+    #[test]
+    fn it_tracks_held_keys_from_key_events() -> Result<(), Chip8Error> {
+        let mut chip8 = Chip8::with_quirks(
+            Box::new(MockNumberGenerator),
+            Box::new(MockAudio),
+            Box::new(ScriptedKeyboardDevice {
+                events: vec![KeyEvent::Down(0xA), KeyEvent::Down(0x3), KeyEvent::Up(0xA)],
+            }),
+            Box::new(MockGraphicsDevice),
+            Quirks::default(),
+        );
+        set_initial_opcode_to(0x00E0, &mut chip8.memory);
+
+        assert_eq!(chip8.emulate_cycle()?, State::Continue);
+
+        assert_eq!(chip8.keyboard[0xA], 0);
+        assert_eq!(chip8.keyboard[0x3], 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_skips_the_keyboard_update_when_there_are_no_pending_events() -> Result<(), Chip8Error> {
+        let mut chip8 = Chip8::with_quirks(
+            Box::new(MockNumberGenerator),
+            Box::new(MockAudio),
+            Box::new(IdleKeyboardDevice),
+            Box::new(MockGraphicsDevice),
+            Quirks::default(),
+        );
+        set_initial_opcode_to(0x00E0, &mut chip8.memory);
+
+        assert_eq!(chip8.emulate_cycle()?, State::Continue);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_runs_one_stage_per_micro_step() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        set_initial_opcode_to(0x6A42, &mut chip8.memory);
+
+        assert_eq!(
+            chip8.micro_step()?,
+            MicroStep::Fetch {
+                address: 0x200,
+                opcode: 0x6A42
+            }
+        );
+        assert_eq!(chip8.v_registers[0xA], 0);
+
+        match chip8.micro_step()? {
+            MicroStep::Decode { operands, .. } => {
+                assert_eq!(operands.vx_index, 0xA);
+                assert_eq!(operands.nn_address, 0x42);
+            }
+            other => panic!("Expected decode stage, got {:?}", other),
+        }
+        assert_eq!(chip8.v_registers[0xA], 0);
+
+        assert_eq!(
+            chip8.micro_step()?,
+            MicroStep::Execute {
+                opcode: 0x6A42,
+                program_counter: 0x202,
+                state: State::Exit
+            }
+        );
+        assert_eq!(chip8.v_registers[0xA], 0x42);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_finishes_the_current_cycle_when_emulating_after_a_micro_step() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        set_initial_opcode_to(0x6A42, &mut chip8.memory);
+
+        chip8.micro_step()?;
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.v_registers[0xA], 0x42);
+        assert_eq!(chip8.program_counter, 0x202);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_correctly_counts_down_the_timers() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        set_initial_opcode_to(0x00E0, &mut chip8.memory);
+
+        chip8.delay_timer = 1;
+        chip8.sound_timer = 1;
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.delay_timer, 0);
+        assert_eq!(chip8.sound_timer, 0);
+
+        chip8.memory[0x202] = 0x00;
+        chip8.memory[0x203] = 0xE0;
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.delay_timer, 0);
+        assert_eq!(chip8.sound_timer, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_runs_cycles_without_drawing() -> Result<(), Chip8Error> {
+        let mut chip8 = Chip8::with_quirks(
+            Box::new(MockNumberGenerator),
+            Box::new(MockAudio),
+            Box::new(MockKeyboardDevice),
+            Box::new(FailingGraphicsDevice),
+            Quirks::default(),
+        );
+        for address in (0x200..0x206).step_by(2) {
+            chip8.memory[address] = 0x70;
+            chip8.memory[address + 1] = 0x01;
+        }
+        chip8.memory[0x206] = 0x00;
+        chip8.memory[0x207] = 0xEE;
+        chip8.delay_timer = 5;
+
+        assert_eq!(chip8.run_cycles(2)?, State::Continue);
+        assert_eq!(chip8.v_registers[0], 2);
+        assert_eq!(chip8.delay_timer, 3);
+
+        assert_eq!(chip8.run_cycles(10)?, State::Finished);
+        assert_eq!(chip8.v_registers[0], 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_runs_until_the_predicate_holds() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        set_initial_opcode_to(0x7001, &mut chip8.memory);
+        chip8.memory[0x202] = 0x12;
+        chip8.memory[0x203] = 0x00;
+
+        let state = chip8.run_until(|chip8| chip8.v_registers[0] == 5)?;
+
+        assert_eq!(state, State::Continue);
+        assert_eq!(chip8.v_registers[0], 5);
+        assert_eq!(chip8.program_counter, 0x202);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_calls_the_hook_before_every_instruction() -> Result<(), Chip8Error> {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let mut chip8 = get_chip8_instance();
+        set_initial_opcode_to(0x7001, &mut chip8.memory);
+        chip8.memory[0x202] = 0x12;
+        chip8.memory[0x203] = 0x00;
+        let hook_seen = Rc::clone(&seen);
+        chip8.set_hook(move |snapshot, instruction| {
+            hook_seen.borrow_mut().push((
+                snapshot.program_counter,
+                snapshot.v_registers[0],
+                instruction,
+            ));
+        });
+
+        chip8.run_cycles(3)?;
+
+        assert_eq!(
+            *seen.borrow(),
+            vec![
+                (0x200, 0, Instruction::AddToRegister { vx: 0, nn: 1 }),
+                (0x202, 1, Instruction::Jump { nnn: 0x200 }),
+                (0x200, 1, Instruction::AddToRegister { vx: 0, nn: 1 }),
+            ]
+        );
+
+        chip8.clear_hook();
+        chip8.run_cycles(1)?;
+        assert_eq!(seen.borrow().len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_profiles_the_instructions_executed() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        set_initial_opcode_to(0x7001, &mut chip8.memory);
+        chip8.memory[0x202] = 0x12;
+        chip8.memory[0x203] = 0x00;
+        assert!(chip8.profile().is_none());
+
+        chip8.set_profiling(true);
+        chip8.run_cycles(5)?;
+
+        let profile = chip8.profile().expect("profiling is enabled");
+        assert_eq!(profile.instructions(), 5);
+        assert_eq!(profile.executions_of("7XNN"), 3);
+        assert_eq!(profile.executions_of("1NNN"), 2);
+        assert_eq!(profile.hottest_addresses(1), vec![(0x200, 3)]);
+
+        chip8.set_profiling(false);
+        assert!(chip8.profile().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_stops_before_the_instruction_at_a_breakpoint() -> Result<(), Chip8Error> {
+        let mut chip8 = Chip8::with_quirks(
+            Box::new(MockNumberGenerator),
+            Box::new(MockAudio),
+            Box::new(IdleKeyboardDevice),
+            Box::new(MockGraphicsDevice),
+            Quirks::default(),
+        );
+        set_initial_opcode_to(0x7001, &mut chip8.memory);
+        chip8.memory[0x202] = 0x12;
+        chip8.memory[0x203] = 0x00;
+        chip8.add_breakpoint(0x202);
+
+        assert_eq!(chip8.emulate_cycle()?, State::Continue);
+        assert_eq!(chip8.emulate_cycle()?, State::BreakpointHit(0x202));
+        assert_eq!(chip8.program_counter, 0x202);
+
+        assert_eq!(chip8.emulate_cycle()?, State::Continue);
+        assert_eq!(chip8.program_counter, 0x200);
+        assert_eq!(chip8.run_cycles(10)?, State::BreakpointHit(0x202));
+        assert_eq!(chip8.v_registers[0], 2);
+
+        assert!(chip8.remove_breakpoint(0x202));
+        assert_eq!(chip8.run_cycles(10)?, State::Continue);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_ends_the_frame_at_a_breakpoint() -> Result<(), Chip8Error> {
+        let mut chip8 = Chip8::with_quirks(
+            Box::new(MockNumberGenerator),
+            Box::new(MockAudio),
+            Box::new(IdleKeyboardDevice),
+            Box::new(MockGraphicsDevice),
+            Quirks::default(),
+        );
+        set_initial_opcode_to(0x7001, &mut chip8.memory);
+        chip8.memory[0x202] = 0x12;
+        chip8.memory[0x203] = 0x00;
+        chip8.delay_timer = 2;
+        chip8.add_breakpoint(0x200);
+
+        assert_eq!(chip8.run_frame(10)?, State::BreakpointHit(0x200));
+        assert_eq!(chip8.v_registers[0], 0);
+        assert_eq!(chip8.delay_timer, 1);
+
+        assert_eq!(chip8.run_frame(10)?, State::BreakpointHit(0x200));
+        assert_eq!(chip8.v_registers[0], 1);
+        assert_eq!(chip8.breakpoints().collect::<Vec<_>>(), vec![0x200]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_steps_one_instruction_even_on_a_breakpoint() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        set_initial_opcode_to(0x7001, &mut chip8.memory);
+        chip8.memory[0x202] = 0x70;
+        chip8.memory[0x203] = 0x01;
+        chip8.add_breakpoint(0x200);
+        chip8.add_breakpoint(0x202);
+
+        assert_eq!(chip8.step()?, State::BreakpointHit(0x202));
+        assert_eq!(chip8.v_registers[0], 1);
+
+        assert_eq!(chip8.step()?, State::Continue);
+        assert_eq!(chip8.v_registers[0], 2);
+        assert_eq!(chip8.program_counter, 0x204);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_steps_over_subroutine_calls() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        set_initial_opcode_to(0x2300, &mut chip8.memory);
+        chip8.memory[0x202] = 0x71;
+        chip8.memory[0x203] = 0x01;
+        chip8.memory[0x300] = 0x70;
+        chip8.memory[0x301] = 0x05;
+        chip8.memory[0x302] = 0x00;
+        chip8.memory[0x303] = 0xEE;
+
+        assert_eq!(chip8.step_over()?, State::Continue);
+        assert_eq!(chip8.program_counter, 0x202);
+        assert_eq!(chip8.v_registers[0], 5);
+        assert_eq!(chip8.stack_pointer, 0);
+
+        assert_eq!(chip8.step_over()?, State::Continue);
+        assert_eq!(chip8.program_counter, 0x204);
+        assert_eq!(chip8.v_registers[1], 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_runs_to_an_address_unless_a_breakpoint_comes_first() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        for address in (0x200..0x20A).step_by(2) {
+            chip8.memory[address] = 0x70;
+            chip8.memory[address + 1] = 0x01;
+        }
+        chip8.add_breakpoint(0x204);
+
+        assert_eq!(chip8.run_to(0x208)?, State::BreakpointHit(0x204));
+        assert_eq!(chip8.v_registers[0], 2);
+
+        assert_eq!(chip8.run_to(0x208)?, State::Continue);
+        assert_eq!(chip8.program_counter, 0x208);
+        assert_eq!(chip8.v_registers[0], 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_stops_after_an_instruction_changes_a_watched_byte() -> Result<(), Chip8Error> {
+        let mut chip8 = Chip8::with_quirks(
+            Box::new(MockNumberGenerator),
+            Box::new(MockAudio),
+            Box::new(IdleKeyboardDevice),
+            Box::new(MockGraphicsDevice),
+            Quirks::default(),
+        );
+        let program = [
+            0xA3, 0x00, 0x60, 0x05, 0xF0, 0x55, 0xF0, 0x55, 0x70, 0x01, 0xF0, 0x55,
+        ];
+        chip8.memory[0x200..0x20C].copy_from_slice(&program);
+        chip8.add_watchpoint(0x300);
+
+        assert_eq!(chip8.run_cycles(10)?, State::WatchpointHit(0x300));
+        assert_eq!(chip8.program_counter, 0x206);
+        assert_eq!(chip8.run_frame(10)?, State::WatchpointHit(0x300));
+        assert_eq!(chip8.program_counter, 0x20C);
+        assert_eq!(chip8.memory[0x300], 6);
+
+        assert!(chip8.remove_watchpoint(0x300));
+        assert_eq!(chip8.watchpoints().count(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_applies_requests_sent_from_another_thread() -> Result<(), Chip8Error> {
+        let mut chip8 = Chip8::with_quirks(
+            Box::new(MockNumberGenerator),
+            Box::new(MockAudio),
+            Box::new(IdleKeyboardDevice),
+            Box::new(MockGraphicsDevice),
+            Quirks::default(),
+        );
+        chip8.load_program(vec![0x70, 0x01, 0x12, 0x00])?;
+        let handle = chip8.handle();
+
+        std::thread::spawn(move || handle.set_speed(4) && handle.pause())
+            .join()
+            .unwrap();
+        chip8.run_frame(100)?;
+        assert_eq!(chip8.v_registers[0], 0);
+
+        let handle = chip8.handle();
+        handle.resume();
+        chip8.run_frame(100)?;
+        assert_eq!(chip8.v_registers[0], 2);
+
+        handle.load_rom(vec![0x61, 0x05, 0x12, 0x02]);
+        chip8.run_frame(1)?;
+        assert_eq!(chip8.v_registers[0], 0);
+        assert_eq!(chip8.v_registers[1], 5);
+
+        drop(chip8);
+        assert!(!handle.reset());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_runs_the_phases_of_a_cycle_separately() -> Result<(), Chip8Error> {
+        let mut chip8 = Chip8::with_quirks(
+            Box::new(MockNumberGenerator),
+            Box::new(MockAudio),
+            Box::new(IdleKeyboardDevice),
+            Box::new(MockGraphicsDevice),
+            Quirks::default(),
+        );
+        chip8.load_program(vec![0x70, 0x01, 0x70, 0x01, 0xD0, 0x05])?;
+        chip8.delay_timer = 5;
+
+        assert_eq!(chip8.fetch()?, Instruction::AddToRegister { vx: 0, nn: 1 });
+        assert_eq!(chip8.program_counter, 0x200);
+        assert_eq!(chip8.execute()?, State::Continue);
+        assert_eq!(chip8.execute()?, State::Continue);
+        assert_eq!(chip8.execute()?, State::Continue);
+        assert_eq!(chip8.v_registers[0], 2);
+        assert_eq!(chip8.delay_timer, 5);
+        assert!(chip8.display_changed);
+
+        chip8.tick_timers()?;
+        assert_eq!(chip8.present()?, State::Continue);
+        assert_eq!(chip8.delay_timer, 4);
+        assert!(!chip8.display_changed);
+
+        Ok(())
+    }
+
+    struct MockClock {
+        now: Rc<Cell<Duration>>,
+    }
+    impl Clock for MockClock {
+        fn now(&self) -> Duration {
+            self.now.get()
+        }
+    }
+
+    #[test]
+    fn it_ticks_the_timers_following_the_clock() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        chip8.memory[0x200..0x204].copy_from_slice(&[0x70, 0x01, 0x12, 0x00]);
+        let now = Rc::new(Cell::new(Duration::from_secs(10)));
+        chip8.set_clock(Box::new(MockClock { now: now.clone() }));
+        chip8.delay_timer = 200;
+
+        chip8.run_cycles(1000)?;
+        assert_eq!(chip8.delay_timer, 200);
+
+        now.set(Duration::from_secs(10) + TIMER_PERIOD * 3 + TIMER_PERIOD / 2);
+        chip8.run_cycles(1)?;
+        assert_eq!(chip8.delay_timer, 197);
+
+        now.set(Duration::from_secs(11));
+        chip8.run_cycles(1)?;
+        assert_eq!(chip8.delay_timer, 140);
+
+        Ok(())
+    }
+
+    struct RecordingAudio {
+        calls: Rc<RefCell<Vec<Option<Duration>>>>,
+    }
+    impl Audio for RecordingAudio {
+        fn play(&self, duration: Duration) -> Result<(), Chip8Error> {
+            self.calls.borrow_mut().push(Some(duration));
+            Ok(())
+        }
+
+        fn stop(&self) -> Result<(), Chip8Error> {
+            self.calls.borrow_mut().push(None);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn it_plays_sound_until_the_sound_timer_runs_out() -> Result<(), Chip8Error> {
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let mut chip8 = Chip8::with_quirks(
+            Box::new(MockNumberGenerator),
+            Box::new(RecordingAudio {
+                calls: calls.clone(),
+            }),
+            Box::new(IdleKeyboardDevice),
+            Box::new(MockGraphicsDevice),
+            Quirks::default(),
+        );
+        let program = [0x60, 0x02, 0xF0, 0x18, 0x70, 0x01, 0x12, 0x04];
+        chip8.load_program(program.to_vec())?;
+
+        chip8.run_frame(2)?;
+        assert_eq!(*calls.borrow(), vec![Some(TIMER_PERIOD * 2)]);
+        chip8.run_frame(1)?;
+        assert_eq!(*calls.borrow(), vec![Some(TIMER_PERIOD * 2), None]);
+        chip8.run_frame(1)?;
+        assert_eq!(calls.borrow().len(), 2);
+
+        chip8.v_registers[1] = 0;
+        chip8.sound_timer = 5;
+        chip8.sound_playing = true;
+        chip8.memory[0x206..0x208].copy_from_slice(&[0xF1, 0x18]);
+        chip8.program_counter = 0x206;
+        chip8.run_cycles(1)?;
+        assert_eq!(calls.borrow().last(), Some(&None));
+        assert_eq!(calls.borrow().len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_cuts_the_sound_when_paused_through_a_handle() -> Result<(), Chip8Error> {
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let mut chip8 = Chip8::builder()
+            .audio(Box::new(RecordingAudio {
+                calls: calls.clone(),
+            }))
+            .build();
+        chip8.load_program(vec![0x60, 0x0A, 0xF0, 0x18, 0x12, 0x04])?;
+        chip8.run_frame(2)?;
+        assert_eq!(*calls.borrow(), vec![Some(TIMER_PERIOD * 10)]);
+
+        let handle = chip8.handle();
+        handle.pause();
+        chip8.run_frame(2)?;
+        assert!(chip8.is_paused());
+        assert_eq!(calls.borrow().last(), Some(&None));
+
+        handle.resume();
+        chip8.run_frame(2)?;
+        assert_eq!(calls.borrow()[2], Some(TIMER_PERIOD * 9));
+
+        Ok(())
+    }
+
+    type Pattern = (Option<[u8; 16]>, u8);
+
+    struct PatternAudio {
+        patterns: Rc<RefCell<Vec<Pattern>>>,
+    }
+    impl Audio for PatternAudio {
+        fn play(&self, _duration: Duration) -> Result<(), Chip8Error> {
+            Ok(())
+        }
+
+        fn stop(&self) -> Result<(), Chip8Error> {
+            Ok(())
+        }
+
+        fn set_pattern(&self, pattern: Option<[u8; 16]>, pitch: u8) -> Result<(), Chip8Error> {
+            self.patterns.borrow_mut().push((pattern, pitch));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn it_hands_the_audio_pattern_and_pitch_to_the_audio_device() -> Result<(), Chip8Error> {
+        let patterns = Rc::new(RefCell::new(Vec::new()));
+        let mut chip8 = Chip8::builder()
+            .variant(Chip8Variant::XoChip)
+            .audio(Box::new(PatternAudio {
+                patterns: patterns.clone(),
+            }))
+            .build();
+        chip8.memory[0x300..0x310].copy_from_slice(&[0xF0; 16]);
+        // I := 0x300, audio, v0 := 112, pitch := v0
+        let program = [0xA3, 0x00, 0xF0, 0x02, 0x60, 0x70, 0xF0, 0x3A];
+        chip8.load_program(program.to_vec())?;
+
+        chip8.run_cycles(4)?;
+
+        assert_eq!(
+            *patterns.borrow(),
+            vec![(Some([0xF0; 16]), 64), (Some([0xF0; 16]), 112)]
+        );
+
+        chip8.reset(true)?;
+
+        assert_eq!(patterns.borrow().last(), Some(&(None, 64)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_restores_a_snapshot() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        set_initial_opcode_to(0xD005, &mut chip8.memory);
+        let snapshot = chip8.snapshot();
+
+        chip8.emulate_cycle()?;
+        chip8.memory[0x300] = 0xFF;
+        assert_ne!(chip8.snapshot(), snapshot);
+
+        chip8.restore(&snapshot);
+        assert_eq!(chip8.snapshot(), snapshot);
+        assert_eq!(chip8.program_counter, 0x200);
+        assert!(!chip8.framebuffer().get_pixel(0, 0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_rewinds_to_the_snapshots_taken_every_interval() -> Result<(), Chip8Error> {
+        let mut chip8 = Chip8::with_quirks(
+            Box::new(MockNumberGenerator),
+            Box::new(MockAudio),
+            Box::new(IdleKeyboardDevice),
+            Box::new(MockGraphicsDevice),
+            Quirks::default(),
+        );
+        set_initial_opcode_to(0x7001, &mut chip8.memory);
+        chip8.memory[0x202] = 0x12;
+        chip8.memory[0x203] = 0x00;
+        chip8.set_rewind(3, 2);
+        assert_eq!(chip8.rewind(1), 0);
+
+        for _ in 0..10 {
+            chip8.run_frame(2)?;
+        }
+        assert_eq!(chip8.v_registers[0], 10);
+
+        assert_eq!(chip8.rewind(3), 4);
+        assert_eq!(chip8.v_registers[0], 6);
+
+        assert_eq!(chip8.rewind(1), 0);
+        assert_eq!(chip8.v_registers[0], 6);
+
+        chip8.run_frame(2)?;
+        chip8.run_frame(2)?;
+        assert_eq!(chip8.rewind(2), 2);
+        assert_eq!(chip8.v_registers[0], 6);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_traces_every_instruction() -> Result<(), Chip8Error> {
+        struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+        impl std::io::Write for SharedBuffer {
+            fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+                self.0.borrow_mut().write(bytes)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let mut chip8 = get_chip8_instance();
+        set_initial_opcode_to(0x7001, &mut chip8.memory);
+        chip8.memory[0x202] = 0x12;
+        chip8.memory[0x203] = 0x00;
+        chip8.set_tracer(Tracer::new(SharedBuffer(Rc::clone(&output))));
+
+        chip8.run_cycles(3)?;
+        let tracer = chip8.take_tracer().expect("a tracer was set");
+        chip8.run_cycles(1)?;
+
+        let output = String::from_utf8(output.borrow().clone()).expect("the trace is text");
+        let lines: Vec<&str> = output.lines().map(|line| &line[..20]).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "PC=0200 OP=7001 I=00",
+                "PC=0202 OP=1200 I=00",
+                "PC=0200 OP=7001 I=00"
+            ]
+        );
+        assert_eq!(tracer.instructions(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_tracks_the_executed_addresses() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        set_initial_opcode_to(0x1204, &mut chip8.memory);
+        chip8.memory[0x204] = 0x12;
+        chip8.memory[0x205] = 0x00;
+        assert!(chip8.coverage().is_none());
+
+        chip8.set_coverage_tracking(true);
+        chip8.run_cycles(4)?;
+
+        let coverage = chip8.coverage().expect("coverage tracking is enabled");
+        assert_eq!(
+            coverage.executed_addresses().collect::<Vec<_>>(),
+            vec![0x200, 0x201, 0x204, 0x205]
+        );
+        assert!(!coverage.is_executed(0x202));
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_exposes_the_cpu_state() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        set_initial_opcode_to(0x2300, &mut chip8.memory);
+        chip8.memory[0x300] = 0x6A;
+        chip8.memory[0x301] = 0x12;
+        chip8.delay_timer = 3;
+        chip8.keyboard[0xB] = 1;
+
+        chip8.run_cycles(2)?;
+
+        assert_eq!(chip8.program_counter(), 0x302);
+        assert_eq!(chip8.stack_pointer(), 1);
+        assert_eq!(chip8.stack(), [0x200]);
+        assert_eq!(chip8.v_register(0xA), 0x12);
+        assert_eq!(chip8.v_registers()[0xA], 0x12);
+        assert_eq!(chip8.delay_timer(), 1);
+        assert!(chip8.is_key_pressed(0xB));
+        assert_eq!(chip8.read_memory(0x300, 2)?, [0x6A, 0x12]);
+        assert!(chip8.read_memory(0xFFF, 2).is_err());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "state-write")]
+    #[test]
+    fn it_writes_the_cpu_state() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        chip8.write_memory(0x300, &[0x70, 0x05])?;
+        chip8.set_program_counter(0x300);
+        chip8.set_v_register(0, 1);
+        chip8.set_index_register(0x123);
+        chip8.set_key_pressed(2, true);
+
+        chip8.run_cycles(1)?;
+
+        assert_eq!(chip8.v_register(0), 6);
+        assert_eq!(chip8.index_register(), 0x123);
+        assert!(chip8.is_key_pressed(2));
+        assert!(chip8.write_memory(0xFFF, &[0, 0]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_only_draws_when_the_display_changed() -> Result<(), Chip8Error> {
+        let draws = Rc::new(Cell::new(0));
+        let mut chip8 = Chip8::with_quirks(
+            Box::new(MockNumberGenerator),
+            Box::new(MockAudio),
+            Box::new(MockKeyboardDevice),
+            Box::new(CountingGraphicsDevice {
+                draws: draws.clone(),
+            }),
+            Quirks::default(),
+        );
+        chip8.load_program(vec![0x00, 0xE0, 0x60, 0x01, 0x60, 0x02, 0xD0, 0x01])?;
+
+        chip8.emulate_cycle()?;
+        chip8.emulate_cycle()?;
+        chip8.emulate_cycle()?;
+        assert_eq!(draws.get(), 1);
+
+        chip8.emulate_cycle()?;
+        assert_eq!(draws.get(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_draws_again_when_asked_to() -> Result<(), Chip8Error> {
+        let draws = Rc::new(Cell::new(0));
+        let mut chip8 = Chip8::with_quirks(
+            Box::new(MockNumberGenerator),
+            Box::new(MockAudio),
+            Box::new(MockKeyboardDevice),
+            Box::new(CountingGraphicsDevice {
+                draws: draws.clone(),
+            }),
+            Quirks::default(),
+        );
+        chip8.load_program(vec![0x60, 0x01, 0x60, 0x02, 0x60, 0x03])?;
+
+        chip8.emulate_cycle()?;
+        chip8.emulate_cycle()?;
+        assert_eq!(draws.get(), 1);
+
+        chip8.redraw();
+        chip8.emulate_cycle()?;
+        assert_eq!(draws.get(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_returns_device_errors_by_default() {
+        let mut chip8 = Chip8::with_quirks(
+            Box::new(MockNumberGenerator),
+            Box::new(MockAudio),
+            Box::new(MockKeyboardDevice),
+            Box::new(FailingGraphicsDevice),
+            Quirks::default(),
+        );
+        set_initial_opcode_to(0x00E0, &mut chip8.memory);
+
+        assert!(chip8.emulate_cycle().is_err());
+    }
+
+    #[test]
+    fn it_logs_device_errors_and_continues_when_configured() -> Result<(), Chip8Error> {
+        let mut chip8 = Chip8::with_quirks(
+            Box::new(MockNumberGenerator),
+            Box::new(MockAudio),
+            Box::new(MockKeyboardDevice),
+            Box::new(FailingGraphicsDevice),
+            Quirks::default(),
+        );
+        chip8.set_device_error_policy(DeviceErrorPolicy::Continue);
+        set_initial_opcode_to(0x00E0, &mut chip8.memory);
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.program_counter, 0x202);
+        assert_eq!(chip8.take_device_errors().len(), 1);
+        assert!(chip8.take_device_errors().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_runs_without_a_graphics_device() -> Result<(), Chip8Error> {
+        let mut chip8 = Chip8::without_graphics(
+            Box::new(MockNumberGenerator),
+            Box::new(MockAudio),
+            Box::new(MockKeyboardDevice),
+            Quirks::default(),
+        );
+        chip8.v_registers[0] = 0;
+        chip8.index_register = 0;
+        set_initial_opcode_to(0xD005, &mut chip8.memory);
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.framebuffer().pixels()[0..4], [1, 1, 1, 1]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_runs_nothing_while_paused() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        set_initial_opcode_to(0x6A42, &mut chip8.memory);
+        chip8.delay_timer = 10;
+
+        chip8.pause()?;
+        chip8.emulate_cycle()?;
+
+        assert!(chip8.is_paused());
+        assert_eq!(chip8.v_registers[0xA], 0);
+        assert_eq!(chip8.delay_timer, 10);
+
+        chip8.resume()?;
+        chip8.emulate_cycle()?;
+
+        assert!(!chip8.is_paused());
+        assert_eq!(chip8.v_registers[0xA], 0x42);
+        assert_eq!(chip8.delay_timer, 9);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_steps_a_frame_at_a_time_while_paused() -> Result<(), Chip8Error> {
+        let mut chip8 = Chip8::with_quirks(
+            Box::new(MockNumberGenerator),
+            Box::new(MockAudio),
+            Box::new(IdleKeyboardDevice),
+            Box::new(MockGraphicsDevice),
+            Quirks::default(),
+        );
+        for address in (0x200..0x210).step_by(2) {
+            chip8.memory[address] = 0x70;
+            chip8.memory[address + 1] = 0x01;
+        }
+        chip8.delay_timer = 5;
+        chip8.pause()?;
+
+        chip8.step_frame(3)?;
+        chip8.run_frame(3)?;
+
+        assert!(chip8.is_paused());
+        assert_eq!(chip8.v_registers[0], 3);
+        assert_eq!(chip8.delay_timer, 4);
+
+        chip8.step_frame(1)?;
+
+        assert_eq!(chip8.v_registers[0], 4);
+        assert_eq!(chip8.delay_timer, 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_keeps_the_timers_running_while_the_cpu_is_halted() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        set_initial_opcode_to(0x6A42, &mut chip8.memory);
+        chip8.delay_timer = 10;
+        chip8.set_component_enabled(Component::Cpu, false);
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.v_registers[0xA], 0);
+        assert_eq!(chip8.program_counter, 0x200);
+        assert_eq!(chip8.delay_timer, 9);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_keeps_the_cpu_running_while_the_timers_are_halted() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        set_initial_opcode_to(0x6A42, &mut chip8.memory);
+        chip8.delay_timer = 10;
+        chip8.set_component_enabled(Component::Timers, false);
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.v_registers[0xA], 0x42);
+        assert_eq!(chip8.delay_timer, 10);
+        assert!(!chip8.is_component_enabled(Component::Timers));
+        assert!(chip8.is_component_enabled(Component::Cpu));
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_clears_the_display() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        chip8.planes[0].set_pixel(1, 0, true);
+        chip8.planes[0].set_pixel(2, 0, true);
+        set_initial_opcode_to(0x00E0, &mut chip8.memory);
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.planes[0].pixels(), &[0u8; 2048][..]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_calls_the_subroutine_at_the_correct_address() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        set_initial_opcode_to(0x2010, &mut chip8.memory);
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.stack[0], 0x200);
+        assert_eq!(chip8.stack_pointer, 1);
+        assert_eq!(chip8.program_counter, 0x010);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_returns_from_a_subroutine() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+
+        chip8.stack[0] = 0x123;
+        chip8.stack_pointer = 1;
+
+        set_initial_opcode_to(0x00EE, &mut chip8.memory);
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.stack_pointer, 0);
+        assert_eq!(chip8.program_counter, 0x125);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_finishes_when_returning_with_an_empty_stack() -> Result<(), Chip8Error> {
+        let mut chip8 = Chip8::with_quirks(
+            Box::new(MockNumberGenerator),
+            Box::new(MockAudio),
+            Box::new(IdleKeyboardDevice),
+            Box::new(MockGraphicsDevice),
+            Quirks::default(),
+        );
+        set_initial_opcode_to(0x00EE, &mut chip8.memory);
+
+        assert_eq!(chip8.emulate_cycle()?, State::Finished);
+        assert_eq!(chip8.program_counter, 0x200);
+        assert_eq!(chip8.emulate_cycle()?, State::Finished);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_halts_on_the_exit_instruction() -> Result<(), Chip8Error> {
+        let mut chip8 = Chip8::with_variant(
+            Box::new(MockNumberGenerator),
+            Box::new(MockAudio),
+            Box::new(IdleKeyboardDevice),
+            Box::new(MockGraphicsDevice),
+            Chip8Variant::SuperChip,
+        );
+        set_initial_opcode_to(0x00FD, &mut chip8.memory);
+
+        assert_eq!(chip8.emulate_cycle()?, State::Halted);
+        assert_eq!(chip8.program_counter, 0x200);
+        assert_eq!(chip8.emulate_cycle()?, State::Halted);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_errors_when_returning_with_an_empty_stack_and_detection_is_disabled() {
+        let mut chip8 = get_chip8_instance();
+        chip8.set_program_end_detection(ProgramEndDetection {
+            return_with_empty_stack: false,
+            ..ProgramEndDetection::default()
+        });
+        set_initial_opcode_to(0x00EE, &mut chip8.memory);
+
+        assert!(matches!(
+            chip8.emulate_cycle(),
+            Err(Chip8Error::StackUnderflow { .. })
+        ));
+    }
+
+    #[test]
+    fn it_errors_when_calling_a_routine_with_a_full_stack() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        // A routine calling itself fills the stack after 16 calls
+        set_initial_opcode_to(0x2200, &mut chip8.memory);
+
+        for _ in 0..16 {
+            chip8.emulate_cycle()?;
+        }
+
+        assert!(matches!(
+            chip8.emulate_cycle(),
+            Err(Chip8Error::StackOverflow { context }) if context.opcode == 0x2200
+        ));
+        assert_eq!(chip8.stack_pointer, 16);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_only_reads_the_low_nibble_of_vx_as_a_key() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        chip8.v_registers[0] = 0x25;
+        chip8.keyboard[5] = 1;
+        set_initial_opcode_to(0xE09E, &mut chip8.memory);
+
+        chip8.emulate_cycle()?;
+        assert_eq!(chip8.program_counter, 0x204);
+
+        chip8.v_registers[0] = 0xFF;
+        chip8.program_counter = 0x200;
+        set_initial_opcode_to(0xE0A1, &mut chip8.memory);
+        chip8.emulate_cycle()?;
+        assert_eq!(chip8.program_counter, 0x204);
+
+        Ok(())
+    }
+
+    struct Mailbox {
+        written: Rc<RefCell<Vec<(u16, u8)>>>,
+    }
+
+    impl Peripheral for Mailbox {
+        fn read(&mut self, offset: u16) -> u8 {
+            0xA0 + offset as u8
+        }
+
+        fn write(&mut self, offset: u16, value: u8) {
+            self.written.borrow_mut().push((offset, value));
+        }
+    }
+
+    #[test]
+    fn it_routes_memory_accesses_to_mapped_peripherals() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        let written = Rc::new(RefCell::new(Vec::new()));
+        chip8.map_peripheral(
+            0xF00..0xF10,
+            Box::new(Mailbox {
+                written: Rc::clone(&written),
+            }),
+        );
+        chip8.load_program(vec![
+            0xAE, 0xFF, // I = 0xEFF, right before the mailbox
+            0x60, 0x01, 0x61, 0x02, 0xF1, 0x55, // store V0 and V1
+            0xAF, 0x08, 0xF1, 0x65, // load V0 and V1 from the mailbox
+        ])?;
+
+        chip8.run_cycles(6)?;
+
+        assert_eq!(*written.borrow(), [(0, 0x02)]);
+        assert_eq!(chip8.memory[0xEFF], 0x01);
+        assert_eq!(chip8.v_registers[0..2], [0xA8, 0xA9]);
+
+        chip8.clear_peripherals();
+        chip8.reset(true)?;
+        chip8.memory[0xF08..0xF0A].copy_from_slice(&[0x07, 0x08]);
+        chip8.run_cycles(6)?;
+        assert_eq!(written.borrow().len(), 1);
+        assert_eq!(chip8.v_registers[0..2], [0x07, 0x08]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_applies_cheats_at_the_end_of_every_frame() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        chip8.load_program(vec![
+            0xA3, 0x00, // I = 0x300
+            0x60, 0x01, 0xF0, 0x55, // lives = 1
+        ])?;
+        let lives = chip8.cheats_mut().add(Cheat::freeze(0x300, 0x09));
+        chip8.cheats_mut().add(Cheat::patch(0x301, 0x42));
+
+        chip8.run_cycles(3)?;
+        assert_eq!(chip8.memory[0x300..0x302], [0x01, 0x00]);
+        chip8.present()?;
+        assert_eq!(chip8.memory[0x300..0x302], [0x09, 0x42]);
+
+        chip8.memory[0x301] = 0x00;
+        chip8.cheats_mut().set_enabled(lives, false);
+        chip8.reset(true)?;
+        chip8.run_cycles(3)?;
+        chip8.present()?;
+        assert_eq!(chip8.memory[0x300..0x302], [0x01, 0x00]);
+        assert!(!chip8.cheats().is_enabled(lives));
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_finishes_when_jumping_to_the_same_address() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        chip8.load_program(vec![0x70, 0x01, 0x12, 0x02])?;
+
+        assert_eq!(chip8.run_cycles(10)?, State::Finished);
+        assert_eq!(chip8.v_registers[0], 1);
+        assert_eq!(chip8.program_counter, 0x202);
+
+        chip8.set_program_end_detection(ProgramEndDetection {
+            jump_to_self: false,
+            ..ProgramEndDetection::default()
+        });
+        chip8.reset(true)?;
+        assert_eq!(chip8.run_cycles(10)?, State::Continue);
+        assert_eq!(chip8.program_counter, 0x202);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_jumps_to_the_correct_address() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+
+        set_initial_opcode_to(0x176C, &mut chip8.memory);
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.program_counter, 0x76C);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_skips_the_next_instruction_if_vx_equals_nn() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        chip8.v_registers[2] = 0x6C;
+        chip8.program_counter = 0x200;
+
+        set_initial_opcode_to(0x326C, &mut chip8.memory);
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.program_counter, 0x204);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_skips_the_next_instruction_if_vx_not_equals_nn() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        chip8.v_registers[2] = 0x6A;
+        chip8.program_counter = 0x200;
+
+        set_initial_opcode_to(0x426C, &mut chip8.memory);
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.program_counter, 0x204);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_skips_the_next_instruction_if_vx_equals_vy() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        chip8.v_registers[2] = 0x6A;
+        chip8.v_registers[3] = 0x6A;
+        chip8.program_counter = 0x200;
+
+        set_initial_opcode_to(0x5230, &mut chip8.memory);
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.program_counter, 0x204);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_stores_the_least_significant_bit_of_vx_in_vf_and_shifts_vx_to_the_right_by_1(
+    ) -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+
+        chip8.v_registers[6] = 0b00000011;
+
+        set_initial_opcode_to(0x86A6, &mut chip8.memory);
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.v_registers[6], 0b00000001);
+        assert_eq!(chip8.v_registers[15], 0b1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_shifts_vy_into_vx_with_the_shift_quirk() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance_with_quirks(Quirks {
+            shift_uses_vy: true,
+            ..Quirks::default()
+        });
+
+        chip8.v_registers[6] = 0b00000000;
+        chip8.v_registers[0xA] = 0b10000011;
+
+        set_initial_opcode_to(0x86AE, &mut chip8.memory);
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.v_registers[6], 0b00000110);
+        assert_eq!(chip8.v_registers[15], 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_resets_vf_after_logic_operations_with_the_vf_reset_quirk() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance_with_quirks(Quirks {
+            logic_resets_vf: true,
+            ..Quirks::default()
+        });
+
+        chip8.v_registers[6] = 0x10;
+        chip8.v_registers[7] = 0x20;
+        chip8.v_registers[15] = 1;
+
+        set_initial_opcode_to(0x8671, &mut chip8.memory);
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.v_registers[6], 0x30);
+        assert_eq!(chip8.v_registers[15], 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_sets_vx_to_vy_minus_vx_vf_is_set_to_0_when_there_is_a_borrow() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+
+        chip8.v_registers[4] = 0x20;
+        chip8.v_registers[5] = 0x11;
+
+        set_initial_opcode_to(0x8457, &mut chip8.memory);
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.v_registers[4], 0xF1);
+        assert_eq!(chip8.v_registers[15], 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_sets_vx_to_vy_minus_vx_vf_is_set_to_1_when_there_isnt_a_borrow() -> Result<(), Chip8Error>
+    {
+        let mut chip8 = get_chip8_instance();
+
+        chip8.v_registers[4] = 0x11;
+        chip8.v_registers[5] = 0x20;
+
+        set_initial_opcode_to(0x8457, &mut chip8.memory);
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.v_registers[4], 0x0F);
+        assert_eq!(chip8.v_registers[15], 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_sets_vf_to_the_value_of_vx_msb_shifts_vx_left_by_1() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+
+        chip8.v_registers[1] = 0b10000000;
+
+        set_initial_opcode_to(0x812E, &mut chip8.memory);
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.v_registers[15usize], 1);
+        assert_eq!(chip8.v_registers[1], 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_skips_the_next_instruction_if_vx_not_equals_vy() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+
+        chip8.v_registers[10] = 0x11;
+        chip8.v_registers[11] = 0x20;
+
+        set_initial_opcode_to(0x9AB0, &mut chip8.memory);
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.program_counter, 0x204);
+
         Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn it_doesnt_skip_the_next_instruction_if_vx_equals_vy() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
 
-    pub fn set_initial_opcode_to(opcode: u16, memory: &mut [u8; 4096]) {
-        memory[0x200] = ((opcode & 0xFF00) >> 8) as u8;
-        memory[0x201] = (opcode & 0x00FF) as u8;
-    }
+        chip8.v_registers[10] = 0x11;
+        chip8.v_registers[11] = 0x11;
 
-    struct MockAudio;
-    impl Audio for MockAudio {
-        fn play(&self) -> Result<(), Chip8Error> {
-            Ok(())
-        }
+        set_initial_opcode_to(0x9AB0, &mut chip8.memory);
 
-        fn stop(&self) -> Result<(), Chip8Error> {
-            Ok(())
-        }
-    }
+        chip8.emulate_cycle()?;
 
-    struct MockNumberGenerator;
-    impl NumberGenerator for MockNumberGenerator {
-        fn generate(&self) -> Result<u8, Chip8Error> {
-            Ok(1)
-        }
+        assert_eq!(chip8.program_counter, 0x202);
+
+        Ok(())
     }
 
-    struct MockKeyboardDevice;
-    impl Keyboard for MockKeyboardDevice {
-        fn wait_next_key_press(&mut self) -> u8 {
-            1
-        }
+    #[test]
+    fn it_sets_the_index_register_value() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
 
-        fn update_state(&mut self, _keyboard: &mut [u8; 16]) -> bool {
-            true
-        }
-    }
+        set_initial_opcode_to(0xA111, &mut chip8.memory);
 
-    struct MockGraphicsDevice;
-    impl Graphics for MockGraphicsDevice {
-        fn draw(&mut self, _graphics: &[u8]) -> Result<(), Chip8Error> {
-            Ok(())
-        }
-    }
+        chip8.emulate_cycle()?;
 
-    fn get_chip8_instance() -> Chip8 {
-        Chip8::new(
-            Box::new(MockNumberGenerator),
-            Box::new(MockAudio),
-            Box::new(MockKeyboardDevice),
-            Box::new(MockGraphicsDevice),
-        )
+        assert_eq!(chip8.index_register, 0x111);
+
+        Ok(())
     }
 
     #[test]
-    fn it_sets_the_correct_default_values() {
-        let chip8 = get_chip8_instance();
+    fn it_sets_the_value_of_vx() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        chip8.v_registers[4] = 0xF;
+        set_initial_opcode_to(0x6423, &mut chip8.memory);
 
-        assert_eq!(chip8.opcode, 0);
-        assert_eq!(chip8.program_counter, 0x200);
-        assert_eq!(chip8.index_register, 0);
-        assert_eq!(chip8.stack_pointer, 0);
-        assert_eq!(chip8.graphics, [0; 2048]);
-        assert_eq!(chip8.v_registers, [0; 16]);
-        assert_eq!(chip8.stack, [0; 16]);
-        assert_eq!(chip8.delay_timer, 0);
-        assert_eq!(chip8.sound_timer, 0);
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.v_registers[4], 0x23);
+
+        Ok(())
     }
 
     #[test]
-    fn it_loads_the_font_set_on_initialization() {
-        let chip8 = get_chip8_instance();
+    fn it_adds_the_value_to_vx() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        chip8.v_registers[1] = 0x10;
+        set_initial_opcode_to(0x7110, &mut chip8.memory);
 
-        assert_eq!(&chip8.memory[0..80], FONT_SET);
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.v_registers[1], 0x20);
+
+        Ok(())
     }
 
     #[test]
-    fn it_loads_the_program_to_memory() -> Result<(), Chip8Error> {
-        let fake_data = vec![1, 2, 3];
-        let fake_data_len = fake_data.len();
+    fn it_sets_the_value_of_vx_to_vy() -> Result<(), Chip8Error> {
         let mut chip8 = get_chip8_instance();
+        chip8.v_registers[1] = 0x10;
+        chip8.v_registers[2] = 0x20;
+        set_initial_opcode_to(0x8120, &mut chip8.memory);
 
-        chip8.load_program(fake_data)?;
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.v_registers[1], 0x20);
 
-        assert_eq!(&chip8.memory[0x200..0x200 + fake_data_len], vec![1, 2, 3]);
         Ok(())
     }
 
     #[test]
-    fn it_fetches_correct_opcode_when_emulating_the_first_cycle() -> Result<(), Chip8Error> {
+    fn it_sets_the_value_of_vx_to_vx_bitwise_or_vy() -> Result<(), Chip8Error> {
         let mut chip8 = get_chip8_instance();
-        chip8.memory[0x200] = 0x10;
-        chip8.memory[0x201] = 0x20;
+        chip8.v_registers[6] = 0x10;
+        chip8.v_registers[7] = 0x20;
+        set_initial_opcode_to(0x8671, &mut chip8.memory);
 
         chip8.emulate_cycle()?;
 
-        assert_eq!(chip8.opcode, 4128);
+        assert_eq!(chip8.v_registers[6], 0x30);
+
         Ok(())
     }
 
     #[test]
-    fn it_correctly_counts_down_the_timers() -> Result<(), Chip8Error> {
+    fn it_sets_the_value_of_vx_to_vx_bitwise_and_vy() -> Result<(), Chip8Error> {
         let mut chip8 = get_chip8_instance();
-        set_initial_opcode_to(0x00E0, &mut chip8.memory);
+        chip8.v_registers[8] = 0xFF;
+        chip8.v_registers[9] = 0x10;
+        set_initial_opcode_to(0x8892, &mut chip8.memory);
 
-        chip8.delay_timer = 1;
-        chip8.sound_timer = 1;
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.v_registers[8], 0x10);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_sets_the_value_of_vx_to_vx_bitwise_xor_vy() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        chip8.v_registers[7] = 0x72;
+        chip8.v_registers[8] = 0x15;
+        set_initial_opcode_to(0x8783, &mut chip8.memory);
 
         chip8.emulate_cycle()?;
 
-        assert_eq!(chip8.delay_timer, 0);
-        assert_eq!(chip8.sound_timer, 0);
+        assert_eq!(chip8.v_registers[7], 0x67);
 
-        chip8.memory[0x202] = 0x00;
-        chip8.memory[0x203] = 0xE0;
+        Ok(())
+    }
+
+    #[test]
+    fn it_adds_the_value_of_vy_to_vx_setting_vf_when_there_is_a_carry() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        chip8.v_registers[0] = 0xC8;
+        chip8.v_registers[1] = 0x64;
+        set_initial_opcode_to(0x8014, &mut chip8.memory);
 
         chip8.emulate_cycle()?;
 
-        assert_eq!(chip8.delay_timer, 0);
-        assert_eq!(chip8.sound_timer, 0);
+        // Overflowing add of 200 + 100 = 44
+        assert_eq!(chip8.v_registers[0], 0x2C);
+        assert_eq!(chip8.v_registers[15usize], 1);
 
         Ok(())
     }
 
     #[test]
-    fn it_clears_the_display() -> Result<(), Chip8Error> {
+    fn it_subtracts_the_value_of_vy_of_vf_clearing_vf_when_there_is_a_borrow(
+    ) -> Result<(), Chip8Error> {
         let mut chip8 = get_chip8_instance();
-        chip8.graphics[1] = 69;
-        chip8.graphics[2] = 98;
-        set_initial_opcode_to(0x00E0, &mut chip8.memory);
+        chip8.v_registers[0] = 0xD1;
+        chip8.v_registers[1] = 0xD2;
+        chip8.v_registers[15usize] = 1;
+        set_initial_opcode_to(0x8015, &mut chip8.memory);
 
         chip8.emulate_cycle()?;
 
-        assert_eq!(chip8.graphics, [0u8; 2048]);
+        assert_eq!(chip8.v_registers[0], 0xFF);
+        assert_eq!(chip8.v_registers[15usize], 0);
 
         Ok(())
     }
 
     #[test]
-    fn it_calls_the_subroutine_at_the_correct_address() -> Result<(), Chip8Error> {
+    fn it_subtracts_the_value_of_vy_of_vf_setting_vf_when_there_isnt_a_borrow(
+    ) -> Result<(), Chip8Error> {
         let mut chip8 = get_chip8_instance();
-        set_initial_opcode_to(0x2010, &mut chip8.memory);
+        chip8.v_registers[0] = 0xD2;
+        chip8.v_registers[1] = 0xD1;
+        set_initial_opcode_to(0x8015, &mut chip8.memory);
 
         chip8.emulate_cycle()?;
 
-        assert_eq!(chip8.stack[0], 0x200);
-        assert_eq!(chip8.stack_pointer, 1);
-        assert_eq!(chip8.program_counter, 0x010);
+        assert_eq!(chip8.v_registers[0], 0x01);
+        assert_eq!(chip8.v_registers[15usize], 1);
 
         Ok(())
     }
 
     #[test]
-    fn it_returns_from_a_subroutine() -> Result<(), Chip8Error> {
+    fn it_clears_vf_when_adding_without_a_carry() -> Result<(), Chip8Error> {
         let mut chip8 = get_chip8_instance();
+        chip8.v_registers[0] = 0x10;
+        chip8.v_registers[1] = 0x20;
+        chip8.v_registers[15usize] = 5;
+        set_initial_opcode_to(0x8014, &mut chip8.memory);
 
-        chip8.stack[0] = 0x123;
-        chip8.stack_pointer = 1;
+        chip8.emulate_cycle()?;
 
-        set_initial_opcode_to(0x00EE, &mut chip8.memory);
+        assert_eq!(chip8.v_registers[0], 0x30);
+        assert_eq!(chip8.v_registers[15usize], 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_keeps_the_flag_when_vf_is_the_register_added_to() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        chip8.v_registers[1] = 0xFF;
+        chip8.v_registers[15usize] = 0x02;
+        set_initial_opcode_to(0x8F14, &mut chip8.memory);
 
         chip8.emulate_cycle()?;
 
-        assert_eq!(chip8.stack_pointer, 0);
-        assert_eq!(chip8.program_counter, 0x125);
+        assert_eq!(chip8.v_registers[15usize], 1);
 
         Ok(())
     }
 
     #[test]
-    fn it_jumps_to_the_correct_address() -> Result<(), Chip8Error> {
+    fn it_jumps_to_the_address_nnn_plus_vx0() -> Result<(), Chip8Error> {
         let mut chip8 = get_chip8_instance();
 
-        set_initial_opcode_to(0x176C, &mut chip8.memory);
+        chip8.v_registers[0] = 0x1;
+        set_initial_opcode_to(0xB100, &mut chip8.memory);
 
         chip8.emulate_cycle()?;
 
-        assert_eq!(chip8.program_counter, 0x76C);
+        assert_eq!(chip8.program_counter, 0x101);
 
         Ok(())
     }
 
     #[test]
-    fn it_skips_the_next_instruction_if_vx_equals_nn() -> Result<(), Chip8Error> {
+    fn it_jumps_to_the_address_xnn_plus_vx_with_the_jump_quirk() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance_with_quirks(Quirks {
+            jump_uses_vx: true,
+            ..Quirks::default()
+        });
+
+        chip8.v_registers[0] = 0x1;
+        chip8.v_registers[2] = 0x4;
+        set_initial_opcode_to(0xB220, &mut chip8.memory);
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.program_counter, 0x224);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "replay")]
+    fn it_plays_a_recorded_run_back_the_same_way() -> Result<(), Chip8Error> {
+        // Waits for key 7, then keeps drawing random numbers
+        let program = vec![
+            0x60, 0x07, 0xE0, 0x9E, 0x12, 0x02, 0xC1, 0xFF, 0x72, 0x01, 0x12, 0x06,
+        ];
+        let mut recorded = Chip8::with_quirks(
+            Box::new(MockNumberGenerator),
+            Box::new(MockAudio),
+            Box::new(ScriptedKeyboardDevice {
+                events: vec![KeyEvent::Down(7)],
+            }),
+            Box::new(MockGraphicsDevice),
+            Quirks::default(),
+        );
+        recorded.load_program(program.clone())?;
+        recorded.start_recording(42);
+        for instructions in [10, 7, 12].iter() {
+            recorded.run_frame(*instructions)?;
+        }
+        let replay = recorded.stop_recording().unwrap();
+        assert_eq!(replay.to_string(), "seed 42\n10 +7\n7\n12\n");
+
+        let mut played = Chip8::with_quirks(
+            Box::new(MockNumberGenerator),
+            Box::new(MockAudio),
+            Box::new(IdleKeyboardDevice),
+            Box::new(MockGraphicsDevice),
+            Quirks::default(),
+        );
+        played.load_program(program)?;
+        played.play_replay(replay);
+        for _ in 0..3 {
+            played.run_frame(1)?;
+        }
+
+        assert_eq!(played.snapshot(), recorded.snapshot());
+        assert!(played.is_replaying());
+        played.run_frame(1)?;
+        assert!(!played.is_replaying());
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "replay")]
+    fn it_records_the_sound_and_plays_it_back_where_it_was_recorded() -> Result<(), Chip8Error> {
+        let program = vec![0x60, 0x02, 0xF0, 0x18, 0x70, 0x01, 0x12, 0x04];
+        let calls = Rc::new(RefCell::new(Vec::new()));
+        let mut chip8 = Chip8::builder()
+            .audio(Box::new(RecordingAudio {
+                calls: calls.clone(),
+            }))
+            .build();
+        chip8.load_program(program.clone())?;
+        chip8.start_recording(1);
+        for _ in 0..3 {
+            chip8.run_frame(4)?;
+        }
+
+        let replay = chip8.stop_recording().unwrap();
+        // On after V0 := 2 and FX18, off when the timer runs out at the end of the second frame
+        assert_eq!(replay.to_string(), "seed 1\n4 on@2\n4 off@4\n4\n");
+
+        // The device follows the replay, even where the sound timer disagrees
+        let replay = "seed 1\n4 on@3\n4\n4 off@1\n".parse::<Replay>().unwrap();
+        chip8.reset(false)?;
+        calls.borrow_mut().clear();
+        chip8.load_program(program)?;
+        chip8.play_replay(replay);
+        chip8.run_frame(4)?;
+        chip8.run_frame(4)?;
+        assert_eq!(*calls.borrow(), vec![Some(TIMER_PERIOD * 2)]);
+        chip8.run_frame(4)?;
+        assert_eq!(*calls.borrow(), vec![Some(TIMER_PERIOD * 2), None]);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "seeded-rng")]
+    fn it_generates_the_same_numbers_after_seeding() -> Result<(), Chip8Error> {
+        let mut first = get_chip8_instance();
+        let mut second = get_chip8_instance();
+        first.seed_rng(7);
+        second.seed_rng(7);
+
+        for chip8 in [&mut first, &mut second].iter_mut() {
+            set_initial_opcode_to(0xC0FF, &mut chip8.memory);
+            chip8.memory[0x202] = 0xC1;
+            chip8.memory[0x203] = 0xFF;
+            chip8.emulate_cycle()?;
+            chip8.emulate_cycle()?;
+        }
+
+        assert_eq!(first.v_registers[0..2], second.v_registers[0..2]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_sets_vx_to_random_number_bitwise_and_nn() -> Result<(), Chip8Error> {
         let mut chip8 = get_chip8_instance();
-        chip8.v_registers[2] = 0x6C;
-        chip8.program_counter = 0x200;
 
-        set_initial_opcode_to(0x326C, &mut chip8.memory);
+        set_initial_opcode_to(0xC313, &mut chip8.memory);
 
         chip8.emulate_cycle()?;
 
-        assert_eq!(chip8.program_counter, 0x204);
+        assert_eq!(chip8.v_registers[3], 0x1);
 
         Ok(())
     }
 
+    //0xDXYN
     #[test]
-    fn it_skips_the_next_instruction_if_vx_not_equals_nn() -> Result<(), Chip8Error> {
+    fn it_draws_the_correct_pixels() -> Result<(), Chip8Error> {
         let mut chip8 = get_chip8_instance();
-        chip8.v_registers[2] = 0x6A;
-        chip8.program_counter = 0x200;
 
-        set_initial_opcode_to(0x426C, &mut chip8.memory);
+        chip8.v_registers[0x1] = 0xAC;
+        chip8.v_registers[0x4] = 0xCA;
+        chip8.index_register = 0x200;
+        chip8.memory[0x200] = 0;
+        chip8.memory[0x201] = 1;
+        chip8.memory[0x201] = 0;
+        chip8.memory[0x201] = 2;
+        chip8.memory[0x201] = 4;
+        set_initial_opcode_to(0xD145, &mut chip8.memory);
 
         chip8.emulate_cycle()?;
 
-        assert_eq!(chip8.program_counter, 0x204);
-
+        assert_eq!(
+            chip8.planes[0].pixels()[684..=691],
+            [1, 1, 0, 1, 0, 0, 0, 1]
+        );
+        assert_eq!(chip8.planes[0].pixels()[749..=755], [1, 0, 0, 0, 1, 0, 1]);
         Ok(())
     }
 
     #[test]
-    fn it_skips_the_next_instruction_if_vx_equals_vy() -> Result<(), Chip8Error> {
-        let mut chip8 = get_chip8_instance();
-        chip8.v_registers[2] = 0x6A;
-        chip8.v_registers[3] = 0x6A;
-        chip8.program_counter = 0x200;
-
-        set_initial_opcode_to(0x5230, &mut chip8.memory);
+    fn it_clips_sprites_at_the_edge_of_the_screen_without_the_wrap_quirk() -> Result<(), Chip8Error>
+    {
+        let mut chip8 = get_chip8_instance_with_quirks(Quirks {
+            wrap_sprites: false,
+            ..Quirks::default()
+        });
+        chip8.v_registers[0] = 60;
+        chip8.v_registers[1] = 0;
+        chip8.index_register = 0x300;
+        chip8.memory[0x300] = 0xFF;
+        set_initial_opcode_to(0xD011, &mut chip8.memory);
 
         chip8.emulate_cycle()?;
 
-        assert_eq!(chip8.program_counter, 0x204);
+        assert_eq!(chip8.planes[0].pixels()[60..64], [1, 1, 1, 1]);
+        assert_eq!(chip8.planes[0].pixels()[0..4], [0, 0, 0, 0]);
 
         Ok(())
     }
 
     #[test]
-    fn it_stores_the_least_significant_bit_of_vx_in_vf_and_shifts_vx_to_the_right_by_1(
+    fn it_clips_sprites_at_the_bottom_of_the_screen_without_the_wrap_quirk(
     ) -> Result<(), Chip8Error> {
-        let mut chip8 = get_chip8_instance();
-
-        chip8.v_registers[6] = 0b00000011;
-
-        set_initial_opcode_to(0x86A6, &mut chip8.memory);
+        let mut chip8 = get_chip8_instance_with_quirks(Quirks {
+            wrap_sprites: false,
+            ..Quirks::default()
+        });
+        chip8.v_registers[0] = 0;
+        chip8.v_registers[1] = 31;
+        chip8.index_register = 0x300;
+        chip8.memory[0x300..0x302].copy_from_slice(&[0x80, 0x80]);
+        set_initial_opcode_to(0xD012, &mut chip8.memory);
 
         chip8.emulate_cycle()?;
 
-        assert_eq!(chip8.v_registers[6], 0b00000001);
-        assert_eq!(chip8.v_registers[15], 0b1);
+        assert_eq!(chip8.planes[0].pixels()[31 * 64], 1);
+        assert_eq!(chip8.planes[0].pixels()[0], 0);
 
         Ok(())
     }
 
     #[test]
-    fn it_sets_vx_to_vy_minus_vx_vf_is_set_to_0_when_there_is_a_borrow() -> Result<(), Chip8Error> {
-        let mut chip8 = get_chip8_instance();
-
-        chip8.v_registers[4] = 0x20;
-        chip8.v_registers[5] = 0x11;
-
-        set_initial_opcode_to(0x8457, &mut chip8.memory);
+    fn it_wraps_the_sprite_start_position_without_the_wrap_quirk() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance_with_quirks(Quirks {
+            wrap_sprites: false,
+            ..Quirks::default()
+        });
+        chip8.v_registers[0] = 66;
+        chip8.v_registers[1] = 33;
+        chip8.index_register = 0x300;
+        chip8.memory[0x300] = 0x80;
+        set_initial_opcode_to(0xD011, &mut chip8.memory);
 
         chip8.emulate_cycle()?;
 
-        assert_eq!(chip8.v_registers[4], 0xF);
-        assert_eq!(chip8.v_registers[15], 0);
+        assert_eq!(chip8.planes[0].pixels()[64 + 2], 1);
 
         Ok(())
     }
 
     #[test]
-    fn it_sets_vx_to_vy_minus_vx_vf_is_set_to_1_when_there_isnt_a_borrow() -> Result<(), Chip8Error>
-    {
-        let mut chip8 = get_chip8_instance();
-
-        chip8.v_registers[4] = 0x11;
-        chip8.v_registers[5] = 0x20;
-
-        set_initial_opcode_to(0x8457, &mut chip8.memory);
+    fn it_wraps_sprites_around_both_edges_with_the_wrap_quirk() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance_with_quirks(Quirks {
+            wrap_sprites: true,
+            ..Quirks::default()
+        });
+        chip8.v_registers[0] = 63;
+        chip8.v_registers[1] = 31;
+        chip8.index_register = 0x300;
+        chip8.memory[0x300..0x302].copy_from_slice(&[0xC0, 0xC0]);
+        set_initial_opcode_to(0xD012, &mut chip8.memory);
 
         chip8.emulate_cycle()?;
 
-        assert_eq!(chip8.v_registers[4], 0xF1);
-        assert_eq!(chip8.v_registers[15], 1);
+        assert_eq!(chip8.planes[0].pixels()[31 * 64 + 63], 1);
+        assert_eq!(chip8.planes[0].pixels()[31 * 64], 1);
+        assert_eq!(chip8.planes[0].pixels()[63], 1);
+        assert_eq!(chip8.planes[0].pixels()[0], 1);
 
         Ok(())
     }
 
     #[test]
-    fn it_sets_vf_to_the_value_of_vx_msb_shifts_vx_left_by_1() -> Result<(), Chip8Error> {
+    fn it_sets_vf_when_any_pixel_collides() -> Result<(), Chip8Error> {
         let mut chip8 = get_chip8_instance();
-
-        chip8.v_registers[1] = 0b10000000;
-
-        set_initial_opcode_to(0x812E, &mut chip8.memory);
+        chip8.index_register = 0x300;
+        chip8.memory[0x300] = 0b11000000;
+        chip8.planes[0].set_pixel(0, 0, true);
+        set_initial_opcode_to(0xD011, &mut chip8.memory);
 
         chip8.emulate_cycle()?;
 
-        assert_eq!(chip8.v_registers[15usize], 1);
-        assert_eq!(chip8.v_registers[1], 0);
+        assert_eq!(chip8.planes[0].pixels()[0..2], [0, 1]);
+        assert_eq!(chip8.v_registers[0xF], 1);
 
         Ok(())
     }
 
     #[test]
-    fn it_skips_the_next_instruction_if_vx_not_equals_vy() -> Result<(), Chip8Error> {
-        let mut chip8 = get_chip8_instance();
+    fn it_switches_to_high_resolution_and_back() -> Result<(), Chip8Error> {
+        let mut chip8 = get_superchip_instance();
+        chip8.planes[0].set_pixel(0, 0, true);
+        set_initial_opcode_to(0x00FF, &mut chip8.memory);
+        chip8.memory[0x202] = 0x00;
+        chip8.memory[0x203] = 0xFE;
 
-        chip8.v_registers[10] = 0x11;
-        chip8.v_registers[11] = 0x20;
+        chip8.emulate_cycle()?;
 
-        set_initial_opcode_to(0x9AB0, &mut chip8.memory);
+        assert_eq!(chip8.resolution(), (128, 64));
+        assert_eq!(chip8.framebuffer().pixels().len(), 8192);
+        assert_eq!(chip8.planes[0].pixels()[0], 0);
 
         chip8.emulate_cycle()?;
 
-        assert_eq!(chip8.program_counter, 0x204);
+        assert_eq!(chip8.resolution(), (64, 32));
+        assert_eq!(chip8.framebuffer().pixels().len(), 2048);
 
         Ok(())
     }
 
     #[test]
-    fn it_doesnt_skip_the_next_instruction_if_vx_equals_vy() -> Result<(), Chip8Error> {
-        let mut chip8 = get_chip8_instance();
-
-        chip8.v_registers[10] = 0x11;
-        chip8.v_registers[11] = 0x11;
-
-        set_initial_opcode_to(0x9AB0, &mut chip8.memory);
+    fn it_draws_16x16_sprites_in_high_resolution() -> Result<(), Chip8Error> {
+        let mut chip8 = get_superchip_instance();
+        chip8.quirks.wrap_sprites = true;
+        chip8.set_display_mode(DisplayMode::HighResolution);
+        chip8.v_registers[0] = 120;
+        chip8.v_registers[1] = 1;
+        chip8.index_register = 0x300;
+        chip8.memory[0x300] = 0x80;
+        chip8.memory[0x301] = 0x01;
+        chip8.memory[0x31E] = 0xFF;
+        chip8.memory[0x31F] = 0xFF;
+        set_initial_opcode_to(0xD010, &mut chip8.memory);
 
         chip8.emulate_cycle()?;
 
-        assert_eq!(chip8.program_counter, 0x202);
+        // First row: leftmost pixel at x = 120, rightmost wraps around to x = 7
+        assert_eq!(chip8.planes[0].pixels()[128 + 120], 1);
+        assert_eq!(chip8.planes[0].pixels()[128 + 121], 0);
+        assert_eq!(chip8.planes[0].pixels()[128 + 7], 1);
+        // Last row is drawn 15 rows below the first one
+        assert_eq!(
+            chip8.planes[0].pixels()[16 * 128 + 120..16 * 128 + 128],
+            [1; 8]
+        );
+        assert_eq!(chip8.planes[0].pixels()[16 * 128..16 * 128 + 8], [1; 8]);
 
         Ok(())
     }
 
     #[test]
-    fn it_sets_the_index_register_value() -> Result<(), Chip8Error> {
-        let mut chip8 = get_chip8_instance();
-
-        set_initial_opcode_to(0xA111, &mut chip8.memory);
+    fn it_scrolls_the_display_down_n_rows() -> Result<(), Chip8Error> {
+        let mut chip8 = get_superchip_instance();
+        chip8.planes[0].set_pixel(3, 0, true);
+        set_initial_opcode_to(0x00C2, &mut chip8.memory);
 
         chip8.emulate_cycle()?;
 
-        assert_eq!(chip8.index_register, 0x111);
+        assert_eq!(chip8.planes[0].pixels()[3], 0);
+        assert_eq!(chip8.planes[0].pixels()[2 * 64 + 3], 1);
 
         Ok(())
     }
 
     #[test]
-    fn it_sets_the_value_of_vx() -> Result<(), Chip8Error> {
-        let mut chip8 = get_chip8_instance();
-        chip8.v_registers[4] = 0xF;
-        set_initial_opcode_to(0x6423, &mut chip8.memory);
+    fn it_scrolls_the_display_right_and_left_by_4_pixels() -> Result<(), Chip8Error> {
+        let mut chip8 = get_superchip_instance();
+        chip8.planes[0].set_pixel(2, 1, true);
+        chip8.planes[0].set_pixel(62, 1, true);
+        set_initial_opcode_to(0x00FB, &mut chip8.memory);
+        chip8.memory[0x202] = 0x00;
+        chip8.memory[0x203] = 0xFC;
 
         chip8.emulate_cycle()?;
 
-        assert_eq!(chip8.v_registers[4], 0x23);
-
-        Ok(())
-    }
-
-    #[test]
-    fn it_adds_the_value_to_vx() -> Result<(), Chip8Error> {
-        let mut chip8 = get_chip8_instance();
-        chip8.v_registers[1] = 0x10;
-        set_initial_opcode_to(0x7110, &mut chip8.memory);
+        assert_eq!(
+            chip8.planes[0].pixels()[64..128]
+                .iter()
+                .filter(|p| **p == 1)
+                .count(),
+            1
+        );
+        assert_eq!(chip8.planes[0].pixels()[64 + 6], 1);
 
         chip8.emulate_cycle()?;
 
-        assert_eq!(chip8.v_registers[1], 0x20);
+        assert_eq!(
+            chip8.planes[0].pixels()[64..128]
+                .iter()
+                .filter(|p| **p == 1)
+                .count(),
+            1
+        );
+        assert_eq!(chip8.planes[0].pixels()[64 + 2], 1);
 
         Ok(())
     }
 
     #[test]
-    fn it_sets_the_value_of_vx_to_vy() -> Result<(), Chip8Error> {
+    fn it_rejects_superchip_instructions_for_the_original_chip8() {
         let mut chip8 = get_chip8_instance();
-        chip8.v_registers[1] = 0x10;
-        chip8.v_registers[2] = 0x20;
-        set_initial_opcode_to(0x8120, &mut chip8.memory);
-
-        chip8.emulate_cycle()?;
-
-        assert_eq!(chip8.v_registers[1], 0x20);
-
-        Ok(())
+        set_initial_opcode_to(0x00FF, &mut chip8.memory);
+
+        assert!(matches!(
+            chip8.emulate_cycle(),
+            Err(Chip8Error::InvalidOpcode {
+                context: ErrorContext {
+                    program_counter: 0x200,
+                    opcode: 0x00FF
+                }
+            })
+        ));
     }
 
     #[test]
-    fn it_sets_the_value_of_vx_to_vx_bitwise_or_vy() -> Result<(), Chip8Error> {
-        let mut chip8 = get_chip8_instance();
-        chip8.v_registers[6] = 0x10;
-        chip8.v_registers[7] = 0x20;
-        set_initial_opcode_to(0x8671, &mut chip8.memory);
+    fn it_loads_a_long_address_in_i_and_skips_over_it() -> Result<(), Chip8Error> {
+        let mut chip8 = get_xochip_instance();
+        // F000 FFF0, then a skip over a second F000 NNNN
+        let program = [0xF0, 0x00, 0xFF, 0xF0, 0x30, 0x00, 0xF0, 0x00, 0x12, 0x34];
+        chip8.load_program(program.to_vec())?;
 
         chip8.emulate_cycle()?;
 
-        assert_eq!(chip8.v_registers[6], 0x30);
+        assert_eq!(chip8.memory().len(), 0x10000);
+        assert_eq!(chip8.index_register, 0xFFF0);
+        assert_eq!(chip8.program_counter, 0x204);
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.program_counter, 0x20A);
 
         Ok(())
     }
 
     #[test]
-    fn it_sets_the_value_of_vx_to_vx_bitwise_and_vy() -> Result<(), Chip8Error> {
-        let mut chip8 = get_chip8_instance();
-        chip8.v_registers[8] = 0xFF;
-        chip8.v_registers[9] = 0x10;
-        set_initial_opcode_to(0x8892, &mut chip8.memory);
+    fn it_stores_and_loads_register_ranges_without_moving_i() -> Result<(), Chip8Error> {
+        let mut chip8 = get_xochip_instance();
+        chip8.v_registers[1..4].copy_from_slice(&[1, 2, 3]);
+        chip8.index_register = 0x300;
+        set_initial_opcode_to(0x5132, &mut chip8.memory);
+        chip8.memory[0x202] = 0x56;
+        chip8.memory[0x203] = 0x43;
 
         chip8.emulate_cycle()?;
 
-        assert_eq!(chip8.v_registers[8], 0x10);
+        assert_eq!(chip8.memory[0x300..0x303], [1, 2, 3]);
+        assert_eq!(chip8.index_register, 0x300);
+
+        chip8.emulate_cycle()?;
+
+        // Loaded backwards, V6 gets the first byte
+        assert_eq!(chip8.v_registers[4..7], [3, 2, 1]);
+        assert_eq!(chip8.index_register, 0x300);
 
         Ok(())
     }
 
     #[test]
-    fn it_sets_the_value_of_vx_to_vx_bitwise_xor_vy() -> Result<(), Chip8Error> {
-        let mut chip8 = get_chip8_instance();
-        chip8.v_registers[7] = 0x72;
-        chip8.v_registers[8] = 0x15;
-        set_initial_opcode_to(0x8783, &mut chip8.memory);
+    fn it_keeps_5xy2_a_skip_before_xochip() -> Result<(), Chip8Error> {
+        let mut chip8 = get_superchip_instance();
+        set_initial_opcode_to(0x5122, &mut chip8.memory);
 
         chip8.emulate_cycle()?;
 
-        assert_eq!(chip8.v_registers[7], 0x67);
+        assert_eq!(chip8.program_counter, 0x204);
+        assert_eq!(chip8.planes().len(), 1);
 
         Ok(())
     }
 
     #[test]
-    fn it_adds_the_value_of_vy_to_vx_setting_vf_when_there_is_a_carry() -> Result<(), Chip8Error> {
-        let mut chip8 = get_chip8_instance();
-        chip8.v_registers[0] = 0xC8;
-        chip8.v_registers[1] = 0x64;
-        set_initial_opcode_to(0x8014, &mut chip8.memory);
-
-        chip8.emulate_cycle()?;
-
-        // Overflowing add of 200 + 100 = 44
-        assert_eq!(chip8.v_registers[0], 0x2C);
-        assert_eq!(chip8.v_registers[15usize], 1);
+    fn it_draws_clears_and_scrolls_the_selected_planes() -> Result<(), Chip8Error> {
+        let mut chip8 = get_xochip_instance();
+        chip8.index_register = 0x300;
+        chip8.memory[0x300] = 0x80;
+        chip8.memory[0x301] = 0x40;
+        // Both planes, one row each, then clear the first plane and scroll the second one up
+        let program = [
+            0xF3, 0x01, 0xD0, 0x01, 0xF1, 0x01, 0x00, 0xE0, 0xF2, 0x01, 0x00, 0xD1,
+        ];
+        chip8.load_program(program.to_vec())?;
+        chip8.v_registers[0] = 1;
+
+        chip8.run_cycles(2)?;
+
+        assert_eq!(chip8.planes().len(), 2);
+        assert!(chip8.planes[0].get_pixel(1, 1));
+        assert!(chip8.planes[1].get_pixel(2, 1));
+        assert_eq!(chip8.v_registers[0xF], 0);
+
+        chip8.run_cycles(4)?;
+
+        assert!(chip8.planes[0].set_pixels().next().is_none());
+        assert!(chip8.planes[1].get_pixel(2, 0));
+        assert!(!chip8.planes[1].get_pixel(2, 1));
 
         Ok(())
     }
 
     #[test]
-    fn it_subtracts_the_value_of_vy_of_vf_setting_vf_then_there_is_a_borrow(
-    ) -> Result<(), Chip8Error> {
+    fn it_rejects_xochip_instructions_for_superchip() {
+        let mut chip8 = get_superchip_instance();
+        set_initial_opcode_to(0xF000, &mut chip8.memory);
+
+        assert!(matches!(
+            chip8.emulate_cycle(),
+            Err(Chip8Error::InvalidOpcode { .. })
+        ));
+    }
+
+    #[test]
+    fn it_skips_invalid_opcodes_when_configured() -> Result<(), Chip8Error> {
         let mut chip8 = get_chip8_instance();
-        chip8.v_registers[0] = 0xD1;
-        chip8.v_registers[1] = 0xD2;
-        set_initial_opcode_to(0x8015, &mut chip8.memory);
+        chip8.set_on_invalid_opcode(OnInvalidOpcode::SkipAndContinue);
+        set_initial_opcode_to(0xFFFF, &mut chip8.memory);
 
         chip8.emulate_cycle()?;
 
-        assert_eq!(chip8.v_registers[0], 0xFF);
-        assert_eq!(chip8.v_registers[15usize], 1);
+        assert_eq!(chip8.program_counter, 0x202);
 
         Ok(())
     }
 
     #[test]
-    fn it_jumps_to_the_address_nnn_plus_vx0() -> Result<(), Chip8Error> {
-        let mut chip8 = get_chip8_instance();
-
-        chip8.v_registers[0] = 0x1;
-        set_initial_opcode_to(0xB100, &mut chip8.memory);
+    fn it_wraps_around_when_skipping_an_invalid_opcode_at_the_end_of_memory(
+    ) -> Result<(), Chip8Error> {
+        let mut chip8 = get_xochip_instance();
+        chip8.set_on_invalid_opcode(OnInvalidOpcode::SkipAndContinue);
+        chip8.memory[0xFFFE..].copy_from_slice(&[0xFF, 0xFF]);
+        chip8.program_counter = 0xFFFE;
 
         chip8.emulate_cycle()?;
 
-        assert_eq!(chip8.program_counter, 0x301);
+        assert_eq!(chip8.program_counter, 0);
 
         Ok(())
     }
 
     #[test]
-    fn it_sets_vx_to_random_number_bitwise_and_nn() -> Result<(), Chip8Error> {
+    fn it_reports_invalid_opcodes_to_the_callback() -> Result<(), Chip8Error> {
+        let reported = Rc::new(Cell::new(None));
         let mut chip8 = get_chip8_instance();
-
-        set_initial_opcode_to(0xC313, &mut chip8.memory);
+        let callback_reported = reported.clone();
+        chip8.set_on_invalid_opcode(OnInvalidOpcode::Callback(Box::new(
+            move |opcode, address| callback_reported.set(Some((opcode, address))),
+        )));
+        set_initial_opcode_to(0x00FF, &mut chip8.memory);
 
         chip8.emulate_cycle()?;
 
-        assert_eq!(chip8.v_registers[3], 0x1);
+        assert_eq!(reported.get(), Some((0x00FF, 0x200)));
+        assert_eq!(chip8.program_counter, 0x202);
 
         Ok(())
     }
 
-    //0xDXYN
     #[test]
-    fn it_draws_the_correct_pixels() -> Result<(), Chip8Error> {
-        let mut chip8 = get_chip8_instance();
-
-        chip8.v_registers[0x1] = 0xAC;
-        chip8.v_registers[0x4] = 0xCA;
-        chip8.index_register = 0x200;
-        chip8.memory[0x200] = 0;
-        chip8.memory[0x201] = 1;
-        chip8.memory[0x201] = 0;
-        chip8.memory[0x201] = 2;
-        chip8.memory[0x201] = 4;
-        set_initial_opcode_to(0xD145, &mut chip8.memory);
-
-        chip8.emulate_cycle()?;
+    fn it_uses_the_quirks_of_the_selected_variant() {
+        let chip8 = Chip8::with_variant(
+            Box::new(MockNumberGenerator),
+            Box::new(MockAudio),
+            Box::new(MockKeyboardDevice),
+            Box::new(MockGraphicsDevice),
+            Chip8Variant::CosmacVip,
+        );
 
-        assert_eq!(chip8.graphics[684..=691], [1, 1, 0, 1, 0, 0, 0, 1]);
-        assert_eq!(chip8.graphics[749..=755], [1, 0, 0, 0, 1, 0, 1]);
-        Ok(())
+        assert_eq!(chip8.variant(), Chip8Variant::CosmacVip);
+        assert_eq!(chip8.quirks, Chip8Variant::CosmacVip.quirks());
     }
 
     #[test]
@@ -990,8 +5327,30 @@ mod tests {
     }
 
     #[test]
-    fn it_waits_for_a_keypress_and_stores_it_in_vx() {
-        // Todo
+    fn it_waits_for_a_keypress_and_stores_it_in_vx() -> Result<(), Chip8Error> {
+        let mut chip8 = Chip8::with_quirks(
+            Box::new(MockNumberGenerator),
+            Box::new(MockAudio),
+            Box::new(IdleKeyboardDevice),
+            Box::new(MockGraphicsDevice),
+            Quirks::default(),
+        );
+        set_initial_opcode_to(0xF30A, &mut chip8.memory);
+        chip8.delay_timer = 5;
+
+        assert_eq!(chip8.emulate_cycle()?, State::WaitingForKey);
+        chip8.keyboard[0xB] = 1;
+        assert_eq!(chip8.emulate_cycle()?, State::WaitingForKey);
+        assert_eq!(chip8.emulate_cycle()?, State::WaitingForKey);
+        assert_eq!(chip8.program_counter, 0x200);
+        assert_eq!(chip8.delay_timer, 2);
+
+        chip8.keyboard[0xB] = 0;
+        assert_eq!(chip8.emulate_cycle()?, State::Continue);
+        assert_eq!(chip8.v_registers[3], 0xB);
+        assert_eq!(chip8.program_counter, 0x202);
+
+        Ok(())
     }
 
     #[test]
@@ -1050,16 +5409,152 @@ mod tests {
     #[test]
     fn it_sets_i_to_sprite_location_read_from_vx() -> Result<(), Chip8Error> {
         let mut chip8 = get_chip8_instance();
-        chip8.v_registers[1] = 10;
+        chip8.v_registers[1] = 0xA;
         set_initial_opcode_to(0xF129, &mut chip8.memory);
 
         chip8.emulate_cycle()?;
 
-        assert_eq!(chip8.index_register, 10);
+        let address = chip8.index_register as usize;
+        assert_eq!(address, FONT_ADDRESS + 50);
+        assert_eq!(chip8.memory[address..address + 5], FONT_SET[50..55]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_sets_i_to_the_big_font_digit_in_vx() -> Result<(), Chip8Error> {
+        let mut chip8 = get_superchip_instance();
+        chip8.v_registers[1] = 2;
+        set_initial_opcode_to(0xF130, &mut chip8.memory);
+
+        chip8.emulate_cycle()?;
+
+        let address = chip8.index_register as usize;
+        assert_eq!(address, 80 + 20);
+        assert_eq!(chip8.memory[address..address + 10], BIG_FONT_SET[20..30]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_saves_and_restores_the_rpl_flags() -> Result<(), Chip8Error> {
+        let mut chip8 = get_superchip_instance();
+        chip8.v_registers[0..3].copy_from_slice(&[7, 8, 9]);
+        set_initial_opcode_to(0xF275, &mut chip8.memory);
+        chip8.memory[0x202] = 0xF2;
+        chip8.memory[0x203] = 0x85;
+
+        chip8.emulate_cycle()?;
+        chip8.v_registers = [0; 16];
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.v_registers[0..4], [7, 8, 9, 0]);
+
+        Ok(())
+    }
+
+    struct MockStorage {
+        flags: Rc<Cell<[u8; 8]>>,
+    }
+
+    impl Storage for MockStorage {
+        fn load_flags(&mut self) -> Result<[u8; 8], Chip8Error> {
+            Ok(self.flags.get())
+        }
+
+        fn save_flags(&mut self, flags: &[u8; 8]) -> Result<(), Chip8Error> {
+            self.flags.set(*flags);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn it_persists_the_rpl_flags_through_storage() -> Result<(), Chip8Error> {
+        let flags = Rc::new(Cell::new([1, 2, 3, 4, 5, 6, 7, 8]));
+        let mut chip8 = get_superchip_instance();
+        chip8.set_storage(Box::new(MockStorage {
+            flags: flags.clone(),
+        }))?;
+        set_initial_opcode_to(0xF185, &mut chip8.memory);
+        chip8.memory[0x202] = 0xF0;
+        chip8.memory[0x203] = 0x75;
+
+        chip8.emulate_cycle()?;
+        assert_eq!(chip8.v_registers[0..3], [1, 2, 0]);
+
+        chip8.v_registers[0] = 9;
+        chip8.emulate_cycle()?;
+        assert_eq!(flags.get(), [9, 2, 3, 4, 5, 6, 7, 8]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_errors_when_accessing_memory_out_of_bounds() {
+        for opcode in [0xF333, 0xF255, 0xF265, 0xD015] {
+            let mut chip8 = get_chip8_instance();
+            chip8.index_register = 0xFFE;
+            set_initial_opcode_to(opcode, &mut chip8.memory);
+
+            let result = chip8.emulate_cycle();
+
+            assert!(
+                matches!(
+                    result,
+                    Err(Chip8Error::MemoryOutOfBounds {
+                        address: 0x1000,
+                        context: Some(ErrorContext {
+                            program_counter: 0x200,
+                            ..
+                        })
+                    })
+                ),
+                "{:04X} returned {:?}",
+                opcode,
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn it_wraps_i_around_the_address_width_when_adding_vx() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance();
+        chip8.index_register = 0xFF0;
+        chip8.v_registers[1] = 0x20;
+        set_initial_opcode_to(0xF11E, &mut chip8.memory);
+        chip8.emulate_cycle()?;
+        assert_eq!(chip8.index_register, 0x010);
+
+        let mut chip8 = get_xochip_instance();
+        chip8.index_register = 0xFF0;
+        chip8.v_registers[1] = 0x20;
+        set_initial_opcode_to(0xF11E, &mut chip8.memory);
+        chip8.emulate_cycle()?;
+        assert_eq!(chip8.index_register, 0x1010);
+
+        chip8.index_register = 0xFFF0;
+        set_initial_opcode_to(0xF11E, &mut chip8.memory);
+        chip8.program_counter = 0x200;
+        chip8.emulate_cycle()?;
+        assert_eq!(chip8.index_register, 0x0010);
 
         Ok(())
     }
 
+    #[test]
+    fn it_errors_when_fetching_past_the_end_of_memory() {
+        let mut chip8 = get_chip8_instance();
+        chip8.program_counter = 0xFFF;
+
+        assert!(matches!(
+            chip8.emulate_cycle(),
+            Err(Chip8Error::MemoryOutOfBounds {
+                address: 0x1000,
+                context: None
+            })
+        ));
+    }
+
     #[test]
     fn it_stores_bcd_of_vx_from_i() -> Result<(), Chip8Error> {
         let mut chip8 = get_chip8_instance();
@@ -1094,6 +5589,23 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn it_increments_i_after_storing_registers_with_the_load_store_quirk() -> Result<(), Chip8Error>
+    {
+        let mut chip8 = get_chip8_instance_with_quirks(Quirks {
+            load_store_increments_i: true,
+            ..Quirks::default()
+        });
+        chip8.index_register = 0x204;
+        set_initial_opcode_to(0xF355, &mut chip8.memory);
+
+        chip8.emulate_cycle()?;
+
+        assert_eq!(chip8.index_register, 0x208);
+
+        Ok(())
+    }
+
     #[test]
     fn it_writes_to_v0_to_vx_starting_at_memory_address_i() -> Result<(), Chip8Error> {
         let mut chip8 = get_chip8_instance();
@@ -0,0 +1,103 @@
+//! Keypad keys played by the notes of a MIDI controller, like the pads of a drum machine for
+//! rhythm roms
+//!
+//! Only the mapping lives in the core, frontends read the controller with the MIDI library of
+//! their platform and hand the keys over as `KeyEvent`s from their `Keyboard`
+
+use std::{collections::BTreeMap, str::FromStr};
+
+use crate::keymap::KEYPAD;
+
+// Note of the bottom left pad of most 4x4 pad controllers, the others go up by one to the right
+const FIRST_PAD_NOTE: u8 = 36;
+
+/// Keypad key played by each note
+///
+/// By default the 16 pads of a 4x4 pad controller, notes 36 to 51, are laid out like the keypad:
+/// the bottom row plays `A 0 B F` and the top one `1 2 3 C`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoteMap {
+    keys: BTreeMap<u8, u8>,
+}
+
+impl NoteMap {
+    /// A map without any note bound
+    pub fn empty() -> NoteMap {
+        NoteMap {
+            keys: BTreeMap::new(),
+        }
+    }
+
+    /// Makes `note` play `key`, replacing whatever it played before
+    pub fn bind(&mut self, note: u8, key: u8) {
+        self.keys.insert(note & 0x7F, key & 0xF);
+    }
+
+    /// Keypad key played by `note`
+    pub fn key(&self, note: u8) -> Option<u8> {
+        self.keys.get(&note).copied()
+    }
+}
+
+impl Default for NoteMap {
+    fn default() -> Self {
+        let mut notes = NoteMap::empty();
+        for (row, keys) in KEYPAD.iter().rev().enumerate() {
+            for (column, key) in keys.iter().enumerate() {
+                notes.bind(FIRST_PAD_NOTE + (row * 4 + column) as u8, *key);
+            }
+        }
+        notes
+    }
+}
+
+/// A note bound to a key, `60=5` has middle C play `5`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoteBinding {
+    /// Note from 0 to 127
+    pub note: u8,
+    /// Keypad key from 0x0 to 0xF
+    pub key: u8,
+}
+
+impl FromStr for NoteBinding {
+    type Err = String;
+
+    fn from_str(binding: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid note binding: {}", binding);
+        let (note, key) = binding.split_once('=').ok_or_else(invalid)?;
+        let note = note
+            .trim()
+            .parse::<u8>()
+            .ok()
+            .filter(|note| *note <= 0x7F)
+            .ok_or_else(invalid)?;
+        let key = key.trim();
+        let key = u8::from_str_radix(key.strip_prefix("0x").unwrap_or(key), 16)
+            .ok()
+            .filter(|key| *key <= 0xF)
+            .ok_or_else(invalid)?;
+        Ok(NoteBinding { note, key })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_lays_the_pads_out_like_the_keypad() -> Result<(), String> {
+        let mut notes = NoteMap::default();
+        assert_eq!(notes.key(36), Some(0xA));
+        assert_eq!(notes.key(51), Some(0xC));
+        assert_eq!(notes.key(60), None);
+
+        let binding = "60=0x5".parse::<NoteBinding>()?;
+        notes.bind(binding.note, binding.key);
+        assert_eq!(notes.key(60), Some(0x5));
+        assert!("128=5".parse::<NoteBinding>().is_err());
+        assert!("60=10".parse::<NoteBinding>().is_err());
+
+        Ok(())
+    }
+}
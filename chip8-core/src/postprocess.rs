@@ -0,0 +1,617 @@
+//! Frame postprocessing shared by every frontend
+//!
+//! The framebuffer is converted into an RGBA image using a [`Palette`] and then
+//! handed through a chain of [`PostProcessor`] stages, e.g. scaler → flicker filter → scanlines → OSD
+
+use std::str::FromStr;
+
+/// Colors used to turn the monochrome framebuffer into an RGBA image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    /// Color of the pixels that are off
+    pub background: [u8; 4],
+    /// Color of the pixels that are on
+    pub foreground: [u8; 4],
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette {
+            background: [0, 0, 0, 255],
+            foreground: [255, 255, 255, 255],
+        }
+    }
+}
+
+impl Palette {
+    /// Green on a dark green, like the phosphor of old monochrome monitors
+    pub fn green_phosphor() -> Palette {
+        Palette {
+            background: [0x0A, 0x1A, 0x0F, 255],
+            foreground: [0x33, 0xFF, 0x66, 255],
+        }
+    }
+
+    /// Orange on a dark brown, like amber monochrome monitors
+    pub fn amber() -> Palette {
+        Palette {
+            background: [0x1A, 0x10, 0x00, 255],
+            foreground: [0xFF, 0xB0, 0x00, 255],
+        }
+    }
+
+    /// Dark green on a pale green, like the LCD of early handhelds
+    pub fn lcd() -> Palette {
+        Palette {
+            background: [0x9B, 0xBC, 0x0F, 255],
+            foreground: [0x0F, 0x38, 0x0F, 255],
+        }
+    }
+
+    /// Every preset with its name, in the order frontends cycle through them
+    pub fn presets() -> [(&'static str, Palette); 4] {
+        [
+            ("default", Palette::default()),
+            ("green-phosphor", Palette::green_phosphor()),
+            ("amber", Palette::amber()),
+            ("lcd", Palette::lcd()),
+        ]
+    }
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    fn from_str(palette: &str) -> Result<Self, Self::Err> {
+        match palette.to_ascii_lowercase().as_str() {
+            "default" | "white" => Ok(Palette::default()),
+            "green-phosphor" | "green" => Ok(Palette::green_phosphor()),
+            "amber" => Ok(Palette::amber()),
+            "lcd" => Ok(Palette::lcd()),
+            _ => Err(format!("Unknown palette: {}", palette)),
+        }
+    }
+}
+
+/// Image stored as tightly packed RGBA bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbaImage {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    /// Creates an image filled with the provided color
+    pub fn new(width: u32, height: u32, color: [u8; 4]) -> RgbaImage {
+        let pixels = color
+            .iter()
+            .copied()
+            .cycle()
+            .take((width * height * 4) as usize)
+            .collect();
+
+        RgbaImage {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Converts a framebuffer where each byte is a pixel (0 or 1) into an image
+    pub fn from_framebuffer(
+        graphics: &[u8],
+        width: u32,
+        height: u32,
+        palette: &Palette,
+    ) -> RgbaImage {
+        let pixels = graphics
+            .iter()
+            .take((width * height) as usize)
+            .flat_map(|pixel| match pixel {
+                0 => palette.background,
+                _ => palette.foreground,
+            })
+            .collect();
+
+        RgbaImage {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Width of the image in pixels
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the image in pixels
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Raw RGBA bytes, row by row
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Returns the color at the given coordinates
+    pub fn get_pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let index = self.index(x, y);
+        let mut color = [0; 4];
+        color.copy_from_slice(&self.pixels[index..index + 4]);
+        color
+    }
+
+    /// Sets the color at the given coordinates, ignoring anything outside the image
+    pub fn put_pixel(&mut self, x: u32, y: u32, color: [u8; 4]) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let index = self.index(x, y);
+        self.pixels[index..index + 4].copy_from_slice(&color);
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        ((y * self.width + x) * 4) as usize
+    }
+}
+
+/// A single stage of the postprocessing pipeline
+pub trait PostProcessor {
+    /// Transforms the image produced by the previous stage
+    fn process(&mut self, image: RgbaImage, palette: &Palette) -> RgbaImage;
+}
+
+/// Chain of postprocessing stages applied to every frame
+pub struct Pipeline {
+    palette: Palette,
+    stages: Vec<Box<dyn PostProcessor>>,
+}
+
+impl Pipeline {
+    /// Creates an empty pipeline that only applies the palette
+    pub fn new(palette: Palette) -> Pipeline {
+        Pipeline {
+            palette,
+            stages: Vec::new(),
+        }
+    }
+
+    /// Appends a stage to the end of the pipeline
+    pub fn with_stage<P>(mut self, stage: P) -> Pipeline
+    where
+        P: PostProcessor + 'static,
+    {
+        self.stages.push(Box::new(stage));
+        self
+    }
+
+    /// Palette currently used to color the framebuffer
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    /// Replaces the palette used for the next frames
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    /// Runs the framebuffer through every stage and returns the final image
+    pub fn process(&mut self, graphics: &[u8], width: u32, height: u32) -> RgbaImage {
+        let image = RgbaImage::from_framebuffer(graphics, width, height, &self.palette);
+        let palette = self.palette;
+
+        self.stages
+            .iter_mut()
+            .fold(image, |image, stage| stage.process(image, &palette))
+    }
+}
+
+/// Nearest neighbour integer upscaling
+pub struct Scaler {
+    factor: u32,
+}
+
+impl Scaler {
+    /// Creates a scaler, a factor of 0 is treated as 1
+    pub fn new(factor: u32) -> Scaler {
+        Scaler {
+            factor: factor.max(1),
+        }
+    }
+}
+
+impl PostProcessor for Scaler {
+    fn process(&mut self, image: RgbaImage, _palette: &Palette) -> RgbaImage {
+        if self.factor == 1 {
+            return image;
+        }
+
+        let mut scaled = RgbaImage::new(
+            image.width() * self.factor,
+            image.height() * self.factor,
+            [0; 4],
+        );
+        for y in 0..scaled.height() {
+            for x in 0..scaled.width() {
+                scaled.put_pixel(x, y, image.get_pixel(x / self.factor, y / self.factor));
+            }
+        }
+        scaled
+    }
+}
+
+/// Smooths the XOR flicker of sprites by letting lit pixels fade out over a few frames
+///
+/// Pixels that go off fade from the foreground to the background color, like the phosphor of a CRT
+/// does, so it works the same whichever of the two is the brighter
+pub struct FlickerFilter {
+    persistence: f32,
+    // How lit each pixel still is, from 0.0 to 1.0
+    glow: Vec<f32>,
+}
+
+impl FlickerFilter {
+    /// Creates a filter keeping `persistence` (0.0 to 1.0) of the previous frame
+    pub fn new(persistence: f32) -> FlickerFilter {
+        FlickerFilter {
+            persistence: persistence.clamp(0.0, 1.0),
+            glow: Vec::new(),
+        }
+    }
+}
+
+impl PostProcessor for FlickerFilter {
+    fn process(&mut self, mut image: RgbaImage, palette: &Palette) -> RgbaImage {
+        let pixel_count = (image.width() * image.height()) as usize;
+        if self.glow.len() != pixel_count {
+            self.glow = vec![0.0; pixel_count];
+        }
+
+        for (pixel, glow) in image.pixels.chunks_exact_mut(4).zip(self.glow.iter_mut()) {
+            if pixel == palette.foreground {
+                *glow = 1.0;
+                continue;
+            }
+            *glow *= self.persistence;
+            // Anything drawn in another color than the background stays as it is
+            if pixel != palette.background || *glow <= 0.0 {
+                continue;
+            }
+            for ((channel, background), foreground) in pixel
+                .iter_mut()
+                .zip(palette.background.iter())
+                .zip(palette.foreground.iter())
+            {
+                let difference = f32::from(*foreground) - f32::from(*background);
+                *channel = (f32::from(*background) + difference * *glow) as u8;
+            }
+        }
+        image
+    }
+}
+
+/// Darkens every other row to imitate a CRT
+pub struct Scanlines {
+    intensity: f32,
+}
+
+impl Scanlines {
+    /// Creates the effect, an intensity of 1.0 turns the odd rows black
+    pub fn new(intensity: f32) -> Scanlines {
+        Scanlines {
+            intensity: intensity.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl PostProcessor for Scanlines {
+    fn process(&mut self, mut image: RgbaImage, _palette: &Palette) -> RgbaImage {
+        let row_length = (image.width() * 4) as usize;
+        let brightness = 1.0 - self.intensity;
+
+        for row in image.pixels.chunks_mut(row_length).skip(1).step_by(2) {
+            for pixel in row.chunks_mut(4) {
+                for channel in pixel.iter_mut().take(3) {
+                    *channel = (*channel as f32 * brightness) as u8;
+                }
+            }
+        }
+        image
+    }
+}
+
+/// Bends the image like the curved glass of a CRT, leaving the corners black
+pub struct Curvature {
+    amount: f32,
+}
+
+impl Curvature {
+    /// Creates the effect, an amount of 0.0 leaves the image flat and 1.0 bends it the most
+    pub fn new(amount: f32) -> Curvature {
+        Curvature {
+            amount: amount.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl PostProcessor for Curvature {
+    fn process(&mut self, image: RgbaImage, _palette: &Palette) -> RgbaImage {
+        if self.amount == 0.0 {
+            return image;
+        }
+
+        let (width, height) = (image.width() as f32, image.height() as f32);
+        let mut curved = RgbaImage::new(image.width(), image.height(), [0, 0, 0, 255]);
+        for y in 0..image.height() {
+            for x in 0..image.width() {
+                // From -1.0 to 1.0 across the image, taken at the center of the pixel
+                let u = (x as f32 + 0.5) / width * 2.0 - 1.0;
+                let v = (y as f32 + 0.5) / height * 2.0 - 1.0;
+                let bent_u = u * (1.0 + self.amount * v * v);
+                let bent_v = v * (1.0 + self.amount * u * u);
+                if bent_u.abs() >= 1.0 || bent_v.abs() >= 1.0 {
+                    continue;
+                }
+                let source_x = ((bent_u + 1.0) / 2.0 * width) as u32;
+                let source_y = ((bent_v + 1.0) / 2.0 * height) as u32;
+                curved.put_pixel(x, y, image.get_pixel(source_x, source_y));
+            }
+        }
+        curved
+    }
+}
+
+/// On screen display of a short text message in the top left corner
+pub struct Osd {
+    message: Option<String>,
+    color: [u8; 4],
+    scale: u32,
+}
+
+impl Osd {
+    const GLYPH_WIDTH: u32 = 3;
+    const GLYPH_HEIGHT: u32 = 5;
+    const MARGIN: u32 = 1;
+
+    /// Creates an OSD drawing glyphs scaled by `scale`
+    pub fn new(scale: u32) -> Osd {
+        Osd {
+            message: None,
+            color: [255, 255, 0, 255],
+            scale: scale.max(1),
+        }
+    }
+
+    /// Changes the color of the text
+    pub fn with_color(mut self, color: [u8; 4]) -> Osd {
+        self.color = color;
+        self
+    }
+
+    /// Sets or clears the message shown on the next frames
+    pub fn set_message(&mut self, message: Option<String>) {
+        self.message = message;
+    }
+
+    fn draw_box(&self, image: &mut RgbaImage, width: u32, height: u32, color: [u8; 4]) {
+        for y in 0..height * self.scale {
+            for x in 0..width * self.scale {
+                image.put_pixel(x, y, color);
+            }
+        }
+    }
+
+    fn draw_glyph(&self, image: &mut RgbaImage, glyph: [u8; 5], left: u32, top: u32) {
+        for (row, bits) in glyph.iter().enumerate() {
+            for col in 0..Self::GLYPH_WIDTH {
+                if bits & (0b100 >> col) == 0 {
+                    continue;
+                }
+                for dy in 0..self.scale {
+                    for dx in 0..self.scale {
+                        image.put_pixel(
+                            (left + col) * self.scale + dx,
+                            (top + row as u32) * self.scale + dy,
+                            self.color,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl PostProcessor for Osd {
+    fn process(&mut self, mut image: RgbaImage, palette: &Palette) -> RgbaImage {
+        let message = match &self.message {
+            Some(message) if !message.is_empty() => message,
+            _ => return image,
+        };
+
+        let characters = message.chars().count() as u32;
+        let box_width = Self::MARGIN + characters * (Self::GLYPH_WIDTH + Self::MARGIN);
+        let box_height = Self::GLYPH_HEIGHT + Self::MARGIN * 2;
+        self.draw_box(&mut image, box_width, box_height, palette.background);
+
+        for (index, character) in message.chars().enumerate() {
+            let left = Self::MARGIN + index as u32 * (Self::GLYPH_WIDTH + Self::MARGIN);
+            self.draw_glyph(&mut image, glyph(character), left, Self::MARGIN);
+        }
+        image
+    }
+}
+
+fn glyph(character: char) -> [u8; 5] {
+    match character.to_ascii_uppercase() {
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b110, 0b001, 0b010, 0b100, 0b111],
+        '3' => [0b110, 0b001, 0b010, 0b001, 0b110],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b110, 0b001, 0b110],
+        '6' => [0b011, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b110],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '!' => [0b010, 0b010, 0b010, 0b000, 0b010],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        _ => [0; 5],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WHITE: [u8; 4] = [255, 255, 255, 255];
+    const BLACK: [u8; 4] = [0, 0, 0, 255];
+
+    #[test]
+    fn it_converts_the_framebuffer_using_the_palette() {
+        let palette = Palette {
+            background: [1, 2, 3, 4],
+            foreground: [5, 6, 7, 8],
+        };
+
+        let image = RgbaImage::from_framebuffer(&[0, 1, 1, 0], 2, 2, &palette);
+
+        assert_eq!(
+            image.pixels(),
+            &[1, 2, 3, 4, 5, 6, 7, 8, 5, 6, 7, 8, 1, 2, 3, 4]
+        );
+    }
+
+    #[test]
+    fn it_parses_the_palette_presets_by_name() {
+        for (name, palette) in Palette::presets().iter() {
+            assert_eq!(name.parse::<Palette>(), Ok(*palette));
+        }
+        assert_eq!("Amber".parse::<Palette>(), Ok(Palette::amber()));
+        assert!("purple".parse::<Palette>().is_err());
+    }
+
+    #[test]
+    fn it_scales_each_pixel_into_a_square() {
+        let mut pipeline = Pipeline::new(Palette::default()).with_stage(Scaler::new(2));
+
+        let image = pipeline.process(&[1, 0], 2, 1);
+
+        assert_eq!((image.width(), image.height()), (4, 2));
+        assert_eq!(image.get_pixel(0, 0), WHITE);
+        assert_eq!(image.get_pixel(1, 1), WHITE);
+        assert_eq!(image.get_pixel(2, 0), BLACK);
+        assert_eq!(image.get_pixel(3, 1), BLACK);
+    }
+
+    #[test]
+    fn it_keeps_erased_pixels_lit_for_a_frame_with_the_flicker_filter() {
+        let mut pipeline = Pipeline::new(Palette::default()).with_stage(FlickerFilter::new(0.5));
+
+        pipeline.process(&[1], 1, 1);
+        let image = pipeline.process(&[0], 1, 1);
+
+        assert_eq!(image.get_pixel(0, 0), [127, 127, 127, 255]);
+    }
+
+    #[test]
+    fn it_fades_towards_a_background_brighter_than_the_foreground() {
+        let palette = Palette {
+            background: [200, 200, 200, 255],
+            foreground: [0, 0, 0, 255],
+        };
+        let mut pipeline = Pipeline::new(palette).with_stage(FlickerFilter::new(0.5));
+
+        pipeline.process(&[1, 0], 2, 1);
+        let image = pipeline.process(&[0, 0], 2, 1);
+
+        assert_eq!(image.get_pixel(0, 0), [100, 100, 100, 255]);
+        assert_eq!(image.get_pixel(1, 0), [200, 200, 200, 255]);
+        let image = pipeline.process(&[0, 0], 2, 1);
+        assert_eq!(image.get_pixel(0, 0), [150, 150, 150, 255]);
+    }
+
+    #[test]
+    fn it_darkens_odd_rows_with_scanlines() {
+        let mut pipeline = Pipeline::new(Palette::default()).with_stage(Scanlines::new(1.0));
+
+        let image = pipeline.process(&[1, 1], 1, 2);
+
+        assert_eq!(image.get_pixel(0, 0), WHITE);
+        assert_eq!(image.get_pixel(0, 1), BLACK);
+    }
+
+    #[test]
+    fn it_curves_the_corners_out_of_the_picture() {
+        let mut flat = Pipeline::new(Palette::default()).with_stage(Curvature::new(0.0));
+        let mut curved = Pipeline::new(Palette::default()).with_stage(Curvature::new(0.25));
+
+        let pixels = [1, 0, 0, 0].repeat(16);
+        assert_eq!(
+            flat.process(&pixels, 8, 8),
+            RgbaImage::from_framebuffer(&pixels, 8, 8, &Palette::default())
+        );
+        let image = curved.process(&[1; 64], 8, 8);
+        assert_eq!(image.get_pixel(0, 0), BLACK);
+        assert_eq!(image.get_pixel(7, 7), BLACK);
+        assert_eq!(image.get_pixel(4, 4), WHITE);
+    }
+
+    #[test]
+    fn it_draws_the_osd_message_on_top_of_the_frame() {
+        let mut osd = Osd::new(1).with_color([9, 9, 9, 9]);
+        osd.set_message(Some("1".to_string()));
+        let mut pipeline = Pipeline::new(Palette::default()).with_stage(osd);
+
+        let image = pipeline.process(&[1; 64], 8, 8);
+
+        // Top row of the "1" glyph is 0b010, drawn after a 1 pixel margin
+        assert_eq!(image.get_pixel(1, 1), BLACK);
+        assert_eq!(image.get_pixel(2, 1), [9, 9, 9, 9]);
+        assert_eq!(image.get_pixel(3, 1), BLACK);
+        assert_eq!(image.get_pixel(7, 7), WHITE);
+    }
+
+    #[test]
+    fn it_runs_the_stages_in_order() {
+        let mut pipeline = Pipeline::new(Palette::default())
+            .with_stage(Scaler::new(2))
+            .with_stage(Scanlines::new(1.0));
+
+        let image = pipeline.process(&[1], 1, 1);
+
+        assert_eq!(image.get_pixel(0, 0), WHITE);
+        assert_eq!(image.get_pixel(0, 1), BLACK);
+    }
+}
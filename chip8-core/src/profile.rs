@@ -0,0 +1,97 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::instruction::Instruction;
+
+/// Execution counts gathered while profiling is enabled with `Chip8::set_profiling`
+///
+/// Instructions are counted per family, using the opcode pattern from `Instruction::pattern`,
+/// and per address, which shows where the hot loops of a rom are
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Profile {
+    instructions: u64,
+    families: BTreeMap<&'static str, u64>,
+    addresses: HashMap<u16, u64>,
+}
+
+impl Profile {
+    /// Total number of instructions executed
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    /// Times an instruction of the given family, like `8XY6`, was executed
+    pub fn executions_of(&self, pattern: &str) -> u64 {
+        self.families.get(pattern).copied().unwrap_or(0)
+    }
+
+    /// Every family that was executed at least once with its count, sorted by pattern
+    pub fn families(&self) -> impl Iterator<Item = (&'static str, u64)> + '_ {
+        self.families
+            .iter()
+            .map(|(&pattern, &count)| (pattern, count))
+    }
+
+    /// Times the instruction at `address` was executed
+    pub fn executions_at(&self, address: u16) -> u64 {
+        self.addresses.get(&address).copied().unwrap_or(0)
+    }
+
+    /// The `count` most executed addresses, busiest first
+    pub fn hottest_addresses(&self, count: usize) -> Vec<(u16, u64)> {
+        let mut addresses: Vec<(u16, u64)> = self
+            .addresses
+            .iter()
+            .map(|(&address, &executions)| (address, executions))
+            .collect();
+        addresses.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        addresses.truncate(count);
+        addresses
+    }
+
+    /// Forgets every count
+    pub fn clear(&mut self) {
+        *self = Profile::default();
+    }
+
+    pub(crate) fn record(&mut self, address: u16, instruction: Instruction) {
+        self.instructions += 1;
+        *self.families.entry(instruction.pattern()).or_insert(0) += 1;
+        *self.addresses.entry(address).or_insert(0) += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::decode;
+
+    #[test]
+    fn it_counts_executions_per_family_and_address() {
+        let mut profile = Profile::default();
+
+        profile.record(0x200, decode(0x7001));
+        profile.record(0x202, decode(0x7102));
+        profile.record(0x200, decode(0x7001));
+        profile.record(0x204, decode(0x8016));
+
+        assert_eq!(profile.instructions(), 4);
+        assert_eq!(profile.executions_of("7XNN"), 3);
+        assert_eq!(profile.executions_of("8XY6"), 1);
+        assert_eq!(profile.executions_of("DXYN"), 0);
+        assert_eq!(profile.executions_at(0x200), 2);
+        assert_eq!(
+            profile.families().collect::<Vec<_>>(),
+            vec![("7XNN", 3), ("8XY6", 1)]
+        );
+    }
+
+    #[test]
+    fn it_sorts_the_hottest_addresses_first() {
+        let mut profile = Profile::default();
+        for address in [0x204, 0x202, 0x202, 0x200, 0x202, 0x200].iter() {
+            profile.record(*address, decode(0x00E0));
+        }
+
+        assert_eq!(profile.hottest_addresses(2), vec![(0x202, 3), (0x200, 2)]);
+    }
+}
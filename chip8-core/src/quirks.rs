@@ -0,0 +1,34 @@
+/// Behaviours that differ between chip8 interpreters
+///
+/// Roms are usually written against one specific interpreter, so some of them
+/// only work with the right combination of quirks enabled.
+/// The default is the original COSMAC VIP behaviour minus its display wait and sprite clipping,
+/// which is what most roms expect
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8XY6 and 8XYE shift VY into VX instead of shifting VX in place
+    pub shift_uses_vy: bool,
+    /// FX55 and FX65 leave I pointing after the last register stored or loaded
+    pub load_store_increments_i: bool,
+    /// 8XY1, 8XY2 and 8XY3 reset VF to 0
+    pub logic_resets_vf: bool,
+    /// BXNN jumps to XNN + VX instead of BNNN jumping to NNN + V0
+    pub jump_uses_vx: bool,
+    /// Sprites drawn past the edge of the screen wrap around instead of being clipped
+    pub wrap_sprites: bool,
+    /// DXYN waits for the next frame before the program continues, pacing drawing like the COSMAC VIP
+    pub display_wait: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            logic_resets_vf: false,
+            jump_uses_vx: false,
+            wrap_sprites: true,
+            display_wait: false,
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+
+use crate::keymap::KEYPAD;
+
+/// Binds every key of the keypad to a physical key by asking for them one after the other, so
+/// frontends can remap the keys without the user editing their config
+///
+/// Keys are asked in the order of the keypad, row by row, and named like the frontend names them.
+/// A key can only play one key of the keypad and can't be one the frontend keeps for itself:
+///
+/// ```
+/// use chip8_core::KeyRemap;
+///
+/// let mut remap = KeyRemap::new();
+/// remap.reserve("Escape", "cancelling");
+///
+/// assert_eq!(remap.waiting_for(), Some(0x1));
+/// remap.press("Q")?;
+/// assert!(remap.press("q").is_err());
+/// assert!(remap.press("Escape").is_err());
+/// assert_eq!(remap.waiting_for(), Some(0x2));
+/// # Ok::<(), String>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct KeyRemap {
+    chosen: Vec<(String, u8)>,
+    reserved: Vec<(String, String)>,
+}
+
+impl KeyRemap {
+    /// A remap waiting for the key of `1`, the top left one of the keypad
+    pub fn new() -> KeyRemap {
+        KeyRemap::default()
+    }
+
+    /// Keeps `key` for something else than the keypad, described by `purpose` when it is pressed
+    pub fn reserve(&mut self, key: &str, purpose: &str) {
+        self.reserved.push((key.to_string(), purpose.to_string()));
+    }
+
+    /// Key of the keypad the next key pressed will play, `None` once they are all bound
+    pub fn waiting_for(&self) -> Option<u8> {
+        KEYPAD.iter().flatten().nth(self.chosen.len()).copied()
+    }
+
+    /// What to ask the user for next, short enough to fit over the display
+    pub fn prompt(&self) -> Option<String> {
+        self.waiting_for()
+            .map(|key| format!("Press key for {:X}", key))
+    }
+
+    /// Binds `key` to the key of the keypad asked for, failing without moving on when it is
+    /// already bound or reserved
+    pub fn press(&mut self, key: &str) -> Result<(), String> {
+        let waiting_for = self
+            .waiting_for()
+            .ok_or("Every key of the keypad is already bound")?;
+        if let Some((_, purpose)) = self
+            .reserved
+            .iter()
+            .find(|(reserved, _)| reserved.eq_ignore_ascii_case(key))
+        {
+            return Err(format!("{} is for {}", key, purpose));
+        }
+        if let Some((_, bound)) = self
+            .chosen
+            .iter()
+            .find(|(chosen, _)| chosen.eq_ignore_ascii_case(key))
+        {
+            return Err(format!("{} is already {:X}", key, bound));
+        }
+        self.chosen.push((key.to_string(), waiting_for));
+        Ok(())
+    }
+
+    /// Every key bound so far with the key of the keypad it plays, like a `[keys]` table
+    pub fn bindings(&self) -> BTreeMap<String, u8> {
+        self.chosen.iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_asks_for_every_key_of_the_keypad_in_order() -> Result<(), String> {
+        let mut remap = KeyRemap::new();
+        let keys = "1234QWERASDFZXCV".chars().map(String::from);
+
+        let mut asked = Vec::new();
+        for key in keys {
+            asked.extend(remap.waiting_for());
+            remap.press(&key)?;
+        }
+
+        assert_eq!(
+            asked,
+            vec![0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF]
+        );
+        assert_eq!(remap.prompt(), None);
+        assert_eq!(remap.bindings().get("X"), Some(&0x0));
+        assert!(remap.press("Up").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_refuses_keys_already_bound_or_reserved() -> Result<(), String> {
+        let mut remap = KeyRemap::new();
+        remap.reserve("P", "hotkey pause");
+
+        remap.press("Up")?;
+
+        assert_eq!(remap.press("up"), Err("up is already 1".to_string()));
+        assert_eq!(remap.press("p"), Err("p is for hotkey pause".to_string()));
+        assert_eq!(remap.prompt(), Some("Press key for 2".to_string()));
+
+        Ok(())
+    }
+}
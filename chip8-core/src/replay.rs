@@ -0,0 +1,546 @@
+use std::{convert::TryInto, fmt, str::FromStr, time::Duration};
+
+use crate::{traits::KeyEvent, TIMER_PERIOD};
+
+// Starts the binary form of a replay, followed by the version of the format
+const MAGIC: &[u8; 4] = b"C8RP";
+const VERSION: u8 = 1;
+
+/// Everything needed to run a program again exactly the same way
+///
+/// Made of the seed of the number generator, and for every frame how many instructions it ran,
+/// the keys pressed and released at its end and when the sound started and stopped. Recorded with
+/// `Chip8::start_recording` and played with `Chip8::play_replay`. As text, the seed comes first and
+/// each frame takes a line with its instructions then `+K` and `-K` for the keys going down and up,
+/// and `on@N` and `off@N` for the sound turning on and off after N instructions of the frame:
+///
+/// ```text
+/// seed 42
+/// 8
+/// 9 +5 on@3
+/// 8 -5 +A off@8
+/// ```
+///
+/// `to_bytes` and `from_bytes` keep it in a compact binary form instead, with every number in
+/// little-endian byte order whatever the platform that recorded it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Replay {
+    seed: u64,
+    frames: Vec<ReplayFrame>,
+}
+
+/// A frame of a `Replay`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplayFrame {
+    /// Instructions the frame was asked to run
+    pub instructions: usize,
+    /// Keys of the keypad that went down or up at the end of the frame, oldest first
+    pub events: Vec<KeyEvent>,
+    /// Times the sound turned on or off during the frame, oldest first
+    pub sounds: Vec<ReplaySound>,
+}
+
+/// The sound turning on or off during a frame of a `Replay`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReplaySound {
+    /// Instructions the frame had run, the timers tick after the last one
+    pub cycle: usize,
+    /// Whether the sound turned on rather than off
+    pub on: bool,
+}
+
+impl Replay {
+    /// An empty replay whose number generator starts from `seed`
+    pub fn new(seed: u64) -> Replay {
+        Replay {
+            seed,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Seed of the number generator
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Every frame, in the order they ran
+    pub fn frames(&self) -> &[ReplayFrame] {
+        &self.frames
+    }
+
+    /// Whether the sound was recorded, replays written before it was have none at all
+    pub fn has_sounds(&self) -> bool {
+        self.frames.iter().any(|frame| !frame.sounds.is_empty())
+    }
+
+    /// When the sound turned on and off, from the start of the replay
+    ///
+    /// Frames last 1/60 of a second with their instructions spread evenly over it, so exporting a
+    /// replay to a video can place the beeps at the same sample whatever the host audio latency
+    pub fn sound_offsets(&self) -> Vec<(Duration, bool)> {
+        let mut offsets = Vec::new();
+        for (index, frame) in self.frames.iter().enumerate() {
+            let start = TIMER_PERIOD * index as u32;
+            for sound in &frame.sounds {
+                let cycle = sound.cycle.min(frame.instructions) as u32;
+                let into_frame = match frame.instructions {
+                    0 => Duration::default(),
+                    instructions => TIMER_PERIOD * cycle / instructions as u32,
+                };
+                offsets.push((start + into_frame, sound.on));
+            }
+        }
+        offsets
+    }
+
+    /// The replay in its binary form
+    ///
+    /// After the magic bytes and the version come the seed as a u64 and the number of frames as a
+    /// u32. Each frame is its instructions as a u32, its key events as a u16 count then a byte
+    /// each, the key with the high bit set when it went down, and its sounds as a u16 count then a
+    /// u32 cycle and a byte set to 1 when the sound turned on
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&self.seed.to_le_bytes());
+        bytes.extend_from_slice(&(self.frames.len() as u32).to_le_bytes());
+        for frame in &self.frames {
+            bytes.extend_from_slice(&(frame.instructions as u32).to_le_bytes());
+            let events = frame
+                .events
+                .iter()
+                .filter_map(|event| match event {
+                    KeyEvent::Down(key) => Some(0x80 | key),
+                    KeyEvent::Up(key) => Some(*key),
+                    KeyEvent::Quit => None,
+                })
+                .collect::<Vec<u8>>();
+            bytes.extend_from_slice(&(events.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(&events);
+            bytes.extend_from_slice(&(frame.sounds.len() as u16).to_le_bytes());
+            for sound in &frame.sounds {
+                bytes.extend_from_slice(&(sound.cycle as u32).to_le_bytes());
+                bytes.push(sound.on as u8);
+            }
+        }
+        bytes
+    }
+
+    /// Reads a replay in either form, the binary one when it starts like `to_bytes` writes it
+    pub fn read(bytes: &[u8]) -> Result<Replay, String> {
+        if bytes.starts_with(MAGIC) {
+            Replay::from_bytes(bytes)
+        } else {
+            String::from_utf8_lossy(bytes).parse()
+        }
+    }
+
+    /// Reads a replay written by `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<Replay, String> {
+        let mut reader = ByteReader { bytes };
+        if reader.take(MAGIC.len()) != Some(&MAGIC[..]) {
+            return Err("Not a binary replay".to_string());
+        }
+        match reader.u8() {
+            Some(VERSION) => (),
+            Some(version) => return Err(format!("Unsupported replay version {}", version)),
+            None => return Err(truncated()),
+        }
+        let seed = reader.u64().ok_or_else(truncated)?;
+        let frame_count = reader.u32().ok_or_else(truncated)?;
+        let frames = (0..frame_count)
+            .map(|index| {
+                reader
+                    .frame()
+                    .ok_or_else(truncated)?
+                    .map_err(|error| format!("{} in frame {}", error, index))
+            })
+            .collect::<Result<_, _>>()?;
+        if !reader.bytes.is_empty() {
+            return Err("Unexpected bytes after the last frame".to_string());
+        }
+        Ok(Replay { seed, frames })
+    }
+}
+
+fn truncated() -> String {
+    "The replay is cut short".to_string()
+}
+
+// Reads the little-endian numbers of a binary replay from the front of the bytes left
+struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, count: usize) -> Option<&'a [u8]> {
+        if self.bytes.len() < count {
+            return None;
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Some(taken)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|bytes| bytes[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_le_bytes(self.take(2)?.try_into().ok()?))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+
+    // None when the bytes run out, an error when they hold something a frame can't
+    fn frame(&mut self) -> Option<Result<ReplayFrame, String>> {
+        let instructions = self.u32()? as usize;
+        let event_count = self.u16()?;
+        let mut events = Vec::new();
+        for byte in self.take(event_count.into())? {
+            if byte & 0x70 != 0 {
+                return Some(Err(format!("Invalid key event {:#04X}", byte)));
+            }
+            let key = byte & 0xF;
+            events.push(if byte & 0x80 != 0 {
+                KeyEvent::Down(key)
+            } else {
+                KeyEvent::Up(key)
+            });
+        }
+        let sound_count = self.u16()?;
+        let mut sounds = Vec::new();
+        for _ in 0..sound_count {
+            let cycle = self.u32()? as usize;
+            let on = match self.u8()? {
+                0 => false,
+                1 => true,
+                state => return Some(Err(format!("Invalid sound state {}", state))),
+            };
+            sounds.push(ReplaySound { cycle, on });
+        }
+        Some(Ok(ReplayFrame {
+            instructions,
+            events,
+            sounds,
+        }))
+    }
+}
+
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "seed {}", self.seed)?;
+        for frame in &self.frames {
+            write!(f, "{}", frame.instructions)?;
+            for event in &frame.events {
+                match event {
+                    KeyEvent::Down(key) => write!(f, " +{:X}", key)?,
+                    KeyEvent::Up(key) => write!(f, " -{:X}", key)?,
+                    KeyEvent::Quit => (),
+                }
+            }
+            for sound in &frame.sounds {
+                let state = if sound.on { "on" } else { "off" };
+                write!(f, " {}@{}", state, sound.cycle)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Replay {
+    type Err = String;
+
+    fn from_str(replay: &str) -> Result<Self, Self::Err> {
+        let mut lines = replay.lines().enumerate();
+        let seed = lines
+            .next()
+            .and_then(|(_, line)| line.strip_prefix("seed "))
+            .and_then(|seed| seed.trim().parse().ok())
+            .ok_or_else(|| "A replay starts with its seed".to_string())?;
+
+        let frames = lines
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                parse_frame(line).ok_or_else(|| format!("Invalid frame on line {}", index + 1))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Replay { seed, frames })
+    }
+}
+
+fn parse_frame(line: &str) -> Option<ReplayFrame> {
+    let mut words = line.split_whitespace();
+    let instructions = words.next()?.parse().ok()?;
+    let mut events = Vec::new();
+    let mut sounds = Vec::new();
+    for word in words {
+        if let Some((state, cycle)) = word.split_once('@') {
+            let on = match state {
+                "on" => true,
+                "off" => false,
+                _ => return None,
+            };
+            sounds.push(ReplaySound {
+                cycle: cycle.parse().ok()?,
+                on,
+            });
+            continue;
+        }
+        let key = u8::from_str_radix(word.get(1..)?, 16)
+            .ok()
+            .filter(|key| *key <= 0xF)?;
+        events.push(match word.chars().next()? {
+            '+' => KeyEvent::Down(key),
+            '-' => KeyEvent::Up(key),
+            _ => return None,
+        });
+    }
+    Some(ReplayFrame {
+        instructions,
+        events,
+        sounds,
+    })
+}
+
+/// What the interpreter does with the replay it was given
+pub(crate) enum ReplayMode {
+    Recording(Replay),
+    /// `next` is the frame `run_frame` runs next, `pending` the one whose keys `present` applies
+    /// and `sound` the first of its sounds not played yet. `with_sounds` is false for replays
+    /// recorded without their sound, which leave it to the sound timer
+    Playing {
+        replay: Replay,
+        next: usize,
+        pending: Option<usize>,
+        sound: usize,
+        with_sounds: bool,
+    },
+}
+
+impl ReplayMode {
+    /// Plays `replay` from its first frame
+    pub(crate) fn playing(replay: Replay) -> ReplayMode {
+        ReplayMode::Playing {
+            with_sounds: replay.has_sounds(),
+            replay,
+            next: 0,
+            pending: None,
+            sound: 0,
+        }
+    }
+
+    /// Instructions the frame about to run should run, recording them or taking them from the replay
+    ///
+    /// Returns None once the replay is over
+    pub(crate) fn start_frame(&mut self, instructions: usize) -> Option<usize> {
+        match self {
+            ReplayMode::Recording(replay) => {
+                replay.frames.push(ReplayFrame {
+                    instructions,
+                    events: Vec::new(),
+                    sounds: Vec::new(),
+                });
+                Some(instructions)
+            }
+            ReplayMode::Playing {
+                replay,
+                next,
+                pending,
+                sound,
+                ..
+            } => {
+                let frame = replay.frames.get(*next)?;
+                *pending = Some(*next);
+                *next += 1;
+                *sound = 0;
+                Some(frame.instructions)
+            }
+        }
+    }
+
+    /// Whether the keys come from the replay instead of the keyboard
+    pub(crate) fn is_playing(&self) -> bool {
+        matches!(self, ReplayMode::Playing { .. })
+    }
+
+    /// Keeps a key read from the keyboard at the end of the frame
+    pub(crate) fn record(&mut self, event: KeyEvent) {
+        if let ReplayMode::Recording(replay) = self {
+            if let Some(frame) = replay.frames.last_mut() {
+                frame.events.push(event);
+            }
+        }
+    }
+
+    /// Whether the sound comes from the replay instead of the sound timer
+    pub(crate) fn plays_sounds(&self) -> bool {
+        matches!(
+            self,
+            ReplayMode::Playing {
+                with_sounds: true,
+                ..
+            }
+        )
+    }
+
+    /// Keeps the sound turning on or off after `cycle` instructions of the frame
+    pub(crate) fn record_sound(&mut self, cycle: usize, on: bool) {
+        if let ReplayMode::Recording(replay) = self {
+            if let Some(frame) = replay.frames.last_mut() {
+                frame.sounds.push(ReplaySound { cycle, on });
+            }
+        }
+    }
+
+    /// Sounds of the running frame due once it ran `cycle` instructions, when playing
+    pub(crate) fn take_sounds(&mut self, cycle: usize) -> Vec<bool> {
+        match self {
+            ReplayMode::Playing {
+                replay,
+                pending: Some(frame),
+                sound,
+                ..
+            } => {
+                let due = replay.frames[*frame].sounds[*sound..]
+                    .iter()
+                    .take_while(|due| due.cycle <= cycle)
+                    .map(|due| due.on)
+                    .collect::<Vec<_>>();
+                *sound += due.len();
+                due
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Keys of the frame that just ran, when playing
+    pub(crate) fn take_events(&mut self) -> Vec<KeyEvent> {
+        match self {
+            ReplayMode::Playing {
+                replay, pending, ..
+            } => pending
+                .take()
+                .map(|frame| replay.frames[frame].events.clone())
+                .unwrap_or_default(),
+            ReplayMode::Recording(_) => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reads_back_a_replay_written_as_text() {
+        let mut replay = Replay::new(42);
+        replay.frames.push(ReplayFrame {
+            instructions: 8,
+            events: Vec::new(),
+            sounds: Vec::new(),
+        });
+        replay.frames.push(ReplayFrame {
+            instructions: 9,
+            events: vec![KeyEvent::Down(5), KeyEvent::Up(0xA)],
+            sounds: vec![ReplaySound { cycle: 3, on: true }],
+        });
+
+        let text = replay.to_string();
+
+        assert_eq!(text, "seed 42\n8\n9 +5 -A on@3\n");
+        assert_eq!(text.parse::<Replay>(), Ok(replay));
+        assert!("8\n9 +5".parse::<Replay>().is_err());
+        assert!("seed 42\n9 +G".parse::<Replay>().is_err());
+        assert!("seed 42\n9 up@3".parse::<Replay>().is_err());
+    }
+
+    #[test]
+    fn it_places_the_sounds_from_the_start_of_the_replay() {
+        let replay = "seed 0\n10\n10 on@5\n10 off@10\n"
+            .parse::<Replay>()
+            .unwrap();
+
+        assert!(replay.has_sounds());
+        assert_eq!(
+            replay.sound_offsets(),
+            vec![
+                (TIMER_PERIOD + TIMER_PERIOD / 2, true),
+                (TIMER_PERIOD * 3, false)
+            ]
+        );
+    }
+
+    // Written by hand from the format, so a change to the encoding can't go unnoticed
+    const BINARY_REPLAY: [u8; 40] = [
+        b'C', b'8', b'R', b'P', 1, // magic and version
+        0x08, 0x07, 0x06, 0x05, 0x04, 0x03, 0x02, 0x01, // seed
+        0x02, 0x00, 0x00, 0x00, // frames
+        0x34, 0x12, 0x00, 0x00, // instructions of the first frame
+        0x02, 0x00, 0x85, 0x0A, // key 5 down, key A up
+        0x01, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, // sound on after 3 instructions
+        0x08, 0x00, 0x00, 0x00, // instructions of the second frame
+        0x00, 0x00, 0x00, 0x00, // no keys or sounds
+    ];
+
+    fn get_binary_replay() -> Replay {
+        let mut replay = Replay::new(0x0102_0304_0506_0708);
+        replay.frames.push(ReplayFrame {
+            instructions: 0x1234,
+            events: vec![KeyEvent::Down(5), KeyEvent::Up(0xA)],
+            sounds: vec![ReplaySound { cycle: 3, on: true }],
+        });
+        replay.frames.push(ReplayFrame {
+            instructions: 8,
+            events: Vec::new(),
+            sounds: Vec::new(),
+        });
+        replay
+    }
+
+    #[test]
+    fn it_writes_binary_replays_in_little_endian() {
+        assert_eq!(get_binary_replay().to_bytes(), BINARY_REPLAY.to_vec());
+    }
+
+    #[test]
+    fn it_reads_back_binary_replays() {
+        assert_eq!(Replay::from_bytes(&BINARY_REPLAY), Ok(get_binary_replay()));
+        assert_eq!(
+            Replay::from_bytes(&get_binary_replay().to_bytes()),
+            Ok(get_binary_replay())
+        );
+        assert_eq!(Replay::read(&BINARY_REPLAY), Ok(get_binary_replay()));
+        assert_eq!(
+            Replay::read(get_binary_replay().to_string().as_bytes()),
+            Ok(get_binary_replay())
+        );
+    }
+
+    #[test]
+    fn it_rejects_broken_binary_replays() {
+        assert!(Replay::from_bytes(b"seed 42\n8\n").is_err());
+        assert!(Replay::from_bytes(&BINARY_REPLAY[..BINARY_REPLAY.len() - 1]).is_err());
+
+        let mut newer = BINARY_REPLAY;
+        newer[4] = 2;
+        assert_eq!(
+            Replay::from_bytes(&newer),
+            Err("Unsupported replay version 2".to_string())
+        );
+
+        let mut bad_key = BINARY_REPLAY;
+        bad_key[23] = 0x15;
+        assert_eq!(
+            Replay::from_bytes(&bad_key),
+            Err("Invalid key event 0x15 in frame 0".to_string())
+        );
+    }
+}
@@ -0,0 +1,45 @@
+use std::collections::VecDeque;
+
+use crate::snapshot::Snapshot;
+
+/// Snapshots taken every few frames, oldest first, each with the frame it was taken on
+pub(crate) struct RewindBuffer {
+    depth: usize,
+    interval: u64,
+    snapshots: VecDeque<(u64, Snapshot)>,
+}
+
+impl RewindBuffer {
+    pub(crate) fn new(depth: usize, interval: u64) -> RewindBuffer {
+        RewindBuffer {
+            depth,
+            interval: interval.max(1),
+            snapshots: VecDeque::with_capacity(depth),
+        }
+    }
+
+    pub(crate) fn is_due(&self, frame: u64) -> bool {
+        self.depth > 0 && frame.is_multiple_of(self.interval)
+    }
+
+    pub(crate) fn push(&mut self, frame: u64, snapshot: Snapshot) {
+        if self.snapshots.len() == self.depth {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back((frame, snapshot));
+    }
+
+    /// Newest snapshot taken on or before `frame`, or the oldest one when they are all newer
+    ///
+    /// Snapshots newer than the one returned are dropped, they belong to a future that won't happen
+    pub(crate) fn rewind_to(&mut self, frame: u64) -> Option<&(u64, Snapshot)> {
+        while self.snapshots.len() > 1 && self.snapshots.back()?.0 > frame {
+            self.snapshots.pop_back();
+        }
+        self.snapshots.back()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+}
@@ -0,0 +1,84 @@
+#[cfg(feature = "rand")]
+use rand::Rng;
+use std::cell::Cell;
+
+use crate::{errors::Chip8Error, traits::NumberGenerator};
+
+/// Small seedable number generator for reproducible runs
+///
+/// Uses SplitMix64, which is fast and good enough for CXNN but not for anything cryptographic
+#[derive(Debug, Clone)]
+pub struct SeededNumberGenerator {
+    state: Cell<u64>,
+}
+
+impl SeededNumberGenerator {
+    /// Creates a generator that always produces the same numbers for the same seed
+    pub fn new(seed: u64) -> SeededNumberGenerator {
+        SeededNumberGenerator {
+            state: Cell::new(seed),
+        }
+    }
+
+    fn next_u64(&self) -> u64 {
+        let state = self.state.get().wrapping_add(0x9E37_79B9_7F4A_7C15);
+        self.state.set(state);
+
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+impl NumberGenerator for SeededNumberGenerator {
+    fn generate(&self) -> Result<u8, Chip8Error> {
+        Ok((self.next_u64() >> 56) as u8)
+    }
+}
+
+/// Number generator backed by the thread local generator of the `rand` crate
+#[cfg(feature = "rand")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomNumberGenerator;
+
+#[cfg(feature = "rand")]
+impl NumberGenerator for RandomNumberGenerator {
+    fn generate(&self) -> Result<u8, Chip8Error> {
+        Ok(rand::thread_rng().gen())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_repeats_the_sequence_for_the_same_seed() -> Result<(), Chip8Error> {
+        let first = SeededNumberGenerator::new(42);
+        let second = SeededNumberGenerator::new(42);
+
+        for _ in 0..100 {
+            assert_eq!(first.generate()?, second.generate()?);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_produces_different_sequences_for_different_seeds() -> Result<(), Chip8Error> {
+        let first = SeededNumberGenerator::new(1);
+        let second = SeededNumberGenerator::new(2);
+
+        let first_numbers = (0..16)
+            .map(|_| first.generate())
+            .collect::<Result<Vec<_>, _>>()?;
+        let second_numbers = (0..16)
+            .map(|_| second.generate())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        assert_ne!(first_numbers, second_numbers);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+//! Database of known roms, identified by the SHA-1 of their contents
+//!
+//! Frontends use it to pick the variant, quirks and speed a game was written for
+//! without asking the player to know them
+
+use crate::{quirks::Quirks, variant::Chip8Variant};
+
+/// What is known about a rom
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RomInfo {
+    /// Lowercase hex SHA-1 of the rom
+    pub sha1: &'static str,
+    /// Name of the game or program
+    pub title: &'static str,
+    /// Interpreter the rom was written for
+    pub variant: Chip8Variant,
+    /// Instructions per second it plays best at
+    pub hertz: u32,
+    /// Quirks it needs when they differ from the ones of its variant
+    pub quirks: Option<Quirks>,
+}
+
+impl RomInfo {
+    /// Quirks to run the rom with
+    pub fn quirks(&self) -> Quirks {
+        self.quirks.unwrap_or_else(|| self.variant.quirks())
+    }
+}
+
+const KNOWN_ROMS: &[RomInfo] = &[
+    RomInfo {
+        sha1: "1ba58656810b67fd131eb9af3e3987863bf26c90",
+        title: "IBM Logo",
+        variant: Chip8Variant::Chip8,
+        hertz: 500,
+        quirks: None,
+    },
+    RomInfo {
+        sha1: "5c28a5f85289c9d859f95fd5eadbdcb1c30bb08b",
+        title: "Space Invaders",
+        variant: Chip8Variant::Chip8,
+        hertz: 500,
+        quirks: None,
+    },
+    RomInfo {
+        sha1: "4d7f6ba126a4335eb67708d1aae1f58aab887f63",
+        title: "C8 Test",
+        variant: Chip8Variant::Chip8,
+        hertz: 500,
+        quirks: None,
+    },
+    RomInfo {
+        sha1: "821751787374cc362f4c58759961f0aa7a2fd410",
+        title: "Flight Runner",
+        variant: Chip8Variant::Chip8,
+        hertz: 500,
+        quirks: None,
+    },
+    RomInfo {
+        sha1: "f1cfcffe1937ed6dd6eeed1a7f85dfc777bda700",
+        title: "Chip8 Opcode Test",
+        variant: Chip8Variant::Chip8,
+        hertz: 500,
+        quirks: None,
+    },
+];
+
+/// Lowercase hex SHA-1 of a rom
+pub fn sha1(rom: &[u8]) -> String {
+    sha1_smol::Sha1::from(rom).digest().to_string()
+}
+
+/// Looks a rom up by its contents
+pub fn lookup(rom: &[u8]) -> Option<&'static RomInfo> {
+    lookup_sha1(&sha1(rom))
+}
+
+/// Looks a rom up by its SHA-1, ignoring case
+pub fn lookup_sha1(sha1: &str) -> Option<&'static RomInfo> {
+    KNOWN_ROMS
+        .iter()
+        .find(|rom| rom.sha1.eq_ignore_ascii_case(sha1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_finds_bundled_roms_by_their_contents() {
+        let rom = include_bytes!("../../roms/IBM Logo.ch8");
+
+        let info = lookup(rom).map(|info| info.title);
+
+        assert_eq!(info, Some("IBM Logo"));
+    }
+
+    #[test]
+    fn it_hashes_roms_with_sha1() {
+        assert_eq!(sha1(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(lookup(&[0x12, 0x00]), None);
+    }
+}
@@ -0,0 +1,100 @@
+/// How a `RamSearch` narrows down its candidates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchFilter {
+    /// Keeps the addresses holding this value
+    Equal(u8),
+    /// Keeps the addresses whose value changed since the last filter
+    Changed,
+    /// Keeps the addresses whose value went up since the last filter
+    Increased,
+    /// Keeps the addresses whose value went down since the last filter
+    Decreased,
+}
+
+impl SearchFilter {
+    fn matches(self, previous: u8, current: u8) -> bool {
+        match self {
+            SearchFilter::Equal(value) => current == value,
+            SearchFilter::Changed => current != previous,
+            SearchFilter::Increased => current > previous,
+            SearchFilter::Decreased => current < previous,
+        }
+    }
+}
+
+/// Finds where a program keeps a value, like its lives or score, by filtering memory again and
+/// again while the value changes in game
+///
+/// It starts with every address as a candidate and a snapshot of memory, every filter compares
+/// memory to the snapshot taken by the previous one. The addresses left are usually turned into
+/// cheats or watchpoints
+#[derive(Debug, Clone)]
+pub struct RamSearch {
+    snapshot: Vec<u8>,
+    candidates: Vec<u16>,
+}
+
+impl RamSearch {
+    /// Starts a search with every address of `memory` as a candidate
+    pub fn new(memory: &[u8]) -> RamSearch {
+        RamSearch {
+            snapshot: memory.to_vec(),
+            candidates: (0..memory.len()).map(|address| address as u16).collect(),
+        }
+    }
+
+    /// Drops the candidates `filter` doesn't match and returns how many are left
+    pub fn filter(&mut self, memory: &[u8], filter: SearchFilter) -> usize {
+        let snapshot = &self.snapshot;
+        self.candidates.retain(|address| {
+            let address = *address as usize;
+            match (snapshot.get(address), memory.get(address)) {
+                (Some(previous), Some(current)) => filter.matches(*previous, *current),
+                _ => false,
+            }
+        });
+        self.snapshot.clear();
+        self.snapshot.extend_from_slice(memory);
+        self.candidates.len()
+    }
+
+    /// Addresses still matching every filter, in increasing order
+    pub fn candidates(&self) -> &[u16] {
+        &self.candidates
+    }
+
+    /// Value at `address` when the last filter ran, or when the search started
+    pub fn previous(&self, address: u16) -> Option<u8> {
+        self.snapshot.get(address as usize).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_narrows_down_the_address_of_a_counter() {
+        let mut memory = [3, 3, 0, 7];
+        let mut search = RamSearch::new(&memory);
+
+        assert_eq!(search.filter(&memory, SearchFilter::Equal(3)), 2);
+        memory = [2, 3, 1, 7];
+        assert_eq!(search.filter(&memory, SearchFilter::Decreased), 1);
+        assert_eq!(search.candidates(), [0]);
+
+        memory = [2, 4, 2, 6];
+        assert_eq!(search.filter(&memory, SearchFilter::Changed), 0);
+        assert_eq!(search.previous(3), Some(6));
+    }
+
+    #[test]
+    fn it_compares_to_the_memory_of_the_previous_filter() {
+        let mut search = RamSearch::new(&[0, 5]);
+
+        search.filter(&[1, 4], SearchFilter::Changed);
+        search.filter(&[2, 4], SearchFilter::Increased);
+
+        assert_eq!(search.candidates(), [0]);
+    }
+}
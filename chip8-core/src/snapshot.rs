@@ -0,0 +1,376 @@
+#[cfg(feature = "save-states")]
+use std::convert::TryFrom;
+use std::ops::Range;
+
+#[cfg(feature = "save-states")]
+use crate::DEFAULT_PITCH;
+use crate::{framebuffer::FrameBuffer, CpuSnapshot, DisplayMode, KeyWait, ProgramEnd};
+
+/// The whole state of the interpreter at one point of the emulation
+///
+/// Taken with `Chip8::snapshot` and given back to `Chip8::restore`. Devices, settings like quirks
+/// or breakpoints, and the keys held on the keyboard aren't part of it.
+///
+/// With the `save-states` feature it can be serialized, to keep it in a file. Registers, stack
+/// and memory are serialized as numbers rather than as their bytes in memory, so a state saved on
+/// any platform loads on any other whatever its byte order
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "save-states",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "SavedSnapshot")
+)]
+pub struct Snapshot {
+    pub(crate) cpu: CpuSnapshot,
+    pub(crate) memory: Vec<u8>,
+    pub(crate) graphics: FrameBuffer,
+    pub(crate) second_plane: FrameBuffer,
+    pub(crate) selected_planes: u8,
+    pub(crate) display_mode: DisplayMode,
+    pub(crate) audio_pattern: Option<[u8; 16]>,
+    pub(crate) pitch: u8,
+    pub(crate) rpl_flags: [u8; 8],
+    pub(crate) program_end: Option<ProgramEnd>,
+    pub(crate) key_wait: Option<KeyWait>,
+    pub(crate) waiting_for_next_frame: bool,
+}
+
+// What a save state holds, checked so that restoring it can't index the stack, keypad or display
+// out of bounds. States saved before XO-CHIP came along have a blank second plane
+#[cfg(feature = "save-states")]
+#[derive(serde::Deserialize)]
+struct SavedSnapshot {
+    cpu: CpuSnapshot,
+    memory: Vec<u8>,
+    graphics: FrameBuffer,
+    #[serde(default)]
+    second_plane: Option<FrameBuffer>,
+    #[serde(default = "first_plane")]
+    selected_planes: u8,
+    display_mode: DisplayMode,
+    #[serde(default)]
+    audio_pattern: Option<[u8; 16]>,
+    #[serde(default = "default_pitch")]
+    pitch: u8,
+    rpl_flags: [u8; 8],
+    program_end: Option<ProgramEnd>,
+    key_wait: Option<KeyWait>,
+    waiting_for_next_frame: bool,
+}
+
+#[cfg(feature = "save-states")]
+impl TryFrom<SavedSnapshot> for Snapshot {
+    type Error = String;
+
+    fn try_from(saved: SavedSnapshot) -> Result<Snapshot, String> {
+        if saved.cpu.stack_pointer as usize > saved.cpu.stack.len() {
+            return Err(format!(
+                "the stack pointer {} is past the end of the stack",
+                saved.cpu.stack_pointer
+            ));
+        }
+        if let Some(KeyWait::Release(key)) = saved.key_wait {
+            if key > 0xF {
+                return Err(format!("waiting for the release of key {:#X}", key));
+            }
+        }
+        let (width, height) = saved.display_mode.resolution();
+        let second_plane = saved
+            .second_plane
+            .unwrap_or_else(|| FrameBuffer::new(width, height));
+        for plane in [&saved.graphics, &second_plane] {
+            if (plane.width(), plane.height()) != (width, height) {
+                return Err(format!(
+                    "the display is {}x{} instead of {}x{}",
+                    plane.width(),
+                    plane.height(),
+                    width,
+                    height
+                ));
+            }
+        }
+        Ok(Snapshot {
+            cpu: saved.cpu,
+            memory: saved.memory,
+            graphics: saved.graphics,
+            second_plane,
+            selected_planes: saved.selected_planes,
+            display_mode: saved.display_mode,
+            audio_pattern: saved.audio_pattern,
+            pitch: saved.pitch,
+            rpl_flags: saved.rpl_flags,
+            program_end: saved.program_end,
+            key_wait: saved.key_wait,
+            waiting_for_next_frame: saved.waiting_for_next_frame,
+        })
+    }
+}
+
+#[cfg(feature = "save-states")]
+fn first_plane() -> u8 {
+    1
+}
+
+#[cfg(feature = "save-states")]
+fn default_pitch() -> u8 {
+    DEFAULT_PITCH
+}
+
+impl Snapshot {
+    /// Registers, stack and timers
+    pub fn cpu(&self) -> &CpuSnapshot {
+        &self.cpu
+    }
+
+    /// The whole memory
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    /// The display, its first plane for XO-CHIP
+    pub fn framebuffer(&self) -> &FrameBuffer {
+        &self.graphics
+    }
+
+    /// What changed between this snapshot and `other`, which is treated as the later one
+    pub fn diff(&self, other: &Snapshot) -> StateDiff {
+        StateDiff {
+            registers: register_changes(&self.cpu, &other.cpu),
+            memory: memory_changes(&self.memory, &other.memory),
+            pixels: pixel_changes(&self.graphics, &other.graphics),
+        }
+    }
+}
+
+/// A register of the CPU
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    /// The program counter
+    ProgramCounter,
+    /// The I register
+    Index,
+    /// One of V0 to VF
+    V(u8),
+    /// Number of return addresses on the stack
+    StackPointer,
+    /// One of the 16 entries of the stack
+    Stack(u8),
+    /// The delay timer
+    DelayTimer,
+    /// The sound timer
+    SoundTimer,
+}
+
+/// A register that holds a different value in two snapshots
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RegisterChange {
+    /// Register that changed
+    pub register: Register,
+    /// Value in the earlier snapshot
+    pub before: u16,
+    /// Value in the later snapshot
+    pub after: u16,
+}
+
+/// Differences between two snapshots, returned by `Snapshot::diff`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateDiff {
+    /// Registers that changed, in the order of `Register`
+    pub registers: Vec<RegisterChange>,
+    /// Addresses of the bytes that changed, with consecutive addresses merged into one range
+    pub memory: Vec<Range<usize>>,
+    /// Column and row of the pixels that changed, row by row.
+    /// Every pixel is listed when the resolution changed
+    pub pixels: Vec<(usize, usize)>,
+}
+
+impl StateDiff {
+    /// Whether nothing changed
+    pub fn is_empty(&self) -> bool {
+        self.registers.is_empty() && self.memory.is_empty() && self.pixels.is_empty()
+    }
+}
+
+fn register_changes(before: &CpuSnapshot, after: &CpuSnapshot) -> Vec<RegisterChange> {
+    let mut values = vec![
+        (
+            Register::ProgramCounter,
+            before.program_counter,
+            after.program_counter,
+        ),
+        (Register::Index, before.index_register, after.index_register),
+    ];
+    values.extend((0..16).map(|index| {
+        (
+            Register::V(index as u8),
+            u16::from(before.v_registers[index]),
+            u16::from(after.v_registers[index]),
+        )
+    }));
+    values.push((
+        Register::StackPointer,
+        before.stack_pointer,
+        after.stack_pointer,
+    ));
+    values.extend((0..16).map(|index| {
+        (
+            Register::Stack(index as u8),
+            before.stack[index],
+            after.stack[index],
+        )
+    }));
+    values.push((
+        Register::DelayTimer,
+        before.delay_timer.into(),
+        after.delay_timer.into(),
+    ));
+    values.push((
+        Register::SoundTimer,
+        before.sound_timer.into(),
+        after.sound_timer.into(),
+    ));
+
+    values
+        .into_iter()
+        .filter(|(_, before, after)| before != after)
+        .map(|(register, before, after)| RegisterChange {
+            register,
+            before,
+            after,
+        })
+        .collect()
+}
+
+fn memory_changes(before: &[u8], after: &[u8]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    let length = before.len().max(after.len());
+    for address in 0..length {
+        if before.get(address) == after.get(address) {
+            continue;
+        }
+        match ranges.last_mut() {
+            Some(range) if range.end == address => range.end += 1,
+            _ => ranges.push(address..address + 1),
+        }
+    }
+
+    ranges
+}
+
+fn pixel_changes(before: &FrameBuffer, after: &FrameBuffer) -> Vec<(usize, usize)> {
+    let resized = before.width() != after.width() || before.height() != after.height();
+    (0..after.height())
+        .flat_map(|y| (0..after.width()).map(move |x| (x, y)))
+        .filter(|&(x, y)| resized || before.get_pixel(x, y) != after.get_pixel(x, y))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_snapshot() -> Snapshot {
+        Snapshot {
+            cpu: CpuSnapshot {
+                program_counter: 0x200,
+                opcode: 0,
+                index_register: 0,
+                v_registers: [0; 16],
+                stack: [0; 16],
+                stack_pointer: 0,
+                delay_timer: 0,
+                sound_timer: 0,
+            },
+            memory: vec![0; 4096],
+            graphics: FrameBuffer::new(64, 32),
+            second_plane: FrameBuffer::new(64, 32),
+            selected_planes: 1,
+            display_mode: DisplayMode::LowResolution,
+            audio_pattern: None,
+            pitch: 64,
+            rpl_flags: [0; 8],
+            program_end: None,
+            key_wait: None,
+            waiting_for_next_frame: false,
+        }
+    }
+
+    #[test]
+    fn it_finds_nothing_between_equal_snapshots() {
+        assert!(get_snapshot().diff(&get_snapshot()).is_empty());
+    }
+
+    #[test]
+    fn it_lists_the_changed_registers_memory_and_pixels() {
+        let before = get_snapshot();
+        let mut after = get_snapshot();
+        after.cpu.program_counter = 0x202;
+        after.cpu.v_registers[3] = 7;
+        after.memory[0x300..0x303].copy_from_slice(&[1, 2, 3]);
+        after.memory[0x310] = 4;
+        after.graphics.set_pixel(5, 1, true);
+
+        let diff = before.diff(&after);
+
+        assert_eq!(
+            diff.registers,
+            vec![
+                RegisterChange {
+                    register: Register::ProgramCounter,
+                    before: 0x200,
+                    after: 0x202
+                },
+                RegisterChange {
+                    register: Register::V(3),
+                    before: 0,
+                    after: 7
+                },
+            ]
+        );
+        assert_eq!(diff.memory, vec![0x300..0x303, 0x310..0x311]);
+        assert_eq!(diff.pixels, vec![(5, 1)]);
+    }
+
+    #[test]
+    fn it_lists_every_pixel_when_the_resolution_changed() {
+        let before = get_snapshot();
+        let mut after = get_snapshot();
+        after.graphics = FrameBuffer::new(128, 64);
+
+        assert_eq!(before.diff(&after).pixels.len(), 128 * 64);
+    }
+
+    #[test]
+    #[cfg(feature = "save-states")]
+    fn it_saves_to_a_file_and_refuses_broken_ones() {
+        let mut snapshot = get_snapshot();
+        snapshot.cpu.stack_pointer = 1;
+        snapshot.key_wait = Some(KeyWait::Release(4));
+        snapshot.graphics.set_pixel(5, 1, true);
+
+        let saved = toml::to_string(&snapshot).unwrap();
+        assert_eq!(toml::from_str::<Snapshot>(&saved).unwrap(), snapshot);
+
+        let broken = saved.replace("stack_pointer = 1", "stack_pointer = 17");
+        assert!(toml::from_str::<Snapshot>(&broken).is_err());
+        let broken = saved.replace("height = 32", "height = 64");
+        assert!(toml::from_str::<Snapshot>(&broken).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "save-states")]
+    fn it_saves_multi_byte_values_as_numbers() {
+        let mut snapshot = get_snapshot();
+        snapshot.cpu.program_counter = 0x1234;
+        snapshot.cpu.index_register = 0x0ABC;
+        snapshot.cpu.stack[0] = 0x0F02;
+        snapshot.cpu.stack_pointer = 1;
+
+        let saved = toml::to_string(&snapshot).unwrap();
+
+        assert!(saved.contains("program_counter = 4660\n"));
+        assert!(saved.contains("index_register = 2748\n"));
+        assert!(saved.contains("stack = [3842, 0,"));
+        assert_eq!(toml::from_str::<Snapshot>(&saved).unwrap(), snapshot);
+    }
+}
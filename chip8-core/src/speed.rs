@@ -0,0 +1,151 @@
+use std::time::Duration;
+
+// Past this much time behind, usually after the window was dragged or the machine slept,
+// the controller gives up on catching up instead of running a burst of instructions
+const MAX_CATCH_UP: Duration = Duration::from_millis(250);
+
+/// Works out how many instructions to run to keep a target speed in real time
+///
+/// Frontends call `instructions_due` once per frame with the current time, usually from a
+/// `Clock`, and hand the result to `Chip8::run_frame`. Leftover fractions of an instruction are
+/// carried to the next call, so the average speed stays on target whatever the frame rate is
+#[derive(Debug, Clone)]
+pub struct SpeedController {
+    hertz: u32,
+    fast_forward: u32,
+    slow_motion: u32,
+    paused: bool,
+    last_update: Option<Duration>,
+    pending: f64,
+}
+
+impl SpeedController {
+    /// Runs `hertz` instructions per second
+    pub fn new(hertz: u32) -> SpeedController {
+        SpeedController {
+            hertz,
+            fast_forward: 1,
+            slow_motion: 1,
+            paused: false,
+            last_update: None,
+            pending: 0.0,
+        }
+    }
+
+    /// Instructions per second at normal speed
+    pub fn hertz(&self) -> u32 {
+        self.hertz
+    }
+
+    /// Changes the instructions per second at normal speed
+    pub fn set_hertz(&mut self, hertz: u32) {
+        self.hertz = hertz;
+    }
+
+    /// How many times faster than normal it runs, 1 being normal speed
+    pub fn fast_forward(&self) -> u32 {
+        self.fast_forward
+    }
+
+    /// Runs `multiplier` times faster, 1 goes back to normal speed
+    pub fn set_fast_forward(&mut self, multiplier: u32) {
+        self.fast_forward = multiplier.max(1);
+    }
+
+    /// How many times slower than normal it runs, 1 being normal speed
+    pub fn slow_motion(&self) -> u32 {
+        self.slow_motion
+    }
+
+    /// Runs `divisor` times slower, 1 goes back to normal speed
+    ///
+    /// It combines with fast forwarding, both at 4 run at normal speed
+    pub fn set_slow_motion(&mut self, divisor: u32) {
+        self.slow_motion = divisor.max(1);
+    }
+
+    /// Stops handing out instructions until `resume`
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Hands out instructions again, the time spent paused isn't made up for
+    pub fn resume(&mut self) {
+        if self.paused {
+            self.paused = false;
+            self.last_update = None;
+        }
+    }
+
+    /// Whether it is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Instructions to run now to keep up with the time elapsed since the last call
+    ///
+    /// The first call after creating or resuming the controller starts counting and returns 0
+    pub fn instructions_due(&mut self, now: Duration) -> usize {
+        if self.paused {
+            return 0;
+        }
+        let elapsed = match self.last_update.replace(now) {
+            Some(last_update) => now.saturating_sub(last_update).min(MAX_CATCH_UP),
+            None => return 0,
+        };
+
+        self.pending +=
+            elapsed.as_secs_f64() * f64::from(self.hertz) * f64::from(self.fast_forward)
+                / f64::from(self.slow_motion);
+        let due = self.pending.floor();
+        self.pending -= due;
+        due as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_carries_fractions_of_an_instruction_between_frames() {
+        let mut speed = SpeedController::new(500);
+        let frame = Duration::from_micros(16_667);
+
+        let due: usize = (0..=60)
+            .map(|frame_number| speed.instructions_due(frame * frame_number))
+            .sum();
+
+        assert_eq!(due, 500);
+    }
+
+    #[test]
+    fn it_runs_faster_when_fast_forwarding_and_stops_when_paused() {
+        let mut speed = SpeedController::new(600);
+        speed.instructions_due(Duration::from_millis(0));
+
+        speed.set_fast_forward(4);
+        assert_eq!(speed.instructions_due(Duration::from_millis(10)), 24);
+
+        speed.pause();
+        assert_eq!(speed.instructions_due(Duration::from_millis(20)), 0);
+
+        speed.resume();
+        speed.set_fast_forward(1);
+        assert_eq!(speed.instructions_due(Duration::from_secs(5)), 0);
+        // Falling seconds behind only catches up on a quarter of a second
+        assert_eq!(speed.instructions_due(Duration::from_secs(10)), 150);
+    }
+
+    #[test]
+    fn it_runs_slower_in_slow_motion() {
+        let mut speed = SpeedController::new(600);
+        speed.instructions_due(Duration::from_millis(0));
+
+        speed.set_slow_motion(4);
+        assert_eq!(speed.instructions_due(Duration::from_millis(100)), 15);
+
+        speed.set_fast_forward(4);
+        assert_eq!(speed.instructions_due(Duration::from_millis(200)), 60);
+    }
+}
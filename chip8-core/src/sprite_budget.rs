@@ -0,0 +1,60 @@
+/// How many sprites `run_frame` lets the program draw per frame, with counters of what it held back
+///
+/// The COSMAC VIP could only draw so many sprites between two interrupts, and the speed of some
+/// games is balanced around it. Set with `Chip8::set_sprite_limit`, once the limit is reached the
+/// next DXYN waits for the following frame while everything before it runs as usual
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpriteBudget {
+    limit: Option<usize>,
+    drawn_this_frame: usize,
+    drawn: u64,
+    deferred_frames: u64,
+}
+
+impl SpriteBudget {
+    /// Most sprites drawn in a frame, `None` when there is no limit
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    /// Sprites drawn since the frame started
+    pub fn drawn_this_frame(&self) -> usize {
+        self.drawn_this_frame
+    }
+
+    /// Sprites drawn since the limit was last set, or since the interpreter was created
+    pub fn drawn(&self) -> u64 {
+        self.drawn
+    }
+
+    /// Frames that ended early on a DXYN over the limit since it was last set
+    pub fn deferred_frames(&self) -> u64 {
+        self.deferred_frames
+    }
+
+    pub(crate) fn set_limit(&mut self, limit: Option<usize>) {
+        *self = SpriteBudget {
+            limit,
+            drawn_this_frame: self.drawn_this_frame,
+            ..SpriteBudget::default()
+        };
+    }
+
+    pub(crate) fn is_spent(&self) -> bool {
+        self.limit
+            .is_some_and(|limit| self.drawn_this_frame >= limit)
+    }
+
+    pub(crate) fn record_draw(&mut self) {
+        self.drawn_this_frame += 1;
+        self.drawn += 1;
+    }
+
+    pub(crate) fn record_deferred(&mut self) {
+        self.deferred_frames += 1;
+    }
+
+    pub(crate) fn next_frame(&mut self) {
+        self.drawn_this_frame = 0;
+    }
+}
@@ -0,0 +1,139 @@
+use std::io::{self, Write};
+
+use crate::CpuSnapshot;
+
+/// Writes one line per instruction executed, to compare runs against other chip8 implementations
+///
+/// Lines show the state right before the instruction runs, in hexadecimal:
+///
+/// `PC=0200 OP=6A12 I=0000 V=00,00,00,00,00,00,00,00,00,00,00,00,00,00,00,00 SP=0 DT=00 ST=00`
+///
+/// By default every instruction is traced, `starting_at` and `stopping_at` narrow it down to a window
+/// of instructions counted from the moment the tracer is given to `Chip8::set_tracer`
+pub struct Tracer {
+    sink: Box<dyn Write>,
+    start: u64,
+    stop: Option<u64>,
+    instructions: u64,
+}
+
+impl Tracer {
+    /// Traces every instruction to `sink`, wrap it in a `BufWriter` when it is a file
+    pub fn new<W: Write + 'static>(sink: W) -> Tracer {
+        Tracer {
+            sink: Box::new(sink),
+            start: 0,
+            stop: None,
+            instructions: 0,
+        }
+    }
+
+    /// Skips the instructions before the `instruction`th one
+    pub fn starting_at(mut self, instruction: u64) -> Tracer {
+        self.start = instruction;
+        self
+    }
+
+    /// Stops tracing once `instruction` instructions ran
+    pub fn stopping_at(mut self, instruction: u64) -> Tracer {
+        self.stop = Some(instruction);
+        self
+    }
+
+    /// Number of instructions seen so far, traced or not
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    /// Flushes the lines written so far
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.sink.flush()
+    }
+
+    /// The line written for the given state
+    pub fn format_line(cpu: &CpuSnapshot) -> String {
+        let v_registers: Vec<String> = cpu
+            .v_registers
+            .iter()
+            .map(|value| format!("{:02X}", value))
+            .collect();
+        format!(
+            "PC={:04X} OP={:04X} I={:04X} V={} SP={:X} DT={:02X} ST={:02X}",
+            cpu.program_counter,
+            cpu.opcode,
+            cpu.index_register,
+            v_registers.join(","),
+            cpu.stack_pointer,
+            cpu.delay_timer,
+            cpu.sound_timer
+        )
+    }
+
+    pub(crate) fn trace(&mut self, cpu: &CpuSnapshot) -> io::Result<()> {
+        let instruction = self.instructions;
+        self.instructions += 1;
+        if instruction < self.start || self.stop.is_some_and(|stop| instruction >= stop) {
+            return Ok(());
+        }
+
+        writeln!(self.sink, "{}", Tracer::format_line(cpu))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, rc::Rc};
+
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+    impl Write for SharedBuffer {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(bytes)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn get_cpu(program_counter: u16) -> CpuSnapshot {
+        let mut v_registers = [0; 16];
+        v_registers[0xA] = 0x12;
+        CpuSnapshot {
+            program_counter,
+            opcode: 0x6A12,
+            index_register: 0x2F0,
+            v_registers,
+            stack: [0; 16],
+            stack_pointer: 1,
+            delay_timer: 0x3C,
+            sound_timer: 0,
+        }
+    }
+
+    #[test]
+    fn it_formats_a_stable_line() {
+        assert_eq!(
+            Tracer::format_line(&get_cpu(0x200)),
+            "PC=0200 OP=6A12 I=02F0 V=00,00,00,00,00,00,00,00,00,00,12,00,00,00,00,00 SP=1 DT=3C ST=00"
+        );
+    }
+
+    #[test]
+    fn it_only_traces_the_configured_window() -> io::Result<()> {
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let mut tracer = Tracer::new(SharedBuffer(Rc::clone(&output)))
+            .starting_at(1)
+            .stopping_at(3);
+
+        for address in (0x200..0x208).step_by(2) {
+            tracer.trace(&get_cpu(address))?;
+        }
+
+        let output = String::from_utf8(output.borrow().clone()).expect("the trace is text");
+        let addresses: Vec<&str> = output.lines().map(|line| &line[..7]).collect();
+        assert_eq!(addresses, vec!["PC=0202", "PC=0204"]);
+        assert_eq!(tracer.instructions(), 4);
+        Ok(())
+    }
+}
@@ -1,13 +1,38 @@
-use crate::errors::Chip8Error;
+use std::time::Duration;
+
+use crate::{errors::Chip8Error, framebuffer::Frame};
+
+/// Input event reported by a keyboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyEvent {
+    /// A key of the chip8 keypad, from 0x0 to 0xF, was pressed
+    Down(u8),
+    /// A key of the chip8 keypad, from 0x0 to 0xF, was released
+    Up(u8),
+    /// The user wants to quit
+    Quit,
+}
 
 /// Trait to hook up keyboard events to the interpreter
 pub trait Keyboard {
-    /// Updates the current state of the keyboard
+    /// Returns the events that happened since the last call, oldest first
     ///
-    /// Returns true if the user triggered an exit event
-    fn update_state(&mut self, keyboard: &mut [u8; 16]) -> bool;
-    /// Add support for blocking and waiting for the next key press
-    fn wait_next_key_press(&mut self) -> u8;
+    /// The core keeps track of which keys are held, so frontends only map their input to keypad keys
+    fn poll_events(&mut self) -> Vec<KeyEvent>;
+    /// Whether there are input events waiting to be handled
+    ///
+    /// When this returns false the core skips `poll_events` for the cycle, so implementations
+    /// that can check their queue cheaply should override it
+    fn has_pending_events(&mut self) -> bool {
+        true
+    }
+    /// Blocks until an input event comes or `timeout` went by, returning false when it can't wait
+    ///
+    /// Called through `Chip8::wait_for_input` while the program is idle, the events are left for
+    /// the next `poll_events`. By default it doesn't wait, leaving the frontend to sleep instead
+    fn wait_for_events(&mut self, _timeout: Duration) -> bool {
+        false
+    }
 }
 
 /// Trait to generate a random number
@@ -18,14 +43,49 @@ pub trait NumberGenerator {
 
 /// Trait to handle the audio device used
 pub trait Audio {
-    /// Start audio output
-    fn play(&self) -> Result<(), Chip8Error>;
-    /// Stop audio output
+    /// Start audio output when the program sets the sound timer
+    ///
+    /// `duration` is how long the sound timer will take to run out. It is called again with the
+    /// new duration when the program sets the timer while the sound is playing
+    fn play(&self, duration: Duration) -> Result<(), Chip8Error>;
+    /// Stop audio output, when the sound timer reaches zero or the interpreter is reset
     fn stop(&self) -> Result<(), Chip8Error>;
+    /// Plays the 128 bits of `pattern` in a loop instead of the usual tone, set by XO-CHIP
+    /// programs with F002 and FX3A
+    ///
+    /// Bits are played at `4000 * 2^((pitch - 64) / 48)` per second, a set bit pushing the speaker
+    /// out. `pattern` is `None` until the program loads one and again after a reset, for the usual
+    /// tone. By default the pattern is ignored and the tone stays the same
+    fn set_pattern(&self, _pattern: Option<[u8; 16]>, _pitch: u8) -> Result<(), Chip8Error> {
+        Ok(())
+    }
+}
+
+/// Trait for devices mapped over a range of memory, like a serial port or a test mailbox
+///
+/// DXYN, FX33, FX55 and FX65 reach the device instead of RAM for the addresses in its range
+pub trait Peripheral {
+    /// Returns the byte at `offset` from the start of the mapped range
+    fn read(&mut self, offset: u16) -> u8;
+    /// Receives the byte written at `offset` from the start of the mapped range
+    fn write(&mut self, offset: u16, value: u8);
+}
+
+/// Trait to persist the SUPER-CHIP RPL user flags across runs
+///
+/// Games use FX75 and FX85 to keep things like high scores, so frontends can save them to disk
+pub trait Storage {
+    /// Returns the flags saved by a previous run
+    fn load_flags(&mut self) -> Result<[u8; 8], Chip8Error>;
+    /// Saves the flags after FX75 updated them
+    fn save_flags(&mut self, flags: &[u8; 8]) -> Result<(), Chip8Error>;
 }
 
 /// Trait to handle graphics drawing on the screen
 pub trait Graphics {
     /// Provides the current state of the graphics so it can be drawn on screen
-    fn draw(&mut self, graphics: &[u8]) -> Result<(), Chip8Error>;
+    ///
+    /// The frame is 64x32 pixels, 64x64 for two-page roms or 128x64 when a SUPER-CHIP
+    /// rom switched to high resolution. It is only called when the display changed since the last draw
+    fn draw(&mut self, frame: &Frame) -> Result<(), Chip8Error>;
 }
@@ -0,0 +1,97 @@
+use std::str::FromStr;
+
+use crate::{instruction::Instruction, quirks::Quirks};
+
+/// Well known chip8 interpreters the core can behave like
+///
+/// The variant decides which instructions are decoded and the default quirks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Chip8Variant {
+    /// The interpretation of chip8 this crate has always followed
+    #[default]
+    Chip8,
+    /// The original interpreter running on the COSMAC VIP
+    CosmacVip,
+    /// CHIP-48 for the HP-48 calculators
+    Chip48,
+    /// SUPER-CHIP 1.1, adding high resolution and scrolling
+    SuperChip,
+    /// XO-CHIP, building on top of SUPER-CHIP with 64 KiB of memory, a second display plane and
+    /// audio patterns
+    XoChip,
+}
+
+impl Chip8Variant {
+    /// Quirks matching how the variant behaves
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Chip8Variant::Chip8 => Quirks::default(),
+            Chip8Variant::CosmacVip => Quirks {
+                shift_uses_vy: true,
+                load_store_increments_i: true,
+                logic_resets_vf: true,
+                jump_uses_vx: false,
+                wrap_sprites: false,
+                display_wait: true,
+            },
+            Chip8Variant::Chip48 | Chip8Variant::SuperChip => Quirks {
+                shift_uses_vy: false,
+                load_store_increments_i: false,
+                logic_resets_vf: false,
+                jump_uses_vx: true,
+                wrap_sprites: false,
+                display_wait: false,
+            },
+            Chip8Variant::XoChip => Quirks {
+                shift_uses_vy: true,
+                load_store_increments_i: true,
+                logic_resets_vf: false,
+                jump_uses_vx: false,
+                wrap_sprites: true,
+                display_wait: false,
+            },
+        }
+    }
+
+    /// Whether the SUPER-CHIP instructions (scrolling, high resolution, big font, RPL flags) are decoded
+    pub fn has_superchip_instructions(self) -> bool {
+        match self {
+            Chip8Variant::SuperChip | Chip8Variant::XoChip => true,
+            Chip8Variant::Chip8 | Chip8Variant::CosmacVip | Chip8Variant::Chip48 => false,
+        }
+    }
+
+    /// Whether the XO-CHIP instructions (long I, register ranges, planes, audio patterns) are decoded
+    pub fn has_xochip_instructions(self) -> bool {
+        self == Chip8Variant::XoChip
+    }
+
+    /// Whether programs running on the variant can use `instruction`
+    pub fn supports(self, instruction: Instruction) -> bool {
+        (!instruction.requires_superchip() || self.has_superchip_instructions())
+            && (!instruction.requires_xochip() || self.has_xochip_instructions())
+    }
+
+    /// Bytes of memory programs can address
+    pub fn memory_size(self) -> usize {
+        match self {
+            Chip8Variant::XoChip => 0x10000,
+            _ => 4096,
+        }
+    }
+}
+
+impl FromStr for Chip8Variant {
+    type Err = String;
+
+    fn from_str(variant: &str) -> Result<Self, Self::Err> {
+        match variant.to_ascii_lowercase().as_str() {
+            "chip8" | "chip-8" => Ok(Chip8Variant::Chip8),
+            "vip" | "cosmac-vip" => Ok(Chip8Variant::CosmacVip),
+            "chip48" | "chip-48" => Ok(Chip8Variant::Chip48),
+            "schip" | "superchip" | "super-chip" => Ok(Chip8Variant::SuperChip),
+            "xochip" | "xo-chip" => Ok(Chip8Variant::XoChip),
+            _ => Err(format!("Unknown chip8 variant: {}", variant)),
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use std::{path::PathBuf, str::FromStr};
+
+use chip8_core::SearchFilter;
+
+/// A single debugger command, parsed from a line typed by the user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// `break <address>`: stops before the instruction at the address runs
+    Break(u16),
+    /// `delete <address>`: removes the breakpoint at the address
+    Delete(u16),
+    /// `breakpoints`: lists the breakpoints
+    Breakpoints,
+    /// `watch <address>`: stops after an instruction changes the byte at the address
+    Watch(u16),
+    /// `unwatch <address>`: removes the watchpoint at the address
+    Unwatch(u16),
+    /// `watchpoints`: lists the watchpoints
+    Watchpoints,
+    /// `script <address> [path]`: runs the rhai script at the path whenever the emulation stops
+    /// at the breakpoint or watchpoint on the address, leaving the path out removes the script
+    Script {
+        /// Address of the breakpoint or watchpoint
+        address: u16,
+        /// File with the script
+        path: Option<PathBuf>,
+    },
+    /// `step`: runs a single instruction
+    Step,
+    /// `next`: runs a single instruction, stepping over subroutine calls
+    Next,
+    /// `until <address>`: runs until the program counter reaches the address
+    Until(u16),
+    /// `continue`: runs until a breakpoint is hit or the program ends
+    Continue,
+    /// `registers`: prints the registers, the stack and the timers
+    Registers,
+    /// `examine <address> [length]`: prints the bytes of memory starting at the address
+    Examine {
+        /// First address to print
+        address: u16,
+        /// Number of bytes to print
+        length: u16,
+    },
+    /// `disassemble [address] [count]`: prints the instructions starting at the address,
+    /// or at the program counter when it is left out
+    Disassemble {
+        /// First address to disassemble
+        address: Option<u16>,
+        /// Number of instructions to print
+        count: u16,
+    },
+    /// `search new`: starts searching memory, with every address as a candidate
+    SearchStart,
+    /// `search eq <value>`, `search changed`, `search increased` or `search decreased`: keeps the
+    /// candidates matching the filter
+    Search(SearchFilter),
+    /// `search`: lists the candidates left
+    SearchResults,
+    /// `help`: lists the commands
+    Help,
+    /// `quit`: stops debugging
+    Quit,
+}
+
+fn parse_address(argument: Option<&str>) -> Result<u16, String> {
+    let argument = argument.ok_or_else(|| "Missing address".to_string())?;
+    let digits = argument.trim_start_matches("0x");
+    u16::from_str_radix(digits, 16).map_err(|_| format!("Invalid address: {}", argument))
+}
+
+fn parse_value(argument: Option<&str>) -> Result<u8, String> {
+    let argument = argument.ok_or_else(|| "Missing value".to_string())?;
+    let digits = argument.trim_start_matches("0x");
+    u8::from_str_radix(digits, 16).map_err(|_| format!("Invalid value: {}", argument))
+}
+
+fn parse_search<'a>(mut words: impl Iterator<Item = &'a str>) -> Result<Command, String> {
+    Ok(match words.next() {
+        None | Some("list") => Command::SearchResults,
+        Some("new") => Command::SearchStart,
+        Some("eq") => Command::Search(SearchFilter::Equal(parse_value(words.next())?)),
+        Some("changed") => Command::Search(SearchFilter::Changed),
+        Some("increased") => Command::Search(SearchFilter::Increased),
+        Some("decreased") => Command::Search(SearchFilter::Decreased),
+        Some(filter) => return Err(format!("Unknown search filter: {}", filter)),
+    })
+}
+
+fn parse_count(argument: Option<&str>, default: u16) -> Result<u16, String> {
+    match argument {
+        Some(count) => count
+            .parse()
+            .map_err(|_| format!("Invalid count: {}", count)),
+        None => Ok(default),
+    }
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    /// Addresses are written in hexadecimal, with or without `0x`, and most commands have a one letter alias
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut words = line.split_whitespace();
+        let name = words.next().ok_or_else(|| "Empty command".to_string())?;
+        let command = match name {
+            "break" | "b" => Command::Break(parse_address(words.next())?),
+            "delete" | "d" => Command::Delete(parse_address(words.next())?),
+            "breakpoints" | "bl" => Command::Breakpoints,
+            "watch" | "w" => Command::Watch(parse_address(words.next())?),
+            "unwatch" | "uw" => Command::Unwatch(parse_address(words.next())?),
+            "watchpoints" | "wl" => Command::Watchpoints,
+            "script" => Command::Script {
+                address: parse_address(words.next())?,
+                path: words.next().map(PathBuf::from),
+            },
+            "step" | "s" => Command::Step,
+            "next" | "n" => Command::Next,
+            "until" | "u" => Command::Until(parse_address(words.next())?),
+            "continue" | "c" => Command::Continue,
+            "registers" | "r" => Command::Registers,
+            "examine" | "x" => Command::Examine {
+                address: parse_address(words.next())?,
+                length: parse_count(words.next(), 16)?,
+            },
+            "disassemble" | "dis" => Command::Disassemble {
+                address: words
+                    .next()
+                    .map(|word| parse_address(Some(word)))
+                    .transpose()?,
+                count: parse_count(words.next(), 8)?,
+            },
+            "search" | "sr" => parse_search(words.by_ref())?,
+            "help" | "h" => Command::Help,
+            "quit" | "q" => Command::Quit,
+            _ => return Err(format!("Unknown command: {}", name)),
+        };
+
+        match words.next() {
+            Some(extra) => Err(format!("Unexpected argument: {}", extra)),
+            None => Ok(command),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_commands_and_their_aliases() {
+        assert_eq!("break 0x2A0".parse(), Ok(Command::Break(0x2A0)));
+        assert_eq!("b 2a0".parse(), Ok(Command::Break(0x2A0)));
+        assert_eq!("n".parse(), Ok(Command::Next));
+        assert_eq!("w 300".parse(), Ok(Command::Watch(0x300)));
+        assert_eq!(
+            "script 2a0 log.rhai".parse(),
+            Ok(Command::Script {
+                address: 0x2A0,
+                path: Some(PathBuf::from("log.rhai"))
+            })
+        );
+        assert_eq!(
+            "x 300 4".parse(),
+            Ok(Command::Examine {
+                address: 0x300,
+                length: 4
+            })
+        );
+        assert_eq!(
+            "sr eq 0x03".parse(),
+            Ok(Command::Search(SearchFilter::Equal(0x03)))
+        );
+        assert_eq!("search".parse(), Ok(Command::SearchResults));
+        assert_eq!(
+            "dis".parse(),
+            Ok(Command::Disassemble {
+                address: None,
+                count: 8
+            })
+        );
+    }
+
+    #[test]
+    fn it_rejects_invalid_commands() {
+        assert!("jump 200".parse::<Command>().is_err());
+        assert!("break".parse::<Command>().is_err());
+        assert!("break zzz".parse::<Command>().is_err());
+        assert!("step 2".parse::<Command>().is_err());
+        assert!("search eq 100".parse::<Command>().is_err());
+    }
+}
@@ -0,0 +1,509 @@
+use std::fmt::Write;
+#[cfg(feature = "scripting")]
+use std::fs;
+
+use chip8_core::{decode, Chip8, Chip8Error, Component, RamSearch, SearchFilter, State};
+
+use crate::command::Command;
+#[cfg(feature = "scripting")]
+use crate::script::Scripts;
+
+const HELP: &str = "\
+break <address>          b   stop before the instruction at the address runs
+delete <address>         d   remove the breakpoint at the address
+breakpoints              bl  list the breakpoints
+watch <address>          w   stop after an instruction changes the byte at the address
+unwatch <address>        uw  remove the watchpoint at the address
+watchpoints              wl  list the watchpoints
+script <address> [path]      run a rhai script when stopping at the address, or remove it
+step                     s   run a single instruction
+next                     n   run a single instruction, stepping over subroutine calls
+until <address>          u   run until the program counter reaches the address
+continue                 c   run until a breakpoint is hit or the program ends
+registers                r   print the registers, the stack and the timers
+examine <address> [len]  x   print the bytes of memory starting at the address
+disassemble [addr] [n]   dis print the instructions starting at the address or the program counter
+search new               sr  start searching memory for a value, like lives or a score
+search eq <value>            keep the addresses holding the value
+search changed               keep the addresses that changed since the last search
+search increased             keep the addresses that went up since the last search
+search decreased             keep the addresses that went down since the last search
+search                       list the addresses left
+help                     h   print this help
+quit                     q   stop debugging
+Addresses and values are written in hexadecimal";
+
+// Searches with more candidates left than this only print how many there are
+const SEARCH_RESULTS: usize = 16;
+
+/// Runs debugger commands against an interpreter and describes what they did
+///
+/// It starts paused, with the timers frozen so they don't run out while the program is inspected.
+/// Stepping runs instructions straight away, `continue` only flags the debugger as running: the
+/// frontend keeps driving the interpreter while `is_running` is true and tells the debugger how it
+/// went with `report`, which pauses again on breakpoints, watchpoints and when the program ends
+///
+/// Scripts attached to a breakpoint or watchpoint run every time the emulation stops there, they
+/// get the interpreter as the `chip8` variable and can call `chip8.resume()` to keep running
+#[derive(Debug, Default)]
+pub struct Debugger {
+    running: bool,
+    quit_requested: bool,
+    search: Option<RamSearch>,
+    #[cfg(feature = "scripting")]
+    scripts: Scripts,
+}
+
+impl Debugger {
+    /// Pauses the interpreter and starts debugging it
+    pub fn attach(chip8: &mut Chip8) -> Debugger {
+        let mut debugger = Debugger::default();
+        debugger.pause(chip8);
+        debugger
+    }
+
+    /// Whether the frontend should keep running the interpreter
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    /// Whether the user asked to stop debugging
+    pub fn quit_requested(&self) -> bool {
+        self.quit_requested
+    }
+
+    /// Stops running and freezes the timers
+    pub fn pause(&mut self, chip8: &mut Chip8) {
+        self.running = false;
+        chip8.set_component_enabled(Component::Timers, false);
+    }
+
+    fn resume(&mut self, chip8: &mut Chip8) {
+        self.running = true;
+        chip8.set_component_enabled(Component::Timers, true);
+    }
+
+    /// Runs the command and returns the text to show to the user
+    pub fn execute(&mut self, chip8: &mut Chip8, command: Command) -> Result<String, Chip8Error> {
+        let output = match command {
+            Command::Break(address) => {
+                chip8.add_breakpoint(address);
+                format!("Breakpoint set at {:#05X}", address)
+            }
+            Command::Delete(address) => {
+                if chip8.remove_breakpoint(address) {
+                    format!("Breakpoint at {:#05X} removed", address)
+                } else {
+                    format!("No breakpoint at {:#05X}", address)
+                }
+            }
+            Command::Breakpoints => {
+                let breakpoints: Vec<String> = chip8
+                    .breakpoints()
+                    .map(|address| format!("{:#05X}", address))
+                    .collect();
+                if breakpoints.is_empty() {
+                    "No breakpoints".to_string()
+                } else {
+                    breakpoints.join("\n")
+                }
+            }
+            Command::Watch(address) => {
+                chip8.add_watchpoint(address);
+                format!("Watchpoint set at {:#05X}", address)
+            }
+            Command::Unwatch(address) => {
+                if chip8.remove_watchpoint(address) {
+                    format!("Watchpoint at {:#05X} removed", address)
+                } else {
+                    format!("No watchpoint at {:#05X}", address)
+                }
+            }
+            Command::Watchpoints => {
+                let watchpoints: Vec<String> = chip8
+                    .watchpoints()
+                    .map(|address| format!("{:#05X}", address))
+                    .collect();
+                if watchpoints.is_empty() {
+                    "No watchpoints".to_string()
+                } else {
+                    watchpoints.join("\n")
+                }
+            }
+            Command::Script { address, path } => self.script(address, path)?,
+            Command::Step => {
+                let state = chip8.step()?;
+                self.stopped(chip8, state)
+            }
+            Command::Next => {
+                let state = chip8.step_over()?;
+                self.stopped(chip8, state)
+            }
+            Command::Until(address) => {
+                let state = chip8.run_to(address)?;
+                self.stopped(chip8, state)
+            }
+            Command::Continue => {
+                self.resume(chip8);
+                "Continuing".to_string()
+            }
+            Command::Registers => registers(chip8),
+            Command::Examine { address, length } => examine(chip8, address, length),
+            Command::Disassemble { address, count } => {
+                let address = address.unwrap_or_else(|| chip8.program_counter());
+                disassemble(chip8, address, count)
+            }
+            Command::SearchStart => {
+                self.search = Some(RamSearch::new(chip8.memory()));
+                format!("Searching {} addresses", chip8.memory().len())
+            }
+            Command::Search(filter) => self.search(chip8, filter),
+            Command::SearchResults => self.search_results(chip8),
+            Command::Help => HELP.to_string(),
+            Command::Quit => {
+                self.quit_requested = true;
+                String::new()
+            }
+        };
+
+        Ok(output)
+    }
+
+    /// Tells the debugger what the interpreter returned while running
+    ///
+    /// Breakpoints and the end of the program pause the debugger, and the text to show is returned
+    pub fn report(&mut self, chip8: &mut Chip8, state: State) -> Option<String> {
+        match state {
+            State::BreakpointHit(_) | State::WatchpointHit(_) | State::Finished | State::Halted => {
+                self.pause(chip8);
+                Some(self.stopped(chip8, state)).filter(|output| !output.is_empty())
+            }
+            State::Continue | State::Exit | State::WaitingForKey => None,
+        }
+    }
+
+    fn search(&mut self, chip8: &Chip8, filter: SearchFilter) -> String {
+        let search = match self.search.as_mut() {
+            Some(search) => search,
+            None => return "No search started, type `search new` first".to_string(),
+        };
+        match search.filter(chip8.memory(), filter) {
+            0 => "No addresses left".to_string(),
+            1..=SEARCH_RESULTS => self.search_results(chip8),
+            left => format!("{} addresses left", left),
+        }
+    }
+
+    fn search_results(&self, chip8: &Chip8) -> String {
+        let search = match self.search.as_ref() {
+            Some(search) => search,
+            None => return "No search started, type `search new` first".to_string(),
+        };
+        let candidates = search.candidates();
+        let mut lines: Vec<String> = candidates
+            .iter()
+            .take(SEARCH_RESULTS)
+            .map(|address| {
+                let value = chip8.memory()[*address as usize];
+                format!("{:#05X}: {:02X}", address, value)
+            })
+            .collect();
+        if candidates.len() > SEARCH_RESULTS {
+            lines.push(format!("... {} more", candidates.len() - SEARCH_RESULTS));
+        }
+        if lines.is_empty() {
+            "No addresses left".to_string()
+        } else {
+            lines.join("\n")
+        }
+    }
+
+    #[cfg(feature = "scripting")]
+    fn script(
+        &mut self,
+        address: u16,
+        path: Option<std::path::PathBuf>,
+    ) -> Result<String, Chip8Error> {
+        let path = match path {
+            Some(path) => path,
+            None if self.scripts.detach(address) => {
+                return Ok(format!("Script at {:#05X} removed", address))
+            }
+            None => return Ok(format!("No script at {:#05X}", address)),
+        };
+
+        let source = fs::read_to_string(&path)?;
+        Ok(match self.scripts.attach(address, &source) {
+            Ok(()) => format!("Script {} attached to {:#05X}", path.display(), address),
+            Err(error) => error,
+        })
+    }
+
+    #[cfg(not(feature = "scripting"))]
+    fn script(
+        &mut self,
+        _address: u16,
+        _path: Option<std::path::PathBuf>,
+    ) -> Result<String, Chip8Error> {
+        Ok("Scripts need chip8-dbg to be built with the `scripting` feature".to_string())
+    }
+
+    // Runs the script attached to where the emulation stopped, which may resume it
+    fn stopped(&mut self, chip8: &mut Chip8, state: State) -> String {
+        #[cfg(feature = "scripting")]
+        if let State::BreakpointHit(address) | State::WatchpointHit(address) = state {
+            if let Some(outcome) = self.scripts.run(chip8, address) {
+                if outcome.resume {
+                    self.resume(chip8);
+                    return outcome.output;
+                }
+                if !outcome.output.is_empty() {
+                    return format!("{}\n{}", outcome.output, describe_stop(chip8, state));
+                }
+            }
+        }
+
+        describe_stop(chip8, state)
+    }
+}
+
+fn describe_stop(chip8: &Chip8, state: State) -> String {
+    let current = disassemble_line(chip8, chip8.program_counter());
+    match state {
+        State::BreakpointHit(_) => format!("Breakpoint hit\n{}", current),
+        State::WatchpointHit(address) => {
+            format!("Watchpoint hit, {:#05X} changed\n{}", address, current)
+        }
+        State::Finished => "Program finished".to_string(),
+        State::Halted => "Program exited".to_string(),
+        State::WaitingForKey => format!("Waiting for a key\n{}", current),
+        State::Continue | State::Exit => current,
+    }
+}
+
+fn registers(chip8: &Chip8) -> String {
+    let snapshot = chip8.cpu_snapshot();
+    let mut output = format!(
+        "PC {:#05X}  I {:#05X}  DT {}  ST {}\n",
+        snapshot.program_counter,
+        snapshot.index_register,
+        snapshot.delay_timer,
+        snapshot.sound_timer
+    );
+    for (index, value) in snapshot.v_registers.iter().enumerate() {
+        let separator = if index % 8 == 7 { '\n' } else { ' ' };
+        let _ = write!(output, "V{:X} {:02X}{}", index, value, separator);
+    }
+    output.push_str("Stack");
+    for address in &snapshot.stack[..snapshot.stack_pointer as usize] {
+        let _ = write!(output, " {:#05X}", address);
+    }
+
+    output
+}
+
+fn examine(chip8: &Chip8, address: u16, length: u16) -> String {
+    let memory = chip8.memory();
+    let start = address as usize;
+    if start >= memory.len() {
+        return format!("{:#05X} is outside of memory", address);
+    }
+    let end = (start + length as usize).min(memory.len());
+
+    let lines: Vec<String> = memory[start..end]
+        .chunks(16)
+        .enumerate()
+        .map(|(row, bytes)| {
+            let bytes: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+            format!("{:#05X}: {}", start + row * 16, bytes.join(" "))
+        })
+        .collect();
+    lines.join("\n")
+}
+
+fn disassemble(chip8: &Chip8, address: u16, count: u16) -> String {
+    let program_counter = chip8.program_counter();
+    let breakpoints: Vec<u16> = chip8.breakpoints().collect();
+
+    let lines: Vec<String> = (0..count)
+        .map(|index| address.wrapping_add(index.wrapping_mul(2)))
+        .take_while(|address| (*address as usize) + 1 < chip8.memory().len())
+        .map(|address| {
+            let breakpoint = if breakpoints.contains(&address) {
+                '*'
+            } else {
+                ' '
+            };
+            let current = if address == program_counter { '>' } else { ' ' };
+            format!(
+                "{}{} {}",
+                breakpoint,
+                current,
+                disassemble_line(chip8, address)
+            )
+        })
+        .collect();
+    lines.join("\n")
+}
+
+fn disassemble_line(chip8: &Chip8, address: u16) -> String {
+    let memory = chip8.memory();
+    let start = address as usize;
+    match memory.get(start..start + 2) {
+        Some(bytes) => {
+            let opcode = u16::from_be_bytes([bytes[0], bytes[1]]);
+            format!("{:#05X}: {:04X}  {}", address, opcode, decode(opcode))
+        }
+        None => format!("{:#05X}: outside of memory", address),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_chip8_instance(program: Vec<u8>) -> Result<Chip8, Chip8Error> {
+        let mut chip8 = Chip8::builder().build();
+        chip8.load_program(program)?;
+        Ok(chip8)
+    }
+
+    #[test]
+    fn it_steps_and_stops_at_breakpoints() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance(vec![0x60, 0x01, 0x70, 0x02, 0x12, 0x02])?;
+        let mut debugger = Debugger::attach(&mut chip8);
+
+        let output = debugger.execute(&mut chip8, Command::Step)?;
+        assert_eq!(output, "0x202: 7002  v0 += 0x02");
+
+        debugger.execute(&mut chip8, Command::Break(0x204))?;
+        let output = debugger.execute(&mut chip8, Command::Until(0x300))?;
+        assert_eq!(output, "Breakpoint hit\n0x204: 1202  jump 0x202");
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_pauses_when_a_breakpoint_is_reported() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance(vec![0x12, 0x00])?;
+        let mut debugger = Debugger::attach(&mut chip8);
+        assert!(!chip8.is_component_enabled(Component::Timers));
+
+        debugger.execute(&mut chip8, Command::Continue)?;
+        assert!(debugger.is_running());
+        assert!(chip8.is_component_enabled(Component::Timers));
+
+        assert_eq!(debugger.report(&mut chip8, State::Continue), None);
+        assert!(debugger
+            .report(&mut chip8, State::BreakpointHit(0x200))
+            .is_some());
+        assert!(!debugger.is_running());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_stops_at_watchpoints() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance(vec![0xA3, 0x00, 0x60, 0x07, 0xF0, 0x55])?;
+        let mut debugger = Debugger::attach(&mut chip8);
+
+        debugger.execute(&mut chip8, Command::Watch(0x300))?;
+        let output = debugger.execute(&mut chip8, Command::Until(0x400))?;
+        assert_eq!(
+            output,
+            "Watchpoint hit, 0x300 changed\n0x206: 0000  native 0x000"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_searches_memory_for_a_changing_value() -> Result<(), Chip8Error> {
+        // Counts down v0 from 3 at 0x300, one step per loop
+        let mut chip8 = get_chip8_instance(vec![
+            0x60, 0x03, 0xA3, 0x00, 0xF0, 0x55, 0x70, 0xFF, 0x12, 0x04,
+        ])?;
+        let mut debugger = Debugger::attach(&mut chip8);
+        assert!(debugger
+            .execute(&mut chip8, Command::Search(SearchFilter::Changed))?
+            .starts_with("No search started"));
+
+        debugger.execute(&mut chip8, Command::Until(0x206))?;
+        debugger.execute(&mut chip8, Command::SearchStart)?;
+        let output = debugger.execute(&mut chip8, Command::Search(SearchFilter::Equal(3)))?;
+        assert!(output.contains("0x300: 03"));
+
+        debugger.execute(&mut chip8, Command::Step)?;
+        debugger.execute(&mut chip8, Command::Until(0x206))?;
+        let output = debugger.execute(&mut chip8, Command::Search(SearchFilter::Decreased))?;
+        assert_eq!(output, "0x300: 02");
+
+        Ok(())
+    }
+
+    #[cfg(feature = "scripting")]
+    #[test]
+    fn it_runs_scripts_when_stopping() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance(vec![0x70, 0x01, 0x70, 0x01, 0x12, 0x00])?;
+        let mut debugger = Debugger::attach(&mut chip8);
+        debugger.execute(&mut chip8, Command::Break(0x202))?;
+        let script = r#"
+            print(`v0 is ${chip8.v(0)}`);
+            chip8.set_v(1, chip8.v(0) * 2);
+            chip8.poke(0x300, 0xAB);
+            if chip8.v(0) < 2 { chip8.resume(); }
+        "#;
+        debugger.scripts.attach(0x202, script).unwrap();
+
+        let output = debugger.execute(&mut chip8, Command::Step)?;
+        assert_eq!(output, "v0 is 1");
+        assert!(debugger.is_running());
+        assert_eq!(chip8.v_register(1), 2);
+        assert_eq!(chip8.memory()[0x300], 0xAB);
+
+        let state = chip8.run_cycles(10)?;
+        let output = debugger.report(&mut chip8, state);
+        assert_eq!(
+            output.as_deref(),
+            Some("v0 is 3\nBreakpoint hit\n0x202: 7001  v0 += 0x01")
+        );
+        assert!(!debugger.is_running());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_prints_registers_memory_and_disassembly() -> Result<(), Chip8Error> {
+        let mut chip8 = get_chip8_instance(vec![0x6A, 0x12, 0xA3, 0x00])?;
+        let mut debugger = Debugger::attach(&mut chip8);
+        debugger.execute(&mut chip8, Command::Step)?;
+        debugger.execute(&mut chip8, Command::Break(0x200))?;
+
+        let registers = debugger.execute(&mut chip8, Command::Registers)?;
+        assert!(registers.starts_with("PC 0x202  I 0x000"));
+        assert!(registers.contains("VA 12"));
+
+        let memory = debugger.execute(
+            &mut chip8,
+            Command::Examine {
+                address: 0x200,
+                length: 4,
+            },
+        )?;
+        assert_eq!(memory, "0x200: 6A 12 A3 00");
+
+        let disassembly = debugger.execute(
+            &mut chip8,
+            Command::Disassemble {
+                address: Some(0x200),
+                count: 2,
+            },
+        )?;
+        assert_eq!(
+            disassembly,
+            "*  0x200: 6A12  va := 0x12\n > 0x202: A300  i := 0x300"
+        );
+
+        Ok(())
+    }
+}
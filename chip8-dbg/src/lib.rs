@@ -0,0 +1,17 @@
+#![warn(missing_docs)]
+
+//! A command line debugger for chip8 programs
+//!
+//! It is built on the breakpoints and stepping of `chip8-core`, so the same `Debugger` can drive
+//! a headless interpreter, like the `chip8-dbg` binary does, or one attached to a frontend
+//!
+//! With the `scripting` feature, rhai scripts can be attached to breakpoints and watchpoints to
+//! log values, patch memory and registers, or keep the program running
+
+mod command;
+mod debugger;
+#[cfg(feature = "scripting")]
+mod script;
+
+pub use command::Command;
+pub use debugger::Debugger;
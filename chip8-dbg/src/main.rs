@@ -0,0 +1,79 @@
+use std::{
+    error::Error,
+    fs,
+    io::{self, prelude::*},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+
+use chip8_core::{Chip8, Chip8Variant, SeededNumberGenerator, State};
+use chip8_dbg::{Command, Debugger};
+
+// Instructions run between checks while continuing, the program has no way to be interrupted otherwise
+const CONTINUE_CYCLES: usize = 1_000_000;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "chip8-dbg")]
+struct CliArgs {
+    #[structopt(long = "rom", short = "r")]
+    rom: PathBuf,
+    #[structopt(long = "variant", short = "v", default_value = "chip8")]
+    variant: Chip8Variant,
+    #[structopt(long = "seed", short = "s", default_value = "0")]
+    seed: u64,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let cli_args = CliArgs::from_args();
+    let mut chip8 = Chip8::builder()
+        .variant(cli_args.variant)
+        .rng(Box::new(SeededNumberGenerator::new(cli_args.seed)))
+        .build();
+    chip8.load_program(fs::read(&cli_args.rom)?)?;
+    let mut debugger = Debugger::attach(&mut chip8);
+
+    println!(
+        "Debugging {}, type `help` for the commands",
+        cli_args.rom.display()
+    );
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    while !debugger.quit_requested() {
+        if debugger.is_running() {
+            let state = chip8.run_cycles(CONTINUE_CYCLES)?;
+            if let Some(message) = debugger.report(&mut chip8, state) {
+                println!("{}", message);
+            } else if let State::Continue | State::WaitingForKey = state {
+                debugger.pause(&mut chip8);
+                match state {
+                    State::WaitingForKey => {
+                        println!("Waiting for a key, which can't be pressed headless")
+                    }
+                    _ => println!("Paused, the program is still running"),
+                }
+            }
+            continue;
+        }
+
+        print!("(chip8) ");
+        io::stdout().flush()?;
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => break,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match line.parse::<Command>() {
+            Ok(command) => match debugger.execute(&mut chip8, command) {
+                Ok(output) if output.is_empty() => (),
+                Ok(output) => println!("{}", output),
+                Err(error) => println!("{}", error),
+            },
+            Err(error) => println!("{}", error),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,198 @@
+use std::{cell::RefCell, collections::HashMap, convert::TryFrom, fmt, rc::Rc};
+
+use chip8_core::Chip8;
+use rhai::{Engine, EvalAltResult, Scope, AST};
+
+/// The interpreter as scripts see it, through the `chip8` variable
+///
+/// Scripts work on a copy that is written back once they finish without errors
+#[derive(Debug, Clone)]
+struct Machine {
+    v_registers: [u8; 16],
+    index_register: u16,
+    program_counter: u16,
+    delay_timer: u8,
+    sound_timer: u8,
+    memory: Vec<u8>,
+    resume: bool,
+}
+
+impl Machine {
+    fn new(chip8: &Chip8) -> Machine {
+        Machine {
+            v_registers: *chip8.v_registers(),
+            index_register: chip8.index_register(),
+            program_counter: chip8.program_counter(),
+            delay_timer: chip8.delay_timer(),
+            sound_timer: chip8.sound_timer(),
+            memory: chip8.memory().to_vec(),
+            resume: false,
+        }
+    }
+
+    fn memory_index(&self, address: i64) -> Result<usize, Box<EvalAltResult>> {
+        match usize::try_from(address) {
+            Ok(index) if index < self.memory.len() => Ok(index),
+            _ => Err(format!("Address {:#05X} is outside of memory", address).into()),
+        }
+    }
+
+    fn apply(&self, chip8: &mut Chip8) -> Result<(), chip8_core::Chip8Error> {
+        for (vx, value) in self.v_registers.iter().enumerate() {
+            if chip8.v_register(vx) != *value {
+                chip8.set_v_register(vx, *value);
+            }
+        }
+        if chip8.index_register() != self.index_register {
+            chip8.set_index_register(self.index_register);
+        }
+        if chip8.program_counter() != self.program_counter {
+            chip8.set_program_counter(self.program_counter);
+        }
+        chip8.set_delay_timer(self.delay_timer);
+        chip8.set_sound_timer(self.sound_timer);
+
+        let changed: Vec<usize> = (0..self.memory.len())
+            .filter(|index| chip8.memory()[*index] != self.memory[*index])
+            .collect();
+        for index in changed {
+            chip8.write_memory(index as u16, &self.memory[index..=index])?;
+        }
+
+        Ok(())
+    }
+}
+
+fn register_machine(engine: &mut Engine) {
+    engine
+        .register_type_with_name::<Machine>("Chip8")
+        .register_get_set(
+            "pc",
+            |machine: &mut Machine| machine.program_counter as i64,
+            |machine: &mut Machine, value: i64| machine.program_counter = value as u16,
+        )
+        .register_get_set(
+            "i",
+            |machine: &mut Machine| machine.index_register as i64,
+            |machine: &mut Machine, value: i64| machine.index_register = value as u16,
+        )
+        .register_get_set(
+            "dt",
+            |machine: &mut Machine| machine.delay_timer as i64,
+            |machine: &mut Machine, value: i64| machine.delay_timer = value as u8,
+        )
+        .register_get_set(
+            "st",
+            |machine: &mut Machine| machine.sound_timer as i64,
+            |machine: &mut Machine, value: i64| machine.sound_timer = value as u8,
+        )
+        .register_fn("v", |machine: &mut Machine, vx: i64| {
+            machine.v_registers[(vx & 0xF) as usize] as i64
+        })
+        .register_fn("set_v", |machine: &mut Machine, vx: i64, value: i64| {
+            machine.v_registers[(vx & 0xF) as usize] = value as u8
+        })
+        .register_fn(
+            "peek",
+            |machine: &mut Machine, address: i64| -> Result<i64, Box<EvalAltResult>> {
+                let index = machine.memory_index(address)?;
+                Ok(machine.memory[index] as i64)
+            },
+        )
+        .register_fn(
+            "poke",
+            |machine: &mut Machine, address: i64, value: i64| -> Result<(), Box<EvalAltResult>> {
+                let index = machine.memory_index(address)?;
+                machine.memory[index] = value as u8;
+                Ok(())
+            },
+        )
+        .register_fn("resume", |machine: &mut Machine| machine.resume = true);
+}
+
+/// What happened when a script ran
+pub(crate) struct ScriptOutcome {
+    /// Lines printed by the script, or the error it failed with
+    pub output: String,
+    /// Whether the script asked to keep running
+    pub resume: bool,
+}
+
+/// Rhai scripts attached to breakpoints and watchpoints
+pub(crate) struct Scripts {
+    engine: Engine,
+    printed: Rc<RefCell<Vec<String>>>,
+    scripts: HashMap<u16, AST>,
+}
+
+impl Default for Scripts {
+    fn default() -> Scripts {
+        let printed = Rc::new(RefCell::new(Vec::new()));
+        let mut engine = Engine::new();
+        let print_output = printed.clone();
+        engine.on_print(move |text| print_output.borrow_mut().push(text.to_string()));
+        let debug_output = printed.clone();
+        engine.on_debug(move |text, _, _| debug_output.borrow_mut().push(text.to_string()));
+        register_machine(&mut engine);
+
+        Scripts {
+            engine,
+            printed,
+            scripts: HashMap::new(),
+        }
+    }
+}
+
+impl fmt::Debug for Scripts {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("Scripts")
+            .field("addresses", &self.scripts.keys())
+            .finish()
+    }
+}
+
+impl Scripts {
+    /// Compiles the script and runs it whenever the emulation stops at `address`
+    pub fn attach(&mut self, address: u16, source: &str) -> Result<(), String> {
+        let ast = self
+            .engine
+            .compile(source)
+            .map_err(|error| format!("Invalid script: {}", error))?;
+        self.scripts.insert(address, ast);
+        Ok(())
+    }
+
+    /// Removes the script at `address`, returning whether there was one
+    pub fn detach(&mut self, address: u16) -> bool {
+        self.scripts.remove(&address).is_some()
+    }
+
+    /// Runs the script attached to `address`, if there is one
+    pub fn run(&mut self, chip8: &mut Chip8, address: u16) -> Option<ScriptOutcome> {
+        let ast = self.scripts.get(&address)?;
+        let mut scope = Scope::new();
+        scope.push("chip8", Machine::new(chip8));
+
+        let result = self.engine.run_ast_with_scope(&mut scope, ast);
+        let mut lines = std::mem::take(&mut *self.printed.borrow_mut());
+        let mut resume = false;
+        match result {
+            Ok(()) => {
+                let machine = scope
+                    .get_value::<Machine>("chip8")
+                    .unwrap_or_else(|| Machine::new(chip8));
+                match machine.apply(chip8) {
+                    Ok(()) => resume = machine.resume,
+                    Err(error) => lines.push(error.to_string()),
+                }
+            }
+            Err(error) => lines.push(format!("Script error: {}", error)),
+        }
+
+        Some(ScriptOutcome {
+            output: lines.join("\n"),
+            resume,
+        })
+    }
+}
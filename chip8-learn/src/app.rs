@@ -0,0 +1,232 @@
+use std::{cell::RefCell, collections::HashMap, collections::VecDeque, rc::Rc};
+
+use chip8_core::{Chip8, Chip8Error, Component, Instruction, KeyEvent, Keymap, MicroStep, State};
+use eframe::egui;
+
+use crate::{
+    datapath::{Activity, Part, Sample},
+    diagram,
+};
+
+// Stages run in a single repaint at most, so a high speed can't freeze the window
+const MAX_STAGES_PER_REPAINT: usize = 3000;
+
+/// Window animating the datapath of the interpreter one fetch, decode or execute stage at a time
+///
+/// It starts paused, like the debuggers, with the timers frozen until the program runs
+pub struct App {
+    pub(crate) chip8: Chip8,
+    key_events: Rc<RefCell<VecDeque<KeyEvent>>>,
+    keymap: Keymap,
+    /// Stage that ran last
+    pub(crate) step: Option<MicroStep>,
+    /// What the last stage did
+    pub(crate) activity: Activity,
+    /// When each part last changed, in seconds of the window's clock, for it to flash
+    pub(crate) changed_at: HashMap<Part, f64>,
+    instruction: Option<Instruction>,
+    running: bool,
+    stages_per_second: f32,
+    // Fraction of a stage left over from the previous repaints
+    pending_stages: f32,
+    status: String,
+}
+
+impl App {
+    pub fn new(
+        chip8: Chip8,
+        key_events: Rc<RefCell<VecDeque<KeyEvent>>>,
+        keymap: Keymap,
+        stages_per_second: f32,
+    ) -> App {
+        let mut app = App {
+            chip8,
+            key_events,
+            keymap,
+            step: None,
+            activity: Activity::default(),
+            changed_at: HashMap::new(),
+            instruction: None,
+            running: false,
+            stages_per_second,
+            pending_stages: 0.0,
+            status: "Paused, press Play or step through the stages".to_string(),
+        };
+        app.pause();
+        app
+    }
+
+    fn pause(&mut self) {
+        self.running = false;
+        self.pending_stages = 0.0;
+        self.chip8.set_component_enabled(Component::Timers, false);
+    }
+
+    fn resume(&mut self) {
+        self.running = true;
+        self.chip8.set_component_enabled(Component::Timers, true);
+        self.status = "Running".to_string();
+    }
+
+    fn reset(&mut self) {
+        self.pause();
+        let result = self.chip8.reset(true);
+        self.step = None;
+        self.instruction = None;
+        self.activity = Activity::default();
+        self.changed_at.clear();
+        self.status = match result {
+            Ok(()) => "Reset, paused".to_string(),
+            Err(error) => error.to_string(),
+        };
+    }
+
+    // Runs the next stage and works out what it did, returning whether it finished an instruction
+    fn step_stage(&mut self, now: f64) -> bool {
+        let memory = self.chip8.memory().to_vec();
+        let before = Sample {
+            cpu: self.chip8.cpu_snapshot(),
+            memory: &memory,
+        };
+        let step = match self.chip8.micro_step() {
+            Ok(step) => step,
+            Err(error) => {
+                self.stopped(Err(error));
+                return true;
+            }
+        };
+        if let MicroStep::Decode { instruction, .. } = step {
+            self.instruction = Some(instruction);
+        }
+        let after = Sample {
+            cpu: self.chip8.cpu_snapshot(),
+            memory: self.chip8.memory(),
+        };
+        self.activity = Activity::of(&step, self.instruction, &before, &after);
+        for part in &self.activity.changed {
+            self.changed_at.insert(*part, now);
+        }
+        self.step = Some(step);
+
+        match step {
+            MicroStep::Execute { state, .. } => {
+                self.instruction = None;
+                match state {
+                    State::Continue | State::WaitingForKey => (),
+                    _ => self.stopped(Ok(state)),
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn step_instruction(&mut self, now: f64) {
+        while !self.step_stage(now) {}
+    }
+
+    fn stopped(&mut self, result: Result<State, Chip8Error>) {
+        self.pause();
+        self.status = match result {
+            Ok(State::Finished) => "Program finished".to_string(),
+            Ok(State::Halted) | Ok(State::Exit) => "Program exited".to_string(),
+            Ok(_) => "Paused".to_string(),
+            Err(error) => error.to_string(),
+        };
+    }
+
+    fn handle_keys(&mut self, ctx: &egui::Context) {
+        let events = ctx.input(|input| input.events.clone());
+        for event in events {
+            if let egui::Event::Key {
+                key,
+                pressed,
+                repeat: false,
+                ..
+            } = event
+            {
+                let mut name = key.name().chars();
+                let key = match (name.next(), name.next()) {
+                    (Some(character), None) => self.keymap.key(character),
+                    _ => None,
+                };
+                if let Some(key) = key {
+                    let event = if pressed {
+                        KeyEvent::Down(key)
+                    } else {
+                        KeyEvent::Up(key)
+                    };
+                    self.key_events.borrow_mut().push_back(event);
+                }
+            }
+        }
+    }
+
+    fn controls(&mut self, ui: &mut egui::Ui, now: f64) {
+        ui.heading("chip8-learn");
+        ui.horizontal(|ui| {
+            if self.running {
+                if ui.button("Pause").clicked() {
+                    self.pause();
+                    self.status = "Paused".to_string();
+                }
+            } else if ui.button("Play").clicked() {
+                self.resume();
+            }
+            if ui.button("Reset").clicked() {
+                self.reset();
+            }
+        });
+        ui.add_enabled_ui(!self.running, |ui| {
+            if ui.button("Step stage").clicked() {
+                self.step_stage(now);
+            }
+            if ui.button("Step instruction").clicked() {
+                self.step_instruction(now);
+            }
+        });
+        ui.add(
+            egui::Slider::new(&mut self.stages_per_second, 0.5..=2000.0)
+                .logarithmic(true)
+                .text("stages/s"),
+        );
+        ui.separator();
+        ui.label(&self.status);
+        ui.separator();
+        ui.label("Registers flash when they change, arrows are memory reads and writes");
+    }
+}
+
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let (now, elapsed) = ctx.input(|input| (input.time, input.stable_dt));
+        self.handle_keys(ctx);
+
+        if self.running {
+            self.pending_stages += elapsed * self.stages_per_second;
+            let stages = (self.pending_stages as usize).min(MAX_STAGES_PER_REPAINT);
+            self.pending_stages = self.pending_stages.fract();
+            for _ in 0..stages {
+                self.step_stage(now);
+                if !self.running {
+                    break;
+                }
+            }
+        }
+
+        egui::SidePanel::left("controls").show(ctx, |ui| self.controls(ui, now));
+        egui::TopBottomPanel::bottom("description").show(ctx, |ui| {
+            ui.monospace(&self.activity.description);
+        });
+        egui::CentralPanel::default().show(ctx, |ui| diagram::draw(ui, self, now));
+
+        // Keeps animating while running or while a part is still flashing
+        let flashing = self
+            .changed_at
+            .values()
+            .any(|changed_at| now - changed_at < diagram::FLASH_SECONDS);
+        if self.running || flashing {
+            ctx.request_repaint();
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use chip8_core::{CpuSnapshot, Instruction, MicroStep};
+
+/// A box of the datapath diagram
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Part {
+    Memory,
+    ProgramCounter,
+    Opcode,
+    Decoder,
+    Register(usize),
+    Index,
+    Stack,
+    DelayTimer,
+    SoundTimer,
+    Display,
+}
+
+/// Bytes moving between memory and another part during a stage, drawn as an arrow
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transfer {
+    pub from: Part,
+    pub to: Part,
+    pub address: u16,
+    pub length: usize,
+}
+
+/// Everything a stage did, for the diagram to highlight
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Activity {
+    /// Parts whose value changed, or that did the work
+    pub changed: Vec<Part>,
+    /// Memory reads and writes
+    pub transfers: Vec<Transfer>,
+    /// What happened, in a sentence
+    pub description: String,
+}
+
+/// State of the interpreter around a stage, taken right before and right after it
+pub struct Sample<'a> {
+    pub cpu: CpuSnapshot,
+    pub memory: &'a [u8],
+}
+
+impl Activity {
+    /// What `step` did, found by comparing the state before and after it. `instruction` is the
+    /// one being executed, decoded by the stage before
+    pub fn of(
+        step: &MicroStep,
+        instruction: Option<Instruction>,
+        before: &Sample,
+        after: &Sample,
+    ) -> Activity {
+        match step {
+            MicroStep::Fetch { address, opcode } => Activity {
+                changed: vec![Part::Opcode],
+                transfers: vec![Transfer {
+                    from: Part::Memory,
+                    to: Part::Opcode,
+                    address: *address,
+                    length: 2,
+                }],
+                description: format!("Fetch: read opcode {:04X} at {:#05X}", opcode, address),
+            },
+            MicroStep::Decode {
+                opcode,
+                instruction,
+                ..
+            } => Activity {
+                changed: vec![Part::Decoder],
+                transfers: Vec::new(),
+                description: format!(
+                    "Decode: {:04X} matches {}, {}",
+                    opcode,
+                    instruction.pattern(),
+                    instruction
+                ),
+            },
+            MicroStep::Execute { .. } => {
+                let mut activity = Activity {
+                    changed: changed_parts(&before.cpu, &after.cpu),
+                    transfers: reads(instruction, &before.cpu),
+                    description: match instruction {
+                        Some(instruction) => format!("Execute: {}", instruction),
+                        None => "Execute".to_string(),
+                    },
+                };
+                activity
+                    .transfers
+                    .extend(writes(instruction, before.memory, after.memory));
+                if instruction.is_some_and(draws) {
+                    activity.changed.push(Part::Display);
+                }
+                activity
+            }
+        }
+    }
+}
+
+fn changed_parts(before: &CpuSnapshot, after: &CpuSnapshot) -> Vec<Part> {
+    let mut changed = (0..16)
+        .filter(|vx| before.v_registers[*vx] != after.v_registers[*vx])
+        .map(Part::Register)
+        .collect::<Vec<_>>();
+    let parts = [
+        (
+            Part::ProgramCounter,
+            before.program_counter != after.program_counter,
+        ),
+        (Part::Index, before.index_register != after.index_register),
+        (
+            Part::Stack,
+            before.stack_pointer != after.stack_pointer || before.stack != after.stack,
+        ),
+        (Part::DelayTimer, before.delay_timer != after.delay_timer),
+        (Part::SoundTimer, before.sound_timer != after.sound_timer),
+    ];
+    changed.extend(
+        parts
+            .iter()
+            .filter(|(_, changed)| *changed)
+            .map(|(part, _)| *part),
+    );
+    changed
+}
+
+// Memory the instruction reads from I, known from what it does since the interpreter doesn't
+// report reads
+fn reads(instruction: Option<Instruction>, before: &CpuSnapshot) -> Vec<Transfer> {
+    let (to, length) = match instruction {
+        // SUPER-CHIP draws 16x16 sprites of 32 bytes for DXY0
+        Some(Instruction::Draw { n: 0, .. }) => (Part::Display, 32),
+        Some(Instruction::Draw { n, .. }) => (Part::Display, n.into()),
+        Some(Instruction::LoadRegisters { vx }) => (Part::Register(vx), vx + 1),
+        _ => return Vec::new(),
+    };
+    vec![Transfer {
+        from: Part::Memory,
+        to,
+        address: before.index_register,
+        length,
+    }]
+}
+
+// Every run of bytes that changed, written by the registers for FX33 and FX55
+fn writes(instruction: Option<Instruction>, before: &[u8], after: &[u8]) -> Vec<Transfer> {
+    let from = match instruction {
+        Some(Instruction::StoreBcd { vx }) | Some(Instruction::StoreRegisters { vx }) => {
+            Part::Register(vx)
+        }
+        _ => Part::Index,
+    };
+    let mut transfers: Vec<Transfer> = Vec::new();
+    let changed = before
+        .iter()
+        .zip(after)
+        .enumerate()
+        .filter(|(_, (before, after))| before != after)
+        .map(|(address, _)| address as u16);
+    for address in changed {
+        match transfers.last_mut() {
+            Some(last) if last.address as usize + last.length == address as usize => {
+                last.length += 1
+            }
+            _ => transfers.push(Transfer {
+                from,
+                to: Part::Memory,
+                address,
+                length: 1,
+            }),
+        }
+    }
+    transfers
+}
+
+fn draws(instruction: Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::Draw { .. }
+            | Instruction::ClearDisplay
+            | Instruction::ScrollDown { .. }
+            | Instruction::ScrollLeft
+            | Instruction::ScrollRight
+            | Instruction::LowResolution
+            | Instruction::HighResolution
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chip8_core::{decode, Chip8, Chip8Error};
+
+    // Runs every stage of the next instruction, returning what each one did
+    fn stages(chip8: &mut Chip8) -> Result<Vec<Activity>, Chip8Error> {
+        let mut instruction = None;
+        let mut activities = Vec::new();
+        for _ in 0..3 {
+            let memory = chip8.memory().to_vec();
+            let cpu = chip8.cpu_snapshot();
+            let step = chip8.micro_step()?;
+            if let MicroStep::Decode {
+                instruction: decoded,
+                ..
+            } = step
+            {
+                instruction = Some(decoded);
+            }
+            let before = Sample {
+                cpu,
+                memory: &memory,
+            };
+            let after = Sample {
+                cpu: chip8.cpu_snapshot(),
+                memory: chip8.memory(),
+            };
+            activities.push(Activity::of(&step, instruction, &before, &after));
+        }
+        Ok(activities)
+    }
+
+    #[test]
+    fn it_follows_an_instruction_through_every_stage() -> Result<(), Chip8Error> {
+        let mut chip8 = Chip8::builder().build();
+        chip8.load_program(vec![0x6A, 0x02])?;
+
+        let activities = stages(&mut chip8)?;
+
+        assert_eq!(
+            activities[0].transfers,
+            vec![Transfer {
+                from: Part::Memory,
+                to: Part::Opcode,
+                address: 0x200,
+                length: 2
+            }]
+        );
+        assert!(activities[1].description.contains("6XNN"));
+        assert_eq!(
+            activities[2].changed,
+            vec![Part::Register(0xA), Part::ProgramCounter]
+        );
+        assert_eq!(decode(0x6A02), Instruction::SetRegister { vx: 0xA, nn: 2 });
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_draws_memory_reads_and_writes() -> Result<(), Chip8Error> {
+        let mut chip8 = Chip8::builder().build();
+        // I at 0x300, V0 to 123, then its decimal digits stored at I and a sprite drawn from I
+        chip8.load_program(vec![0xA3, 0x00, 0x60, 0x7B, 0xF0, 0x33, 0xD0, 0x03])?;
+        stages(&mut chip8)?;
+        stages(&mut chip8)?;
+
+        let bcd = stages(&mut chip8)?;
+        let draw = stages(&mut chip8)?;
+
+        assert_eq!(
+            bcd[2].transfers,
+            vec![Transfer {
+                from: Part::Register(0),
+                to: Part::Memory,
+                address: 0x300,
+                length: 3
+            }]
+        );
+        assert_eq!(
+            draw[2].transfers,
+            vec![Transfer {
+                from: Part::Memory,
+                to: Part::Display,
+                address: 0x300,
+                length: 3
+            }]
+        );
+        assert!(draw[2].changed.contains(&Part::Display));
+
+        Ok(())
+    }
+}
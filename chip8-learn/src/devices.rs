@@ -0,0 +1,24 @@
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+use chip8_core::{KeyEvent, Keyboard};
+
+/// Key events typed in the window, queued by the app until the interpreter polls them
+pub struct WindowKeyboard {
+    events: Rc<RefCell<VecDeque<KeyEvent>>>,
+}
+
+impl WindowKeyboard {
+    pub fn new(events: Rc<RefCell<VecDeque<KeyEvent>>>) -> WindowKeyboard {
+        WindowKeyboard { events }
+    }
+}
+
+impl Keyboard for WindowKeyboard {
+    fn poll_events(&mut self) -> Vec<KeyEvent> {
+        self.events.borrow_mut().drain(..).collect()
+    }
+
+    fn has_pending_events(&mut self) -> bool {
+        !self.events.borrow().is_empty()
+    }
+}
@@ -0,0 +1,391 @@
+use chip8_core::MicroStep;
+use eframe::egui::{
+    pos2, vec2, Align2, Color32, FontId, Painter, Pos2, Rect, Sense, Stroke, StrokeKind, Ui,
+};
+
+use crate::{
+    app::App,
+    datapath::{Part, Transfer},
+};
+
+/// Seconds a part keeps flashing after it changed
+pub const FLASH_SECONDS: f64 = 1.0;
+
+// The diagram is laid out on a canvas of this size, scaled to fit the window
+const CANVAS: (f32, f32) = (960.0, 640.0);
+const MEMORY: Rect = rect(20.0, 20.0, 220.0, 620.0);
+const STAGES: [(&str, Rect); 3] = [
+    ("Fetch", rect(260.0, 20.0, 460.0, 70.0)),
+    ("Decode", rect(480.0, 20.0, 680.0, 70.0)),
+    ("Execute", rect(700.0, 20.0, 940.0, 70.0)),
+];
+const OPCODE: Rect = rect(260.0, 100.0, 460.0, 150.0);
+const DECODER: Rect = rect(480.0, 100.0, 940.0, 150.0);
+const PROGRAM_COUNTER: Rect = rect(260.0, 180.0, 360.0, 220.0);
+const INDEX: Rect = rect(380.0, 180.0, 480.0, 220.0);
+const DELAY_TIMER: Rect = rect(500.0, 180.0, 580.0, 220.0);
+const SOUND_TIMER: Rect = rect(600.0, 180.0, 680.0, 220.0);
+const STACK: Rect = rect(720.0, 180.0, 940.0, 420.0);
+const REGISTERS: Pos2 = pos2(260.0, 250.0);
+const REGISTER_SIZE: (f32, f32) = (100.0, 36.0);
+const DISPLAY: Rect = rect(260.0, 440.0, 580.0, 600.0);
+// Rows of four bytes shown around the program counter and around I
+const MEMORY_ROWS: u16 = 12;
+const ROW_HEIGHT: f32 = 20.0;
+
+const HIGHLIGHT: Color32 = Color32::from_rgb(250, 200, 60);
+const READ: Color32 = Color32::from_rgb(90, 170, 250);
+const WRITE: Color32 = Color32::from_rgb(250, 120, 60);
+
+const fn rect(left: f32, top: f32, right: f32, bottom: f32) -> Rect {
+    Rect {
+        min: pos2(left, top),
+        max: pos2(right, bottom),
+    }
+}
+
+/// Draws the datapath with what the last stage did, `now` being the window's clock in seconds
+pub fn draw(ui: &mut Ui, app: &App, now: f64) {
+    let (response, painter) = ui.allocate_painter(ui.available_size(), Sense::hover());
+    let area = response.rect;
+    let scale = (area.width() / CANVAS.0).min(area.height() / CANVAS.1);
+    let diagram = Diagram {
+        painter,
+        origin: area.min,
+        scale,
+        app,
+        now,
+    };
+    diagram.draw();
+}
+
+struct Diagram<'a> {
+    painter: Painter,
+    origin: Pos2,
+    scale: f32,
+    app: &'a App,
+    now: f64,
+}
+
+impl Diagram<'_> {
+    fn draw(&self) {
+        let cpu = self.app.chip8.cpu_snapshot();
+
+        let current_stage = match self.app.step {
+            Some(MicroStep::Fetch { .. }) => Some(0),
+            Some(MicroStep::Decode { .. }) => Some(1),
+            Some(MicroStep::Execute { .. }) => Some(2),
+            None => None,
+        };
+        for (stage, (name, area)) in STAGES.iter().enumerate() {
+            let fill = if current_stage == Some(stage) {
+                HIGHLIGHT
+            } else {
+                self.background()
+            };
+            self.part_box(*area, fill, &[*name]);
+        }
+
+        self.part(
+            Part::Opcode,
+            OPCODE,
+            &["Opcode", &format!("{:04X}", cpu.opcode)],
+        );
+        let instruction = chip8_core::decode(cpu.opcode);
+        self.part(
+            Part::Decoder,
+            DECODER,
+            &[
+                "Decoder",
+                &format!("{}  {}", instruction.pattern(), instruction),
+            ],
+        );
+        self.part(
+            Part::ProgramCounter,
+            PROGRAM_COUNTER,
+            &["PC", &format!("{:03X}", cpu.program_counter)],
+        );
+        self.part(
+            Part::Index,
+            INDEX,
+            &["I", &format!("{:03X}", cpu.index_register)],
+        );
+        self.part(
+            Part::DelayTimer,
+            DELAY_TIMER,
+            &["DT", &cpu.delay_timer.to_string()],
+        );
+        self.part(
+            Part::SoundTimer,
+            SOUND_TIMER,
+            &["ST", &cpu.sound_timer.to_string()],
+        );
+
+        let mut stack = vec!["Stack".to_string()];
+        stack.extend(
+            cpu.stack
+                .iter()
+                .take(cpu.stack_pointer as usize)
+                .rev()
+                .map(|address| format!("{:03X}", address)),
+        );
+        let stack = stack.iter().map(String::as_str).collect::<Vec<_>>();
+        self.part(Part::Stack, STACK, &stack);
+
+        for (vx, value) in cpu.v_registers.iter().enumerate() {
+            self.part(
+                Part::Register(vx),
+                register_rect(vx),
+                &[&format!("V{:X}  {:02X}", vx, value)],
+            );
+        }
+
+        self.display();
+        self.memory(cpu.program_counter, cpu.index_register);
+        for transfer in &self.app.activity.transfers {
+            self.transfer(transfer);
+        }
+    }
+
+    // A box whose background fades from the highlight back to normal after it changed
+    fn part(&self, part: Part, area: Rect, lines: &[&str]) {
+        let fill = match self.app.changed_at.get(&part) {
+            Some(changed_at) if self.now - changed_at < FLASH_SECONDS => {
+                let faded = ((self.now - changed_at) / FLASH_SECONDS) as f32;
+                lerp(HIGHLIGHT, self.background(), faded)
+            }
+            _ => self.background(),
+        };
+        self.part_box(area, fill, lines);
+    }
+
+    fn part_box(&self, area: Rect, fill: Color32, lines: &[&str]) {
+        let area = self.place(area);
+        self.painter.rect_filled(area, 4.0, fill);
+        self.painter
+            .rect_stroke(area, 4.0, self.stroke(), StrokeKind::Inside);
+        let text = if fill == self.background() {
+            self.text_color()
+        } else {
+            Color32::BLACK
+        };
+        // A single line is centered, more of them are listed from the top
+        if let [line] = lines {
+            self.painter.text(
+                area.center(),
+                Align2::CENTER_CENTER,
+                line,
+                self.font(),
+                text,
+            );
+            return;
+        }
+        for (row, line) in lines.iter().enumerate() {
+            let position = area.left_top()
+                + vec2(6.0, 4.0) * self.scale
+                + vec2(0.0, row as f32 * 16.0 * self.scale);
+            self.painter
+                .text(position, Align2::LEFT_TOP, line, self.font(), text);
+        }
+    }
+
+    fn display(&self) {
+        let fill = match self.app.changed_at.get(&Part::Display) {
+            Some(changed_at) if self.now - changed_at < FLASH_SECONDS => HIGHLIGHT,
+            _ => self.text_color(),
+        };
+        let area = self.place(DISPLAY);
+        self.painter.rect_filled(area, 0.0, Color32::BLACK);
+        let framebuffer = self.app.chip8.framebuffer();
+        let pixel = vec2(
+            area.width() / framebuffer.width() as f32,
+            area.height() / framebuffer.height() as f32,
+        );
+        for (x, y) in framebuffer.set_pixels() {
+            let top_left = area.left_top() + vec2(x as f32 * pixel.x, y as f32 * pixel.y);
+            self.painter
+                .rect_filled(Rect::from_min_size(top_left, pixel), 0.0, fill);
+        }
+        self.painter
+            .rect_stroke(area, 0.0, self.stroke(), StrokeKind::Outside);
+    }
+
+    // Bytes around the program counter in the top half and around I in the bottom one, with the
+    // ones moved by the last stage in the color of their arrow
+    fn memory(&self, program_counter: u16, index: u16) {
+        self.part_box(MEMORY, self.background(), &[]);
+        let memory = self.app.chip8.memory();
+        let windows = self.memory_windows(program_counter, index);
+        for (first_row, (title, window)) in ["Memory at PC", "Memory at I"]
+            .iter()
+            .zip(&windows)
+            .enumerate()
+        {
+            let first_row = first_row as u16 * MEMORY_ROWS;
+            self.painter.text(
+                self.place_point(pos2(MEMORY.min.x + 8.0, memory_row_top(first_row) - 24.0)),
+                Align2::LEFT_TOP,
+                title,
+                self.font(),
+                self.text_color(),
+            );
+            for row in 0..MEMORY_ROWS {
+                let address = window + row * 4;
+                let top = memory_row_top(first_row + row);
+                self.painter.text(
+                    self.place_point(pos2(MEMORY.min.x + 8.0, top)),
+                    Align2::LEFT_TOP,
+                    format!("{:03X}", address),
+                    self.font(),
+                    self.text_color().gamma_multiply(0.6),
+                );
+                for column in 0..4 {
+                    let address = address + column;
+                    let byte = memory.get(address as usize).copied().unwrap_or_default();
+                    let color = self
+                        .app
+                        .activity
+                        .transfers
+                        .iter()
+                        .find(|transfer| {
+                            (transfer.address..transfer.address + transfer.length as u16)
+                                .contains(&address)
+                        })
+                        .map(|transfer| self.transfer_color(transfer))
+                        .unwrap_or_else(|| self.text_color());
+                    self.painter.text(
+                        self.place_point(pos2(MEMORY.min.x + 60.0 + column as f32 * 36.0, top)),
+                        Align2::LEFT_TOP,
+                        format!("{:02X}", byte),
+                        self.font(),
+                        color,
+                    );
+                }
+            }
+        }
+    }
+
+    fn memory_windows(&self, program_counter: u16, index: u16) -> [u16; 2] {
+        [
+            (program_counter & !3).saturating_sub(8),
+            (index & !3).saturating_sub(4),
+        ]
+    }
+
+    // Where an arrow to or from an address leaves the memory box, the row holding it when shown
+    fn memory_anchor(&self, address: u16) -> Pos2 {
+        let cpu = self.app.chip8.cpu_snapshot();
+        let windows = self.memory_windows(cpu.program_counter, cpu.index_register);
+        let row = windows.iter().enumerate().find_map(|(window, first)| {
+            let row = address.checked_sub(*first)? / 4;
+            (row < MEMORY_ROWS).then(|| window as u16 * MEMORY_ROWS + row)
+        });
+        let y = match row {
+            Some(row) => memory_row_top(row) + ROW_HEIGHT / 2.0,
+            None => MEMORY.center().y,
+        };
+        self.place_point(pos2(MEMORY.max.x, y))
+    }
+
+    fn transfer(&self, transfer: &Transfer) {
+        // Every transfer has memory on one end
+        let memory = self.memory_anchor(transfer.address);
+        let (from, to) = if transfer.to == Part::Memory {
+            (self.place(part_rect(transfer.from)).left_center(), memory)
+        } else {
+            (memory, self.place(part_rect(transfer.to)).left_center())
+        };
+
+        let color = self.transfer_color(transfer);
+        let stroke = Stroke::new(2.0 * self.scale, color);
+        self.painter.line_segment([from, to], stroke);
+        let direction = (to - from).normalized();
+        let tip = 12.0 * self.scale;
+        self.painter
+            .arrow(to - direction * tip, direction * tip, stroke);
+        let label = match transfer.length {
+            1 => format!("{:03X}", transfer.address),
+            length => format!("{:03X}, {} bytes", transfer.address, length),
+        };
+        self.painter.text(
+            from + (to - from) / 2.0,
+            Align2::CENTER_BOTTOM,
+            label,
+            self.font(),
+            color,
+        );
+    }
+
+    fn transfer_color(&self, transfer: &Transfer) -> Color32 {
+        if transfer.to == Part::Memory {
+            WRITE
+        } else {
+            READ
+        }
+    }
+
+    fn place(&self, area: Rect) -> Rect {
+        Rect::from_min_max(self.place_point(area.min), self.place_point(area.max))
+    }
+
+    fn place_point(&self, point: Pos2) -> Pos2 {
+        self.origin + point.to_vec2() * self.scale
+    }
+
+    fn font(&self) -> FontId {
+        FontId::monospace(13.0 * self.scale)
+    }
+
+    fn background(&self) -> Color32 {
+        self.painter.ctx().style().visuals.extreme_bg_color
+    }
+
+    fn text_color(&self) -> Color32 {
+        self.painter.ctx().style().visuals.text_color()
+    }
+
+    fn stroke(&self) -> Stroke {
+        Stroke::new(self.scale, self.text_color().gamma_multiply(0.5))
+    }
+}
+
+fn part_rect(part: Part) -> Rect {
+    match part {
+        Part::Memory => MEMORY,
+        Part::ProgramCounter => PROGRAM_COUNTER,
+        Part::Opcode => OPCODE,
+        Part::Decoder => DECODER,
+        Part::Register(vx) => register_rect(vx),
+        Part::Index => INDEX,
+        Part::Stack => STACK,
+        Part::DelayTimer => DELAY_TIMER,
+        Part::SoundTimer => SOUND_TIMER,
+        Part::Display => DISPLAY,
+    }
+}
+
+// V0 to VF in a 4x4 grid
+fn register_rect(vx: usize) -> Rect {
+    let (column, row) = ((vx % 4) as f32, (vx / 4) as f32);
+    let min = REGISTERS
+        + vec2(
+            column * (REGISTER_SIZE.0 + 5.0),
+            row * (REGISTER_SIZE.1 + 5.0),
+        );
+    Rect::from_min_size(min, vec2(REGISTER_SIZE.0, REGISTER_SIZE.1))
+}
+
+// Top of a memory row, the rows of the program counter window first and then the ones of I
+fn memory_row_top(row: u16) -> f32 {
+    let gap = if row >= MEMORY_ROWS { 40.0 } else { 0.0 };
+    MEMORY.min.y + 40.0 + gap + row as f32 * ROW_HEIGHT
+}
+
+fn lerp(from: Color32, to: Color32, amount: f32) -> Color32 {
+    let channel = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * amount) as u8;
+    Color32::from_rgb(
+        channel(from.r(), to.r()),
+        channel(from.g(), to.g()),
+        channel(from.b(), to.b()),
+    )
+}
@@ -0,0 +1,59 @@
+use std::{cell::RefCell, collections::VecDeque, error::Error, fs, path::PathBuf, rc::Rc};
+use structopt::StructOpt;
+
+mod app;
+mod datapath;
+mod devices;
+mod diagram;
+
+use app::App;
+use chip8_core::{
+    Chip8, Chip8Variant, InstantClock, KeyboardLayout, NullAudio, RomConfig, SeededNumberGenerator,
+};
+use devices::WindowKeyboard;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "chip8-learn")]
+struct CliArgs {
+    #[structopt(long = "rom", short = "r")]
+    rom: PathBuf,
+    /// Fetch, decode and execute stages animated per second
+    #[structopt(long = "speed", default_value = "2")]
+    speed: f32,
+    #[structopt(long = "variant", short = "v")]
+    variant: Option<Chip8Variant>,
+    #[structopt(long = "seed", short = "s", default_value = "0")]
+    seed: u64,
+    #[structopt(long = "layout", short = "l")]
+    layout: Option<KeyboardLayout>,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let cli_args = CliArgs::from_args();
+    // Flags win over the rom config, which wins over the defaults
+    let rom_config = RomConfig::load_for_rom(&cli_args.rom)?.unwrap_or_default();
+    let variant = cli_args.variant.or(rom_config.variant).unwrap_or_default();
+    let layout = cli_args
+        .layout
+        .or(rom_config.keymap.layout)
+        .unwrap_or_default();
+
+    let key_events = Rc::new(RefCell::new(VecDeque::new()));
+    let mut chip8 = Chip8::without_graphics(
+        Box::new(SeededNumberGenerator::new(cli_args.seed)),
+        Box::new(NullAudio),
+        Box::new(WindowKeyboard::new(key_events.clone())),
+        rom_config.quirks(variant.quirks()),
+    );
+    chip8.set_clock(Box::new(InstantClock::new()));
+    chip8.load_program(fs::read(&cli_args.rom)?)?;
+
+    let app = App::new(chip8, key_events, rom_config.keymap(layout), cli_args.speed);
+    let options = eframe::NativeOptions {
+        viewport: eframe::egui::ViewportBuilder::default().with_inner_size([1200.0, 760.0]),
+        ..Default::default()
+    };
+    eframe::run_native("chip8-learn", options, Box::new(|_| Ok(Box::new(app))))?;
+
+    Ok(())
+}
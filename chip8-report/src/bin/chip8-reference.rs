@@ -0,0 +1,33 @@
+use std::{error::Error, fs, path::PathBuf};
+use structopt::StructOpt;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "chip8-reference")]
+struct CliArgs {
+    /// Writes an HTML page instead of Markdown
+    #[structopt(long = "html")]
+    html: bool,
+    /// Where to write the reference, printed when missing
+    #[structopt(long = "output", short = "o")]
+    output: Option<PathBuf>,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let cli_args = CliArgs::from_args();
+    let reference = if cli_args.html {
+        chip8_report::reference_html()
+    } else {
+        chip8_report::reference_markdown()
+    };
+
+    match cli_args.output {
+        Some(output) => {
+            fs::write(&output, reference)
+                .map_err(|error| format!("Couldn't write {}: {}", output.display(), error))?;
+            println!("Wrote the instruction reference to {}", output.display());
+        }
+        None => print!("{}", reference),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,98 @@
+use std::fmt::Write;
+
+use chip8_core::Chip8Variant;
+
+use crate::run::{Outcome, RomReport, VARIANTS};
+
+pub(crate) const STYLE: &str = "body { font-family: sans-serif; }
+table { border-collapse: collapse; }
+th, td { border: 1px solid #999; padding: 4px 8px; }
+td.clean { background: #b8e6b8; }
+td.invalid-opcode { background: #f2b8b8; }
+td.stack-fault { background: #f2d0a0; }
+td.blank-screen { background: #d8d8d8; }
+td.failed { background: #e0b8f0; }";
+
+/// An HTML page with a row per rom and a column per variant, each cell colored by its outcome
+/// and a last row counting the roms that ran clean
+pub fn to_html(reports: &[RomReport]) -> String {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<title>Chip8 compatibility</title>\n");
+    let _ = writeln!(html, "<style>\n{}\n</style>", STYLE);
+    html.push_str("</head>\n<body>\n<table>\n<tr><th>Rom</th>");
+    for variant in VARIANTS.iter() {
+        let _ = write!(html, "<th>{}</th>", variant_name(*variant));
+    }
+    html.push_str("</tr>\n");
+
+    for report in reports {
+        let _ = write!(html, "<tr><td>{}</td>", escape(&report.name));
+        for outcome in &report.outcomes {
+            let _ = write!(
+                html,
+                "<td class=\"{}\">{}</td>",
+                outcome.name(),
+                escape(&outcome.to_string())
+            );
+        }
+        html.push_str("</tr>\n");
+    }
+
+    html.push_str("<tr><th>Clean</th>");
+    for column in 0..VARIANTS.len() {
+        let clean = reports
+            .iter()
+            .filter(|report| report.outcomes.get(column) == Some(&Outcome::Clean))
+            .count();
+        let _ = write!(html, "<th>{}/{}</th>", clean, reports.len());
+    }
+    html.push_str("</tr>\n</table>\n</body>\n</html>\n");
+    html
+}
+
+pub(crate) fn variant_name(variant: Chip8Variant) -> &'static str {
+    match variant {
+        Chip8Variant::Chip8 => "CHIP-8",
+        Chip8Variant::CosmacVip => "COSMAC VIP",
+        Chip8Variant::Chip48 => "CHIP-48",
+        Chip8Variant::SuperChip => "SUPER-CHIP",
+        Chip8Variant::XoChip => "XO-CHIP",
+    }
+}
+
+pub(crate) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_writes_a_row_per_rom_and_counts_the_clean_runs() {
+        let reports = vec![RomReport {
+            name: "<pong>.ch8".to_string(),
+            outcomes: vec![
+                Outcome::Clean,
+                Outcome::BlankScreen,
+                Outcome::StackFault { address: 0x200 },
+                Outcome::InvalidOpcode {
+                    opcode: 0x00C1,
+                    address: 0x204,
+                },
+                Outcome::Clean,
+            ],
+        }];
+
+        let html = to_html(&reports);
+
+        assert!(html.contains("<th>COSMAC VIP</th>"));
+        assert!(html.contains("<tr><td>&lt;pong&gt;.ch8</td><td class=\"clean\">Ran clean</td>"));
+        assert!(html.contains("<td class=\"invalid-opcode\">Invalid opcode 00C1 at 0x204</td>"));
+        assert!(html.contains("<tr><th>Clean</th><th>1/1</th><th>0/1</th>"));
+    }
+}
@@ -0,0 +1,23 @@
+#![warn(missing_docs)]
+
+//! Compatibility reports of chip8 roms across the variants of the interpreter
+//!
+//! Every rom runs for a while under the quirks of each variant without any key pressed, and ends up
+//! in one of a few outcomes:
+//!
+//! - it ran clean, drawing something and ending or still running when the time was up
+//! - it hit an invalid opcode, usually an instruction of another variant or data run as code
+//! - it faulted on the stack, returning without a call or calling too deep
+//! - it never lit a pixel, which usually means it is stuck or waiting on something else
+//!
+//! The results make up an HTML matrix with a row per rom and a column per variant
+//!
+//! The crate also writes the reference of the instructions, generated from what the core decodes
+
+mod html;
+mod reference;
+mod run;
+
+pub use html::to_html;
+pub use reference::{reference_html, reference_markdown};
+pub use run::{run_rom, Outcome, RomReport, VARIANTS};
@@ -0,0 +1,55 @@
+use std::{error::Error, fs, path::PathBuf};
+use structopt::StructOpt;
+
+use chip8_report::RomReport;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "chip8-report")]
+struct CliArgs {
+    /// Directory of the roms to run, every file in it is taken for one
+    roms: PathBuf,
+    /// Seconds each rom runs under each variant
+    #[structopt(long = "seconds", short = "s", default_value = "10")]
+    seconds: u32,
+    /// Instructions run per second
+    #[structopt(long = "hertz", short = "h", default_value = "500")]
+    hertz: u32,
+    /// Where to write the report
+    #[structopt(long = "output", short = "o", default_value = "compatibility.html")]
+    output: PathBuf,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let cli_args = CliArgs::from_args();
+    let mut roms = fs::read_dir(&cli_args.roms)
+        .map_err(|error| format!("Couldn't read {}: {}", cli_args.roms.display(), error))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    roms.retain(|path| path.is_file());
+    roms.sort();
+
+    let mut reports = Vec::new();
+    for path in &roms {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let report = RomReport::new(&name, &fs::read(path)?, cli_args.seconds, cli_args.hertz);
+        let outcomes = report
+            .outcomes
+            .iter()
+            .map(|outcome| outcome.name())
+            .collect::<Vec<_>>();
+        println!("{}: {}", name, outcomes.join(", "));
+        reports.push(report);
+    }
+
+    fs::write(&cli_args.output, chip8_report::to_html(&reports))?;
+    println!(
+        "Wrote the report of {} roms to {}",
+        reports.len(),
+        cli_args.output.display()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,177 @@
+use std::fmt::Write;
+
+use chip8_core::{decode, instruction_set, Chip8Variant, Instruction, Quirks};
+
+use crate::{
+    html::{escape, variant_name, STYLE},
+    run::VARIANTS,
+};
+
+/// A Markdown table of every instruction the core decodes, with an example in the Octo syntax,
+/// what it does, the variants that run it and the quirks changing it
+pub fn reference_markdown() -> String {
+    let mut markdown = String::from("# Chip8 instructions\n\n");
+    markdown.push_str("| Opcode | Example | Description | Variants | Quirks |\n");
+    markdown.push_str("|---|---|---|---|---|\n");
+    for row in rows() {
+        let _ = writeln!(
+            markdown,
+            "| `{}` | `{:04X}` `{}` | {} | {} | {} |",
+            row.pattern,
+            row.example,
+            markdown_cell(&row.octo),
+            markdown_cell(row.summary),
+            row.variants,
+            row.quirks
+                .iter()
+                .map(|quirk| format!("`{}` on {}", quirk.name, quirk.variants))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    markdown
+}
+
+/// The same table as `reference_markdown` as an HTML page
+pub fn reference_html() -> String {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<title>Chip8 instructions</title>\n");
+    let _ = writeln!(html, "<style>\n{}\n</style>", STYLE);
+    html.push_str("</head>\n<body>\n<table>\n");
+    html.push_str(
+        "<tr><th>Opcode</th><th>Example</th><th>Description</th><th>Variants</th><th>Quirks</th></tr>\n",
+    );
+    for row in rows() {
+        let quirks = row
+            .quirks
+            .iter()
+            .map(|quirk| format!("<code>{}</code> on {}", quirk.name, quirk.variants))
+            .collect::<Vec<_>>()
+            .join("<br>");
+        let _ = writeln!(
+            html,
+            "<tr><td><code>{}</code></td><td><code>{:04X}</code> <code>{}</code></td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            row.pattern,
+            row.example,
+            escape(&row.octo),
+            escape(row.summary),
+            row.variants,
+            quirks
+        );
+    }
+    html.push_str("</table>\n</body>\n</html>\n");
+    html
+}
+
+struct Row {
+    pattern: &'static str,
+    example: u16,
+    octo: String,
+    summary: &'static str,
+    variants: String,
+    quirks: Vec<QuirkNote>,
+}
+
+struct QuirkNote {
+    name: &'static str,
+    variants: String,
+}
+
+fn rows() -> Vec<Row> {
+    instruction_set()
+        .into_iter()
+        .map(|instruction| {
+            let example = example_opcode(instruction.pattern());
+            Row {
+                pattern: instruction.pattern(),
+                example,
+                octo: decode(example).to_string(),
+                summary: instruction.summary(),
+                variants: variant_names(|variant| variant.supports(instruction)),
+                quirks: quirk_notes(instruction),
+            }
+        })
+        .collect()
+}
+
+// The opcode of the pattern with each operand digit numbered by its position, `8XY6` gives 8236
+fn example_opcode(pattern: &str) -> u16 {
+    pattern
+        .chars()
+        .enumerate()
+        .fold(0, |opcode, (position, digit)| {
+            let digit = digit.to_digit(16).unwrap_or(position as u32 + 1);
+            (opcode << 4) | digit as u16
+        })
+}
+
+fn quirk_notes(instruction: Instruction) -> Vec<QuirkNote> {
+    instruction
+        .affecting_quirks()
+        .iter()
+        .map(|&name| QuirkNote {
+            name,
+            variants: variant_names(|variant| quirk_enabled(variant.quirks(), name)),
+        })
+        .collect()
+}
+
+fn quirk_enabled(quirks: Quirks, name: &str) -> bool {
+    match name {
+        "shift_uses_vy" => quirks.shift_uses_vy,
+        "load_store_increments_i" => quirks.load_store_increments_i,
+        "logic_resets_vf" => quirks.logic_resets_vf,
+        "jump_uses_vx" => quirks.jump_uses_vx,
+        "wrap_sprites" => quirks.wrap_sprites,
+        "display_wait" => quirks.display_wait,
+        _ => false,
+    }
+}
+
+fn variant_names(filter: impl Fn(Chip8Variant) -> bool) -> String {
+    let names = VARIANTS
+        .iter()
+        .copied()
+        .filter(|variant| filter(*variant))
+        .map(variant_name)
+        .collect::<Vec<_>>();
+    match names.len() {
+        0 => "none".to_string(),
+        count if count == VARIANTS.len() => "all".to_string(),
+        _ => names.join(", "),
+    }
+}
+
+// Pipes would end the cell early, even inside code
+fn markdown_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_numbers_the_operands_of_the_examples() {
+        assert_eq!(example_opcode("8XY6"), 0x8236);
+        assert_eq!(example_opcode("1NNN"), 0x1234);
+        assert_eq!(example_opcode("00E0"), 0x00E0);
+    }
+
+    #[test]
+    fn it_writes_a_row_per_instruction_with_its_variants_and_quirks() {
+        let markdown = reference_markdown();
+        assert_eq!(markdown.lines().count(), 4 + instruction_set().len());
+        assert!(markdown.contains(
+            "| `8XY1` | `8231` `v2 \\|= v3` | Sets VX to VX \\| VY | all | `logic_resets_vf` on COSMAC VIP |"
+        ));
+        assert!(markdown.contains(
+            "| `00FF` | `00FF` `hires` | Switches to the 128x64 display | SUPER-CHIP, XO-CHIP |  |"
+        ));
+
+        let html = reference_html();
+        assert!(html.contains("<td><code>F000</code></td>"));
+        assert!(html.contains("<td>Sets VX to VX &amp; VY</td>"));
+    }
+}
@@ -0,0 +1,187 @@
+use std::fmt;
+
+use chip8_core::{Chip8, Chip8Error, Chip8Variant, ProgramEndDetection, State};
+
+// Frames run per second of emulated time, at which the timers tick
+const FRAMES_PER_SECOND: u32 = 60;
+
+/// Every variant a rom is run under, in the order of the columns of the report
+pub const VARIANTS: [Chip8Variant; 5] = [
+    Chip8Variant::Chip8,
+    Chip8Variant::CosmacVip,
+    Chip8Variant::Chip48,
+    Chip8Variant::SuperChip,
+    Chip8Variant::XoChip,
+];
+
+/// How a rom fared under a variant
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// It drew something and didn't fail until it ended or the time was up
+    Clean,
+    /// It ran into an opcode the variant doesn't have
+    InvalidOpcode {
+        /// The opcode
+        opcode: u16,
+        /// Where it was
+        address: u16,
+    },
+    /// It returned without a call or called too deep
+    StackFault {
+        /// Where the return or call was
+        address: u16,
+    },
+    /// It never lit a single pixel
+    BlankScreen,
+    /// It failed any other way, like reading past the end of memory
+    Failed(String),
+}
+
+impl Outcome {
+    /// Short name of the outcome, also used as the class of its cells in the report
+    pub fn name(&self) -> &'static str {
+        match self {
+            Outcome::Clean => "clean",
+            Outcome::InvalidOpcode { .. } => "invalid-opcode",
+            Outcome::StackFault { .. } => "stack-fault",
+            Outcome::BlankScreen => "blank-screen",
+            Outcome::Failed(_) => "failed",
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Clean => write!(f, "Ran clean"),
+            Outcome::InvalidOpcode { opcode, address } => {
+                write!(f, "Invalid opcode {:04X} at {:#05X}", opcode, address)
+            }
+            Outcome::StackFault { address } => write!(f, "Stack fault at {:#05X}", address),
+            Outcome::BlankScreen => write!(f, "Blank screen"),
+            Outcome::Failed(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// Outcomes of a rom, one per variant in the order of `VARIANTS`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomReport {
+    /// Name of the rom, usually its file name
+    pub name: String,
+    /// How it fared under each variant
+    pub outcomes: Vec<Outcome>,
+}
+
+impl RomReport {
+    /// Runs `rom` under every variant for `seconds` at `hertz` instructions per second
+    pub fn new(name: &str, rom: &[u8], seconds: u32, hertz: u32) -> RomReport {
+        RomReport {
+            name: name.to_string(),
+            outcomes: VARIANTS
+                .iter()
+                .map(|variant| run_rom(rom, *variant, seconds, hertz))
+                .collect(),
+        }
+    }
+}
+
+/// Runs `rom` under the quirks of `variant` for `seconds` at `hertz` instructions per second,
+/// without any key pressed
+///
+/// Random numbers come from a fixed seed, so the same rom always ends up the same way
+pub fn run_rom(rom: &[u8], variant: Chip8Variant, seconds: u32, hertz: u32) -> Outcome {
+    let mut chip8 = Chip8::builder().variant(variant).build();
+    // Returning with nothing on the stack is a fault here rather than a way of ending
+    chip8.set_program_end_detection(ProgramEndDetection {
+        return_with_empty_stack: false,
+        jump_to_self: true,
+    });
+    if let Err(error) = chip8.load_program(rom.to_vec()) {
+        return Outcome::Failed(error.to_string());
+    }
+
+    let instructions_per_frame = (hertz / FRAMES_PER_SECOND).max(1) as usize;
+    let mut drew = false;
+    for _ in 0..seconds * FRAMES_PER_SECOND {
+        let state = match chip8.run_frame(instructions_per_frame) {
+            Ok(state) => state,
+            Err(error) => return classify(error),
+        };
+        drew |= chip8.framebuffer().set_pixels().next().is_some();
+        if matches!(state, State::Finished | State::Halted | State::Exit) {
+            break;
+        }
+    }
+
+    if drew {
+        Outcome::Clean
+    } else {
+        Outcome::BlankScreen
+    }
+}
+
+fn classify(error: Chip8Error) -> Outcome {
+    match error {
+        Chip8Error::InvalidOpcode { context } => Outcome::InvalidOpcode {
+            opcode: context.opcode,
+            address: context.program_counter,
+        },
+        Chip8Error::StackUnderflow { context } | Chip8Error::StackOverflow { context } => {
+            Outcome::StackFault {
+                address: context.program_counter,
+            }
+        }
+        error => Outcome::Failed(error.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECONDS: u32 = 1;
+    const HERTZ: u32 = 500;
+
+    fn run(rom: &[u8]) -> Outcome {
+        run_rom(rom, Chip8Variant::Chip8, SECONDS, HERTZ)
+    }
+
+    #[test]
+    fn it_tells_clean_runs_from_blank_screens() {
+        // Draws the font sprite of 0, then jumps to itself
+        let drawing = [0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x06];
+        // Only adds to V0 in a loop
+        let counting = [0x70, 0x01, 0x12, 0x00];
+
+        assert_eq!(run(&drawing), Outcome::Clean);
+        assert_eq!(run(&counting), Outcome::BlankScreen);
+    }
+
+    #[test]
+    fn it_reports_invalid_opcodes_and_stack_faults() {
+        assert_eq!(
+            run(&[0x60, 0x01, 0xFF, 0xFF]),
+            Outcome::InvalidOpcode {
+                opcode: 0xFFFF,
+                address: 0x202
+            }
+        );
+        assert_eq!(run(&[0x00, 0xEE]), Outcome::StackFault { address: 0x200 });
+        // Calls itself until the stack is full
+        assert_eq!(run(&[0x22, 0x00]), Outcome::StackFault { address: 0x200 });
+    }
+
+    #[test]
+    fn it_runs_roms_under_every_variant() {
+        // Scrolls down, which only SUPER-CHIP and XO-CHIP have, then draws
+        let rom = [0x00, 0xC1, 0x60, 0x00, 0xF0, 0x29, 0xD0, 0x05, 0x12, 0x08];
+
+        let report = RomReport::new("scroll.ch8", &rom, SECONDS, HERTZ);
+
+        assert_eq!(report.outcomes.len(), VARIANTS.len());
+        assert_eq!(report.outcomes[0].name(), "invalid-opcode");
+        assert_eq!(report.outcomes[3], Outcome::Clean);
+        assert_eq!(report.outcomes[4], Outcome::Clean);
+    }
+}
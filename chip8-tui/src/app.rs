@@ -0,0 +1,207 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    error::Error,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use chip8_core::{Chip8, Chip8Error, Component, KeyEvent, Keymap, State};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    DefaultTerminal,
+};
+
+use crate::{
+    hotkeys::{Hotkey, Hotkeys},
+    ui,
+};
+
+const FRAME_DURATION: Duration = Duration::from_micros(16_667);
+// Terminals only report key presses, so a key is held until it hasn't been repeated for a few frames
+const KEY_HOLD_FRAMES: u8 = 6;
+
+/// Terminal debugger driving the interpreter one frame at a time
+///
+/// It starts paused, like the other debuggers, with the timers frozen until the program runs
+pub struct App {
+    pub(crate) chip8: Chip8,
+    key_events: Rc<RefCell<VecDeque<KeyEvent>>>,
+    held_keys: [u8; 16],
+    instructions_per_frame: usize,
+    keymap: Keymap,
+    pub(crate) hotkeys: Hotkeys,
+    pub(crate) running: bool,
+    pub(crate) cursor: u16,
+    pub(crate) status: String,
+    quit: bool,
+}
+
+impl App {
+    pub fn new(
+        chip8: Chip8,
+        key_events: Rc<RefCell<VecDeque<KeyEvent>>>,
+        instructions_per_frame: usize,
+        keymap: Keymap,
+        hotkeys: Hotkeys,
+    ) -> App {
+        let cursor = chip8.program_counter();
+        let mut app = App {
+            chip8,
+            key_events,
+            held_keys: [0; 16],
+            instructions_per_frame,
+            keymap,
+            status: format!(
+                "Paused, press {} to run",
+                hotkeys.key_name(Hotkey::RunPause)
+            ),
+            hotkeys,
+            running: false,
+            cursor,
+            quit: false,
+        };
+        app.pause();
+        app
+    }
+
+    /// Draws and handles input until the user quits
+    pub fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<(), Box<dyn Error>> {
+        while !self.quit {
+            let frame_start = Instant::now();
+            terminal.draw(|frame| ui::draw(frame, self))?;
+
+            while let Some(timeout) = FRAME_DURATION.checked_sub(frame_start.elapsed()) {
+                if !event::poll(timeout)? {
+                    break;
+                }
+                if let Event::Key(key) = event::read()? {
+                    if key.kind != KeyEventKind::Release {
+                        self.handle_key(key.code, key.modifiers);
+                    }
+                }
+            }
+
+            self.release_keys();
+            if self.running {
+                self.run_frame();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn pause(&mut self) {
+        self.running = false;
+        self.chip8.set_component_enabled(Component::Timers, false);
+    }
+
+    fn resume(&mut self) {
+        self.running = true;
+        self.chip8.set_component_enabled(Component::Timers, true);
+        self.status = format!(
+            "Running, press {} to pause",
+            self.hotkeys.key_name(Hotkey::RunPause)
+        );
+    }
+
+    fn handle_key(&mut self, code: KeyCode, modifiers: KeyModifiers) {
+        if let Some(hotkey) = self.hotkeys.for_key(code) {
+            return self.handle_hotkey(hotkey);
+        }
+        match code {
+            KeyCode::Esc => self.quit = true,
+            KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => self.quit = true,
+            KeyCode::Up => self.cursor = self.cursor.wrapping_sub(2),
+            KeyCode::Down => self.cursor = self.cursor.wrapping_add(2),
+            KeyCode::Home => self.cursor = self.chip8.program_counter(),
+            KeyCode::Char(character) => {
+                if let Some(key) = self.keymap.key(character) {
+                    if self.held_keys[key as usize] == 0 {
+                        self.key_events.borrow_mut().push_back(KeyEvent::Down(key));
+                    }
+                    self.held_keys[key as usize] = KEY_HOLD_FRAMES;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn handle_hotkey(&mut self, hotkey: Hotkey) {
+        match hotkey {
+            Hotkey::RunPause if self.running => {
+                self.pause();
+                self.status = "Paused".to_string();
+                self.cursor = self.chip8.program_counter();
+            }
+            Hotkey::RunPause => self.resume(),
+            Hotkey::Breakpoint => self.toggle_breakpoint(),
+            Hotkey::StepOver if !self.running => {
+                let result = self.chip8.step_over();
+                self.stopped(result);
+            }
+            Hotkey::Step if !self.running => {
+                let result = self.chip8.step();
+                self.stopped(result);
+            }
+            Hotkey::StepOver | Hotkey::Step => (),
+        }
+    }
+
+    fn release_keys(&mut self) {
+        for (key, frames) in self.held_keys.iter_mut().enumerate() {
+            if *frames == 0 {
+                continue;
+            }
+            *frames -= 1;
+            if *frames == 0 {
+                self.key_events
+                    .borrow_mut()
+                    .push_back(KeyEvent::Up(key as u8));
+            }
+        }
+    }
+
+    fn toggle_breakpoint(&mut self) {
+        if self.chip8.remove_breakpoint(self.cursor) {
+            self.status = format!("Breakpoint at {:#05X} removed", self.cursor);
+        } else {
+            self.chip8.add_breakpoint(self.cursor);
+            self.status = format!("Breakpoint set at {:#05X}", self.cursor);
+        }
+    }
+
+    fn run_frame(&mut self) {
+        let result = self.chip8.run_frame(self.instructions_per_frame);
+        match result {
+            Ok(State::Continue) | Ok(State::WaitingForKey) => (),
+            Ok(State::Exit) => self.quit = true,
+            _ => {
+                self.pause();
+                self.stopped(result);
+            }
+        }
+    }
+
+    fn stopped(&mut self, result: Result<State, Chip8Error>) {
+        self.cursor = self.chip8.program_counter();
+        self.status = match result {
+            Ok(State::BreakpointHit(address)) => format!("Breakpoint hit at {:#05X}", address),
+            Ok(State::WatchpointHit(address)) => {
+                format!("Watchpoint hit, {:#05X} changed", address)
+            }
+            Ok(State::Finished) => "Program finished".to_string(),
+            Ok(State::Halted) => "Program exited".to_string(),
+            Ok(State::WaitingForKey) => "Waiting for a key".to_string(),
+            Ok(State::Exit) => {
+                self.quit = true;
+                String::new()
+            }
+            Ok(State::Continue) => "Paused".to_string(),
+            Err(error) => {
+                self.pause();
+                error.to_string()
+            }
+        };
+    }
+}
@@ -0,0 +1,118 @@
+use std::{fmt, str::FromStr};
+
+use frontend_common::{HotkeyRegistry, KeyChord};
+use ratatui::crossterm::event::KeyCode;
+
+/// Controls of the debugger, as opposed to keys of the keypad
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hotkey {
+    /// Runs or pauses the program, F5 by default
+    RunPause,
+    /// Runs a single instruction while paused, F11 by default
+    Step,
+    /// Runs a single instruction while paused, a whole subroutine for calls, F10 by default
+    StepOver,
+    /// Sets or removes a breakpoint on the selected line, F9 by default
+    Breakpoint,
+}
+
+impl Hotkey {
+    fn defaults() -> Vec<(Hotkey, KeyChord)> {
+        vec![
+            (Hotkey::RunPause, KeyChord::new("F5")),
+            (Hotkey::Step, KeyChord::new("F11")),
+            (Hotkey::StepOver, KeyChord::new("F10")),
+            (Hotkey::Breakpoint, KeyChord::new("F9")),
+        ]
+    }
+
+    fn help(self) -> &'static str {
+        match self {
+            Hotkey::RunPause => "run/pause",
+            Hotkey::Step => "step",
+            Hotkey::StepOver => "step over",
+            Hotkey::Breakpoint => "breakpoint",
+        }
+    }
+}
+
+impl fmt::Display for Hotkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Hotkey::RunPause => "run_pause",
+            Hotkey::Step => "step",
+            Hotkey::StepOver => "step_over",
+            Hotkey::Breakpoint => "breakpoint",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Hotkey {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Hotkey::defaults()
+            .into_iter()
+            .map(|(hotkey, _)| hotkey)
+            .find(|hotkey| hotkey.to_string() == name)
+            .ok_or_else(|| format!("Unknown hotkey: {}", name))
+    }
+}
+
+/// The hotkeys of the debugger with the keys bound to them
+#[derive(Debug)]
+pub struct Hotkeys {
+    registry: HotkeyRegistry<Hotkey>,
+}
+
+impl Hotkeys {
+    /// Binds every hotkey to its default key, away from the keys the debugger handles itself
+    pub fn new() -> Result<Hotkeys, String> {
+        let mut registry = HotkeyRegistry::new(Hotkey::defaults());
+        registry.reserve(KeyChord::new("Esc"), "quit");
+        registry.reserve(KeyChord::new("Up"), "moving the selection up");
+        registry.reserve(KeyChord::new("Down"), "moving the selection down");
+        registry.reserve(KeyChord::new("Home"), "going to PC");
+        registry.check_conflicts()?;
+        Ok(Hotkeys { registry })
+    }
+
+    /// The hotkey bound to `code`, if any
+    pub fn for_key(&self, code: KeyCode) -> Option<Hotkey> {
+        self.registry.hotkey(&KeyChord::new(&code.to_string()))
+    }
+
+    /// Name of the key bound to `hotkey`
+    pub fn key_name(&self, hotkey: Hotkey) -> String {
+        self.registry
+            .chord(hotkey)
+            .map(KeyChord::to_string)
+            .unwrap_or_default()
+    }
+
+    /// Every hotkey with its key, like `F5 run/pause`, for the help line
+    pub fn help(&self) -> Vec<String> {
+        self.registry
+            .bindings()
+            .map(|(hotkey, chord)| format!("{} {}", chord, hotkey.help()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_finds_the_hotkeys_of_terminal_keys() -> Result<(), String> {
+        let hotkeys = Hotkeys::new()?;
+
+        assert_eq!(hotkeys.for_key(KeyCode::F(5)), Some(Hotkey::RunPause));
+        assert_eq!(hotkeys.for_key(KeyCode::F(10)), Some(Hotkey::StepOver));
+        assert_eq!(hotkeys.for_key(KeyCode::Char('f')), None);
+        assert_eq!(hotkeys.help()[1], "F11 step");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+use std::{cell::RefCell, collections::VecDeque, error::Error, fs, path::PathBuf, rc::Rc};
+use structopt::StructOpt;
+
+mod app;
+mod devices;
+mod hotkeys;
+mod ui;
+
+use app::App;
+use chip8_core::{
+    Chip8, Chip8Variant, InstantClock, KeyboardLayout, NullAudio, RomConfig, SeededNumberGenerator,
+};
+use devices::TerminalKeyboard;
+use hotkeys::Hotkeys;
+
+const FRAMES_PER_SECOND: u32 = 60;
+const DEFAULT_HERTZ: u32 = 500;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "chip8-tui")]
+struct CliArgs {
+    #[structopt(long = "rom", short = "r")]
+    rom: PathBuf,
+    #[structopt(long = "hertz", short = "h")]
+    hertz: Option<u32>,
+    #[structopt(long = "variant", short = "v")]
+    variant: Option<Chip8Variant>,
+    #[structopt(long = "seed", short = "s", default_value = "0")]
+    seed: u64,
+    #[structopt(long = "layout", short = "l")]
+    layout: Option<KeyboardLayout>,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let cli_args = CliArgs::from_args();
+    // Flags win over the rom config, which wins over the defaults
+    let rom_config = RomConfig::load_for_rom(&cli_args.rom)?.unwrap_or_default();
+    let hertz = cli_args.hertz.or(rom_config.hertz).unwrap_or(DEFAULT_HERTZ);
+    let instructions_per_frame = (hertz / FRAMES_PER_SECOND).max(1) as usize;
+    let variant = cli_args.variant.or(rom_config.variant).unwrap_or_default();
+    let layout = cli_args
+        .layout
+        .or(rom_config.keymap.layout)
+        .unwrap_or_default();
+
+    let key_events = Rc::new(RefCell::new(VecDeque::new()));
+    let mut chip8 = Chip8::without_graphics(
+        Box::new(SeededNumberGenerator::new(cli_args.seed)),
+        Box::new(NullAudio),
+        Box::new(TerminalKeyboard::new(key_events.clone())),
+        rom_config.quirks(variant.quirks()),
+    );
+    chip8.set_clock(Box::new(InstantClock::new()));
+    chip8.load_program(fs::read(&cli_args.rom)?)?;
+
+    let mut app = App::new(
+        chip8,
+        key_events,
+        instructions_per_frame,
+        rom_config.keymap(layout),
+        Hotkeys::new()?,
+    );
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+
+    result
+}
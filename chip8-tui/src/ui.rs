@@ -0,0 +1,199 @@
+use chip8_core::{decode, FrameBuffer, KEYPAD};
+use ratatui::{
+    layout::{Constraint, Layout},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, Paragraph},
+    Frame,
+};
+
+use crate::app::App;
+
+const NAVIGATION_HELP: &str = "Up/Down move  Home go to PC  Esc quit";
+
+/// Draws every panel of the debugger
+pub fn draw(frame: &mut Frame, app: &App) {
+    let [main, status, help] = Layout::vertical([
+        Constraint::Min(0),
+        Constraint::Length(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [left, right] =
+        Layout::horizontal([Constraint::Min(0), Constraint::Length(36)]).areas(main);
+    let [display, registers] =
+        Layout::vertical([Constraint::Min(0), Constraint::Length(8)]).areas(left);
+    let [disassembly, stack, keypad] = Layout::vertical([
+        Constraint::Min(0),
+        Constraint::Length(6),
+        Constraint::Length(6),
+    ])
+    .areas(right);
+
+    let pixels: Vec<Line> = half_blocks(app.chip8.framebuffer())
+        .into_iter()
+        .map(Line::from)
+        .collect();
+    frame.render_widget(
+        Paragraph::new(pixels).block(Block::bordered().title("Display")),
+        display,
+    );
+    frame.render_widget(
+        Paragraph::new(register_lines(app)).block(Block::bordered().title("Registers")),
+        registers,
+    );
+    let rows = disassembly.height.saturating_sub(2);
+    frame.render_widget(
+        Paragraph::new(disassembly_lines(app, rows)).block(Block::bordered().title("Disassembly")),
+        disassembly,
+    );
+    frame.render_widget(
+        Paragraph::new(stack_lines(app)).block(Block::bordered().title("Stack")),
+        stack,
+    );
+    frame.render_widget(
+        Paragraph::new(keypad_lines(app)).block(Block::bordered().title("Keypad")),
+        keypad,
+    );
+    frame.render_widget(Line::from(app.status.as_str()), status);
+    let mut help_line = app.hotkeys.help();
+    help_line.push(NAVIGATION_HELP.to_string());
+    frame.render_widget(
+        Line::styled(
+            help_line.join("  "),
+            Style::default().add_modifier(Modifier::DIM),
+        ),
+        help,
+    );
+}
+
+/// Renders the frame buffer two rows at a time, using half blocks for each pair of pixels
+pub fn half_blocks(frame_buffer: &FrameBuffer) -> Vec<String> {
+    (0..frame_buffer.height())
+        .step_by(2)
+        .map(|y| {
+            (0..frame_buffer.width())
+                .map(|x| {
+                    let top = frame_buffer.get_pixel(x, y);
+                    let bottom = y + 1 < frame_buffer.height() && frame_buffer.get_pixel(x, y + 1);
+                    match (top, bottom) {
+                        (true, true) => '█',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (false, false) => ' ',
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn register_lines(app: &App) -> Vec<Line<'static>> {
+    let chip8 = &app.chip8;
+    let mut lines = vec![Line::from(format!(
+        "PC {:#05X}  I {:#05X}  DT {:3}  ST {:3}",
+        chip8.program_counter(),
+        chip8.index_register(),
+        chip8.delay_timer(),
+        chip8.sound_timer()
+    ))];
+    for (row, values) in chip8.v_registers().chunks(8).enumerate() {
+        let registers: Vec<String> = values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| format!("V{:X} {:02X}", row * 8 + index, value))
+            .collect();
+        lines.push(Line::from(registers.join("  ")));
+    }
+
+    lines
+}
+
+fn disassembly_lines(app: &App, rows: u16) -> Vec<Line<'static>> {
+    let chip8 = &app.chip8;
+    let program_counter = chip8.program_counter();
+    let breakpoints: Vec<u16> = chip8.breakpoints().collect();
+    let first = app.cursor.saturating_sub((rows / 2) * 2);
+
+    (0..rows)
+        .map(|row| first.wrapping_add(row * 2))
+        .map(|address| {
+            let opcode = match chip8.read_memory(address, 2) {
+                Ok(bytes) => u16::from_be_bytes([bytes[0], bytes[1]]),
+                Err(_) => return Line::from(""),
+            };
+            let breakpoint = if breakpoints.contains(&address) {
+                '*'
+            } else {
+                ' '
+            };
+            let current = if address == program_counter { '>' } else { ' ' };
+            let text = format!(
+                "{}{} {:#05X}: {:04X}  {}",
+                breakpoint,
+                current,
+                address,
+                opcode,
+                decode(opcode)
+            );
+
+            let mut style = Style::default();
+            if address == program_counter {
+                style = style.add_modifier(Modifier::BOLD);
+            }
+            if address == app.cursor {
+                style = style.add_modifier(Modifier::REVERSED);
+            }
+            Line::styled(text, style)
+        })
+        .collect()
+}
+
+fn stack_lines(app: &App) -> Vec<Line<'static>> {
+    let stack = app.chip8.stack();
+    if stack.is_empty() {
+        return vec![Line::from("empty")];
+    }
+
+    stack
+        .iter()
+        .rev()
+        .map(|address| Line::from(format!("{:#05X}", address)))
+        .collect()
+}
+
+fn keypad_lines(app: &App) -> Vec<Line<'static>> {
+    KEYPAD
+        .iter()
+        .map(|row| {
+            let keys: Vec<String> = row
+                .iter()
+                .map(|key| {
+                    if app.chip8.is_key_pressed(*key) {
+                        format!("[{:X}]", key)
+                    } else {
+                        format!(" {:X} ", key)
+                    }
+                })
+                .collect();
+            Line::from(keys.join(" "))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_renders_two_rows_of_pixels_per_line() {
+        let mut frame_buffer = FrameBuffer::new(4, 3);
+        frame_buffer.set_pixel(0, 0, true);
+        frame_buffer.set_pixel(0, 1, true);
+        frame_buffer.set_pixel(1, 0, true);
+        frame_buffer.set_pixel(2, 1, true);
+        frame_buffer.set_pixel(3, 2, true);
+
+        assert_eq!(half_blocks(&frame_buffer), vec!["█▀▄ ", "   ▀"]);
+    }
+}
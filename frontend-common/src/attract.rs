@@ -0,0 +1,230 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use chip8_core::{Chip8, Chip8Error, Replay, State};
+
+const FRAMES_PER_SECOND: u32 = 60;
+
+/// Attract mode, a kiosk that plays a playlist of roms over and over, each with a replay
+///
+/// Each rom plays for the same number of seconds, or less when its replay or the program ends
+/// first, and is then swapped for the next one in the interpreter that is already running.
+/// Playlists are toml files listing the roms and their replays, relative to the playlist:
+///
+/// ```toml
+/// seconds = 30
+///
+/// [[rom]]
+/// path = "pong.ch8"
+/// replay = "pong.replay"
+///
+/// [[rom]]
+/// path = "invaders.ch8"
+/// replay = "invaders.replay"
+/// ```
+#[derive(Debug, Clone)]
+pub struct AttractMode {
+    roms: Vec<AttractRom>,
+    frames_per_rom: u32,
+    state: AttractState,
+}
+
+/// A rom of the playlist, with the replay played on it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttractRom {
+    /// Where the rom was read from
+    pub path: PathBuf,
+    /// The program
+    pub rom: Vec<u8>,
+    /// Input played on the program
+    pub replay: Replay,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AttractState {
+    /// Nothing loaded in the interpreter yet
+    Stopped,
+    /// The rom at `index` is in the interpreter, swapped for the next one after `frames_left`
+    Playing { index: usize, frames_left: u32 },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SavedPlaylist {
+    seconds: u32,
+    rom: Vec<SavedRom>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SavedRom {
+    path: PathBuf,
+    replay: PathBuf,
+}
+
+impl AttractMode {
+    /// Plays each of `roms` for `seconds`, failing without any rom to play
+    pub fn new(roms: Vec<AttractRom>, seconds: u32) -> Result<AttractMode, Chip8Error> {
+        if roms.is_empty() {
+            return Err(Chip8Error::InvalidConfig(
+                "The playlist has no rom".to_string(),
+            ));
+        }
+        Ok(AttractMode {
+            roms,
+            frames_per_rom: (seconds * FRAMES_PER_SECOND).max(1),
+            state: AttractState::Stopped,
+        })
+    }
+
+    /// Reads a playlist and every rom and replay in it, so none is missing halfway through
+    pub fn load(path: &Path) -> Result<AttractMode, Chip8Error> {
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        let read = |file: &Path| {
+            let file = directory.join(file);
+            fs::read(&file).map_err(|error| {
+                Chip8Error::InvalidConfig(format!("Couldn't read {}: {}", file.display(), error))
+            })
+        };
+
+        let playlist = fs::read_to_string(path)
+            .map_err(|error| format!("Couldn't read {}: {}", path.display(), error))
+            .and_then(|playlist| {
+                toml::from_str::<SavedPlaylist>(&playlist).map_err(|error| error.to_string())
+            })
+            .map_err(Chip8Error::InvalidConfig)?;
+        let roms = playlist
+            .rom
+            .iter()
+            .map(|saved| {
+                let replay = Replay::read(&read(&saved.replay)?).map_err(|error| {
+                    Chip8Error::InvalidConfig(format!("{}: {}", saved.replay.display(), error))
+                })?;
+                Ok(AttractRom {
+                    path: directory.join(&saved.path),
+                    rom: read(&saved.path)?,
+                    replay,
+                })
+            })
+            .collect::<Result<_, Chip8Error>>()?;
+        AttractMode::new(roms, playlist.seconds)
+    }
+
+    /// Every rom of the playlist, in the order they play
+    pub fn roms(&self) -> &[AttractRom] {
+        &self.roms
+    }
+
+    /// The rom in the interpreter, none before `start`
+    pub fn current(&self) -> Option<&AttractRom> {
+        match self.state {
+            AttractState::Stopped => None,
+            AttractState::Playing { index, .. } => self.roms.get(index),
+        }
+    }
+
+    /// Loads the first rom of the playlist in `chip8` and starts its replay
+    pub fn start(&mut self, chip8: &mut Chip8) -> Result<&AttractRom, Chip8Error> {
+        self.play(chip8, 0)
+    }
+
+    /// Counts the frame `chip8` just ran, returning `state`, and swaps the rom for the next one
+    /// when it played long enough, its replay is over or the program ended. Returns the rom it
+    /// swapped to
+    pub fn next_frame(
+        &mut self,
+        chip8: &mut Chip8,
+        state: State,
+    ) -> Result<Option<&AttractRom>, Chip8Error> {
+        let (index, frames_left) = match self.state {
+            AttractState::Stopped => return self.start(chip8).map(Some),
+            AttractState::Playing { index, frames_left } => (index, frames_left - 1),
+        };
+        let ended = matches!(state, State::Finished | State::Halted);
+        if frames_left > 0 && chip8.is_replaying() && !ended {
+            self.state = AttractState::Playing { index, frames_left };
+            return Ok(None);
+        }
+        self.play(chip8, (index + 1) % self.roms.len()).map(Some)
+    }
+
+    // Hot swaps the rom at `index` in, freshly loaded as its replay expects
+    fn play(&mut self, chip8: &mut Chip8, index: usize) -> Result<&AttractRom, Chip8Error> {
+        let rom = &self.roms[index];
+        chip8.reset(false)?;
+        chip8.load_program(rom.rom.clone())?;
+        chip8.play_replay(rom.replay.clone());
+        self.state = AttractState::Playing {
+            index,
+            frames_left: self.frames_per_rom,
+        };
+        Ok(rom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A rom that keeps adding `step` to V0, with a replay of `frames` frames of 4 instructions
+    fn counter(name: &str, step: u8, frames: usize) -> AttractRom {
+        let replay = format!("seed 0\n{}", "4\n".repeat(frames));
+        AttractRom {
+            path: PathBuf::from(name),
+            rom: vec![0x70, step, 0x12, 0x00],
+            replay: replay.parse().unwrap(),
+        }
+    }
+
+    fn run(attract: &mut AttractMode, chip8: &mut Chip8, frames: usize) -> Vec<String> {
+        let mut swapped = Vec::new();
+        for _ in 0..frames {
+            let state = chip8.run_frame(1).unwrap();
+            if let Some(rom) = attract.next_frame(chip8, state).unwrap() {
+                swapped.push(rom.path.display().to_string());
+            }
+        }
+        swapped
+    }
+
+    #[test]
+    fn it_swaps_roms_after_their_time_and_loops_over_the_playlist() -> Result<(), Chip8Error> {
+        let mut attract =
+            AttractMode::new(vec![counter("ones", 1, 600), counter("twos", 2, 600)], 1)?;
+        let mut chip8 = Chip8::builder().build();
+
+        assert_eq!(attract.start(&mut chip8)?.path, Path::new("ones"));
+        assert!(run(&mut attract, &mut chip8, 59).is_empty());
+        // The replay ran 4 instructions per frame, two of them adding to V0
+        assert_eq!(chip8.v_register(0), 118);
+
+        assert_eq!(run(&mut attract, &mut chip8, 1), vec!["twos"]);
+        assert_eq!(chip8.v_register(0), 0);
+        assert_eq!(run(&mut attract, &mut chip8, 60), vec!["ones"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_moves_on_when_the_replay_is_over() -> Result<(), Chip8Error> {
+        let mut attract =
+            AttractMode::new(vec![counter("short", 1, 10), counter("long", 2, 600)], 30)?;
+        let mut chip8 = Chip8::builder().build();
+        attract.start(&mut chip8)?;
+
+        assert!(run(&mut attract, &mut chip8, 10).is_empty());
+        // The interpreter finds out the replay is over when running the frame after its last one
+        assert_eq!(run(&mut attract, &mut chip8, 1), vec!["long"]);
+        assert_eq!(
+            attract.current().map(|rom| rom.path.as_path()),
+            Some(Path::new("long"))
+        );
+        assert!(AttractMode::new(Vec::new(), 30).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,325 @@
+//! Controls of the emulator itself bound to keys, shared by every frontend
+//!
+//! Frontends list their hotkeys with the keys bound to them by default, let the user override
+//! them by name and check the result before starting, so a new hotkey can't silently take a key
+//! that already does something else
+
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+/// A key, optionally with Shift held, like `F5` or `Shift+N`
+///
+/// Keys are named like the frontend names them, and compared ignoring case
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyChord {
+    key: String,
+    shift: bool,
+}
+
+impl KeyChord {
+    /// `key` pressed on its own
+    pub fn new(key: &str) -> KeyChord {
+        KeyChord {
+            key: key.to_string(),
+            shift: false,
+        }
+    }
+
+    /// `key` pressed with Shift held
+    pub fn shifted(key: &str) -> KeyChord {
+        KeyChord {
+            key: key.to_string(),
+            shift: true,
+        }
+    }
+
+    /// Name of the key, without the modifier
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Whether Shift has to be held
+    pub fn shift(&self) -> bool {
+        self.shift
+    }
+
+    /// Whether both are the same key, ignoring case
+    pub fn is_same_key(&self, other: &KeyChord) -> bool {
+        self.key.eq_ignore_ascii_case(&other.key)
+    }
+
+    fn clashes_with(&self, other: &KeyChord) -> bool {
+        self.is_same_key(other) && self.shift == other.shift
+    }
+}
+
+impl FromStr for KeyChord {
+    type Err = String;
+
+    fn from_str(chord: &str) -> Result<Self, Self::Err> {
+        let chord = chord.trim();
+        // A lone `+` is the key itself, `Shift++` is that key with Shift held
+        let shifted = chord
+            .split_once('+')
+            .filter(|(modifier, key)| !modifier.is_empty() && !key.is_empty())
+            .map(|(modifier, key)| (modifier.trim(), key.trim()));
+        match shifted {
+            Some((modifier, key)) if modifier.eq_ignore_ascii_case("shift") => {
+                Ok(KeyChord::shifted(key))
+            }
+            Some((modifier, _)) => Err(format!("Unknown modifier: {}", modifier)),
+            None if chord.is_empty() => Err("Missing key".to_string()),
+            None => Ok(KeyChord::new(chord)),
+        }
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.shift {
+            write!(f, "Shift+{}", self.key)
+        } else {
+            write!(f, "{}", self.key)
+        }
+    }
+}
+
+/// Every hotkey of a frontend with the key bound to it
+///
+/// `H` is the frontend's own list of hotkeys, named in config files by its `Display` and `FromStr`
+#[derive(Debug, Clone)]
+pub struct HotkeyRegistry<H> {
+    bindings: Vec<(H, KeyChord)>,
+    reserved: Vec<(String, KeyChord)>,
+}
+
+impl<H> HotkeyRegistry<H>
+where
+    H: Copy + PartialEq + fmt::Display + FromStr<Err = String>,
+{
+    /// Binds every hotkey to its default key
+    pub fn new(defaults: impl IntoIterator<Item = (H, KeyChord)>) -> HotkeyRegistry<H> {
+        HotkeyRegistry {
+            bindings: defaults.into_iter().collect(),
+            reserved: Vec::new(),
+        }
+    }
+
+    /// Keeps `chord` for something that isn't a hotkey, like quitting or a key of the keypad,
+    /// described by `purpose` when a hotkey is bound to it
+    pub fn reserve(&mut self, chord: KeyChord, purpose: &str) {
+        self.reserved.push((purpose.to_string(), chord));
+    }
+
+    /// Binds `hotkey` to `chord` instead of its default key
+    pub fn bind(&mut self, hotkey: H, chord: KeyChord) {
+        match self.bindings.iter_mut().find(|(bound, _)| *bound == hotkey) {
+            Some((_, bound)) => *bound = chord,
+            None => self.bindings.push((hotkey, chord)),
+        }
+    }
+
+    /// Binds the hotkeys named in `overrides`, like a `[hotkeys]` table of a config
+    ///
+    /// ```
+    /// # use std::{collections::BTreeMap, fmt, str::FromStr};
+    /// use frontend_common::hotkeys::{HotkeyRegistry, KeyChord};
+    ///
+    /// #[derive(Debug, Clone, Copy, PartialEq)]
+    /// enum Hotkey {
+    ///     Pause,
+    /// }
+    /// # impl fmt::Display for Hotkey {
+    /// #     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "pause") }
+    /// # }
+    /// # impl FromStr for Hotkey {
+    /// #     type Err = String;
+    /// #     fn from_str(name: &str) -> Result<Self, String> {
+    /// #         match name { "pause" => Ok(Hotkey::Pause), _ => Err(format!("Unknown hotkey: {}", name)) }
+    /// #     }
+    /// # }
+    ///
+    /// let mut hotkeys = HotkeyRegistry::new(vec![(Hotkey::Pause, KeyChord::new("P"))]);
+    /// let overrides: BTreeMap<String, String> = vec![("pause".into(), "Space".into())]
+    ///     .into_iter()
+    ///     .collect();
+    /// hotkeys.apply_overrides(&overrides)?;
+    ///
+    /// assert_eq!(hotkeys.hotkey(&KeyChord::new("space")), Some(Hotkey::Pause));
+    /// # Ok::<(), String>(())
+    /// ```
+    pub fn apply_overrides(&mut self, overrides: &BTreeMap<String, String>) -> Result<(), String> {
+        for (name, chord) in overrides {
+            let hotkey = name.parse::<H>()?;
+            let chord = chord
+                .parse()
+                .map_err(|error| format!("Invalid key for hotkey {}: {}", name, error))?;
+            self.bind(hotkey, chord);
+        }
+        Ok(())
+    }
+
+    /// Fails with every hotkey bound to a key that is already taken, by a reserved key or an
+    /// other hotkey
+    pub fn check_conflicts(&self) -> Result<(), String> {
+        let mut conflicts = Vec::new();
+        for (index, (hotkey, chord)) in self.bindings.iter().enumerate() {
+            let taken_by = self
+                .reserved
+                .iter()
+                .filter(|(_, reserved)| reserved.clashes_with(chord))
+                .map(|(purpose, _)| purpose.clone())
+                .chain(
+                    self.bindings[..index]
+                        .iter()
+                        .filter(|(_, bound)| bound.clashes_with(chord))
+                        .map(|(other, _)| format!("hotkey {}", other)),
+                );
+            for purpose in taken_by {
+                conflicts.push(format!("{} of hotkey {} is {}", chord, hotkey, purpose));
+            }
+        }
+
+        if conflicts.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Conflicting hotkeys: {}", conflicts.join(", ")))
+        }
+    }
+
+    /// Key bound to `hotkey`
+    pub fn chord(&self, hotkey: H) -> Option<&KeyChord> {
+        self.bindings
+            .iter()
+            .find(|(bound, _)| *bound == hotkey)
+            .map(|(_, chord)| chord)
+    }
+
+    /// Hotkey bound to `chord`
+    pub fn hotkey(&self, chord: &KeyChord) -> Option<H> {
+        self.bindings
+            .iter()
+            .find(|(_, bound)| bound.clashes_with(chord))
+            .map(|(hotkey, _)| *hotkey)
+    }
+
+    /// Every hotkey with the key bound to it, in the order they were registered
+    pub fn bindings(&self) -> impl Iterator<Item = (H, &KeyChord)> {
+        self.bindings.iter().map(|(hotkey, chord)| (*hotkey, chord))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Hotkey {
+        Pause,
+        Step,
+        Reset,
+    }
+
+    impl fmt::Display for Hotkey {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let name = match self {
+                Hotkey::Pause => "pause",
+                Hotkey::Step => "step",
+                Hotkey::Reset => "reset",
+            };
+            write!(f, "{}", name)
+        }
+    }
+
+    impl FromStr for Hotkey {
+        type Err = String;
+
+        fn from_str(name: &str) -> Result<Self, Self::Err> {
+            match name {
+                "pause" => Ok(Hotkey::Pause),
+                "step" => Ok(Hotkey::Step),
+                "reset" => Ok(Hotkey::Reset),
+                _ => Err(format!("Unknown hotkey: {}", name)),
+            }
+        }
+    }
+
+    fn registry() -> HotkeyRegistry<Hotkey> {
+        HotkeyRegistry::new(vec![
+            (Hotkey::Pause, KeyChord::new("P")),
+            (Hotkey::Step, KeyChord::new("N")),
+            (Hotkey::Reset, KeyChord::new("Backspace")),
+        ])
+    }
+
+    fn overrides(overrides: &[(&str, &str)]) -> BTreeMap<String, String> {
+        overrides
+            .iter()
+            .map(|(name, chord)| (name.to_string(), chord.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn it_parses_keys_with_and_without_shift() {
+        assert_eq!("F5".parse(), Ok(KeyChord::new("F5")));
+        assert_eq!("shift + N".parse(), Ok(KeyChord::shifted("N")));
+        assert_eq!("+".parse(), Ok(KeyChord::new("+")));
+        assert_eq!("Shift++".parse(), Ok(KeyChord::shifted("+")));
+        assert!("Ctrl+N".parse::<KeyChord>().is_err());
+        assert!(" ".parse::<KeyChord>().is_err());
+        assert_eq!(KeyChord::shifted("N").to_string(), "Shift+N");
+    }
+
+    #[test]
+    fn it_finds_hotkeys_by_key_ignoring_case() {
+        let hotkeys = registry();
+
+        assert_eq!(hotkeys.hotkey(&KeyChord::new("p")), Some(Hotkey::Pause));
+        assert_eq!(hotkeys.hotkey(&KeyChord::shifted("P")), None);
+        assert_eq!(hotkeys.chord(Hotkey::Step), Some(&KeyChord::new("N")));
+    }
+
+    #[test]
+    fn it_binds_hotkeys_named_in_overrides() -> Result<(), String> {
+        let mut hotkeys = registry();
+
+        hotkeys.apply_overrides(&overrides(&[("step", "Shift+P")]))?;
+
+        assert_eq!(hotkeys.hotkey(&KeyChord::shifted("P")), Some(Hotkey::Step));
+        assert_eq!(hotkeys.hotkey(&KeyChord::new("N")), None);
+        assert!(hotkeys
+            .apply_overrides(&overrides(&[("turbo", "T")]))
+            .is_err());
+        assert!(hotkeys
+            .apply_overrides(&overrides(&[("step", "Alt+T")]))
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_reports_hotkeys_bound_to_the_same_key() -> Result<(), String> {
+        let mut hotkeys = registry();
+        hotkeys.check_conflicts()?;
+
+        hotkeys.apply_overrides(&overrides(&[("reset", "p")]))?;
+
+        assert_eq!(
+            hotkeys.check_conflicts(),
+            Err("Conflicting hotkeys: p of hotkey reset is hotkey pause".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn it_reports_hotkeys_bound_to_reserved_keys() {
+        let mut hotkeys = registry();
+        hotkeys.reserve(KeyChord::new("Escape"), "quit");
+        hotkeys.reserve(KeyChord::new("n"), "key 9 of the keypad");
+
+        assert_eq!(
+            hotkeys.check_conflicts(),
+            Err("Conflicting hotkeys: N of hotkey step is key 9 of the keypad".to_string())
+        );
+    }
+}
@@ -0,0 +1,13 @@
+#![warn(missing_docs)]
+
+//! Pieces shared by the chip8 frontends that aren't part of the interpreter
+//!
+//! The core runs the programs, this crate has what the frontends built on top of it would
+//! otherwise each write again, like binding the controls of the emulator to keys or playing a
+//! playlist of roms in attract mode
+
+mod attract;
+pub mod hotkeys;
+
+pub use attract::{AttractMode, AttractRom};
+pub use hotkeys::{HotkeyRegistry, KeyChord};
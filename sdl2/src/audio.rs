@@ -1,4 +1,4 @@
-use std::error::Error;
+use std::{cell::RefCell, error::Error, time::Duration};
 
 use chip8_core::{Audio, Chip8Error};
 use sdl2::{
@@ -7,7 +7,7 @@ use sdl2::{
 };
 
 pub struct SdlAudio {
-    audio_device: AudioDevice<SquareWave>,
+    audio_device: RefCell<AudioDevice<SquareWave>>,
 }
 
 impl SdlAudio {
@@ -22,20 +22,35 @@ impl SdlAudio {
             phase_inc: 440.0 / spec.freq as f32,
             phase: 0.0,
             volume: 0.25,
+            sample_rate: spec.freq as f32,
+            pattern: None,
+            bit_inc: 0.0,
+            bit: 0.0,
         })?;
 
-        Ok(SdlAudio { audio_device })
+        Ok(SdlAudio {
+            audio_device: RefCell::new(audio_device),
+        })
     }
 }
 
 impl Audio for SdlAudio {
-    fn play(&self) -> Result<(), Chip8Error> {
-        self.audio_device.resume();
+    fn play(&self, _duration: Duration) -> Result<(), Chip8Error> {
+        self.audio_device.borrow().resume();
         Ok(())
     }
 
     fn stop(&self) -> Result<(), Chip8Error> {
-        self.audio_device.pause();
+        self.audio_device.borrow().pause();
+        Ok(())
+    }
+
+    fn set_pattern(&self, pattern: Option<[u8; 16]>, pitch: u8) -> Result<(), Chip8Error> {
+        let mut audio_device = self.audio_device.borrow_mut();
+        let mut wave = audio_device.lock();
+        let bits_per_second = 4000.0 * 2f32.powf((f32::from(pitch) - 64.0) / 48.0);
+        wave.pattern = pattern;
+        wave.bit_inc = bits_per_second / wave.sample_rate;
         Ok(())
     }
 }
@@ -44,12 +59,27 @@ struct SquareWave {
     phase_inc: f32,
     phase: f32,
     volume: f32,
+    sample_rate: f32,
+    // XO-CHIP audio pattern played instead of the tone, one bit after the other
+    pattern: Option<[u8; 16]>,
+    bit_inc: f32,
+    bit: f32,
 }
 
 impl AudioCallback for SquareWave {
     type Channel = f32;
 
     fn callback(&mut self, out: &mut [f32]) {
+        if let Some(pattern) = self.pattern {
+            for x in out.iter_mut() {
+                let bit = self.bit as usize;
+                let on = pattern[bit / 8] & (0x80 >> (bit % 8)) != 0;
+                *x = if on { self.volume } else { -self.volume };
+                self.bit = (self.bit + self.bit_inc) % 128.0;
+            }
+            return;
+        }
+
         // Generate a square wave
         for x in out.iter_mut() {
             *x = if self.phase <= 0.5 {
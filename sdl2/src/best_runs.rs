@@ -0,0 +1,56 @@
+use std::{error::Error, fs, io, path::PathBuf};
+
+use chip8_core::{romdb, BestRun};
+
+use crate::save_states::SaveStates;
+
+/// Best run of one rom, raced as a ghost, kept under `~/.local/share/chip8/ghosts`
+///
+/// Files are named after the hash of the rom like save states, and hold the score and replay of
+/// the run
+pub struct BestRuns {
+    path: Option<PathBuf>,
+}
+
+impl BestRuns {
+    pub fn for_rom(rom_data: &[u8]) -> BestRuns {
+        let path = BestRuns::default_directory()
+            .map(|directory| directory.join(format!("{}.replay", romdb::sha1(rom_data))));
+        BestRuns { path }
+    }
+
+    /// Where best runs are kept, next to the save states
+    pub fn default_directory() -> Option<PathBuf> {
+        SaveStates::default_directory()
+            .and_then(|states| states.parent().map(|directory| directory.join("ghosts")))
+    }
+
+    /// The best run of the rom, none before its first run ends
+    pub fn load(&self) -> Result<Option<BestRun>, Box<dyn Error>> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let best_run = match fs::read_to_string(path) {
+            Ok(best_run) => best_run,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(format!("Couldn't read {}: {}", path.display(), error).into()),
+        };
+        best_run
+            .parse()
+            .map(Some)
+            .map_err(|error| format!("Invalid best run {}: {}", path.display(), error).into())
+    }
+
+    pub fn save(&self, best_run: &BestRun) -> Result<(), Box<dyn Error>> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or("Couldn't find a directory to keep best runs in")?;
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        fs::write(path, best_run.to_string())
+            .map_err(|error| format!("Couldn't write {}: {}", path.display(), error).into())
+    }
+}
@@ -0,0 +1,221 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use chip8_core::{midi::NoteMap, KeyboardLayout, Palette, PaletteOverrides};
+use sdl2::{controller::Button, keyboard::Keycode};
+use serde::{Deserialize, Deserializer};
+use toml_edit::{DocumentMut, Item, Table, Value};
+
+/// Settings shared by every rom, read from `~/.config/chip8/config.toml` or the `--config` flag
+///
+/// Keys are named like SDL names them, and win over the keyboard layout. Controller buttons use
+/// the SDL game controller names and win over the default mapping. Notes of a MIDI controller
+/// are numbered from 0 to 127 and win over the default pads, 36 to 51. Colors are picked from a preset
+/// and can be changed one by one on top of it:
+///
+/// ```toml
+/// layout = "azerty"
+/// fast_forward = 8
+/// decay = 0.6
+/// crt = true
+/// palette = "amber"
+/// midi = "MPD218"
+///
+/// [colors]
+/// foreground = "#FFD080"
+///
+/// [keys]
+/// Up = 0x5
+/// Down = 0x8
+/// Left = 0x7
+/// Right = 0x9
+///
+/// [buttons]
+/// a = 0x5
+/// start = 0x1
+///
+/// [hotkeys]
+/// pause = "Space"
+/// step_instruction = "Shift+Space"
+///
+/// [notes]
+/// 60 = 0x5
+/// 62 = 0x6
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    #[serde(deserialize_with = "parsed")]
+    pub layout: Option<KeyboardLayout>,
+    pub fast_forward: Option<u32>,
+    pub decay: Option<f32>,
+    pub crt: bool,
+    pub midi: Option<String>,
+    #[serde(deserialize_with = "parsed")]
+    palette: Option<Palette>,
+    colors: PaletteOverrides,
+    keys: BTreeMap<String, u8>,
+    buttons: BTreeMap<String, u8>,
+    notes: BTreeMap<String, u8>,
+    pub hotkeys: BTreeMap<String, String>,
+}
+
+// The d-pad plays like WASD on a QWERTY keyboard, the face buttons like the keys around them
+const DEFAULT_BUTTONS: [(Button, u8); 8] = [
+    (Button::DPadUp, 0x5),
+    (Button::DPadDown, 0x8),
+    (Button::DPadLeft, 0x7),
+    (Button::DPadRight, 0x9),
+    (Button::A, 0x6),
+    (Button::B, 0x4),
+    (Button::X, 0x1),
+    (Button::Y, 0xC),
+];
+
+impl Config {
+    /// Where the config lives when `--config` isn't passed, following the XDG convention
+    pub fn default_path() -> Option<PathBuf> {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+            .map(|config| config.join("chip8").join("config.toml"))
+    }
+
+    /// Reads the config at `path`, or at the default path where it is fine for it to be missing
+    pub fn load(path: Option<&Path>) -> Result<Config, Box<dyn Error>> {
+        let (path, required) = match path.map(Path::to_path_buf) {
+            Some(path) => (path, true),
+            None => match Config::default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Config::default()),
+            },
+        };
+
+        let config = match fs::read_to_string(&path) {
+            Ok(config) => config,
+            Err(error) if error.kind() == io::ErrorKind::NotFound && !required => {
+                return Ok(Config::default())
+            }
+            Err(error) => return Err(format!("Couldn't read {}: {}", path.display(), error).into()),
+        };
+        toml::from_str(&config)
+            .map_err(|error| format!("Invalid config {}: {}", path.display(), error).into())
+    }
+
+    /// Replaces the `[keys]` table of the config at `path`, or at the default path, keeping the
+    /// rest of it as it was written, comments included. Returns where it was saved
+    pub fn save_keys(
+        path: Option<&Path>,
+        keys: &BTreeMap<String, u8>,
+    ) -> Result<PathBuf, Box<dyn Error>> {
+        let path = path
+            .map(Path::to_path_buf)
+            .or_else(Config::default_path)
+            .ok_or("Couldn't find where to keep the config")?;
+        let mut config = match fs::read_to_string(&path) {
+            Ok(config) => config
+                .parse::<DocumentMut>()
+                .map_err(|error| format!("Invalid config {}: {}", path.display(), error))?,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => DocumentMut::new(),
+            Err(error) => return Err(format!("Couldn't read {}: {}", path.display(), error).into()),
+        };
+
+        // Names differing only by case are the same key to SDL
+        if key_bindings(keys, "the new keys")?.len() < keys.len() {
+            return Err("The new keys bind the same key twice".into());
+        }
+        let mut table = Table::new();
+        for (name, key) in keys {
+            let key = format!("{:#X}", key).replacen("0X", "0x", 1);
+            table.insert(name, Item::Value(key.parse::<Value>()?));
+        }
+        config.insert("keys", Item::Table(table));
+
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        fs::write(&path, config.to_string())
+            .map_err(|error| format!("Couldn't write {}: {}", path.display(), error))?;
+        Ok(path)
+    }
+
+    /// Keypad keys pressed by each physical key bound in the config
+    pub fn key_bindings(&self) -> Result<HashMap<Keycode, u8>, String> {
+        key_bindings(&self.keys, "the config")
+    }
+
+    /// Colors of the preset, or the default ones, with those changed by the config on top
+    pub fn palette(&self) -> Palette {
+        let palette = self.palette.unwrap_or_default();
+        Palette {
+            background: self.colors.background.unwrap_or(palette.background),
+            foreground: self.colors.foreground.unwrap_or(palette.foreground),
+        }
+    }
+
+    /// Keypad keys pressed by each controller button, the default mapping with the config on top
+    pub fn button_bindings(&self) -> Result<HashMap<Button, u8>, String> {
+        let mut bindings: HashMap<Button, u8> = DEFAULT_BUTTONS.iter().copied().collect();
+        for (name, key) in &self.buttons {
+            let button = Button::from_string(name)
+                .ok_or_else(|| format!("Unknown controller button in the config: {}", name))?;
+            bindings.insert(button, keypad_key(name, *key)?);
+        }
+        Ok(bindings)
+    }
+
+    /// Keypad keys played by each note of a MIDI controller, the default pads with the config on top
+    pub fn note_map(&self) -> Result<NoteMap, String> {
+        let mut notes = NoteMap::default();
+        for (name, key) in &self.notes {
+            let note = name
+                .parse::<u8>()
+                .ok()
+                .filter(|note| *note <= 0x7F)
+                .ok_or_else(|| format!("Unknown note in the config: {}", name))?;
+            notes.bind(note, keypad_key(name, *key)?);
+        }
+        Ok(notes)
+    }
+}
+
+/// Keypad keys pressed by each of the `keys` named like SDL names them, read from `source`
+pub fn key_bindings(
+    keys: &BTreeMap<String, u8>,
+    source: &str,
+) -> Result<HashMap<Keycode, u8>, String> {
+    keys.iter()
+        .map(|(name, key)| {
+            let keycode = Keycode::from_name(name)
+                .ok_or_else(|| format!("Unknown key in {}: {}", source, name))?;
+            Ok((keycode, keypad_key(name, *key)?))
+        })
+        .collect()
+}
+
+pub fn keypad_key(name: &str, key: u8) -> Result<u8, String> {
+    if key > 0xF {
+        return Err(format!(
+            "{} is bound to {:#X}, the keypad goes up to 0xF",
+            name, key
+        ));
+    }
+    Ok(key)
+}
+
+fn parsed<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr<Err = String>,
+{
+    String::deserialize(deserializer)?
+        .parse()
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
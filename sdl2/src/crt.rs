@@ -0,0 +1,33 @@
+use std::cell::Cell;
+
+/// Scanlines and the curved screen of an old CRT, drawn over the display when turned on
+///
+/// Shared between the main loop, which turns it on and off, and the graphics device, which draws it
+#[derive(Debug, Default)]
+pub struct Crt {
+    enabled: Cell<bool>,
+    changed: Cell<bool>,
+}
+
+impl Crt {
+    pub fn new(enabled: bool) -> Crt {
+        Crt {
+            enabled: Cell::new(enabled),
+            changed: Cell::new(false),
+        }
+    }
+
+    pub fn toggle(&self) {
+        self.enabled.set(!self.enabled.get());
+        self.changed.set(true);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.get()
+    }
+
+    /// Whether it was turned on or off since the last call, the display has to be drawn again to show it
+    pub fn take_changed(&self) -> bool {
+        self.changed.replace(false)
+    }
+}
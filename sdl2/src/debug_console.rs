@@ -0,0 +1,68 @@
+use std::{
+    io::{self, prelude::*},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use chip8_core::{Chip8, State};
+use chip8_dbg::{Command, Debugger};
+
+/// Debugger reading commands from the terminal while the window keeps running
+///
+/// Commands are read on their own thread so the window stays responsive while waiting for them
+pub struct DebugConsole {
+    debugger: Debugger,
+    commands: Receiver<String>,
+}
+
+impl DebugConsole {
+    pub fn attach(chip8: &mut Chip8) -> DebugConsole {
+        let (sender, commands) = mpsc::channel();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        println!("Debugger attached and paused, type `help` for the commands");
+        DebugConsole {
+            debugger: Debugger::attach(chip8),
+            commands,
+        }
+    }
+
+    /// Runs the commands typed since the last call
+    pub fn run_commands(&mut self, chip8: &mut Chip8) {
+        for line in self.commands.try_iter() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let output = match line.parse::<Command>() {
+                Ok(command) => match self.debugger.execute(chip8, command) {
+                    Ok(output) => output,
+                    Err(error) => error.to_string(),
+                },
+                Err(error) => error,
+            };
+            if !output.is_empty() {
+                println!("{}", output);
+            }
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.debugger.is_running()
+    }
+
+    pub fn quit_requested(&self) -> bool {
+        self.debugger.quit_requested()
+    }
+
+    pub fn report(&mut self, chip8: &mut Chip8, state: State) {
+        if let Some(message) = self.debugger.report(chip8, state) {
+            println!("{}", message);
+        }
+    }
+}
@@ -0,0 +1,144 @@
+use std::{cell::RefCell, error::Error};
+
+use chip8_core::{
+    BestRun, Chip8, Chip8Variant, Font, Frame, FrameBuffer, Ghost, Palette, Quirks, RgbaImage,
+    Score,
+};
+
+use crate::best_runs::BestRuns;
+
+// Share of the foreground color the pixels of the ghost get, over the background
+const GHOST_OPACITY: f32 = 0.3;
+
+/// Racing the best run of a rom: the live run is recorded and kept as the new best run when it
+/// scores higher, while the best one plays as a ghost
+pub struct Race {
+    score: Score,
+    best_runs: BestRuns,
+    best_run: Option<BestRun>,
+    ghost: Option<Ghost>,
+    rom: Vec<u8>,
+    variant: Chip8Variant,
+    quirks: Quirks,
+    font: Font,
+}
+
+/// Display of the best run being raced, drawn faintly behind the live one
+///
+/// Shared between the main loop, which updates it every frame, and the graphics device, which draws it
+#[derive(Debug, Default)]
+pub struct GhostLayer {
+    display: RefCell<Option<FrameBuffer>>,
+}
+
+impl GhostLayer {
+    /// Shows `display` from the next time the live one is drawn, or nothing
+    pub fn set(&self, display: Option<&FrameBuffer>) {
+        self.display.replace(display.cloned());
+    }
+
+    /// Colors the pixels lit by the ghost but not by the live game in `image`, which is `frame`
+    /// colored. Nothing is drawn while the two don't have the same resolution
+    pub fn draw(&self, image: &mut RgbaImage, frame: &Frame, palette: &Palette) {
+        let display = self.display.borrow();
+        let display = match display.as_ref() {
+            Some(display)
+                if (display.width(), display.height()) == (frame.width(), frame.height()) =>
+            {
+                display
+            }
+            _ => return,
+        };
+
+        let mut color = palette.background;
+        for (channel, foreground) in color.iter_mut().zip(palette.foreground.iter()).take(3) {
+            let background = f32::from(*channel);
+            *channel = (background + (f32::from(*foreground) - background) * GHOST_OPACITY) as u8;
+        }
+        for (x, y) in display.set_pixels() {
+            if !frame.get_pixel(x, y) {
+                image.put_pixel(x as u32, y as u32, color);
+            }
+        }
+    }
+}
+
+impl Race {
+    /// Races the best run of `rom`, whose ghost runs with the variant, quirks and font of the
+    /// live game
+    pub fn new(
+        score: Score,
+        rom: &[u8],
+        variant: Chip8Variant,
+        quirks: Quirks,
+        font: Font,
+    ) -> Result<Race, Box<dyn Error>> {
+        let best_runs = BestRuns::for_rom(rom);
+        Ok(Race {
+            score,
+            best_run: best_runs.load()?,
+            best_runs,
+            ghost: None,
+            rom: rom.to_vec(),
+            variant,
+            quirks,
+            font,
+        })
+    }
+
+    /// Score of the best run, none before the first run ends
+    pub fn best_score(&self) -> Option<u16> {
+        self.best_run.as_ref().map(|best_run| best_run.score)
+    }
+
+    /// Starts recording `chip8`, with the rom freshly loaded, and the ghost of the best run
+    pub fn start(&mut self, chip8: &mut Chip8, seed: u64) -> Result<(), Box<dyn Error>> {
+        self.ghost = match &self.best_run {
+            Some(best_run) => {
+                let builder = Chip8::builder()
+                    .variant(self.variant)
+                    .quirks(self.quirks)
+                    .font(self.font);
+                Some(Ghost::new(
+                    builder,
+                    self.rom.clone(),
+                    best_run.replay.clone(),
+                )?)
+            }
+            None => None,
+        };
+        chip8.start_recording(seed);
+        Ok(())
+    }
+
+    /// Runs a frame of the ghost, for every frame the live game runs
+    pub fn run_frame(&mut self, layer: &GhostLayer) -> Result<(), Box<dyn Error>> {
+        if let Some(ghost) = self.ghost.as_mut() {
+            ghost.run_frame()?;
+        }
+        layer.set(self.ghost.as_ref().map(Ghost::framebuffer));
+        Ok(())
+    }
+
+    /// Stops recording `chip8` and keeps its run when it beats the best one, returning its score
+    /// when it did
+    pub fn finish(&mut self, chip8: &mut Chip8) -> Result<Option<u16>, Box<dyn Error>> {
+        let replay = match chip8.stop_recording() {
+            Some(replay) => replay,
+            None => return Ok(None),
+        };
+        let score = self.score.read(chip8);
+        let beaten = self
+            .best_run
+            .as_ref()
+            .is_none_or(|best_run| best_run.is_beaten_by(score));
+        if !beaten {
+            return Ok(None);
+        }
+
+        let best_run = BestRun { score, replay };
+        self.best_runs.save(&best_run)?;
+        self.best_run = Some(best_run);
+        Ok(Some(score))
+    }
+}
@@ -1,17 +1,50 @@
-use chip8_core::{Chip8Error, Graphics};
-use sdl2::{pixels::Color, rect::Rect, render::Canvas, video::Window, Sdl};
-use std::error::Error;
+use chip8_core::{
+    Chip8Error, Curvature, FlickerFilter, Frame, Graphics, Palette, Pipeline, PostProcessor,
+    RgbaImage, Scaler, Scanlines,
+};
+use sdl2::{
+    pixels::{Color, PixelFormatEnum},
+    rect::Rect,
+    render::{Canvas, Texture},
+    video::Window,
+    Sdl,
+};
+use std::{error::Error, rc::Rc};
+
+use crate::{
+    crt::Crt,
+    ghost::GhostLayer,
+    osd::{self, Osd},
+    virtual_keypad::VirtualKeypad,
+};
 
 pub struct SdlGraphics {
     canvas: Canvas<Window>,
+    osd: Rc<Osd>,
+    keypad: Rc<VirtualKeypad>,
+    crt: Rc<Crt>,
+    ghost: Rc<GhostLayer>,
+    // The display is streamed to the window through this texture, scaled up as it is copied
+    texture: Option<Texture>,
+    // Colors the display before it is drawn, with the phosphor decay when there is one
+    pipeline: Pipeline,
 }
 
 impl SdlGraphics {
-    const WIDTH: u32 = 640;
-    const HEIGHT: u32 = 320;
-    const SCALE: u32 = 10;
+    pub const WIDTH: u32 = 640;
+    pub const HEIGHT: u32 = 320;
+    const MESSAGE_SCALE: u32 = 3;
+    const SCANLINES: f32 = 0.4;
+    const CURVATURE: f32 = 0.05;
 
-    pub fn new(sdl_context: &Sdl) -> Result<SdlGraphics, Box<dyn Error>> {
+    pub fn new(
+        sdl_context: &Sdl,
+        osd: Rc<Osd>,
+        keypad: Rc<VirtualKeypad>,
+        crt: Rc<Crt>,
+        ghost: Rc<GhostLayer>,
+        decay: f32,
+    ) -> Result<SdlGraphics, Box<dyn Error>> {
         let canvas = sdl_context
             .video()?
             .window("chip8", Self::WIDTH, Self::HEIGHT)
@@ -21,29 +54,94 @@ impl SdlGraphics {
             .into_canvas()
             .build()?;
 
-        Ok(SdlGraphics { canvas })
+        let mut pipeline = Pipeline::new(Palette::default());
+        if decay > 0.0 {
+            pipeline = pipeline.with_stage(FlickerFilter::new(decay));
+        }
+
+        Ok(SdlGraphics {
+            canvas,
+            osd,
+            keypad,
+            crt,
+            ghost,
+            texture: None,
+            pipeline,
+        })
+    }
+
+    // Scales the display up to the window to draw scanlines between its rows and bend it
+    fn crt(image: RgbaImage, scale: u32, palette: &Palette) -> RgbaImage {
+        let image = Scaler::new(scale).process(image, palette);
+        let image = Scanlines::new(Self::SCANLINES).process(image, palette);
+        Curvature::new(Self::CURVATURE).process(image, palette)
+    }
+
+    // Streams the image to the texture and stretches it over `destination` in a single copy
+    fn draw_image(&mut self, image: &RgbaImage, destination: Rect) -> Result<(), String> {
+        let (width, height) = (image.width(), image.height());
+        // The texture is only made again when the image changes size, like between low and high
+        // resolution or when the CRT effect is turned on
+        let fits = self.texture.as_ref().is_some_and(|texture| {
+            let query = texture.query();
+            (query.width, query.height) == (width, height)
+        });
+        if !fits {
+            if let Some(texture) = self.texture.take() {
+                // Safe as the canvas that made it is still there
+                unsafe { texture.destroy() };
+            }
+            let texture = self
+                .canvas
+                .create_texture_streaming(PixelFormatEnum::RGBA32, width, height)
+                .map_err(|error| error.to_string())?;
+            self.texture = Some(texture);
+        }
+
+        if let Some(texture) = self.texture.as_mut() {
+            texture
+                .update(None, image.pixels(), (width * 4) as usize)
+                .map_err(|error| error.to_string())?;
+            self.canvas.copy(texture, None, destination)?;
+        }
+        Ok(())
     }
 }
 
 impl Graphics for SdlGraphics {
-    fn draw(&mut self, graphics: &[u8]) -> Result<(), Chip8Error> {
-        let rects = graphics
-            .iter()
-            .enumerate()
-            .filter(|(_, pixel)| **pixel == 1)
-            .map(|(idx, _)| {
-                let idx = idx as u32;
-                let row = (idx / 64) * Self::SCALE;
-                let col = (idx % 64) * Self::SCALE;
-                Rect::new(col as i32, row as i32, Self::SCALE, Self::SCALE)
-            })
-            .collect::<Vec<Rect>>();
-
-        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+    fn draw(&mut self, frame: &Frame) -> Result<(), Chip8Error> {
+        let columns = frame.width() as u32;
+        let rows = frame.height() as u32;
+        let scale = (Self::WIDTH / columns).min(Self::HEIGHT / rows);
+        let lit = (0..frame.height())
+            .flat_map(|row| (0..frame.width()).map(move |col| frame.get_pixel(col, row) as u8))
+            .collect::<Vec<u8>>();
+        self.pipeline.set_palette(*frame.palette());
+        let mut image = self.pipeline.process(&lit, columns, rows);
+
+        let palette = frame.palette();
+        self.ghost.draw(&mut image, frame, palette);
+        let image = if self.crt.is_enabled() {
+            Self::crt(image, scale, palette)
+        } else {
+            image
+        };
+        // What the display doesn't cover, when it doesn't fit the window exactly, is left in the
+        // background color
+        let [r, g, b, a] = palette.background;
+        self.canvas.set_draw_color(Color::RGBA(r, g, b, a));
         self.canvas.clear();
-        self.canvas.set_draw_color(Color::RGB(255, 255, 255));
-        if let Err(message) = self.canvas.fill_rects(&rects) {
-            return Err(Chip8Error::GraphicsError(message));
+        self.draw_image(&image, Rect::new(0, 0, columns * scale, rows * scale))
+            .map_err(|message| Chip8Error::GraphicsError(message.into()))?;
+        // The keypad and then the message are drawn on top of the display
+        if self.keypad.is_visible() {
+            self.keypad
+                .draw(&mut self.canvas, palette)
+                .map_err(|message| Chip8Error::GraphicsError(message.into()))?;
+        }
+        if let Some(message) = self.osd.message() {
+            osd::draw_message(&mut self.canvas, &message, Self::MESSAGE_SCALE, palette)
+                .map_err(|message| Chip8Error::GraphicsError(message.into()))?;
         }
         self.canvas.present();
 
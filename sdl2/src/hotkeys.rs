@@ -0,0 +1,233 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    fmt,
+    str::FromStr,
+};
+
+use frontend_common::{HotkeyRegistry, KeyChord};
+use sdl2::keyboard::{Keycode, Mod, Scancode};
+
+/// Controls of the emulator itself, as opposed to keys of the keypad
+///
+/// Named in the `[hotkeys]` table of the config like `save_state` or `select_slot_3`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hotkey {
+    /// Starts the rom over, Backspace by default
+    Reset,
+    /// Pauses or resumes the emulation, P by default
+    Pause,
+    /// Runs a single frame while paused, N by default
+    StepFrame,
+    /// Runs a single instruction while paused, Shift+N by default
+    StepInstruction,
+    /// Saves the state in the selected slot, F5 by default
+    SaveState,
+    /// Loads the state of the selected slot, F7 by default
+    LoadState,
+    /// Selects the slot states are saved to and loaded from, Shift and its number by default
+    SelectSlot(u8),
+    /// Shows or hides the keypad played with the mouse, F2 by default
+    ToggleKeypad,
+    /// Switches to the next palette preset, F3 by default
+    CyclePalette,
+    /// Turns the CRT effect on and off, F4 by default
+    ToggleCrt,
+    /// Runs faster while held, Tab by default
+    FastForward,
+    /// Runs at a quarter of the speed while held, the key left of 1 by default
+    SlowMotion,
+    /// Goes back in time while held, the backslash key by default
+    Rewind,
+    /// Asks for the key of every key of the keypad and saves them to the config, F6 by default
+    RemapKeys,
+    /// Goes back to the latest checkpoint of `--checkpoint`, F8 by default
+    LoadCheckpoint,
+}
+
+impl Hotkey {
+    /// Every hotkey with the key bound to it unless the config says otherwise
+    fn defaults() -> Vec<(Hotkey, KeyChord)> {
+        let mut defaults = vec![
+            (Hotkey::Reset, KeyChord::new("Backspace")),
+            (Hotkey::Pause, KeyChord::new("P")),
+            (Hotkey::StepFrame, KeyChord::new("N")),
+            (Hotkey::StepInstruction, KeyChord::shifted("N")),
+            (Hotkey::SaveState, KeyChord::new("F5")),
+            (Hotkey::LoadState, KeyChord::new("F7")),
+            (Hotkey::ToggleKeypad, KeyChord::new("F2")),
+            (Hotkey::CyclePalette, KeyChord::new("F3")),
+            (Hotkey::ToggleCrt, KeyChord::new("F4")),
+            (Hotkey::FastForward, KeyChord::new("Tab")),
+            (Hotkey::SlowMotion, KeyChord::new("`")),
+            (Hotkey::Rewind, KeyChord::new("\\")),
+            (Hotkey::RemapKeys, KeyChord::new("F6")),
+            (Hotkey::LoadCheckpoint, KeyChord::new("F8")),
+        ];
+        defaults.extend((0..10).map(|slot| {
+            (
+                Hotkey::SelectSlot(slot),
+                KeyChord::shifted(&slot.to_string()),
+            )
+        }));
+        defaults
+    }
+
+    /// Hotkeys that last for as long as they're held, found by position so the layout doesn't matter
+    fn is_held(self) -> bool {
+        matches!(
+            self,
+            Hotkey::FastForward | Hotkey::SlowMotion | Hotkey::Rewind
+        )
+    }
+}
+
+impl fmt::Display for Hotkey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Hotkey::Reset => "reset",
+            Hotkey::Pause => "pause",
+            Hotkey::StepFrame => "step_frame",
+            Hotkey::StepInstruction => "step_instruction",
+            Hotkey::SaveState => "save_state",
+            Hotkey::LoadState => "load_state",
+            Hotkey::SelectSlot(slot) => return write!(f, "select_slot_{}", slot),
+            Hotkey::ToggleKeypad => "toggle_keypad",
+            Hotkey::CyclePalette => "cycle_palette",
+            Hotkey::ToggleCrt => "toggle_crt",
+            Hotkey::FastForward => "fast_forward",
+            Hotkey::SlowMotion => "slow_motion",
+            Hotkey::Rewind => "rewind",
+            Hotkey::RemapKeys => "remap_keys",
+            Hotkey::LoadCheckpoint => "load_checkpoint",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for Hotkey {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Hotkey::defaults()
+            .into_iter()
+            .map(|(hotkey, _)| hotkey)
+            .find(|hotkey| hotkey.to_string() == name)
+            .ok_or_else(|| format!("Unknown hotkey: {}", name))
+    }
+}
+
+/// Hotkeys pressed since the main loop last handled them and the ones held down, shared with the
+/// keyboard that reads them
+#[derive(Debug)]
+pub struct Hotkeys {
+    registry: HotkeyRegistry<Hotkey>,
+    pressed_by: HashMap<(Keycode, bool), Hotkey>,
+    held_by: HashMap<Scancode, Hotkey>,
+    pressed: RefCell<Vec<Hotkey>>,
+    held: RefCell<Vec<Hotkey>>,
+}
+
+impl Hotkeys {
+    /// Binds every hotkey to its default key, or the one named in `overrides`
+    ///
+    /// Fails on keys SDL doesn't know and on keys already taken, by quitting, another hotkey or
+    /// the keypad, which `keypad_key` tells the key of
+    pub fn new(
+        overrides: &BTreeMap<String, String>,
+        keypad_key: impl Fn(Keycode) -> Option<u8>,
+    ) -> Result<Hotkeys, String> {
+        let mut registry = HotkeyRegistry::new(Hotkey::defaults());
+        registry.apply_overrides(overrides)?;
+        registry.reserve(KeyChord::new("Escape"), "quit");
+
+        let mut pressed_by = HashMap::new();
+        let mut held_by = HashMap::new();
+        for (hotkey, chord) in registry.bindings() {
+            if hotkey.is_held() {
+                if chord.shift() {
+                    return Err(format!("Hotkey {} is held, it can't use Shift", hotkey));
+                }
+                let scancode = Scancode::from_name(chord.key())
+                    .ok_or_else(|| format!("Unknown key for hotkey {}: {}", hotkey, chord))?;
+                held_by.insert(scancode, hotkey);
+            } else {
+                let keycode = Keycode::from_name(chord.key())
+                    .ok_or_else(|| format!("Unknown key for hotkey {}: {}", hotkey, chord))?;
+                pressed_by.insert((keycode, chord.shift()), hotkey);
+            }
+        }
+
+        // Shift and a number picks a slot while pressing the number on the keypad, as it always has
+        let keypad_chords = pressed_by
+            .keys()
+            .filter(|(_, shift)| !shift)
+            .map(|(keycode, _)| *keycode)
+            .chain(
+                held_by
+                    .keys()
+                    .filter_map(|scancode| Keycode::from_scancode(*scancode)),
+            )
+            .filter_map(|keycode| Some((keycode, keypad_key(keycode)?)))
+            .collect::<Vec<_>>();
+        for (keycode, key) in keypad_chords {
+            registry.reserve(
+                KeyChord::new(&keycode.name()),
+                &format!("key {:X} of the keypad", key),
+            );
+        }
+        registry.check_conflicts()?;
+
+        Ok(Hotkeys {
+            registry,
+            pressed_by,
+            held_by,
+            pressed: RefCell::new(Vec::new()),
+            held: RefCell::new(Vec::new()),
+        })
+    }
+
+    /// Name of the key bound to `hotkey`, to tell the user which one to press
+    pub fn key_name(&self, hotkey: Hotkey) -> String {
+        self.registry
+            .chord(hotkey)
+            .map(KeyChord::to_string)
+            .unwrap_or_default()
+    }
+
+    /// Keys bound to a hotkey without Shift, which can't play the keypad too
+    pub fn unshifted_keys(&self) -> impl Iterator<Item = (Hotkey, &str)> {
+        self.registry
+            .bindings()
+            .filter(|(_, chord)| !chord.shift())
+            .map(|(hotkey, chord)| (hotkey, chord.key()))
+    }
+
+    /// Hotkey triggered by pressing `keycode`
+    pub fn for_key(&self, keycode: Keycode, keymod: Mod) -> Option<Hotkey> {
+        let shift = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+        self.pressed_by.get(&(keycode, shift)).copied()
+    }
+
+    /// Hotkey that lasts for as long as `scancode` is held
+    pub fn held_by(&self, scancode: Scancode) -> Option<Hotkey> {
+        self.held_by.get(&scancode).copied()
+    }
+
+    pub fn press(&self, hotkey: Hotkey) {
+        self.pressed.borrow_mut().push(hotkey);
+    }
+
+    /// The hotkeys pressed since the last call, oldest first
+    pub fn take(&self) -> Vec<Hotkey> {
+        self.pressed.take()
+    }
+
+    pub fn set_held(&self, held: Vec<Hotkey>) {
+        self.held.replace(held);
+    }
+
+    pub fn is_held(&self, hotkey: Hotkey) -> bool {
+        self.held.borrow().contains(&hotkey)
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    error::Error,
+    fs, io,
+    path::PathBuf,
+    str::FromStr,
+};
+
+use chip8_core::romdb;
+use sdl2::keyboard::Keycode;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{self, Config};
+
+/// Keys bound for one rom, kept under `~/.config/chip8/keys` next to the config
+///
+/// Files are named after the hash of the rom, so it finds its keys wherever it is. They hold a
+/// `[keys]` table like the config, whose bindings they win over:
+///
+/// ```toml
+/// [keys]
+/// W = 0x5
+/// S = 0x8
+/// ```
+pub struct KeyProfile {
+    path: Option<PathBuf>,
+    keys: BTreeMap<String, u8>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+struct SavedProfile {
+    keys: BTreeMap<String, u8>,
+}
+
+/// A key bound from the command line, `Up=5` has the up arrow press `5` on the keypad
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBinding {
+    name: String,
+    key: u8,
+}
+
+impl KeyProfile {
+    /// Reads the keys bound for the rom, none when it doesn't have any yet
+    pub fn load_for_rom(rom_data: &[u8]) -> Result<KeyProfile, Box<dyn Error>> {
+        let path = KeyProfile::default_directory()
+            .map(|directory| directory.join(format!("{}.toml", romdb::sha1(rom_data))));
+        let mut profile = KeyProfile {
+            path,
+            keys: BTreeMap::new(),
+        };
+        let path = match &profile.path {
+            Some(path) => path,
+            None => return Ok(profile),
+        };
+
+        let saved = match fs::read_to_string(path) {
+            Ok(saved) => saved,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(profile),
+            Err(error) => return Err(format!("Couldn't read {}: {}", path.display(), error).into()),
+        };
+        let saved: SavedProfile = toml::from_str(&saved)
+            .map_err(|error| format!("Invalid key profile {}: {}", path.display(), error))?;
+        profile.keys = saved.keys;
+        Ok(profile)
+    }
+
+    /// Where profiles are kept, next to the config
+    pub fn default_directory() -> Option<PathBuf> {
+        Config::default_path()
+            .and_then(|config| config.parent().map(|directory| directory.join("keys")))
+    }
+
+    /// Binds a key on top of the ones the profile already has
+    pub fn bind(&mut self, binding: &KeyBinding) {
+        self.keys.insert(binding.name.clone(), binding.key);
+    }
+
+    /// Keypad keys pressed by each physical key bound in the profile
+    pub fn key_bindings(&self) -> Result<HashMap<Keycode, u8>, String> {
+        config::key_bindings(&self.keys, "the key profile")
+    }
+
+    /// Keeps the keys bound so far for the next time the rom starts, returning where
+    pub fn save(&self) -> Result<PathBuf, Box<dyn Error>> {
+        let path = self
+            .path
+            .clone()
+            .ok_or("Couldn't find a directory to keep key profiles in")?;
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        let saved = SavedProfile {
+            keys: self.keys.clone(),
+        };
+        fs::write(&path, toml::to_string(&saved)?)
+            .map_err(|error| format!("Couldn't write {}: {}", path.display(), error))?;
+        Ok(path)
+    }
+}
+
+impl FromStr for KeyBinding {
+    type Err = String;
+
+    fn from_str(binding: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid key binding: {}", binding);
+        let (name, key) = binding.rsplit_once('=').ok_or_else(invalid)?;
+        let key = key.trim();
+        let key =
+            u8::from_str_radix(key.strip_prefix("0x").unwrap_or(key), 16).map_err(|_| invalid())?;
+        let name = name.trim();
+        Keycode::from_name(name).ok_or_else(|| format!("Unknown key: {}", name))?;
+        Ok(KeyBinding {
+            name: name.to_string(),
+            key: config::keypad_key(name, key)?,
+        })
+    }
+}
@@ -1,103 +1,212 @@
-use std::error::Error;
+use std::{collections::HashMap, error::Error, rc::Rc, time::Duration};
 
-use chip8_core::Keyboard;
-use sdl2::{event::Event, keyboard::Keycode, EventPump, Sdl};
+use chip8_core::{KeyEvent, Keyboard, Keymap};
+use sdl2::{
+    controller::{Button, GameController},
+    event::Event,
+    keyboard::Keycode,
+    mouse::MouseButton,
+    EventPump, GameControllerSubsystem, Sdl,
+};
 
+use crate::{
+    hotkeys::Hotkeys, midi::MidiKeyboard, remap::RemapScreen, virtual_keypad::VirtualKeypad,
+};
+
+/// Reads which keypad keys are held from the keyboard, controller, MIDI and virtual keypad state
+/// every frame
+///
+/// Events are only used for quitting, hotkeys, clicks and controllers coming and going. Scanning the state
+/// instead of following KeyDown/KeyUp means a key released while the window didn't have focus
+/// doesn't stay stuck down
 pub struct SdlKeyboard {
     event_pump: EventPump,
+    controller_subsystem: GameControllerSubsystem,
+    // Controllers can only be read while they are open, keyed by their joystick id
+    controllers: HashMap<u32, GameController>,
+    hotkeys: Rc<Hotkeys>,
+    keypad: Rc<VirtualKeypad>,
+    remap: Rc<RemapScreen>,
+    keymap: Keymap,
+    bindings: HashMap<Keycode, u8>,
+    buttons: HashMap<Button, u8>,
+    midi: Option<MidiKeyboard>,
+    // Keys of the notes still held on the MIDI controller, which only sends events
+    midi_held: [bool; 16],
+    held: [bool; 16],
+    // Taken off the queue while waiting for input, handled first by the next poll
+    waited: Option<Event>,
 }
 
 impl SdlKeyboard {
-    pub fn new(sdl_context: &Sdl) -> Result<Self, Box<dyn Error>> {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        sdl_context: &Sdl,
+        hotkeys: Rc<Hotkeys>,
+        keypad: Rc<VirtualKeypad>,
+        remap: Rc<RemapScreen>,
+        keymap: Keymap,
+        bindings: HashMap<Keycode, u8>,
+        buttons: HashMap<Button, u8>,
+        midi: Option<MidiKeyboard>,
+    ) -> Result<Self, Box<dyn Error>> {
+        // Controllers plugged in before starting are announced with the same event as hot-plugged ones
         Ok(SdlKeyboard {
             event_pump: sdl_context.event_pump()?,
+            controller_subsystem: sdl_context.game_controller()?,
+            controllers: HashMap::new(),
+            hotkeys,
+            keypad,
+            remap,
+            keymap,
+            bindings,
+            buttons,
+            midi,
+            midi_held: [false; 16],
+            held: [false; 16],
+            waited: None,
         })
     }
+
+    fn held_keys(&self) -> [bool; 16] {
+        let mut held = [false; 16];
+        let keyboard_state = self.event_pump.keyboard_state();
+        let keys = keyboard_state
+            .pressed_scancodes()
+            .filter(|_| !self.remap.is_active())
+            .filter_map(Keycode::from_scancode)
+            .filter_map(|keycode| chip8_key(&self.keymap, &self.bindings, keycode));
+        let buttons = self.controllers.values().flat_map(|controller| {
+            self.buttons
+                .iter()
+                .filter(move |(button, _)| controller.button(**button))
+                .map(|(_, key)| *key)
+        });
+        for key in keys.chain(buttons).chain(self.keypad.pressed()) {
+            held[(key & 0xF) as usize] = true;
+        }
+        for (held, midi_held) in held.iter_mut().zip(self.midi_held.iter()) {
+            *held |= midi_held;
+        }
+        self.hotkeys.set_held(
+            keyboard_state
+                .pressed_scancodes()
+                .filter_map(|scancode| self.hotkeys.held_by(scancode))
+                .collect(),
+        );
+        held
+    }
 }
 
 impl Keyboard for SdlKeyboard {
-    fn update_state(&mut self, keyboard: &mut [u8; 16]) -> bool {
-        for event in self.event_pump.poll_iter() {
+    fn poll_events(&mut self) -> Vec<KeyEvent> {
+        let mut events = Vec::new();
+        for event in self
+            .waited
+            .take()
+            .into_iter()
+            .chain(self.event_pump.poll_iter())
+        {
             match event {
+                // Keys pressed while remapping only bind keys, Escape stops it instead of quitting
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    repeat: false,
+                    ..
+                } if self.remap.is_active() => {
+                    if keycode == Keycode::Escape {
+                        self.remap.cancel();
+                    } else if let Some(bindings) = self.remap.press(keycode) {
+                        self.bindings = bindings;
+                    }
+                }
                 Event::Quit { .. }
                 | Event::KeyDown {
                     keycode: Some(Keycode::Escape),
                     ..
-                } => return true,
+                } => events.push(KeyEvent::Quit),
                 Event::KeyDown {
                     keycode: Some(keycode),
+                    keymod,
+                    repeat: false,
                     ..
-                } => match keycode {
-                    Keycode::Num1 => keyboard[0] = 1,
-                    Keycode::Num2 => keyboard[1] = 1,
-                    Keycode::Num3 => keyboard[2] = 1,
-                    Keycode::Num4 => keyboard[3] = 1,
-                    Keycode::Q => keyboard[4] = 1,
-                    Keycode::W => keyboard[5] = 1,
-                    Keycode::E => keyboard[6] = 1,
-                    Keycode::R => keyboard[7] = 1,
-                    Keycode::A => keyboard[8] = 1,
-                    Keycode::S => keyboard[9] = 1,
-                    Keycode::D => keyboard[10] = 1,
-                    Keycode::F => keyboard[11] = 1,
-                    Keycode::Z => keyboard[12] = 1,
-                    Keycode::X => keyboard[13] = 1,
-                    Keycode::C => keyboard[14] = 1,
-                    Keycode::V => keyboard[15] = 1,
-                    _ => (),
-                },
-                Event::KeyUp {
-                    keycode: Some(keycode),
+                } => {
+                    if let Some(hotkey) = self.hotkeys.for_key(keycode, keymod) {
+                        self.hotkeys.press(hotkey);
+                    }
+                }
+                Event::MouseButtonDown {
+                    mouse_btn: MouseButton::Left,
+                    x,
+                    y,
+                    ..
+                } => self.keypad.press_at(x, y),
+                Event::MouseButtonUp {
+                    mouse_btn: MouseButton::Left,
                     ..
-                } => match keycode {
-                    Keycode::Num1 => keyboard[0] = 0,
-                    Keycode::Num2 => keyboard[1] = 0,
-                    Keycode::Num3 => keyboard[2] = 0,
-                    Keycode::Num4 => keyboard[3] = 0,
-                    Keycode::Q => keyboard[4] = 0,
-                    Keycode::W => keyboard[5] = 0,
-                    Keycode::E => keyboard[6] = 0,
-                    Keycode::R => keyboard[7] = 0,
-                    Keycode::A => keyboard[8] = 0,
-                    Keycode::S => keyboard[9] = 0,
-                    Keycode::D => keyboard[10] = 0,
-                    Keycode::F => keyboard[11] = 0,
-                    Keycode::Z => keyboard[12] = 0,
-                    Keycode::X => keyboard[13] = 0,
-                    Keycode::C => keyboard[14] = 0,
-                    Keycode::V => keyboard[15] = 0,
-                    _ => (),
-                },
+                } => self.keypad.release(),
+                Event::ControllerDeviceAdded { which, .. } => {
+                    match self.controller_subsystem.open(which) {
+                        Ok(controller) => {
+                            println!("Connected {}", controller.name());
+                            self.controllers
+                                .insert(controller.instance_id(), controller);
+                        }
+                        Err(error) => eprintln!("Couldn't open controller {}: {}", which, error),
+                    }
+                }
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    if let Some(controller) = self.controllers.remove(&which) {
+                        println!("Disconnected {}", controller.name());
+                    }
+                }
                 _ => (),
             }
         }
-        false
-    }
 
-    fn wait_next_key_press(&mut self) -> u8 {
-        let key_pressed = match self.event_pump.wait_event() {
-            Event::KeyDown { keycode, .. } => keycode.unwrap(),
-            _ => panic!("Crashed while waiting for event"),
-        };
+        let midi_events = self.midi.as_mut().map(Keyboard::poll_events);
+        for event in midi_events.into_iter().flatten() {
+            match event {
+                KeyEvent::Down(key) => self.midi_held[key as usize] = true,
+                KeyEvent::Up(key) => self.midi_held[key as usize] = false,
+                _ => (),
+            }
+        }
+
+        let held = self.held_keys();
+        for (key, (was_held, is_held)) in self.held.iter().zip(held.iter()).enumerate() {
+            match (was_held, is_held) {
+                (false, true) => events.push(KeyEvent::Down(key as u8)),
+                (true, false) => events.push(KeyEvent::Up(key as u8)),
+                _ => (),
+            }
+        }
+        self.held = held;
+        events
+    }
 
-        match key_pressed {
-            Keycode::Num1 => 0x1,
-            Keycode::Num2 => 0x2,
-            Keycode::Num3 => 0x3,
-            Keycode::Num4 => 0xC,
-            Keycode::Q => 0x4,
-            Keycode::W => 0x5,
-            Keycode::E => 0x6,
-            Keycode::R => 0xD,
-            Keycode::A => 0x7,
-            Keycode::S => 0x8,
-            Keycode::D => 0x9,
-            Keycode::F => 0xE,
-            Keycode::Z => 0xA,
-            Keycode::X => 0x0,
-            Keycode::C => 0xB,
-            Keycode::V => 0xF,
-            _ => 0x0,
+    fn wait_for_events(&mut self, timeout: Duration) -> bool {
+        if self.waited.is_none() {
+            self.waited = self
+                .event_pump
+                .wait_event_timeout(timeout.as_millis() as u32);
         }
+        true
+    }
+}
+
+/// Maps a key bound in the config to its keypad key, or else the character on it using the keymap
+pub fn chip8_key(keymap: &Keymap, bindings: &HashMap<Keycode, u8>, keycode: Keycode) -> Option<u8> {
+    if let Some(key) = bindings.get(&keycode) {
+        return Some(*key);
+    }
+    if keycode == Keycode::Space {
+        return keymap.key(' ');
+    }
+    let name = keycode.name();
+    let mut characters = name.chars();
+    match (characters.next(), characters.next()) {
+        (Some(character), None) => keymap.key(character),
+        _ => None,
     }
 }
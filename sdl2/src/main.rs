@@ -1,54 +1,597 @@
-use std::{error::Error, path::PathBuf, thread, time::Duration};
+use std::{
+    error::Error,
+    fs,
+    path::PathBuf,
+    rc::Rc,
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 use structopt::StructOpt;
 
 mod audio;
+mod best_runs;
+mod config;
+mod crt;
+mod debug_console;
+mod ghost;
 mod graphics;
+mod hotkeys;
+mod key_profiles;
 mod keyboard;
-mod number_generator;
+mod midi;
+mod osd;
+mod remap;
 mod rom_loader;
+mod save_states;
+mod storage;
+mod virtual_keypad;
 
 use audio::SdlAudio;
-use chip8_core::{Chip8, State};
+#[cfg(unix)]
+use chip8_core::ControlServer;
+use chip8_core::{
+    romdb, Achievements, Cheat, Checkpoints, Chip8, Chip8Variant, Clock, Condition,
+    DeviceErrorPolicy, Font, InstantClock, KeyRemap, KeyboardLayout, OnInvalidOpcode, Palette,
+    RandomNumberGenerator, Replay, RomConfig, Score, SpeedController, State,
+};
+use config::Config;
+use crt::Crt;
+use debug_console::DebugConsole;
+use frontend_common::AttractMode;
+use ghost::{GhostLayer, Race};
 use graphics::SdlGraphics;
+use hotkeys::{Hotkey, Hotkeys};
+use key_profiles::{KeyBinding, KeyProfile};
 use keyboard::SdlKeyboard;
-use number_generator::RandomNumberGenerator;
+use midi::MidiKeyboard;
+use osd::Osd;
+use remap::RemapScreen;
 use rom_loader::RomLoader;
+use save_states::SaveStates;
+use storage::FileStorage;
+use virtual_keypad::VirtualKeypad;
+
+const FRAME_DURATION: Duration = Duration::from_micros(16_667);
+// Frames are this far apart while the program waits for a key in silence, a key press wakes it up
+const IDLE_FRAME_DURATION: Duration = Duration::from_millis(100);
+const DEFAULT_HERTZ: u32 = 500;
+const DEFAULT_FAST_FORWARD: u32 = 8;
+const SLOW_MOTION: u32 = 4;
+// Ten seconds of snapshots, one every other frame, played back twice as fast as they were taken
+const REWIND_DEPTH: usize = 300;
+const REWIND_INTERVAL: usize = 2;
+const REWIND_STEP: usize = 4;
 
 #[derive(StructOpt, Debug)]
 #[structopt(name = "chip8-sdl")]
 struct CliArgs {
-    #[structopt(long = "rom", short = "r")]
-    rom: PathBuf,
-    #[structopt(long = "hertz", short = "h", default_value = "500")]
-    hertz: u32,
+    #[structopt(long = "rom", short = "r", required_unless = "attract")]
+    rom: Option<PathBuf>,
+    #[structopt(long = "hertz", short = "h")]
+    hertz: Option<u32>,
+    #[structopt(long = "variant", short = "v")]
+    variant: Option<Chip8Variant>,
+    #[structopt(long = "seed", short = "s")]
+    seed: Option<u64>,
+    #[structopt(long = "debug", short = "d")]
+    debug: bool,
+    #[structopt(long = "layout", short = "l")]
+    layout: Option<KeyboardLayout>,
+    #[structopt(long = "font", short = "f", default_value = "schip")]
+    font: Font,
+    #[structopt(long = "cheat", short = "c")]
+    cheats: Vec<Cheat>,
+    #[structopt(long = "config")]
+    config: Option<PathBuf>,
+    #[structopt(long = "key", short = "k")]
+    keys: Vec<KeyBinding>,
+    #[structopt(long = "save-keys")]
+    save_keys: bool,
+    #[structopt(long = "palette", short = "p")]
+    palette: Option<Palette>,
+    #[structopt(long = "crt")]
+    crt: bool,
+    #[structopt(long = "decay")]
+    decay: Option<f32>,
+    #[structopt(long = "fast-forward")]
+    fast_forward: Option<u32>,
+    #[structopt(long = "record", conflicts_with = "replay")]
+    record: Option<PathBuf>,
+    #[structopt(long = "replay")]
+    replay: Option<PathBuf>,
+    #[structopt(long = "midi")]
+    midi: Option<String>,
+    #[structopt(long = "checkpoint")]
+    checkpoints: Vec<Condition>,
+    #[structopt(long = "attract", conflicts_with_all = &["record", "replay"])]
+    attract: Option<PathBuf>,
+    #[structopt(long = "ghost", conflicts_with_all = &["record", "replay", "attract"])]
+    ghost: Option<Score>,
+    #[structopt(long = "control")]
+    control: Option<PathBuf>,
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let cli_args = CliArgs::from_args();
-    let rom_data = RomLoader::load_rom(&cli_args.rom)?;
-    let sleep_time = 1000 / cli_args.hertz;
+    let mut attract = cli_args
+        .attract
+        .as_deref()
+        .map(AttractMode::load)
+        .transpose()?;
+    // Attract mode is set up for the first rom of the playlist unless another one is given
+    let rom_path = cli_args
+        .rom
+        .clone()
+        .or_else(|| {
+            attract
+                .as_ref()
+                .map(|attract| attract.roms()[0].path.clone())
+        })
+        .ok_or("Pass a rom or an attract mode playlist")?;
+    let rom_data = RomLoader::load_rom(&rom_path)?;
+    let rom_info = romdb::lookup(&rom_data);
+    if let Some(rom_info) = rom_info {
+        println!("Recognized {}", rom_info.title);
+    }
+    let config = Config::load(cli_args.config.as_deref())?;
+    let rom_config = RomConfig::load_for_rom(&rom_path)?.unwrap_or_default();
+    // Achievements are for a single rom, attract mode plays several
+    let mut achievements = Achievements::load_for_rom(&rom_path)?.filter(|_| attract.is_none());
+    let mut checkpoints = Checkpoints::new(cli_args.checkpoints.clone());
+    if let Some(achievements) = &achievements {
+        println!("Loaded {} achievements", achievements.iter().count());
+    }
+
+    // Flags win over the rom config, which wins over the rom database and then the defaults
+    let hertz = cli_args
+        .hertz
+        .or(rom_config.hertz)
+        .or_else(|| rom_info.map(|rom_info| rom_info.hertz))
+        .unwrap_or(DEFAULT_HERTZ);
+    let mut speed = SpeedController::new(hertz);
+    let fast_forward = cli_args
+        .fast_forward
+        .or(config.fast_forward)
+        .unwrap_or(DEFAULT_FAST_FORWARD);
 
     let sdl_context = sdl2::init()?;
     let sdl_audio = SdlAudio::new(&sdl_context)?;
-    let sdl_graphics = SdlGraphics::new(&sdl_context)?;
-    let sdl_keyboard = SdlKeyboard::new(&sdl_context)?;
-
-    let mut chip8 = Chip8::new(
-        Box::new(RandomNumberGenerator),
-        Box::new(sdl_audio),
-        Box::new(sdl_keyboard),
-        Box::new(sdl_graphics),
-    );
+    let osd = Rc::new(Osd::default());
+    let keypad = Rc::new(VirtualKeypad::default());
+    let remap = Rc::new(RemapScreen::default());
+    // Share of its brightness a pixel keeps every frame after going off, none by default
+    let decay = cli_args.decay.or(config.decay).unwrap_or_default();
+    let crt = Rc::new(Crt::new(cli_args.crt || config.crt));
+    let ghost_layer = Rc::new(GhostLayer::default());
+    let sdl_graphics = SdlGraphics::new(
+        &sdl_context,
+        osd.clone(),
+        keypad.clone(),
+        crt.clone(),
+        ghost_layer.clone(),
+        decay,
+    )?;
+    // Keys bound on the command line win over the ones saved for the rom, which win over the config
+    let mut key_profile = KeyProfile::load_for_rom(&rom_data)?;
+    for binding in &cli_args.keys {
+        key_profile.bind(binding);
+    }
+    if cli_args.save_keys {
+        let path = key_profile.save()?;
+        println!("Saved the keys of this rom to {}", path.display());
+    }
+    let mut key_bindings = config.key_bindings()?;
+    key_bindings.extend(key_profile.key_bindings()?);
+    let layout = cli_args
+        .layout
+        .or(rom_config.keymap.layout)
+        .or(config.layout)
+        .unwrap_or_default();
+    let keymap = rom_config.keymap(layout);
+    let hotkeys = Rc::new(Hotkeys::new(&config.hotkeys, |keycode| {
+        keyboard::chip8_key(&keymap, &key_bindings, keycode)
+    })?);
+    let midi = match cli_args.midi.as_ref().or(config.midi.as_ref()) {
+        Some(input) => Some(
+            MidiKeyboard::connect(input, config.note_map()?)
+                .map_err(|error| format!("Couldn't connect to {}: {}", input, error))?,
+        ),
+        None => None,
+    };
+    let sdl_keyboard = SdlKeyboard::new(
+        &sdl_context,
+        hotkeys.clone(),
+        keypad.clone(),
+        remap.clone(),
+        keymap,
+        key_bindings,
+        config.button_bindings()?,
+        midi,
+    )?;
+
+    let chosen_variant = cli_args.variant.or(rom_config.variant);
+    let variant = chosen_variant
+        .or_else(|| rom_info.map(|rom_info| rom_info.variant))
+        .unwrap_or_default();
+    let quirks = match (chosen_variant, rom_info) {
+        (None, Some(rom_info)) => rom_info.quirks(),
+        _ => variant.quirks(),
+    };
+    let quirks = rom_config.quirks(quirks);
+    let mut chip8 = Chip8::builder()
+        .variant(variant)
+        .quirks(quirks)
+        .rng(Box::new(RandomNumberGenerator))
+        .audio(Box::new(sdl_audio))
+        .keyboard(Box::new(sdl_keyboard))
+        .graphics(Box::new(sdl_graphics))
+        .build();
 
+    let clock = InstantClock::new();
+    chip8.set_clock(Box::new(clock));
+    chip8.set_device_error_policy(DeviceErrorPolicy::Continue);
+    chip8.set_on_invalid_opcode(OnInvalidOpcode::Callback(Box::new(|opcode, address| {
+        eprintln!("Skipped invalid opcode {:04X} at {:#05X}", opcode, address)
+    })));
+    chip8.set_font(cli_args.font);
+    chip8.set_palette(
+        cli_args
+            .palette
+            .unwrap_or_else(|| rom_config.palette(config.palette())),
+    );
+    chip8.set_rewind(REWIND_DEPTH, REWIND_INTERVAL);
+    for cheat in cli_args.cheats {
+        chip8.cheats_mut().add(cheat);
+    }
+    if let Some(seed) = cli_args.seed {
+        chip8.seed_rng(seed);
+    }
+    chip8.set_storage(Box::new(FileStorage::for_rom(&rom_path)))?;
+    let mut save_states = SaveStates::for_rom(&rom_data);
+    let mut race = match cli_args.ghost {
+        Some(score) => Some(Race::new(score, &rom_data, variant, quirks, cli_args.font)?),
+        None => None,
+    };
     chip8.load_program(rom_data)?;
 
+    // Replays start from the rom freshly loaded, with the seed they were recorded with
+    if let Some(path) = &cli_args.replay {
+        let replay = fs::read(path)
+            .map_err(|error| format!("Couldn't read {}: {}", path.display(), error))?;
+        let replay = Replay::read(&replay)?;
+        chip8.play_replay(replay);
+    } else if cli_args.record.is_some() {
+        chip8.start_recording(cli_args.seed.unwrap_or_else(time_seed));
+    }
+    let mut replaying = chip8.is_replaying();
+    if let Some(race) = race.as_mut() {
+        race.start(&mut chip8, cli_args.seed.unwrap_or_else(time_seed))?;
+        match race.best_score() {
+            Some(score) => println!("Racing the best run, {} points", score),
+            None => println!("Recording the first run to race"),
+        }
+    }
+    if let Some(attract) = attract.as_mut() {
+        let rom = attract.start(&mut chip8)?;
+        println!("Attract mode, playing {}", rom.path.display());
+    }
+
+    let mut debug_console = if cli_args.debug {
+        Some(DebugConsole::attach(&mut chip8))
+    } else {
+        None
+    };
+    // Scripts pause, load roms, save states and press keys through the socket while the game runs
+    #[cfg(unix)]
+    if let Some(path) = &cli_args.control {
+        let server = ControlServer::bind(path, chip8.handle())
+            .map_err(|error| format!("Couldn't create {}: {}", path.display(), error))?;
+        thread::spawn(move || server.serve());
+    }
+
+    let mut program_ended = false;
+    // Whether the emulation was already paused when remapping started, while remapping
+    let mut paused_before_remap = None;
     'main: loop {
-        if let State::Exit = chip8.emulate_cycle()? {
-            break 'main;
+        let frame_start = clock.now();
+        let mut running = true;
+        if let Some(debug_console) = debug_console.as_mut() {
+            debug_console.run_commands(&mut chip8);
+            if debug_console.quit_requested() {
+                break 'main;
+            }
+            running = debug_console.is_running();
+        }
+
+        // While paused no instructions run, but the window is still drawn and its events handled
+        let rewinding = hotkeys.is_held(Hotkey::Rewind);
+        if running && !rewinding {
+            speed.resume();
+        } else {
+            speed.pause();
+        }
+        speed.set_fast_forward(if hotkeys.is_held(Hotkey::FastForward) {
+            fast_forward
+        } else {
+            1
+        });
+        speed.set_slow_motion(if hotkeys.is_held(Hotkey::SlowMotion) {
+            SLOW_MOTION
+        } else {
+            1
+        });
+        // Rewinding replaces running the frame, only drawing the state it went back to
+        let state = if rewinding {
+            if chip8.rewind(REWIND_STEP) > 0 {
+                program_ended = false;
+            }
+            chip8.present()?
+        } else {
+            chip8.run_frame(speed.instructions_due(clock.now()))?
+        };
+        // The ghost runs a frame for every frame the live game runs
+        if let Some(race) = race
+            .as_mut()
+            .filter(|_| running && !rewinding && !chip8.is_paused())
+        {
+            race.run_frame(&ghost_layer)?;
+        }
+        // Attract mode swaps the rom for the next one instead of letting it end
+        let state = match attract.as_mut().filter(|_| !rewinding) {
+            Some(attract) => match attract.next_frame(&mut chip8, state)? {
+                Some(rom) => {
+                    println!("Attract mode, playing {}", rom.path.display());
+                    if let Some(name) = rom.path.file_stem() {
+                        osd.show(&name.to_string_lossy());
+                    }
+                    State::Continue
+                }
+                None => state,
+            },
+            None => state,
+        };
+        if let Some(achievements) = achievements.as_mut().filter(|_| !rewinding) {
+            for achievement in achievements.check(&chip8) {
+                println!(
+                    "Unlocked {}: {}",
+                    achievement.title, achievement.description
+                );
+                osd.show(&achievement.title);
+            }
+        }
+        if !rewinding && checkpoints.check(&chip8) {
+            osd.show("Checkpoint");
+        }
+        if let Some(debug_console) = debug_console.as_mut() {
+            debug_console.report(&mut chip8, state);
+        }
+
+        match state {
+            State::Exit => break 'main,
+            State::Halted => {
+                if debug_console.is_none() {
+                    println!("Program exited");
+                }
+                break 'main;
+            }
+            State::Finished if !program_ended => {
+                program_ended = true;
+                println!(
+                    "Program ended, press {} to restart or Escape to quit",
+                    hotkeys.key_name(Hotkey::Reset)
+                );
+            }
+            _ => (),
         };
 
-        thread::sleep(Duration::from_millis(sleep_time.into()));
+        if replaying && !chip8.is_replaying() {
+            replaying = false;
+            println!("Replay finished, the keyboard is back in control");
+        }
+
+        for hotkey in hotkeys.take() {
+            match hotkey {
+                Hotkey::Reset => {
+                    // Restarting ends the run being raced and starts the next one
+                    if let Some(race) = race.as_mut() {
+                        finish_race(race, &mut chip8, &osd)?;
+                    }
+                    chip8.reset(true)?;
+                    if let Some(race) = race.as_mut() {
+                        race.start(&mut chip8, cli_args.seed.unwrap_or_else(time_seed))?;
+                    }
+                    program_ended = false;
+                }
+                Hotkey::Pause if chip8.is_paused() => {
+                    chip8.resume()?;
+                    println!("Resumed");
+                }
+                Hotkey::Pause => {
+                    chip8.pause()?;
+                    println!(
+                        "Paused, press {} to resume or {} to step a frame, {} an instruction",
+                        hotkeys.key_name(Hotkey::Pause),
+                        hotkeys.key_name(Hotkey::StepFrame),
+                        hotkeys.key_name(Hotkey::StepInstruction)
+                    );
+                }
+                Hotkey::StepFrame | Hotkey::StepInstruction if chip8.is_paused() => {
+                    let instructions = match hotkey {
+                        Hotkey::StepFrame => instructions_per_frame(&speed),
+                        _ => 1,
+                    };
+                    // The frame reads the keyboard, which may have been asked to quit meanwhile
+                    if chip8.step_frame(instructions)? == State::Exit {
+                        break 'main;
+                    }
+                }
+                Hotkey::StepFrame | Hotkey::StepInstruction => (),
+                Hotkey::SaveState => match save_states.save(&chip8.snapshot()) {
+                    Ok(()) => osd.show(&format!("Saved slot {}", save_states.slot())),
+                    Err(error) => {
+                        eprintln!("{}", error);
+                        osd.show("Save failed");
+                    }
+                },
+                Hotkey::LoadState => match save_states.load() {
+                    Ok(snapshot) => {
+                        chip8.restore(&snapshot);
+                        program_ended = false;
+                        osd.show(&format!("Loaded slot {}", save_states.slot()));
+                    }
+                    Err(error) => {
+                        eprintln!("{}", error);
+                        osd.show("Load failed");
+                    }
+                },
+                Hotkey::RemapKeys if !remap.is_active() => {
+                    let mut key_remap = KeyRemap::new();
+                    key_remap.reserve("Escape", "cancelling");
+                    for (hotkey, key) in hotkeys.unshifted_keys() {
+                        key_remap.reserve(key, &format!("hotkey {}", hotkey));
+                    }
+                    remap.start(key_remap);
+                    paused_before_remap = Some(chip8.is_paused());
+                    chip8.pause()?;
+                    println!("Remapping the keys, press the one asked for each key of the keypad or Escape to cancel");
+                }
+                Hotkey::RemapKeys => (),
+                Hotkey::LoadCheckpoint => match checkpoints.latest() {
+                    Some(checkpoint) => {
+                        chip8.restore(checkpoint);
+                        // Catches the watched values up with the checkpoint, which isn't a new one
+                        checkpoints.check(&chip8);
+                        program_ended = false;
+                        osd.show("Checkpoint loaded");
+                    }
+                    None => osd.show("No checkpoint"),
+                },
+                Hotkey::ToggleKeypad => keypad.toggle(),
+                Hotkey::ToggleCrt => crt.toggle(),
+                Hotkey::CyclePalette => {
+                    let (name, palette) = next_palette(chip8.palette());
+                    chip8.set_palette(palette);
+                    osd.show(name);
+                }
+                Hotkey::SelectSlot(slot) => {
+                    save_states.select_slot(slot);
+                    osd.show(&format!("Slot {}", slot));
+                }
+                // Held rather than pressed, they set the speed at the start of every frame
+                Hotkey::FastForward | Hotkey::SlowMotion | Hotkey::Rewind => (),
+            }
+        }
+
+        if let Some(refused) = remap.take_refused() {
+            osd.show(&refused);
+        }
+        if let Some(keys) = remap.take_finished() {
+            match Config::save_keys(cli_args.config.as_deref(), &keys) {
+                Ok(path) => {
+                    println!("Saved the keys to {}", path.display());
+                    osd.show("Keys saved");
+                }
+                Err(error) => {
+                    eprintln!("{}", error);
+                    osd.show("Save failed");
+                }
+            }
+        }
+        if !remap.is_active() && paused_before_remap.take() == Some(false) {
+            chip8.resume()?;
+        }
+
+        // Stays up until the program runs again, whether reset, rewound or loaded
+        let restart_hint = format!("Ended - {} restarts", hotkeys.key_name(Hotkey::Reset));
+        let remap_prompt = remap.prompt();
+        osd.pin(
+            remap_prompt
+                .as_deref()
+                .or_else(|| program_ended.then_some(restart_hint.as_str())),
+        );
+
+        // Messages and the keypad are drawn over the display, which has to be drawn again for them
+        // to come and go, for pixels to keep fading out and for the CRT effect to be turned on or off
+        let redraw = decay > 0.0
+            || race.is_some()
+            || osd.message().is_some()
+            || osd.take_expired()
+            || keypad.take_changed()
+            || crt.take_changed();
+        if redraw {
+            chip8.redraw();
+        }
+
+        for error in chip8.take_device_errors() {
+            eprintln!("{}", error);
+        }
+
+        // Nothing moves while FX0A waits in silence, so frames slow down until the next input
+        let idle = chip8.is_idle() && !redraw && !rewinding && debug_console.is_none();
+        if idle {
+            let timeout = IDLE_FRAME_DURATION.saturating_sub(clock.now() - frame_start);
+            if !chip8.wait_for_input(timeout) {
+                thread::sleep(timeout);
+            }
+        } else {
+            thread::sleep(FRAME_DURATION.saturating_sub(clock.now() - frame_start));
+        }
+    }
+
+    if let Some(race) = race.as_mut() {
+        finish_race(race, &mut chip8, &osd)?;
+    }
+    if let (Some(path), Some(replay)) = (&cli_args.record, chip8.stop_recording()) {
+        // Replays are text unless they are meant to be a binary file
+        let bytes = match path.extension() {
+            Some(extension) if extension == "bin" => replay.to_bytes(),
+            _ => replay.to_string().into_bytes(),
+        };
+        fs::write(path, bytes)
+            .map_err(|error| format!("Couldn't write {}: {}", path.display(), error))?;
+        println!(
+            "Recorded {} frames to {}",
+            replay.frames().len(),
+            path.display()
+        );
+    }
+    // The server thread never ends, so its socket is removed here rather than when it's dropped
+    #[cfg(unix)]
+    if let Some(path) = &cli_args.control {
+        let _ = fs::remove_file(path);
     }
 
     Ok(())
 }
+
+// Keeps the run that just ended when it beats the best one
+fn finish_race(race: &mut Race, chip8: &mut Chip8, osd: &Osd) -> Result<(), Box<dyn Error>> {
+    if let Some(score) = race.finish(chip8)? {
+        println!("New best run, {} points", score);
+        osd.show("New best run");
+    }
+    Ok(())
+}
+
+// Recordings without a seed given still need one, the time is as good as any
+fn time_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+// The preset after `palette`, or the first one when the colors aren't a preset
+fn next_palette(palette: Palette) -> (&'static str, Palette) {
+    let presets = Palette::presets();
+    let next = presets
+        .iter()
+        .position(|(_, preset)| *preset == palette)
+        .map_or(0, |current| (current + 1) % presets.len());
+    presets[next]
+}
+
+// What a frame runs at normal speed, used when stepping a frame at a time
+fn instructions_per_frame(speed: &SpeedController) -> usize {
+    (f64::from(speed.hertz()) * FRAME_DURATION.as_secs_f64()).round() as usize
+}
@@ -0,0 +1,86 @@
+use std::{
+    error::Error,
+    sync::mpsc::{self, Receiver},
+};
+
+use chip8_core::{midi::NoteMap, KeyEvent, Keyboard};
+use midir::{MidiInput, MidiInputConnection};
+
+const CLIENT_NAME: &str = "chip8";
+
+/// Reports the notes played on a MIDI controller as the keypad keys the `NoteMap` binds them to
+///
+/// midir delivers the messages on a thread of its own, polling only drains what it sent. The
+/// connection is closed when the keyboard is dropped
+pub struct MidiKeyboard {
+    events: Receiver<KeyEvent>,
+    _connection: MidiInputConnection<()>,
+}
+
+impl MidiKeyboard {
+    /// Connects to the first input port whose name contains `port`, ignoring case
+    pub fn connect(port: &str, notes: NoteMap) -> Result<MidiKeyboard, Box<dyn Error>> {
+        let input = MidiInput::new(CLIENT_NAME)?;
+        let ports = input.ports();
+        let wanted = port.to_lowercase();
+        let found = ports
+            .iter()
+            .find(|found| {
+                input
+                    .port_name(found)
+                    .is_ok_and(|name| name.to_lowercase().contains(&wanted))
+            })
+            .ok_or_else(|| {
+                format!(
+                    "No MIDI input matches {}, the inputs are: {}",
+                    port,
+                    input_names(&input).join(", ")
+                )
+            })?;
+
+        let (sender, events) = mpsc::channel();
+        let connection = input
+            .connect(
+                found,
+                CLIENT_NAME,
+                move |_, message, _| {
+                    if let Some(event) = key_event(message, &notes) {
+                        let _ = sender.send(event);
+                    }
+                },
+                (),
+            )
+            .map_err(|error| error.to_string())?;
+        Ok(MidiKeyboard {
+            events,
+            _connection: connection,
+        })
+    }
+}
+
+impl Keyboard for MidiKeyboard {
+    fn poll_events(&mut self) -> Vec<KeyEvent> {
+        self.events.try_iter().collect()
+    }
+}
+
+fn input_names(input: &MidiInput) -> Vec<String> {
+    input
+        .ports()
+        .iter()
+        .filter_map(|port| input.port_name(port).ok())
+        .collect()
+}
+
+// A note on with a velocity of 0 is how many controllers let a note go, other messages play nothing
+fn key_event(message: &[u8], notes: &NoteMap) -> Option<KeyEvent> {
+    match *message {
+        [status, note, velocity] if status & 0xF0 == 0x90 && velocity > 0 => {
+            notes.key(note).map(KeyEvent::Down)
+        }
+        [status, note, _] if status & 0xF0 == 0x80 || status & 0xF0 == 0x90 => {
+            notes.key(note).map(KeyEvent::Up)
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,123 @@
+use std::{
+    cell::{Cell, RefCell},
+    time::{Duration, Instant},
+};
+
+use chip8_core::{Palette, PostProcessor, RgbaImage};
+use sdl2::{pixels::Color, rect::Rect, render::Canvas, video::Window};
+
+const MESSAGE_DURATION: Duration = Duration::from_secs(2);
+// Size of the glyphs of the core OSD, which leaves a pixel around each of them
+const GLYPH_WIDTH: u32 = 3;
+const GLYPH_HEIGHT: u32 = 5;
+
+/// Short messages drawn over the display, like the confirmation of a save state
+///
+/// Shared between the main loop, which shows them, and the graphics device, which draws them
+#[derive(Debug, Default)]
+pub struct Osd {
+    message: RefCell<Option<(String, Instant)>>,
+    pinned: RefCell<Option<String>>,
+    expired: Cell<bool>,
+}
+
+impl Osd {
+    /// Shows `message` for a couple of seconds, replacing the one shown before
+    pub fn show(&self, message: &str) {
+        self.message
+            .replace(Some((message.to_string(), Instant::now())));
+    }
+
+    /// Keeps `message` up until it is pinned again, under the ones shown meanwhile
+    pub fn pin(&self, message: Option<&str>) {
+        let message = message.map(str::to_string);
+        if *self.pinned.borrow() != message {
+            self.pinned.replace(message);
+            self.expired.set(true);
+        }
+    }
+
+    /// The message to draw, if one is up
+    pub fn message(&self) -> Option<String> {
+        let mut message = self.message.borrow_mut();
+        match message.as_ref() {
+            Some((_, shown_at)) if shown_at.elapsed() >= MESSAGE_DURATION => {
+                *message = None;
+                self.expired.set(true);
+                self.pinned.borrow().clone()
+            }
+            Some((text, _)) => Some(text.clone()),
+            None => self.pinned.borrow().clone(),
+        }
+    }
+
+    /// Whether the message went away since the last call, the display has to be drawn again to clear it
+    pub fn take_expired(&self) -> bool {
+        self.message();
+        self.expired.replace(false)
+    }
+}
+
+/// Draws `text` in the top left corner over a box of the background color, `scale` times bigger
+/// than the font
+pub fn draw_message(
+    canvas: &mut Canvas<Window>,
+    text: &str,
+    scale: u32,
+    palette: &Palette,
+) -> Result<(), String> {
+    let width = text.chars().count() as u32 * (GLYPH_WIDTH + 1) + 1;
+    let height = GLYPH_HEIGHT + 2;
+    let [r, g, b, a] = palette.background;
+    canvas.set_draw_color(Color::RGBA(r, g, b, a));
+    canvas.fill_rect(Rect::new(0, 0, width * scale, height * scale))?;
+    draw_text(
+        canvas,
+        text,
+        (scale as i32, scale as i32),
+        scale,
+        palette.foreground,
+    )
+}
+
+/// Draws `text` with its top left corner at `position`, `scale` times bigger than the font
+///
+/// The text comes out of the OSD stage of the core, drawn here a rect per lit pixel like the display
+pub fn draw_text(
+    canvas: &mut Canvas<Window>,
+    text: &str,
+    position: (i32, i32),
+    scale: u32,
+    color: [u8; 4],
+) -> Result<(), String> {
+    let width = text.chars().count() as u32 * (GLYPH_WIDTH + 1) + 1;
+    let height = GLYPH_HEIGHT + 2;
+    // Anything but the color of the text tells the glyphs apart from what is around them
+    let backdrop = [!color[0], !color[1], !color[2], color[3]];
+    let palette = Palette {
+        background: backdrop,
+        foreground: backdrop,
+    };
+    let mut stage = chip8_core::Osd::new(1).with_color(color);
+    stage.set_message(Some(text.to_string()));
+    let image = stage.process(RgbaImage::new(width, height, backdrop), &palette);
+
+    let (left, top) = position;
+    let scale_i32 = scale as i32;
+    // The stage leaves a pixel of margin around the text, which isn't part of it here
+    let rects = (1..height)
+        .flat_map(|y| (1..width).map(move |x| (x, y)))
+        .filter(|&(x, y)| image.get_pixel(x, y) == color)
+        .map(|(x, y)| {
+            Rect::new(
+                left + (x as i32 - 1) * scale_i32,
+                top + (y as i32 - 1) * scale_i32,
+                scale,
+                scale,
+            )
+        })
+        .collect::<Vec<Rect>>();
+    let [r, g, b, a] = color;
+    canvas.set_draw_color(Color::RGBA(r, g, b, a));
+    canvas.fill_rects(&rects)
+}
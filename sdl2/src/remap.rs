@@ -0,0 +1,72 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+};
+
+use chip8_core::KeyRemap;
+use sdl2::keyboard::Keycode;
+
+/// Asks for the key of every key of the keypad in turn, over the display, once its hotkey is
+/// pressed
+///
+/// Shared between the keyboard, which hands it the keys pressed meanwhile instead of playing them,
+/// and the main loop, which starts it, shows what it asks and saves the keys once they are all bound
+#[derive(Debug, Default)]
+pub struct RemapScreen {
+    remap: RefCell<Option<KeyRemap>>,
+    refused: RefCell<Option<String>>,
+    finished: RefCell<Option<BTreeMap<String, u8>>>,
+}
+
+impl RemapScreen {
+    pub fn start(&self, remap: KeyRemap) {
+        self.remap.replace(Some(remap));
+        self.refused.replace(None);
+        self.finished.replace(None);
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.remap.borrow().is_some()
+    }
+
+    /// Stops asking, leaving the keys as they were
+    pub fn cancel(&self) {
+        self.remap.replace(None);
+    }
+
+    /// Binds `keycode` to the key of the keypad asked for, returning the keys the keyboard plays
+    /// from now on once they are all bound
+    pub fn press(&self, keycode: Keycode) -> Option<HashMap<Keycode, u8>> {
+        let mut remap = self.remap.borrow_mut();
+        if let Err(error) = remap.as_mut()?.press(&keycode.name()) {
+            self.refused.replace(Some(error));
+            return None;
+        }
+        if remap.as_ref()?.waiting_for().is_some() {
+            return None;
+        }
+
+        let bindings = remap.take()?.bindings();
+        let keys = bindings
+            .iter()
+            .filter_map(|(name, key)| Some((Keycode::from_name(name)?, *key)))
+            .collect();
+        self.finished.replace(Some(bindings));
+        Some(keys)
+    }
+
+    /// What to ask the user for, while remapping
+    pub fn prompt(&self) -> Option<String> {
+        self.remap.borrow().as_ref().and_then(KeyRemap::prompt)
+    }
+
+    /// Why the last key pressed wasn't taken, if it wasn't
+    pub fn take_refused(&self) -> Option<String> {
+        self.refused.take()
+    }
+
+    /// Every key bound, once the last one is
+    pub fn take_finished(&self) -> Option<BTreeMap<String, u8>> {
+        self.finished.take()
+    }
+}
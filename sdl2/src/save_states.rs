@@ -0,0 +1,70 @@
+use std::{
+    env,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chip8_core::{romdb, Snapshot};
+
+/// Save states of one rom, kept in numbered slots under `~/.local/share/chip8/states`
+///
+/// Files are named after the hash of the rom, so renaming or moving it keeps its states
+pub struct SaveStates {
+    directory: Option<PathBuf>,
+    rom_hash: String,
+    slot: u8,
+}
+
+impl SaveStates {
+    pub fn for_rom(rom_data: &[u8]) -> SaveStates {
+        SaveStates {
+            directory: SaveStates::default_directory(),
+            rom_hash: romdb::sha1(rom_data),
+            slot: 1,
+        }
+    }
+
+    /// Where states are kept, following the XDG convention
+    pub fn default_directory() -> Option<PathBuf> {
+        env::var_os("XDG_DATA_HOME")
+            .map(PathBuf::from)
+            .or_else(|| {
+                env::var_os("HOME").map(|home| Path::new(&home).join(".local").join("share"))
+            })
+            .map(|data| data.join("chip8").join("states"))
+    }
+
+    /// The slot `save` and `load` use
+    pub fn slot(&self) -> u8 {
+        self.slot
+    }
+
+    pub fn select_slot(&mut self, slot: u8) {
+        self.slot = slot;
+    }
+
+    pub fn save(&self, snapshot: &Snapshot) -> Result<(), Box<dyn Error>> {
+        let path = self.path()?;
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        fs::write(&path, toml::to_string(snapshot)?)
+            .map_err(|error| format!("Couldn't write {}: {}", path.display(), error).into())
+    }
+
+    pub fn load(&self) -> Result<Snapshot, Box<dyn Error>> {
+        let path = self.path()?;
+        let state = fs::read_to_string(&path)
+            .map_err(|error| format!("Couldn't read {}: {}", path.display(), error))?;
+        toml::from_str(&state)
+            .map_err(|error| format!("Invalid save state {}: {}", path.display(), error).into())
+    }
+
+    fn path(&self) -> Result<PathBuf, String> {
+        self.directory
+            .as_ref()
+            .map(|directory| directory.join(format!("{}.{}.toml", self.rom_hash, self.slot)))
+            .ok_or_else(|| "Couldn't find a directory to keep save states in".to_string())
+    }
+}
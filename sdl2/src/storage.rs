@@ -0,0 +1,39 @@
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use chip8_core::{Chip8Error, Storage};
+
+/// Keeps the RPL user flags in a file next to the rom
+pub struct FileStorage {
+    path: PathBuf,
+}
+
+impl FileStorage {
+    pub fn for_rom(rom_path: &Path) -> FileStorage {
+        FileStorage {
+            path: rom_path.with_extension("flags"),
+        }
+    }
+}
+
+impl Storage for FileStorage {
+    fn load_flags(&mut self) -> Result<[u8; 8], Chip8Error> {
+        let mut flags = [0; 8];
+        match fs::read(&self.path) {
+            Ok(saved_flags) => {
+                let length = saved_flags.len().min(flags.len());
+                flags[..length].copy_from_slice(&saved_flags[..length]);
+                Ok(flags)
+            }
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(flags),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    fn save_flags(&mut self, flags: &[u8; 8]) -> Result<(), Chip8Error> {
+        Ok(fs::write(&self.path, flags)?)
+    }
+}
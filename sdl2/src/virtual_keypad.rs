@@ -0,0 +1,123 @@
+use std::cell::Cell;
+
+use chip8_core::Palette;
+use sdl2::{
+    pixels::Color,
+    rect::{Point, Rect},
+    render::Canvas,
+    video::Window,
+};
+
+use crate::{graphics::SdlGraphics, osd};
+
+// Keys as they are laid out on the COSMAC VIP keypad
+const LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+const BUTTON_SIZE: u32 = 36;
+const MARGIN: u32 = 8;
+const LABEL_SCALE: u32 = 4;
+
+/// A 4x4 keypad drawn over the bottom right corner of the window, played with the mouse or a
+/// touchscreen
+///
+/// Shared between the keyboard, which turns clicks into key presses, the graphics device, which
+/// draws it, and the main loop, which shows and hides it
+#[derive(Debug, Default)]
+pub struct VirtualKeypad {
+    visible: Cell<bool>,
+    pressed: Cell<Option<u8>>,
+    changed: Cell<bool>,
+}
+
+impl VirtualKeypad {
+    pub fn toggle(&self) {
+        self.visible.set(!self.visible.get());
+        self.pressed.set(None);
+        self.changed.set(true);
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible.get()
+    }
+
+    /// Presses the key under `x`, `y` in the window, if the keypad is shown and there is one
+    pub fn press_at(&self, x: i32, y: i32) {
+        if !self.is_visible() {
+            return;
+        }
+        let key = buttons()
+            .find(|(_, button)| button.contains_point(Point::new(x, y)))
+            .map(|(key, _)| key);
+        if key.is_some() {
+            self.pressed.set(key);
+            self.changed.set(true);
+        }
+    }
+
+    pub fn release(&self) {
+        if self.pressed.take().is_some() {
+            self.changed.set(true);
+        }
+    }
+
+    /// The key held down with the mouse
+    pub fn pressed(&self) -> Option<u8> {
+        self.pressed.get()
+    }
+
+    /// Whether it changed since the last call, the display has to be drawn again to show it
+    pub fn take_changed(&self) -> bool {
+        self.changed.replace(false)
+    }
+
+    /// Draws the keypad over what is already on the canvas, the key held down lit up
+    pub fn draw(&self, canvas: &mut Canvas<Window>, palette: &Palette) -> Result<(), String> {
+        let [r, g, b, a] = palette.background;
+        let background = Color::RGBA(r, g, b, a);
+        let [r, g, b, a] = palette.foreground;
+        let foreground = Color::RGBA(r, g, b, a);
+
+        for (key, button) in buttons() {
+            let pressed = self.pressed() == Some(key);
+            let (fill, label) = if pressed {
+                (foreground, palette.background)
+            } else {
+                (background, palette.foreground)
+            };
+            canvas.set_draw_color(fill);
+            canvas.fill_rect(button)?;
+            canvas.set_draw_color(foreground);
+            canvas.draw_rect(button)?;
+
+            // Glyphs are 3x5 pixels of the font
+            let (width, height) = (3 * LABEL_SCALE as i32, 5 * LABEL_SCALE as i32);
+            let position = (
+                button.center().x() - width / 2,
+                button.center().y() - height / 2,
+            );
+            osd::draw_text(canvas, &format!("{:X}", key), position, LABEL_SCALE, label)?;
+        }
+        Ok(())
+    }
+}
+
+// Every key with where its button is in the window
+fn buttons() -> impl Iterator<Item = (u8, Rect)> {
+    let left = (SdlGraphics::WIDTH - 4 * BUTTON_SIZE - MARGIN) as i32;
+    let top = (SdlGraphics::HEIGHT - 4 * BUTTON_SIZE - MARGIN) as i32;
+    LAYOUT.iter().enumerate().flat_map(move |(row, keys)| {
+        keys.iter().enumerate().map(move |(col, key)| {
+            let button = Rect::new(
+                left + (col as u32 * BUTTON_SIZE) as i32,
+                top + (row as u32 * BUTTON_SIZE) as i32,
+                BUTTON_SIZE,
+                BUTTON_SIZE,
+            );
+            (*key, button)
+        })
+    })
+}